@@ -0,0 +1,53 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use texteditor::{Config, Document, Editor, Terminal};
+
+const WIDTH: u16 = 200;
+const HEIGHT: u16 = 60;
+
+/// Opens a large document in an editor drawing to a headless terminal.
+fn setup_editor(line: &str) -> Editor {
+    let text = format!("{}\n", line).repeat(100_000);
+    let mut editor =
+        Editor::with_terminal(Config::default(), Terminal::headless(WIDTH, HEIGHT), None);
+    editor.open(Document::scratch("large.txt", &text));
+    editor
+}
+
+fn render_viewport(c: &mut Criterion) {
+    let mut editor = setup_editor(
+        "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Praesent ultricies lacus ut molestie dapibus.",
+    );
+    editor.goto(50_000, 0);
+
+    c.bench_function("render a full viewport of a large document", |b| {
+        b.iter(|| editor.redraw().unwrap())
+    });
+}
+
+fn render_viewport_with_wide_characters(c: &mut Criterion) {
+    let mut editor =
+        setup_editor("漢字を含む行です。\temoji 🎉 and accents é à ü, ｆｕｌｌｗｉｄｔｈ");
+    editor.goto(50_000, 0);
+
+    c.bench_function("render a full viewport of wide characters and tabs", |b| {
+        b.iter(|| editor.redraw().unwrap())
+    });
+}
+
+fn render_viewport_with_long_lines(c: &mut Criterion) {
+    let mut editor = setup_editor(&"long line ".repeat(1000));
+    editor.goto(50_000, 0);
+
+    c.bench_function(
+        "render a full viewport of lines too long for the screen",
+        |b| b.iter(|| editor.redraw().unwrap()),
+    );
+}
+
+criterion_group!(
+    benches,
+    render_viewport,
+    render_viewport_with_wide_characters,
+    render_viewport_with_long_lines
+);
+criterion_main!(benches);
@@ -0,0 +1,88 @@
+use unicode_width::UnicodeWidthStr;
+
+/// Aligns lines into columns on every occurrence of a delimiter, padding the text before each
+/// delimiter with spaces. A space is kept before a delimiter if any line had whitespace before
+/// it in that column, so aligning text which is already aligned leaves it unchanged. Lines
+/// without the delimiter are left as they are.
+///
+/// # Arguments
+///
+/// * 'lines' - The lines to align.
+/// * 'delimiter' - The text which separates columns, such as `=` or `|`.
+pub fn align(lines: &mut [String], delimiter: &str) {
+    if delimiter.is_empty() {
+        return;
+    }
+
+    let rows: Vec<Vec<&str>> = lines
+        .iter()
+        .map(|line| line.split(delimiter).collect())
+        .collect();
+
+    // The width of the widest cell in each column and whether any cell is followed by
+    // whitespace, excluding the last cell of each row, which isn't followed by a delimiter.
+    let mut widths: Vec<usize> = vec![];
+    let mut spaced: Vec<bool> = vec![];
+    for cells in rows.iter() {
+        for (i, cell) in cells.iter().take(cells.len() - 1).enumerate() {
+            let trimmed = cell.trim_end();
+            let space = trimmed.len() < cell.len();
+            match widths.get_mut(i) {
+                Some(width) => {
+                    *width = (*width).max(trimmed.width());
+                    spaced[i] |= space;
+                }
+                None => {
+                    widths.push(trimmed.width());
+                    spaced.push(space);
+                }
+            }
+        }
+    }
+
+    let aligned: Vec<String> = rows
+        .iter()
+        .map(|cells| {
+            let mut line = String::new();
+            for (i, cell) in cells.iter().enumerate() {
+                if i == cells.len() - 1 {
+                    line.push_str(cell);
+                    break;
+                }
+                let trimmed = cell.trim_end();
+                line.push_str(trimmed);
+                let padding = widths[i] - trimmed.width() + usize::from(spaced[i]);
+                line.push_str(&" ".repeat(padding));
+                line.push_str(delimiter);
+            }
+            line
+        })
+        .collect();
+
+    for (line, aligned) in lines.iter_mut().zip(aligned) {
+        *line = aligned;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split('\n').map(String::from).collect()
+    }
+
+    #[test_case("a = 1\nabc = 2", "=", "a   = 1\nabc = 2";           "assignments")]
+    #[test_case("a|bb|c\naaa|b|c", "|", "a  |bb|c\naaa|b |c";        "table")]
+    #[test_case("a = 1\nnone\nab = 2", "=", "a  = 1\nnone\nab = 2";  "line_without_delimiter")]
+    #[test_case("a   = 1\nabc = 2", "=", "a   = 1\nabc = 2";         "already_aligned")]
+    #[test_case("a = 1\nabc= 2\nb=3", "=", "a   = 1\nabc = 2\nb   =3"; "mixed_spacing")]
+    #[test_case("é, 1\nab, 2", ",", "é , 1\nab, 2";                  "wide_characters")]
+    fn align_lines(text: &str, delimiter: &str, expected: &str) {
+        let mut actual = lines(text);
+        align(&mut actual, delimiter);
+        assert_eq!(lines(expected), actual);
+    }
+}
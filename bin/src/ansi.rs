@@ -0,0 +1,299 @@
+use console::{Color, Style};
+
+/// The character starting an escape sequence.
+const ESCAPE: char = '\x1b';
+
+/// Whether the text contains escape sequences, such as the colours in a build log.
+pub fn contains_escapes(text: &str) -> bool {
+    text.contains("\x1b[") || text.contains("\x1b]")
+}
+
+/// Removes escape sequences from a line, leaving the text which would be displayed.
+pub fn strip(line: &str) -> String {
+    parse(line).into_iter().map(|(text, _)| text).collect()
+}
+
+/// The number of displayed characters before a character in a line, skipping the characters in
+/// escape sequences.
+///
+/// # Arguments
+///
+/// * 'line' - The line containing the character.
+/// * 'column' - The index of the character in the line, including escape sequences.
+pub fn visible_column(line: &str, column: u32) -> u32 {
+    let mut visible = 0;
+    for (i, escape) in classify(line).into_iter().enumerate() {
+        if i as u32 >= column {
+            break;
+        }
+        if !escape {
+            visible += 1;
+        }
+    }
+    visible
+}
+
+/// The index of a displayed character in a line, including the characters in escape sequences.
+/// This is the inverse of `visible_column`.
+///
+/// # Arguments
+///
+/// * 'line' - The line containing the character.
+/// * 'visible' - The number of displayed characters before the character.
+pub fn raw_column(line: &str, visible: u32) -> u32 {
+    let mut seen = 0;
+    for (i, escape) in classify(line).into_iter().enumerate() {
+        if !escape {
+            if seen == visible {
+                return i as u32;
+            }
+            seen += 1;
+        }
+    }
+    line.chars().count() as u32
+}
+
+/// Splits a line into runs of text with the style set by the escape sequences before them. Only
+/// SGR sequences, which set colours and attributes, affect the style. Other sequences, such as
+/// cursor movement and window titles, are dropped.
+pub fn parse(line: &str) -> Vec<(String, Style)> {
+    let mut spans: Vec<(String, Style)> = vec![];
+    let mut state = State::default();
+    let mut text = String::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != ESCAPE {
+            text.push(c);
+            continue;
+        }
+        if !text.is_empty() {
+            spans.push((std::mem::take(&mut text), state.style()));
+        }
+        match chars.next() {
+            Some('[') => {
+                let mut parameters = String::new();
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        if c == 'm' {
+                            state.apply(&parameters);
+                        }
+                        break;
+                    }
+                    parameters.push(c);
+                }
+            }
+            Some(']') => {
+                // Operating system commands end with BEL or ST (ESC \).
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == ESCAPE && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !text.is_empty() {
+        spans.push((text, state.style()));
+    }
+    spans
+}
+
+/// Whether each character of a line is part of an escape sequence.
+fn classify(line: &str) -> Vec<bool> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut escapes = vec![false; chars.len()];
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] != ESCAPE {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        i += 1;
+        match chars.get(i) {
+            Some('[') => {
+                i += 1;
+                while i < chars.len() && !('\x40'..='\x7e').contains(&chars[i]) {
+                    i += 1;
+                }
+                i += 1;
+            }
+            Some(']') => {
+                i += 1;
+                while i < chars.len() {
+                    if chars[i] == '\x07' {
+                        i += 1;
+                        break;
+                    }
+                    if chars[i] == ESCAPE && chars.get(i + 1) == Some(&'\\') {
+                        i += 2;
+                        break;
+                    }
+                    i += 1;
+                }
+            }
+            Some(_) => i += 1,
+            None => {}
+        }
+        let end = i.min(chars.len());
+        escapes[start..end].fill(true);
+    }
+    escapes
+}
+
+/// The colours and attributes set by the SGR sequences read so far.
+#[derive(Debug, Default, Clone, Copy)]
+struct State {
+    foreground: Option<Color>,
+    background: Option<Color>,
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underlined: bool,
+    reverse: bool,
+}
+
+impl State {
+    /// Applies the parameters of an SGR sequence, such as `1;31` for bold red text.
+    fn apply(&mut self, parameters: &str) {
+        let mut codes = parameters
+            .split([';', ':'])
+            .map(|code| code.parse::<u8>().unwrap_or(0));
+        while let Some(code) = codes.next() {
+            match code {
+                0 => *self = State::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underlined = true,
+                7 => self.reverse = true,
+                22 => (self.bold, self.dim) = (false, false),
+                23 => self.italic = false,
+                24 => self.underlined = false,
+                27 => self.reverse = false,
+                30..=37 => self.foreground = Some(basic_color(code - 30)),
+                38 => self.foreground = extended_color(&mut codes),
+                39 => self.foreground = None,
+                40..=47 => self.background = Some(basic_color(code - 40)),
+                48 => self.background = extended_color(&mut codes),
+                49 => self.background = None,
+                90..=97 => self.foreground = Some(Color::Color256(code - 90 + 8)),
+                100..=107 => self.background = Some(Color::Color256(code - 100 + 8)),
+                _ => {}
+            }
+        }
+    }
+
+    fn style(&self) -> Style {
+        let mut style = Style::new().force_styling(true);
+        if let Some(color) = self.foreground {
+            style = style.fg(color);
+        }
+        if let Some(color) = self.background {
+            style = style.bg(color);
+        }
+        for (set, attribute) in [
+            (self.bold, Style::bold as fn(Style) -> Style),
+            (self.dim, Style::dim),
+            (self.italic, Style::italic),
+            (self.underlined, Style::underlined),
+            (self.reverse, Style::reverse),
+        ] {
+            if set {
+                style = attribute(style);
+            }
+        }
+        style
+    }
+}
+
+fn basic_color(index: u8) -> Color {
+    match index {
+        0 => Color::Black,
+        1 => Color::Red,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        4 => Color::Blue,
+        5 => Color::Magenta,
+        6 => Color::Cyan,
+        _ => Color::White,
+    }
+}
+
+/// Reads a 256 colour (`5;n`) or true colour (`2;r;g;b`) from the rest of an SGR sequence. True
+/// colours are shown as the nearest colour in the 256 colour cube.
+fn extended_color(codes: &mut impl Iterator<Item = u8>) -> Option<Color> {
+    match codes.next()? {
+        5 => codes.next().map(Color::Color256),
+        2 => {
+            let mut component = || codes.next().map(|c| (c as u16 * 5 + 127) / 255);
+            let (r, g, b) = (component()?, component()?, component()?);
+            Some(Color::Color256((16 + r * 36 + g * 6 + b) as u8))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("\x1b[31merror\x1b[0m", true;   "colour")]
+    #[test_case("\x1b]0;title\x07", true;       "window_title")]
+    #[test_case("plain text", false;             "plain")]
+    fn detect_escapes(text: &str, expected: bool) {
+        assert_eq!(expected, contains_escapes(text));
+    }
+
+    #[test_case("\x1b[1;31merror\x1b[0m: failed", "error: failed"; "sgr")]
+    #[test_case("\x1b]0;title\x07Lorem", "Lorem";                   "osc_bel")]
+    #[test_case("\x1b]8;;http://a\x1b\\link", "link";               "osc_st")]
+    #[test_case("\x1b[2KLorem\x1b[", "Lorem";                       "other_and_unfinished")]
+    fn strip_escapes(line: &str, expected: &str) {
+        assert_eq!(expected, strip(line));
+    }
+
+    #[test]
+    fn parse_styles() {
+        let line = "a\x1b[1;31mb\x1b[22mc\x1b[0md\x1b[38;5;208;48;2;0;0;255me";
+        let spans = parse(line);
+        let bold_red = Style::new().force_styling(true).fg(Color::Red).bold();
+        let expected = vec![
+            (String::from("a"), Style::new().force_styling(true)),
+            (String::from("b"), bold_red),
+            (
+                String::from("c"),
+                Style::new().force_styling(true).fg(Color::Red),
+            ),
+            (String::from("d"), Style::new().force_styling(true)),
+            (
+                String::from("e"),
+                Style::new()
+                    .force_styling(true)
+                    .fg(Color::Color256(208))
+                    .bg(Color::Color256(21)),
+            ),
+        ];
+        assert_eq!(expected, spans);
+    }
+
+    #[test_case("\x1b[31mab\x1b[0mc", 0, 0; "start")]
+    #[test_case("\x1b[31mab\x1b[0mc", 6, 1; "inside_text")]
+    #[test_case("\x1b[31mab\x1b[0mc", 9, 2; "inside_escape")]
+    #[test_case("\x1b[31mab\x1b[0mc", 12, 3; "end")]
+    fn visible_column_of_character(line: &str, column: u32, expected: u32) {
+        assert_eq!(expected, visible_column(line, column));
+    }
+
+    #[test_case("\x1b[31mab\x1b[0mc", 0, 5;  "first")]
+    #[test_case("\x1b[31mab\x1b[0mc", 2, 11; "after_escape")]
+    #[test_case("\x1b[31mab\x1b[0mc", 3, 12; "end")]
+    fn raw_column_of_character(line: &str, visible: u32, expected: u32) {
+        assert_eq!(expected, raw_column(line, visible));
+    }
+}
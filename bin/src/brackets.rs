@@ -0,0 +1,232 @@
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+use console::{Color, Style};
+
+use crate::selection::Position;
+
+/// The pairs of brackets which are matched and coloured.
+const PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+/// The colours of nested brackets, by depth. Deeper brackets cycle back to the first colour.
+const COLORS: [Color; 3] = [
+    Color::Color256(214),
+    Color::Color256(170),
+    Color::Color256(39),
+];
+/// The number of lines scanned above and below the visible lines. Brackets are matched within
+/// this window, so depths are counted from the start of it rather than the start of the document.
+const WINDOW: u32 = 2000;
+
+/// What a scan was made for. Any edit to the document or scrolling it calls for a new scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanKey {
+    /// The number of edits made to the document.
+    pub edits: u64,
+    /// The first visible line.
+    pub top: u32,
+    /// The last visible line.
+    pub last: u32,
+}
+
+/// The brackets on the visible lines of a document, matched by scanning the lines around them,
+/// along with the pairs of brackets which enclose any visible text.
+#[derive(Debug)]
+pub struct BracketScan {
+    key: ScanKey,
+    /// The column and depth of each bracket on the visible lines, by line. Brackets without a
+    /// match have no depth.
+    brackets: BTreeMap<u32, Vec<(u32, Option<usize>)>>,
+    /// The matched pairs which enclose visible text, as the positions of their brackets.
+    pairs: Vec<RangeInclusive<Position>>,
+    /// The innermost pair around the cursor.
+    scope: Option<RangeInclusive<Position>>,
+}
+
+impl BracketScan {
+    /// Matches the brackets on and around the visible lines.
+    ///
+    /// # Arguments
+    ///
+    /// * 'key' - What the scan is for.
+    /// * 'line_count' - The number of lines in the document.
+    /// * 'read' - Reads the lines in a range.
+    pub fn new(
+        key: ScanKey,
+        line_count: u32,
+        read: impl FnOnce(std::ops::Range<u32>) -> Vec<String>,
+    ) -> BracketScan {
+        let start = key.top.saturating_sub(WINDOW).max(1);
+        let end = (key.last + WINDOW).min(line_count);
+        let visible = key.top..=key.last;
+        let mut scan = BracketScan {
+            key,
+            brackets: BTreeMap::new(),
+            pairs: vec![],
+            scope: None,
+        };
+
+        let mut open: Vec<(char, Position)> = vec![];
+        for (line, text) in (start..).zip(read(start..end + 1)) {
+            for (column, c) in (0..).zip(text.chars()) {
+                let position = Position { row: line, column };
+                let depth = if PAIRS.iter().any(|(o, _)| *o == c) {
+                    open.push((c, position));
+                    Some(open.len() - 1)
+                } else if let Some((opening, _)) = PAIRS.iter().find(|(_, close)| *close == c) {
+                    match open.last() {
+                        Some((o, start)) if o == opening => {
+                            if start.row <= key.last && line >= key.top {
+                                scan.pairs.push(*start..=position);
+                            }
+                            open.pop();
+                            Some(open.len())
+                        }
+                        _ => None,
+                    }
+                } else {
+                    continue;
+                };
+                if visible.contains(&line) {
+                    scan.brackets.entry(line).or_default().push((column, depth));
+                }
+            }
+        }
+
+        // Brackets still open at the end of the document are never closed.
+        if end == line_count {
+            for (_, position) in open {
+                let brackets = scan.brackets.get_mut(&position.row).into_iter().flatten();
+                for (column, depth) in brackets {
+                    if *column == position.column {
+                        *depth = None;
+                    }
+                }
+            }
+        }
+        scan
+    }
+
+    /// Whether the scan was made for the document and lines as they are now.
+    pub fn is_current(&self, key: ScanKey) -> bool {
+        self.key == key
+    }
+
+    /// Finds the innermost pair of brackets around the cursor, returning whether it changed.
+    /// The cursor is inside a pair when it's on either bracket or between them.
+    pub fn set_cursor(&mut self, cursor: Position) -> bool {
+        let scope = self
+            .pairs
+            .iter()
+            .filter(|pair| pair.contains(&cursor))
+            .max_by_key(|pair| *pair.start())
+            .cloned();
+        let changed = scope != self.scope;
+        self.scope = scope;
+        changed
+    }
+
+    /// The style of a character in a visible line. Brackets are coloured by their depth, and
+    /// unmatched brackets are red. When dimming is on, text outside the innermost pair around the
+    /// cursor is dimmed.
+    ///
+    /// # Arguments
+    ///
+    /// * 'position' - The position of the character.
+    /// * 'rainbow' - Whether brackets are coloured.
+    /// * 'dim' - Whether text outside the cursor's brackets is dimmed.
+    pub fn style(&self, position: Position, rainbow: bool, dim: bool) -> Style {
+        let mut style = Style::new().force_styling(true);
+        let bracket = self
+            .brackets
+            .get(&position.row)
+            .and_then(|brackets| brackets.iter().find(|(c, _)| *c == position.column));
+        if let (true, Some((_, depth))) = (rainbow, bracket) {
+            style = match depth {
+                Some(depth) => style.fg(COLORS[depth % COLORS.len()]),
+                None => style.fg(Color::Red),
+            };
+        }
+        if dim && self.scope.as_ref().is_some_and(|s| !s.contains(&position)) {
+            style = style.dim();
+        }
+        style
+    }
+
+    /// Whether anything on a line is styled, so lines without brackets can be drawn plainly.
+    pub fn styles_line(&self, line: u32, dim: bool) -> bool {
+        self.brackets.contains_key(&line) || (dim && self.scope.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    const TEXT: [&str; 5] = ["fn f(a: [u8]) {", "    g(a);", "    ]", "}", "("];
+
+    fn scan(top: u32, last: u32) -> BracketScan {
+        let key = ScanKey {
+            edits: 0,
+            top,
+            last,
+        };
+        BracketScan::new(key, TEXT.len() as u32, |lines| {
+            lines
+                .map(|line| TEXT[line as usize - 1].to_string())
+                .collect()
+        })
+    }
+
+    fn depths(scan: &BracketScan, line: u32) -> Vec<(u32, Option<usize>)> {
+        scan.brackets.get(&line).cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn brackets_by_depth() {
+        let scan = scan(1, 5);
+        assert_eq!(
+            vec![
+                (4, Some(0)),
+                (8, Some(1)),
+                (11, Some(1)),
+                (12, Some(0)),
+                (14, Some(0))
+            ],
+            depths(&scan, 1)
+        );
+        assert_eq!(vec![(5, Some(1)), (7, Some(1))], depths(&scan, 2));
+        assert_eq!(vec![(4, None)], depths(&scan, 3));
+        assert_eq!(vec![(0, Some(0))], depths(&scan, 4));
+        assert_eq!(vec![(0, None)], depths(&scan, 5));
+    }
+
+    #[test]
+    fn only_visible_lines_are_kept() {
+        let scan = scan(2, 2);
+        assert_eq!(vec![2], scan.brackets.keys().copied().collect::<Vec<_>>());
+        // Depths are counted from before the visible lines.
+        assert_eq!(vec![(5, Some(1)), (7, Some(1))], depths(&scan, 2));
+    }
+
+    #[test_case((2, 6), Some(((2, 5), (2, 7))); "inside_call")]
+    #[test_case((2, 2), Some(((1, 14), (4, 0))); "inside_block")]
+    #[test_case((1, 14), Some(((1, 14), (4, 0))); "on_bracket")]
+    #[test_case((1, 2), None;                     "outside")]
+    fn scope_around_cursor(cursor: (u32, u32), expected: Option<((u32, u32), (u32, u32))>) {
+        let position = |(row, column)| Position { row, column };
+        let mut scan = scan(1, 5);
+        scan.set_cursor(position(cursor));
+        let expected = expected.map(|(start, end)| position(start)..=position(end));
+        assert_eq!(expected, scan.scope);
+    }
+
+    #[test]
+    fn cursor_moves_change_scope() {
+        let mut scan = scan(1, 5);
+        assert!(scan.set_cursor(Position { row: 2, column: 6 }));
+        assert!(!scan.set_cursor(Position { row: 2, column: 6 }));
+        assert!(scan.set_cursor(Position { row: 1, column: 0 }));
+    }
+}
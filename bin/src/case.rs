@@ -0,0 +1,76 @@
+/// A change to the case of some text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Case {
+    Upper,
+    Lower,
+    /// Capitalises the first letter of each word and lowercases the rest.
+    Title,
+    /// Swaps uppercase letters for lowercase and lowercase letters for uppercase.
+    Toggle,
+}
+
+impl Case {
+    /// Looks up a case by the name used at the command prompt.
+    pub fn from_name(name: &str) -> Option<Case> {
+        match name {
+            "upper" | "uppercase" => Some(Case::Upper),
+            "lower" | "lowercase" => Some(Case::Lower),
+            "title" | "titlecase" => Some(Case::Title),
+            "toggle" | "togglecase" => Some(Case::Toggle),
+            _ => None,
+        }
+    }
+
+    /// Converts text to this case. Characters without a case are left unchanged.
+    pub fn convert(self, text: &str) -> String {
+        match self {
+            Case::Upper => text.to_uppercase(),
+            Case::Lower => text.to_lowercase(),
+            Case::Title => {
+                let mut converted = String::with_capacity(text.len());
+                let mut in_word = false;
+                for c in text.chars() {
+                    match in_word {
+                        true => converted.extend(c.to_lowercase()),
+                        false => converted.extend(c.to_uppercase()),
+                    }
+                    // Apostrophes don't start a new word, so "don't" becomes "Don't".
+                    in_word = c.is_alphanumeric() || (in_word && c == '\'');
+                }
+                converted
+            }
+            Case::Toggle => text
+                .chars()
+                .flat_map(|c| match c.is_uppercase() {
+                    true => c.to_lowercase().collect::<Vec<_>>(),
+                    false => c.to_uppercase().collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(Case::Upper, "Lorem ipsum", "LOREM IPSUM";   "upper")]
+    #[test_case(Case::Lower, "Lorem IPSUM", "lorem ipsum";   "lower")]
+    #[test_case(Case::Title, "lorem IPSUM", "Lorem Ipsum";   "title")]
+    #[test_case(Case::Title, "don't stop", "Don't Stop";     "title_apostrophe")]
+    #[test_case(Case::Title, "x-ray\nvision", "X-Ray\nVision"; "title_punctuation")]
+    #[test_case(Case::Toggle, "Lorem IPSUM", "lOREM ipsum";  "toggle")]
+    #[test_case(Case::Upper, "straße", "STRASSE";            "changes_length")]
+    fn convert(case: Case, text: &str, expected: &str) {
+        assert_eq!(expected, case.convert(text));
+    }
+
+    #[test_case("upper", Some(Case::Upper);      "upper")]
+    #[test_case("titlecase", Some(Case::Title);  "long_name")]
+    #[test_case("sideways", None;                "unknown")]
+    fn from_name(name: &str, expected: Option<Case>) {
+        assert_eq!(expected, Case::from_name(name));
+    }
+}
@@ -0,0 +1,146 @@
+use std::time::Duration;
+
+use console::style;
+
+use crate::layout;
+
+/// How long a chord is left pending before the keys which can follow it are shown.
+pub const HINT_DELAY: Duration = Duration::from_millis(500);
+
+/// A key which waits for another key to be pressed, which picks a command to run.
+#[derive(Debug, PartialEq)]
+pub struct Chord {
+    /// The name of the key which starts the chord.
+    pub prefix: &'static str,
+    /// The keys which can follow the prefix, and the command each one runs, as it would be
+    /// entered at the command prompt.
+    pub continuations: &'static [(char, &'static str)],
+}
+
+/// The chord started by Ctrl+X, for commands which are otherwise only run from the prompt.
+pub const CTRL_X: Chord = Chord {
+    prefix: "Ctrl+X",
+    continuations: &[
+        ('b', "bookmark"),
+        ('t', "todo"),
+        ('n', "signnext"),
+        ('p', "signprev"),
+        ('e', "copen"),
+        ('r', "recent"),
+        ('l', "buffers"),
+        ('u', "undotree"),
+        ('m', "minimap"),
+        ('s', "scrollbar"),
+        ('z', "zen"),
+        ('a', "ansi"),
+        ('f', "foldtoggle"),
+        ('i', "inspect"),
+        ('h', "help"),
+        // Ctrl+K and Ctrl+F, completing the text before the cursor.
+        ('\u{b}', "complete dict"),
+        ('\u{6}', "complete file"),
+    ],
+};
+
+/// The name of a key which can follow a chord's prefix. Control characters stand for their
+/// letter pressed along with Ctrl.
+pub fn key_name(key: char) -> String {
+    match key {
+        '\u{1}'..='\u{1a}' => format!("Ctrl+{}", (key as u8 + b'@') as char),
+        key => key.to_string(),
+    }
+}
+
+impl Chord {
+    /// The command run by a key following the prefix, if the key is part of the chord.
+    pub fn command(&self, key: char) -> Option<&'static str> {
+        self.continuations
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, command)| *command)
+    }
+
+    /// Draws the keys which can follow the prefix along with their commands, laid out in as many
+    /// columns as fit the width.
+    ///
+    /// # Arguments
+    ///
+    /// * 'width' - The number of cells available to draw the hint in.
+    pub fn render_hint(&self, width: usize) -> Vec<String> {
+        let entry_width = self
+            .continuations
+            .iter()
+            .map(|(key, command)| {
+                layout::display_width(&key_name(*key)) + layout::display_width(command) + 5
+            })
+            .max()
+            .unwrap_or(0);
+        let columns = (width / entry_width.max(1)).max(1);
+
+        let mut rows = vec![format!("{}-", self.prefix)];
+        for chunk in self.continuations.chunks(columns) {
+            let row: String = chunk
+                .iter()
+                .map(|(key, command)| {
+                    let name = key_name(*key);
+                    format!(
+                        "  {} → {:<width$}",
+                        style(&name).bold().force_styling(true),
+                        command,
+                        width = entry_width - layout::display_width(&name) - 5
+                    )
+                })
+                .collect();
+            rows.push(row);
+        }
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    const CHORD: Chord = Chord {
+        prefix: "Ctrl+T",
+        continuations: &[('a', "ansi"), ('z', "zen"), ('m', "minimap")],
+    };
+
+    #[test_case('z', Some("zen"); "bound")]
+    #[test_case('q', None;        "unbound")]
+    fn command_for_key(key: char, expected: Option<&str>) {
+        assert_eq!(expected, CHORD.command(key));
+    }
+
+    #[test]
+    fn hint_lays_out_columns() {
+        let bold = |c: char| style(c).bold().force_styling(true).to_string();
+        let rows = CHORD.render_hint(30);
+
+        assert_eq!("Ctrl+T-", rows[0]);
+        assert_eq!(
+            format!("  {} → ansi     {} → zen    ", bold('a'), bold('z')),
+            rows[1]
+        );
+        assert_eq!(format!("  {} → minimap", bold('m')), rows[2]);
+    }
+
+    #[test_case('a', "a";          "letter")]
+    #[test_case('\u{b}', "Ctrl+K"; "control")]
+    fn name_of_key(key: char, expected: &str) {
+        assert_eq!(expected, key_name(key));
+    }
+
+    #[test]
+    fn commands_parse() {
+        for (_, command) in CTRL_X.continuations {
+            assert!(
+                crate::command::Command::parse(command).is_ok(),
+                "{}",
+                command
+            );
+        }
+    }
+}
@@ -0,0 +1,393 @@
+//! Shared editing between editors over TCP, started with `--serve` and joined with `--connect`.
+//!
+//! The host is trusted completely and clients are trusted by the host. Connections aren't
+//! authenticated or encrypted, so anyone who can reach the host's address can read the shared
+//! document, edit it and see where everyone's cursors are. The host only listens on loopback
+//! addresses unless `--allow-remote` is given; sharing with other machines is safest through a
+//! tunnel such as `ssh -L`.
+//!
+//! There's no merging of concurrent edits. Clients send edits positioned against their copy of
+//! the document, and the host applies them in the order they arrive. An edit made before a
+//! client has received the host's latest text can land in the wrong place, or be dropped if it
+//! no longer falls on character boundaries. Every participant then ends up with the host's copy.
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use console::Color;
+use log::{error, info};
+
+use crate::editor::Event;
+use crate::selection::Position;
+
+/// The participant id of the host. Clients are numbered from 1 in the order they join.
+pub const HOST: u32 = 0;
+
+/// The colours used for other participants' cursors, chosen by their id.
+const CURSOR_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Magenta,
+    Color::Yellow,
+    Color::Green,
+    Color::Blue,
+    Color::Red,
+];
+
+/// A message sent between participants in a shared editing session, one per line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// The whole text of the shared document, sent by the host whenever it changes.
+    Text(String),
+    /// An edit made by a client, sent to the host to apply. Positions are in bytes.
+    Change {
+        pos: usize,
+        deleted: usize,
+        inserted: String,
+    },
+    /// Where a participant's cursor is. Clients send their own id as 0, which the host replaces
+    /// with the id it gave them before passing the message on.
+    Cursor { id: u32, position: Position },
+    /// A participant has left the session.
+    Leave { id: u32 },
+}
+
+impl Message {
+    pub fn parse(line: &str) -> Option<Message> {
+        let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match kind {
+            "text" => Some(Message::Text(unescape(rest))),
+            "change" => {
+                let mut fields = rest.splitn(3, ' ');
+                Some(Message::Change {
+                    pos: fields.next()?.parse().ok()?,
+                    deleted: fields.next()?.parse().ok()?,
+                    inserted: unescape(fields.next().unwrap_or_default()),
+                })
+            }
+            "cursor" => {
+                let mut fields = rest.split(' ').map(|f| f.parse().ok());
+                let (id, row, column) = (fields.next()??, fields.next()??, fields.next()??);
+                Some(Message::Cursor {
+                    id,
+                    position: Position { row, column },
+                })
+            }
+            "leave" => Some(Message::Leave {
+                id: rest.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The message as a line of text, including the line break.
+    pub fn encode(&self) -> String {
+        match self {
+            Message::Text(text) => format!("text {}\n", escape(text)),
+            Message::Change {
+                pos,
+                deleted,
+                inserted,
+            } => format!("change {} {} {}\n", pos, deleted, escape(inserted)),
+            Message::Cursor { id, position } => {
+                format!("cursor {} {} {}\n", id, position.row, position.column)
+            }
+            Message::Leave { id } => format!("leave {}\n", id),
+        }
+    }
+}
+
+/// Escapes line breaks and backslashes so text fits on a single line.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// The colour of a participant's cursor.
+pub fn cursor_color(id: u32) -> Color {
+    CURSOR_COLORS[id as usize % CURSOR_COLORS.len()]
+}
+
+/// A connection to another participant.
+struct Peer {
+    id: u32,
+    stream: TcpStream,
+}
+
+enum Role {
+    /// Shares the document with every client which connects.
+    Host {
+        address: SocketAddr,
+        peers: Arc<Mutex<Vec<Peer>>>,
+    },
+    /// Edits a document shared by a host.
+    Client { stream: TcpStream },
+}
+
+/// A shared editing session, either hosting a document or connected to a host. The host's copy
+/// of the document is the one every participant ends up with: clients send their edits to the
+/// host, which applies them in the order they arrive and sends the resulting text back out.
+///
+/// Messages from other participants are read on background threads and posted to the editor's
+/// event queue.
+pub struct Session {
+    role: Role,
+    /// Where the other participants' cursors are, by id.
+    cursors: BTreeMap<u32, Position>,
+    /// The cursor position last sent, so it's only sent again once it moves.
+    sent_cursor: Option<Position>,
+}
+
+impl Session {
+    /// Starts listening for clients.
+    ///
+    /// # Arguments
+    ///
+    /// * 'address' - The address to listen on, such as `127.0.0.1:7878`.
+    /// * 'sender' - The queue messages from clients are posted to.
+    pub fn serve(address: impl ToSocketAddrs, sender: Sender<Event>) -> io::Result<Session> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        info!("Serving document on {}", address);
+        let peers = Arc::new(Mutex::new(vec![]));
+
+        let accepted = peers.clone();
+        thread::spawn(move || {
+            for (id, stream) in (HOST + 1..).zip(listener.incoming()) {
+                let stream = match stream.and_then(|s| s.try_clone().map(|c| (s, c))) {
+                    Ok((stream, reader)) => {
+                        read_messages(id, reader, sender.clone());
+                        stream
+                    }
+                    Err(e) => {
+                        error!("Unable to accept connection: {}", e);
+                        continue;
+                    }
+                };
+                if let Ok(mut peers) = accepted.lock() {
+                    peers.push(Peer { id, stream });
+                }
+                if sender.send(Event::ParticipantJoined(id)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Session {
+            role: Role::Host { address, peers },
+            cursors: BTreeMap::new(),
+            sent_cursor: None,
+        })
+    }
+
+    /// Connects to a host to edit the document it's sharing.
+    ///
+    /// # Arguments
+    ///
+    /// * 'address' - The address the host is listening on.
+    /// * 'sender' - The queue messages from the host are posted to.
+    pub fn connect(address: impl ToSocketAddrs, sender: Sender<Event>) -> io::Result<Session> {
+        let stream = TcpStream::connect(address)?;
+        read_messages(HOST, stream.try_clone()?, sender);
+        Ok(Session {
+            role: Role::Client { stream },
+            cursors: BTreeMap::new(),
+            sent_cursor: None,
+        })
+    }
+
+    pub fn is_host(&self) -> bool {
+        matches!(self.role, Role::Host { .. })
+    }
+
+    /// The address the host is listening on, which is useful when listening on port 0.
+    pub fn address(&self) -> Option<SocketAddr> {
+        match &self.role {
+            Role::Host { address, .. } => Some(*address),
+            Role::Client { .. } => None,
+        }
+    }
+
+    /// Sends a message to every other participant, or to the host when connected as a client.
+    pub fn send(&mut self, message: &Message) {
+        self.send_where(message, |_| true);
+    }
+
+    /// Sends a message to a single client. Clients can only send messages to the host.
+    pub fn send_to(&mut self, id: u32, message: &Message) {
+        self.send_where(message, |peer| peer == id);
+    }
+
+    /// Sends a message to every client apart from one, such as the client the message came from.
+    pub fn send_except(&mut self, id: u32, message: &Message) {
+        self.send_where(message, |peer| peer != id);
+    }
+
+    fn send_where(&mut self, message: &Message, include: impl Fn(u32) -> bool) {
+        let line = message.encode();
+        match &mut self.role {
+            Role::Host { peers, .. } => {
+                let Ok(mut peers) = peers.lock() else {
+                    return;
+                };
+                for peer in peers.iter_mut().filter(|p| include(p.id)) {
+                    // A client which can't be written to has gone, which its reader reports.
+                    let _ = peer.stream.write_all(line.as_bytes());
+                }
+            }
+            Role::Client { stream } => {
+                if let Err(e) = stream.write_all(line.as_bytes()) {
+                    error!("Unable to send to host: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Sends the cursor position to the other participants if it has moved since it was last
+    /// sent.
+    pub fn send_cursor(&mut self, position: Position) {
+        if self.sent_cursor == Some(position) {
+            return;
+        }
+        self.sent_cursor = Some(position);
+        self.send(&Message::Cursor { id: HOST, position });
+    }
+
+    /// Forgets the last cursor position sent, so it's sent again, such as to a new client.
+    pub fn resend_cursor(&mut self) {
+        self.sent_cursor = None;
+    }
+
+    /// Stops sending messages to a client which has left.
+    pub fn remove(&mut self, id: u32) {
+        self.cursors.remove(&id);
+        if let Role::Host { peers, .. } = &self.role {
+            if let Ok(mut peers) = peers.lock() {
+                peers.retain(|peer| peer.id != id);
+            }
+        }
+    }
+
+    pub fn cursors(&self) -> &BTreeMap<u32, Position> {
+        &self.cursors
+    }
+
+    pub fn set_cursor(&mut self, id: u32, position: Position) {
+        self.cursors.insert(id, position);
+    }
+}
+
+impl Drop for Session {
+    /// Closes the connections, which are shared with the threads reading from them.
+    fn drop(&mut self) {
+        match &self.role {
+            Role::Host { peers, .. } => {
+                if let Ok(peers) = peers.lock() {
+                    for peer in peers.iter() {
+                        let _ = peer.stream.shutdown(Shutdown::Both);
+                    }
+                }
+            }
+            Role::Client { stream } => {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+}
+
+/// Reads messages from a participant on a background thread until the connection closes.
+fn read_messages(id: u32, stream: TcpStream, sender: Sender<Event>) {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            match Message::parse(&line) {
+                Some(message) => {
+                    if sender.send(Event::Remote(id, message)).is_err() {
+                        return;
+                    }
+                }
+                None => error!("Invalid message from participant {}: {}", id, line),
+            }
+        }
+        let _ = sender.send(Event::ParticipantLeft(id));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(Message::Text(String::from("Lorem\nipsum \\n\r\n"));   "text")]
+    #[test_case(Message::Change { pos: 3, deleted: 2, inserted: String::from("a b\n") }; "change")]
+    #[test_case(Message::Change { pos: 3, deleted: 2, inserted: String::new() };         "deletion")]
+    #[test_case(Message::Cursor { id: 2, position: Position { row: 4, column: 7 } };     "cursor")]
+    #[test_case(Message::Leave { id: 1 };                                               "leave")]
+    fn round_trip(message: Message) {
+        let line = message.encode();
+        assert_eq!(1, line.lines().count());
+        assert_eq!(Some(message), Message::parse(line.trim_end_matches('\n')));
+    }
+
+    #[test_case("hello";          "unknown")]
+    #[test_case("change 1";       "missing_fields")]
+    #[test_case("cursor 1 a 2";   "invalid_number")]
+    fn invalid_message(line: &str) {
+        assert_eq!(None, Message::parse(line));
+    }
+
+    #[test]
+    fn client_and_host_exchange_messages() {
+        let timeout = std::time::Duration::from_secs(5);
+        let (host_sender, host_events) = std::sync::mpsc::channel();
+        let (client_sender, client_events) = std::sync::mpsc::channel();
+        let mut host = Session::serve("127.0.0.1:0", host_sender).unwrap();
+        let mut client = Session::connect(host.address().unwrap(), client_sender).unwrap();
+
+        let joined = host_events.recv_timeout(timeout).unwrap();
+        assert!(matches!(joined, Event::ParticipantJoined(1)));
+
+        let change = Message::Change {
+            pos: 0,
+            deleted: 0,
+            inserted: String::from("Lorem"),
+        };
+        client.send(&change);
+        let received = host_events.recv_timeout(timeout).unwrap();
+        assert!(matches!(received, Event::Remote(1, message) if message == change));
+
+        let text = Message::Text(String::from("Lorem\n"));
+        host.send_to(1, &text);
+        let received = client_events.recv_timeout(timeout).unwrap();
+        assert!(matches!(received, Event::Remote(HOST, message) if message == text));
+
+        drop(client);
+        let left = host_events.recv_timeout(timeout).unwrap();
+        assert!(matches!(left, Event::ParticipantLeft(1)));
+    }
+}
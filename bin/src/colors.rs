@@ -0,0 +1,289 @@
+use std::borrow::Cow;
+
+use serde::Deserialize;
+
+/// The colours a terminal can show. Styles are drawn with the named colours and the 256-colour
+/// palette, and mapped down to what the terminal supports as they're written to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+pub enum ColorSupport {
+    /// No colours at all. Other styles, such as bold and reverse video, are kept.
+    #[serde(rename = "none")]
+    None,
+    /// The 8 named colours and their bright variants.
+    #[serde(rename = "16")]
+    Basic,
+    /// The 256-colour palette.
+    #[serde(rename = "256")]
+    Extended,
+    /// Any 24-bit colour.
+    #[serde(rename = "truecolor")]
+    TrueColor,
+}
+
+impl ColorSupport {
+    /// Detects the colours the terminal supports from the environment.
+    pub fn detect() -> ColorSupport {
+        ColorSupport::from_env(|name| std::env::var(name).ok())
+    }
+
+    /// Works out the colours a terminal supports from its environment variables. `NO_COLOR` turns
+    /// colours off, `COLORTERM` is set by terminals with 24-bit colour, and `TERM` names the
+    /// terminal otherwise. Windows Terminal sets none of these but supports 24-bit colour.
+    ///
+    /// # Arguments
+    ///
+    /// * 'var' - Looks up an environment variable.
+    pub fn from_env(var: impl Fn(&str) -> Option<String>) -> ColorSupport {
+        if var("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            return ColorSupport::None;
+        }
+        if var("COLORTERM").is_some_and(|v| v == "truecolor" || v == "24bit") {
+            return ColorSupport::TrueColor;
+        }
+        if var("WT_SESSION").is_some() {
+            return ColorSupport::TrueColor;
+        }
+        match var("TERM").as_deref() {
+            Some("dumb") => ColorSupport::None,
+            Some(term) if term.contains("256color") => ColorSupport::Extended,
+            _ => ColorSupport::Basic,
+        }
+    }
+
+    /// Rewrites the colours in the escape sequences in some text to ones the terminal supports.
+    /// The text is returned as it is if nothing needs to change.
+    ///
+    /// # Arguments
+    ///
+    /// * 'text' - Text styled with SGR escape sequences.
+    pub fn downgrade<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if *self == ColorSupport::TrueColor || !text.contains("\x1b[") {
+            return text.into();
+        }
+
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("\x1b[") {
+            result += &rest[..start];
+            let params = &rest[start + 2..];
+            let end = params.find(|c: char| !c.is_ascii_digit() && c != ';' && c != ':');
+            match end {
+                // An empty sequence resets the style, like `0`.
+                Some(0) if params.starts_with('m') => {
+                    result += "\x1b[m";
+                    rest = &params[1..];
+                }
+                Some(end) if params[end..].starts_with('m') => {
+                    let codes = self.map_codes(&params[..end]);
+                    if !codes.is_empty() {
+                        result += &format!("\x1b[{}m", codes);
+                    }
+                    rest = &params[end + 1..];
+                }
+                // Sequences other than styles are left alone.
+                _ => {
+                    result += "\x1b[";
+                    rest = params;
+                }
+            }
+        }
+        result += rest;
+        result.into()
+    }
+
+    /// Maps the parameters of an SGR sequence, dropping colours when there are none. Parameters
+    /// which can't be read, such as colours with colon-separated values or values over 255, are
+    /// left as they are, or dropped along with the rest of the sequence when there are no
+    /// colours.
+    fn map_codes(&self, params: &str) -> String {
+        let codes: Result<Vec<u8>, _> = params
+            .split(';')
+            .map(|c| match c {
+                "" => Ok(0),
+                c => c.parse::<u8>(),
+            })
+            .collect();
+        let mut codes = match codes {
+            Ok(codes) => codes.into_iter(),
+            Err(_) if *self == ColorSupport::None => return String::new(),
+            Err(_) => return params.to_string(),
+        };
+        let mut mapped: Vec<String> = vec![];
+        while let Some(code) = codes.next() {
+            let background = matches!(code, 40..=49 | 100..=107);
+            let color = match code {
+                38 | 48 => match codes.next() {
+                    Some(5) => codes.next().map(Color::Indexed),
+                    Some(2) => {
+                        let mut channel = || codes.next().unwrap_or(0);
+                        Some(Color::Rgb(channel(), channel(), channel()))
+                    }
+                    _ => None,
+                },
+                30..=37 | 39 | 40..=47 | 49 | 90..=97 | 100..=107 => {
+                    if *self != ColorSupport::None {
+                        mapped.push(code.to_string());
+                    }
+                    continue;
+                }
+                _ => {
+                    mapped.push(code.to_string());
+                    continue;
+                }
+            };
+            if let Some(color) = color {
+                if let Some(code) = self.map_color(color, background) {
+                    mapped.push(code);
+                }
+            }
+        }
+        mapped.join(";")
+    }
+
+    /// The SGR parameters for a colour, mapped to the nearest colour the terminal supports.
+    fn map_color(&self, color: Color, background: bool) -> Option<String> {
+        let base = if background { 40 } else { 30 };
+        match (self, color) {
+            (ColorSupport::None, _) => None,
+            (ColorSupport::Basic, color) => {
+                let (r, g, b) = color.rgb();
+                Some(match nearest_basic(r, g, b) {
+                    index @ 0..=7 => format!("{}", base + index),
+                    index => format!("{}", base + 60 + index - 8),
+                })
+            }
+            (_, Color::Indexed(index)) => Some(format!("{};5;{}", base + 8, index)),
+            (ColorSupport::Extended, Color::Rgb(r, g, b)) => {
+                Some(format!("{};5;{}", base + 8, nearest_indexed(r, g, b)))
+            }
+            (ColorSupport::TrueColor, Color::Rgb(r, g, b)) => {
+                Some(format!("{};2;{};{};{}", base + 8, r, g, b))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Color {
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// The values of the 16 basic colours, as xterm shows them.
+const BASIC: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// The levels of each channel in the 6×6×6 colour cube of the 256-colour palette.
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl Color {
+    fn rgb(&self) -> (u8, u8, u8) {
+        match *self {
+            Color::Indexed(index @ 0..=15) => BASIC[index as usize],
+            Color::Indexed(index @ 16..=231) => {
+                let index = index - 16;
+                (
+                    CUBE[(index / 36) as usize],
+                    CUBE[(index / 6 % 6) as usize],
+                    CUBE[(index % 6) as usize],
+                )
+            }
+            Color::Indexed(index) => {
+                let level = 8 + (index - 232) * 10;
+                (level, level, level)
+            }
+            Color::Rgb(r, g, b) => (r, g, b),
+        }
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let channel = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    channel(r1, r2) + channel(g1, g2) + channel(b1, b2)
+}
+
+/// The index of the basic colour closest to a colour.
+fn nearest_basic(r: u8, g: u8, b: u8) -> u8 {
+    (0..16)
+        .min_by_key(|&i| distance(BASIC[i as usize], (r, g, b)))
+        .unwrap_or(0)
+}
+
+/// The index of the colour in the 256-colour palette closest to a colour, from the colour cube
+/// or the greyscale ramp.
+fn nearest_indexed(r: u8, g: u8, b: u8) -> u8 {
+    (16..=255)
+        .min_by_key(|&i| distance(Color::Indexed(i).rgb(), (r, g, b)))
+        .unwrap_or(16)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(&[],                                          ColorSupport::Basic;     "nothing_set")]
+    #[test_case(&[("TERM", "xterm-256color")],                ColorSupport::Extended;  "term_256")]
+    #[test_case(&[("TERM", "xterm"), ("COLORTERM", "24bit")], ColorSupport::TrueColor; "colorterm")]
+    #[test_case(&[("WT_SESSION", "1")],                       ColorSupport::TrueColor; "windows_terminal")]
+    #[test_case(&[("TERM", "dumb")],                          ColorSupport::None;      "dumb")]
+    #[test_case(&[("TERM", "xterm-256color"), ("NO_COLOR", "1")], ColorSupport::None;  "no_color")]
+    fn detect(vars: &[(&str, &str)], expected: ColorSupport) {
+        let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+        let actual = ColorSupport::from_env(|name| vars.get(name).map(|v| v.to_string()));
+        assert_eq!(expected, actual);
+    }
+
+    #[test_case(ColorSupport::TrueColor, "\x1b[38;5;244m▒\x1b[0m";        "truecolor_unchanged")]
+    #[test_case(ColorSupport::Extended,  "\x1b[38;5;244m▒\x1b[0m";        "extended_unchanged")]
+    #[test_case(ColorSupport::Basic,     "\x1b[90m▒\x1b[0m";              "basic_mapped")]
+    #[test_case(ColorSupport::None,      "▒\x1b[0m";                      "none_dropped")]
+    fn downgrade_indexed(support: ColorSupport, expected: &str) {
+        assert_eq!(expected, support.downgrade("\x1b[38;5;244m▒\x1b[0m"));
+    }
+
+    #[test_case("\x1b[37m\x1b[48;5;238m┃\x1b[0m", "\x1b[37m\x1b[100m┃\x1b[0m"; "background")]
+    #[test_case("\x1b[1;38;5;196mx\x1b[0m",     "\x1b[1;91mx\x1b[0m";        "combined")]
+    #[test_case("\x1b[38;2;0;0;200mx",          "\x1b[34mx";                 "rgb")]
+    #[test_case("\x1b[5Gx\x1b[m",               "\x1b[5Gx\x1b[m";            "other_sequences")]
+    #[test_case("\x1b[38:2::0:0:200mx",         "\x1b[38:2::0:0:200mx";      "colon_separated")]
+    #[test_case("\x1b[38;5;300mx",              "\x1b[38;5;300mx";           "out_of_range")]
+    #[test_case("\x1b[1;;31mx",                 "\x1b[1;0;31mx";             "empty_parameter")]
+    fn downgrade_to_basic(text: &str, expected: &str) {
+        assert_eq!(expected, ColorSupport::Basic.downgrade(text));
+    }
+
+    #[test_case("\x1b[1;31mx\x1b[0m", "\x1b[1mx\x1b[0m"; "attributes_kept")]
+    #[test_case("\x1b[31mx\x1b[0m",   "x\x1b[0m";        "colour_only")]
+    #[test_case("\x1b[38:5:196mx",    "x";               "unreadable")]
+    fn downgrade_to_none(text: &str, expected: &str) {
+        assert_eq!(expected, ColorSupport::None.downgrade(text));
+    }
+
+    #[test]
+    fn rgb_to_indexed() {
+        assert_eq!(
+            "\x1b[48;5;21mx",
+            ColorSupport::Extended.downgrade("\x1b[48;2;0;0;255mx")
+        );
+    }
+}
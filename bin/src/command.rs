@@ -0,0 +1,879 @@
+use std::path::PathBuf;
+
+use crate::case::Case;
+use crate::completion::{expand_home, Source};
+use crate::conflict::Keep;
+use crate::dropped;
+use crate::export::Format;
+use crate::macros;
+use crate::marks;
+use crate::range::{self, Address, LineRange};
+use crate::registers;
+use crate::sort::SortOptions;
+use crate::substitute::Substitution;
+use crate::surround::{self, Pair};
+use crate::textobject::TextObject;
+use crate::undo::Distance;
+
+/// A command entered at the command prompt.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Saves the document, optionally to a new path. Forcing the write will attempt to save
+    /// documents which are read-only.
+    Write {
+        path: Option<PathBuf>,
+        force: bool,
+    },
+    /// Saves every modified document which has a path.
+    WriteAll,
+    /// Closes the editor.
+    Quit,
+    /// Closes the editor, which is only done with unsaved changes when forced.
+    QuitAll {
+        force: bool,
+    },
+    /// Closes every buffer except the current one. Modified buffers are only closed when forced.
+    CloseOthers {
+        force: bool,
+    },
+    /// Opens a document, switching to it if it's already open. Without a path the current
+    /// document is reloaded from disk, which is only done for modified documents when forced.
+    Edit {
+        path: Option<PathBuf>,
+        force: bool,
+    },
+    /// Opens several documents, which are quoted or have their spaces escaped as in a shell.
+    Open(Vec<PathBuf>),
+    /// Moves the current document's file to a new path, after asking unless forced.
+    Rename {
+        path: PathBuf,
+        force: bool,
+    },
+    /// Deletes the current document's file and closes it, after asking unless forced.
+    DeleteFile {
+        force: bool,
+    },
+    /// Makes the current document read-only, or editable again.
+    ReadOnly(bool),
+    /// Opens an untitled document in a new buffer.
+    New,
+    /// Opens the drafts saved from unnamed buffers when the editor last exited, or discards
+    /// them if forced.
+    Drafts {
+        force: bool,
+    },
+    /// Opens a picker listing recently opened files.
+    Recent,
+    /// Opens a picker listing the open buffers, with a preview of the highlighted one.
+    Buffers,
+    /// Opens the help, at a topic if one is given.
+    Help(Option<String>),
+    /// Moves the cursor to the start of a line.
+    Goto(u32),
+    /// Closes, opens or toggles the fold at the cursor.
+    Fold(FoldAction),
+    /// Moves the cursor to the next occurrence of some text, or the previous one.
+    Find {
+        pattern: String,
+        forward: bool,
+    },
+    /// Changes the case of the selected text.
+    ChangeCase(Case),
+    /// Sorts the selected lines, or the whole document if nothing is selected.
+    Sort(SortOptions),
+    /// Removes repeated lines from the selection, or the whole document.
+    Unique,
+    /// Reverses the order of the selected lines, or the whole document.
+    Reverse,
+    /// Aligns the selected lines, or the whole document, into columns on a delimiter.
+    Align(String),
+    /// Re-wraps the selected paragraphs, or the paragraph at the cursor, to the text width.
+    Reflow,
+    /// Works out the indentation of the selected lines, or the whole document, from the
+    /// filetype's indent rules.
+    Reindent,
+    /// Shows details of the character under the cursor and the cursor's position.
+    Inspect,
+    /// Shows details of the current document and its file in a panel.
+    Info,
+    /// Runs a shell command, showing its output in a new buffer.
+    Shell(String),
+    /// Runs the configured build command, with any extra arguments, and collects the error
+    /// locations from its output.
+    Make(Option<String>),
+    /// Jumps to the next error location from the last build.
+    NextError,
+    /// Jumps to the previous error location from the last build.
+    PreviousError,
+    /// Opens the error locations from the last build in a new buffer.
+    ListErrors,
+    Undo,
+    Redo,
+    /// Opens a picker showing the document's undo tree, to move to any revision.
+    UndoTree,
+    /// Moves back through the document's history by a number of revisions or an amount of time.
+    Earlier(Distance),
+    /// Moves forward through the document's history by a number of revisions or an amount of
+    /// time.
+    Later(Distance),
+    /// Toggles a bookmark sign on the current line, or the selected lines.
+    Bookmark,
+    /// Toggles a TODO sign on the current line, or the selected lines.
+    Todo,
+    /// Jumps to the next line with a sign.
+    NextSign,
+    /// Jumps to the previous line with a sign.
+    PreviousSign,
+    /// Starts or stops merge mode, which counts the merge conflicts left in the document.
+    Merge,
+    /// Resolves the merge conflict at the cursor by keeping one or both sides.
+    Resolve(Keep),
+    /// Jumps to the next merge conflict.
+    NextConflict,
+    /// Jumps to the previous merge conflict.
+    PreviousConflict,
+    /// Shows or hides the minimap.
+    ToggleMinimap,
+    /// Shows or hides the scrollbar.
+    ToggleScrollbar,
+    /// Shows or hides the guides at each level of indentation.
+    ToggleIndentGuides,
+    /// Switches whether word motions in the current buffer stop at the parts of camelCase and
+    /// snake_case words.
+    ToggleSubwords,
+    /// Turns paste mode on or off, where Enter and Tab are inserted as they're typed.
+    TogglePaste,
+    /// Enters or leaves zen mode, which centers the text and hides everything around it.
+    ToggleZen,
+    /// Switches between displaying escape sequences as colours and as raw text.
+    ToggleAnsi,
+    /// Shows or hides how long each stage of the last frame took.
+    TogglePerf,
+    /// Opens the most recent log messages in a new buffer.
+    Messages,
+    /// Exports the selected lines, or the whole document, to a file. Without a path the format's
+    /// extension is added to the document's path. Existing files are only replaced when forced.
+    Export {
+        format: Format,
+        path: Option<PathBuf>,
+        force: bool,
+    },
+    /// Pipes lines through a shell command, replacing them with its output.
+    Filter {
+        lines: FilterLines,
+        command: String,
+    },
+    /// Starts recording a macro into a register, or stops recording without one.
+    Record(Option<char>),
+    /// Plays the macro in a register, once on each selected line if there's a selection.
+    Play(char),
+    /// Opens the macro in a register as text, which is stored back when it's saved.
+    EditMacro(char),
+    /// Sets a global mark at the cursor, which can be jumped to from any file.
+    Mark(char),
+    /// Removes a global mark.
+    DeleteMark(char),
+    /// Opens a picker listing the global marks, to jump to one.
+    Marks,
+    /// Opens the file a global mark was set in and moves the cursor to the mark, written `'A`.
+    JumpToMark(char),
+    /// Deletes the current line, or the selected lines, into a register if one is given.
+    DeleteLines(Option<char>),
+    /// Copies the current line, or the selected lines, into a register if one is given.
+    Yank(Option<char>),
+    /// Puts the text in a register, or the unnamed register, after the cursor. Whole lines are
+    /// put below the current line.
+    Put(Option<char>),
+    /// Opens a picker listing the registers with text in them, to put one.
+    Registers,
+    /// Completes the text before the cursor, from a list of completions if there's more than
+    /// one.
+    Complete(Source),
+    /// Selects a text object around the cursor, such as a word or the text in brackets.
+    Select(TextObject),
+    /// Surrounds the selection, or a text object around the cursor, with a pair such as
+    /// brackets, quotes or tags.
+    Surround {
+        pair: Pair,
+        object: Option<TextObject>,
+    },
+    /// Deletes the pair around the cursor.
+    Unsurround(TextObject),
+    /// Replaces the pair around the cursor with another pair.
+    Resurround {
+        target: TextObject,
+        pair: Pair,
+    },
+    /// Replaces matches of a pattern on the current line, or the selected lines.
+    Substitute(Substitution),
+    /// Runs a command on a range of lines in place of the selection, or moves the cursor to the
+    /// last line of the range without one.
+    Ranged {
+        lines: LineRange,
+        command: Option<Box<Command>>,
+    },
+}
+
+/// What a fold command does to the fold at the cursor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FoldAction {
+    Close,
+    Open,
+    /// Opens every fold in the document.
+    OpenAll,
+    Toggle,
+}
+
+/// The lines piped through a shell command by a filter.
+#[derive(Debug, PartialEq)]
+pub enum FilterLines {
+    /// The lines covered by the selection, written `'<,'>`.
+    Selection,
+    /// Every line in the document, written `%`.
+    All,
+    /// The lines given by the range before the command, such as `10,20!sort`.
+    Range,
+}
+
+impl Command {
+    /// Parses the text entered at the command prompt. Commands take the form `name[!] [argument]`,
+    /// where a trailing `!` forces the command.
+    ///
+    /// # Arguments
+    ///
+    /// * 'input' - The text entered at the prompt, with or without a leading ':'.
+    pub fn parse(input: &str) -> Result<Command, String> {
+        let input = input.trim();
+        let input = input.strip_prefix(':').unwrap_or(input).trim_start();
+
+        if let Some(shell) = parse_shell(input) {
+            return shell;
+        }
+        if let Some(result) = parse_find(input) {
+            return result;
+        }
+        if let Some(result) = parse_substitute(input) {
+            return result;
+        }
+        if let (Some(lines), rest) = range::parse(input)? {
+            return parse_ranged(lines, rest.trim_start());
+        }
+
+        let (name, argument) = match input.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, Some(argument.trim())),
+            None => (input, None),
+        };
+        let (name, force) = match name.strip_suffix('!') {
+            Some(name) => (name, true),
+            None => (name, false),
+        };
+
+        if let Some(case) = Case::from_name(name) {
+            return Ok(Command::ChangeCase(case));
+        }
+        if let Some(register) = name.strip_prefix('@') {
+            return macros::parse_register(register).map(Command::Play);
+        }
+
+        match name {
+            "w" | "write" => Ok(Command::Write {
+                path: argument.filter(|a| !a.is_empty()).map(expand_home),
+                force,
+            }),
+            "wa" | "wall" => Ok(Command::WriteAll),
+            "fold" | "foldclose" => Ok(Command::Fold(FoldAction::Close)),
+            "foldopen" if force => Ok(Command::Fold(FoldAction::OpenAll)),
+            "foldopen" => Ok(Command::Fold(FoldAction::Open)),
+            "foldtoggle" => Ok(Command::Fold(FoldAction::Toggle)),
+            "q" | "quit" => Ok(Command::Quit),
+            "qa" | "qall" => Ok(Command::QuitAll { force }),
+            "only" | "closeothers" => Ok(Command::CloseOthers { force }),
+            "e" | "edit" => Ok(Command::Edit {
+                path: argument.filter(|a| !a.is_empty()).map(expand_home),
+                force,
+            }),
+            "revert" => Ok(Command::Edit { path: None, force }),
+            "open" => {
+                let paths = dropped::split_words(argument.unwrap_or_default());
+                match paths.is_empty() {
+                    true => Err(String::from("No file name entered")),
+                    false => Ok(Command::Open(
+                        paths.iter().map(|path| expand_home(path)).collect(),
+                    )),
+                }
+            }
+            "rename" => match argument.filter(|a| !a.is_empty()) {
+                Some(path) => Ok(Command::Rename {
+                    path: expand_home(path),
+                    force,
+                }),
+                None => Err(String::from("No new path entered")),
+            },
+            "delete-file" => Ok(Command::DeleteFile { force }),
+            "ro" | "readonly" => Ok(Command::ReadOnly(true)),
+            "noro" | "noreadonly" => Ok(Command::ReadOnly(false)),
+            "d" | "delete" => parse_target(argument).map(Command::DeleteLines),
+            "y" | "yank" => parse_target(argument).map(Command::Yank),
+            "pu" | "put" => parse_register(argument).map(Command::Put),
+            "reg" | "registers" => Ok(Command::Registers),
+            "complete" => Source::parse(argument.unwrap_or_default()).map(Command::Complete),
+            "select" => TextObject::parse(argument.unwrap_or_default()).map(Command::Select),
+            "surround" | "ys" => parse_surround(argument.unwrap_or_default()),
+            "unsurround" | "ds" => {
+                surround::target(argument.unwrap_or_default().trim()).map(Command::Unsurround)
+            }
+            "resurround" | "cs" => parse_resurround(argument.unwrap_or_default()),
+            "new" | "enew" => Ok(Command::New),
+            "drafts" => Ok(Command::Drafts { force }),
+            "recent" | "oldfiles" => Ok(Command::Recent),
+            "buffers" | "ls" => Ok(Command::Buffers),
+            "h" | "help" => Ok(Command::Help(
+                argument.filter(|a| !a.is_empty()).map(String::from),
+            )),
+            "sort" => SortOptions::parse(argument.unwrap_or_default(), force).map(Command::Sort),
+            "uniq" | "unique" => Ok(Command::Unique),
+            "reverse" => Ok(Command::Reverse),
+            "reflow" | "gq" => Ok(Command::Reflow),
+            "=" | "reindent" => Ok(Command::Reindent),
+            "inspect" | "ga" => Ok(Command::Inspect),
+            "info" | "fileinfo" => Ok(Command::Info),
+            "make" => Ok(Command::Make(
+                argument.filter(|a| !a.is_empty()).map(String::from),
+            )),
+            "cn" | "cnext" => Ok(Command::NextError),
+            "cp" | "cprev" | "cprevious" => Ok(Command::PreviousError),
+            "copen" | "clist" => Ok(Command::ListErrors),
+            "u" | "undo" => Ok(Command::Undo),
+            "red" | "redo" => Ok(Command::Redo),
+            "undotree" | "undolist" => Ok(Command::UndoTree),
+            "ea" | "earlier" => Distance::parse(argument.unwrap_or_default()).map(Command::Earlier),
+            "lat" | "later" => Distance::parse(argument.unwrap_or_default()).map(Command::Later),
+            "ansi" => Ok(Command::ToggleAnsi),
+            "minimap" => Ok(Command::ToggleMinimap),
+            "scrollbar" => Ok(Command::ToggleScrollbar),
+            "guides" => Ok(Command::ToggleIndentGuides),
+            "subword" => Ok(Command::ToggleSubwords),
+            "paste" => Ok(Command::TogglePaste),
+            "zen" => Ok(Command::ToggleZen),
+            "perf" => Ok(Command::TogglePerf),
+            "mes" | "messages" => Ok(Command::Messages),
+            "bm" | "bookmark" => Ok(Command::Bookmark),
+            "todo" => Ok(Command::Todo),
+            "sn" | "signnext" => Ok(Command::NextSign),
+            "sN" | "signprev" => Ok(Command::PreviousSign),
+            "merge" => Ok(Command::Merge),
+            "ours" => Ok(Command::Resolve(Keep::Ours)),
+            "theirs" => Ok(Command::Resolve(Keep::Theirs)),
+            "both" => Ok(Command::Resolve(Keep::Both)),
+            "xn" | "conflictnext" => Ok(Command::NextConflict),
+            "xN" | "conflictprev" => Ok(Command::PreviousConflict),
+            "export" => {
+                let (format, path) = match argument.and_then(|a| a.split_once(char::is_whitespace))
+                {
+                    Some((format, path)) => (format, Some(path.trim())),
+                    None => (argument.unwrap_or_default(), None),
+                };
+                Ok(Command::Export {
+                    format: Format::parse(format)?,
+                    path: path.map(expand_home),
+                    force,
+                })
+            }
+            "rec" | "record" => match argument.filter(|a| !a.is_empty()) {
+                Some(register) => {
+                    macros::parse_register(register).map(|r| Command::Record(Some(r)))
+                }
+                None => Ok(Command::Record(None)),
+            },
+            "k" | "mark" => marks::parse_name(argument.unwrap_or_default()).map(Command::Mark),
+            "delm" | "delmark" => {
+                marks::parse_name(argument.unwrap_or_default()).map(Command::DeleteMark)
+            }
+            "marks" => Ok(Command::Marks),
+            "macro" => macros::parse_register(argument.unwrap_or_default()).map(Command::EditMacro),
+            "align" => match argument.filter(|a| !a.is_empty()) {
+                Some(delimiter) => Ok(Command::Align(delimiter.to_string())),
+                None => Err(String::from("No delimiter entered")),
+            },
+            "" => Err(String::from("No command entered")),
+            _ => Err(format!("Unknown command: {}", name)),
+        }
+    }
+
+    /// Whether the command acts on lines, so it can be given a range.
+    fn takes_range(&self) -> bool {
+        matches!(
+            self,
+            Command::Sort(_)
+                | Command::Unique
+                | Command::Reverse
+                | Command::Align(_)
+                | Command::Reflow
+                | Command::Reindent
+                | Command::Export { .. }
+                | Command::Filter { .. }
+                | Command::Play(_)
+                | Command::Bookmark
+                | Command::Todo
+                | Command::DeleteLines(_)
+                | Command::Yank(_)
+                | Command::Substitute(_)
+        )
+    }
+}
+
+/// Parses the command after a range. A lone line number moves the cursor to that line, and a
+/// shell command after a range filters the lines in it.
+///
+/// # Arguments
+///
+/// * 'lines' - The range before the command.
+/// * 'rest' - The text after the range.
+fn parse_ranged(lines: LineRange, rest: &str) -> Result<Command, String> {
+    if rest.is_empty() {
+        if let LineRange::Lines(start, end) = lines {
+            match (start.address, start.offset, start == end) {
+                (Address::Line(line), 0, true) => return Ok(Command::Goto(line)),
+                // Global marks can be in other files, so jumping to them opens the file.
+                (Address::Mark(name), 0, true) if name.is_ascii_uppercase() => {
+                    return Ok(Command::JumpToMark(name))
+                }
+                _ => {}
+            }
+        }
+        return Ok(Command::Ranged {
+            lines,
+            command: None,
+        });
+    }
+
+    let command = match Command::parse(rest)? {
+        Command::Shell(command) => Command::Filter {
+            lines: FilterLines::Range,
+            command,
+        },
+        command if command.takes_range() => command,
+        _ => return Err(String::from("The command doesn't take a range")),
+    };
+    Ok(Command::Ranged {
+        lines,
+        command: Some(Box::new(command)),
+    })
+}
+
+/// Parses the register given to a command, which is optional.
+fn parse_register(argument: Option<&str>) -> Result<Option<char>, String> {
+    match argument.filter(|a| !a.is_empty()) {
+        Some(name) => registers::parse_name(name).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Parses the register given to a command which writes to it, which is optional.
+fn parse_target(argument: Option<&str>) -> Result<Option<char>, String> {
+    match argument.filter(|a| !a.is_empty()) {
+        Some(name) => registers::parse_target(name),
+        None => Ok(None),
+    }
+}
+
+/// Parses the pair to surround text with, followed by the text object to surround if it's not
+/// the selection.
+fn parse_surround(argument: &str) -> Result<Command, String> {
+    let (pair, object) = surround::split_pair(argument.trim());
+    Ok(Command::Surround {
+        pair: Pair::parse(pair)?,
+        object: match object {
+            "" => None,
+            object => Some(TextObject::parse(object)?),
+        },
+    })
+}
+
+/// Parses the pair around the cursor to change, followed by the pair to change it to.
+fn parse_resurround(argument: &str) -> Result<Command, String> {
+    let argument = argument.trim();
+    let (target, pair) = argument.split_once(' ').unwrap_or((argument, ""));
+    Ok(Command::Resurround {
+        target: surround::target(target)?,
+        pair: Pair::parse(pair.trim())?,
+    })
+}
+
+/// Parses a search, `/text` to search forwards or `?text` to search backwards. Returns None if
+/// the input isn't a search.
+fn parse_find(input: &str) -> Option<Result<Command, String>> {
+    let (pattern, forward) = match input.strip_prefix('/') {
+        Some(pattern) => (pattern, true),
+        None => (input.strip_prefix('?')?, false),
+    };
+    if pattern.is_empty() {
+        return Some(Err(String::from("No search text entered")));
+    }
+    Some(Ok(Command::Find {
+        pattern: pattern.to_string(),
+        forward,
+    }))
+}
+
+/// Parses a substitution, `s/pattern/replacement/flags`, which can use any delimiter which isn't
+/// a letter, digit or space. Returns None if the input isn't a substitution.
+fn parse_substitute(input: &str) -> Option<Result<Command, String>> {
+    let rest = input.strip_prefix('s')?;
+    if rest.starts_with(|c: char| c.is_alphanumeric() || c.is_whitespace()) {
+        return None;
+    }
+    Some(Substitution::parse(rest).map(Command::Substitute))
+}
+
+/// Parses a shell command, `!command`, or a filter, `'<,'>!command` or `%!command`. Returns None
+/// if the input isn't a shell command.
+fn parse_shell(input: &str) -> Option<Result<Command, String>> {
+    let (lines, command) = if let Some(command) = input.strip_prefix("'<,'>!") {
+        (Some(FilterLines::Selection), command)
+    } else if let Some(command) = input.strip_prefix("%!") {
+        (Some(FilterLines::All), command)
+    } else {
+        (None, input.strip_prefix('!')?)
+    };
+
+    let command = command.trim().to_string();
+    if command.is_empty() {
+        return Some(Err(String::from("No shell command entered")));
+    }
+    Some(Ok(match lines {
+        Some(lines) => Command::Filter { lines, command },
+        None => Command::Shell(command),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::textobject::Kind;
+
+    #[test_case("w", None, false;                     "write")]
+    #[test_case(":write", None, false;                "write_with_colon")]
+    #[test_case("w!", None, true;                     "force_write")]
+    #[test_case("w other.txt", Some("other.txt"), false; "write_to_path")]
+    #[test_case("w!  other.txt ", Some("other.txt"), true; "force_write_to_path")]
+    fn parse_write(input: &str, path: Option<&str>, force: bool) {
+        let expected = Command::Write {
+            path: path.map(PathBuf::from),
+            force,
+        };
+        let actual = Command::parse(input);
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn parse_quit() {
+        assert_eq!(Ok(Command::Quit), Command::parse("q"));
+    }
+
+    #[test_case("e", None, false;                       "reload")]
+    #[test_case("e!", None, true;                       "force_reload")]
+    #[test_case("revert!", None, true;                  "revert")]
+    #[test_case("edit other.txt", Some("other.txt"), false; "open_path")]
+    fn parse_edit(input: &str, path: Option<&str>, force: bool) {
+        let expected = Command::Edit {
+            path: path.map(PathBuf::from),
+            force,
+        };
+        assert_eq!(Ok(expected), Command::parse(input));
+    }
+
+    #[test]
+    fn parse_open() {
+        let expected = vec![PathBuf::from("a b.txt"), PathBuf::from("c.txt")];
+        assert_eq!(
+            Ok(Command::Open(expected)),
+            Command::parse("open a\\ b.txt 'c.txt'")
+        );
+        assert_eq!(
+            Err(String::from("No file name entered")),
+            Command::parse("open")
+        );
+    }
+
+    #[test_case("wa", Command::WriteAll;                            "write_all")]
+    #[test_case("qa", Command::QuitAll { force: false };            "quit_all")]
+    #[test_case("qall!", Command::QuitAll { force: true };          "force_quit_all")]
+    #[test_case("only", Command::CloseOthers { force: false };      "close_others")]
+    #[test_case("closeothers!", Command::CloseOthers { force: true }; "force_close_others")]
+    fn parse_buffer_commands(input: &str, expected: Command) {
+        assert_eq!(Ok(expected), Command::parse(input));
+    }
+
+    #[test_case(":!ls -la", Ok(Command::Shell(String::from("ls -la"))); "shell")]
+    #[test_case("'<,'>!sort -u", Ok(Command::Filter { lines: FilterLines::Selection, command: String::from("sort -u") }); "filter_selection")]
+    #[test_case("%! jq .", Ok(Command::Filter { lines: FilterLines::All, command: String::from("jq .") }); "filter_all")]
+    #[test_case("!", Err(String::from("No shell command entered")); "missing_command")]
+    fn parse_shell_command(input: &str, expected: Result<Command, String>) {
+        assert_eq!(expected, Command::parse(input));
+    }
+
+    #[test_case("make", Command::Make(None);                            "make")]
+    #[test_case("make test", Command::Make(Some(String::from("test"))); "make_with_arguments")]
+    #[test_case("cn", Command::NextError;                               "next_error")]
+    #[test_case("cprevious", Command::PreviousError;                    "previous_error")]
+    #[test_case("copen", Command::ListErrors;                           "list_errors")]
+    fn parse_quickfix(input: &str, expected: Command) {
+        assert_eq!(Ok(expected), Command::parse(input));
+    }
+
+    #[test_case("u", Command::Undo;             "undo")]
+    #[test_case("redo", Command::Redo;          "redo")]
+    #[test_case("undotree", Command::UndoTree;  "undo_tree")]
+    fn parse_undo(input: &str, expected: Command) {
+        assert_eq!(Ok(expected), Command::parse(input));
+    }
+
+    #[test_case("earlier 30s", Ok(Command::Earlier(Distance::Time(Duration::from_secs(30)))); "earlier")]
+    #[test_case("later 2m", Ok(Command::Later(Distance::Time(Duration::from_secs(120))));    "later")]
+    #[test_case("ea", Ok(Command::Earlier(Distance::Revisions(1)));                         "default")]
+    #[test_case("later soon", Err(String::from("Invalid time: soon"));                     "invalid")]
+    fn parse_time_travel(input: &str, expected: Result<Command, String>) {
+        assert_eq!(expected, Command::parse(input));
+    }
+
+    #[test_case("export html", Ok(Command::Export { format: Format::Html, path: None, force: false }); "export")]
+    #[test_case("export! ansi out.ans", Ok(Command::Export { format: Format::Ansi, path: Some(PathBuf::from("out.ans")), force: true }); "export_to_path")]
+    #[test_case("export", Err(String::from("No export format entered")); "missing_format")]
+    fn parse_export(input: &str, expected: Result<Command, String>) {
+        assert_eq!(expected, Command::parse(input));
+    }
+
+    #[test_case("bm", Command::Bookmark;           "bookmark")]
+    #[test_case("todo", Command::Todo;             "todo")]
+    #[test_case("signnext", Command::NextSign;     "next_sign")]
+    #[test_case("sN", Command::PreviousSign;       "previous_sign")]
+    fn parse_signs(input: &str, expected: Command) {
+        assert_eq!(Ok(expected), Command::parse(input));
+    }
+
+    #[test_case("merge", Command::Merge;                     "merge")]
+    #[test_case("ours", Command::Resolve(Keep::Ours);        "ours")]
+    #[test_case("both", Command::Resolve(Keep::Both);        "both")]
+    #[test_case("conflictnext", Command::NextConflict;       "next_conflict")]
+    #[test_case("xN", Command::PreviousConflict;             "previous_conflict")]
+    fn parse_conflicts(input: &str, expected: Command) {
+        assert_eq!(Ok(expected), Command::parse(input));
+    }
+
+    #[test_case("ansi", Command::ToggleAnsi;       "ansi")]
+    #[test_case("minimap", Command::ToggleMinimap; "minimap")]
+    #[test_case("scrollbar", Command::ToggleScrollbar; "scrollbar")]
+    #[test_case("guides", Command::ToggleIndentGuides; "guides")]
+    #[test_case("subword", Command::ToggleSubwords;    "subword")]
+    #[test_case("paste", Command::TogglePaste;         "paste")]
+    #[test_case("zen", Command::ToggleZen;             "zen")]
+    #[test_case("perf", Command::TogglePerf;           "perf")]
+    #[test_case("messages", Command::Messages;         "messages")]
+    fn parse_toggle(input: &str, expected: Command) {
+        assert_eq!(Ok(expected), Command::parse(input));
+    }
+
+    #[test_case("record a", Ok(Command::Record(Some('a')));             "record")]
+    #[test_case("rec", Ok(Command::Record(None));                       "stop")]
+    #[test_case("record ab", Err(String::from("Invalid register: ab")); "invalid_register")]
+    #[test_case("@q", Ok(Command::Play('q'));                           "play")]
+    #[test_case("@", Err(String::from("No register entered"));          "play_without_register")]
+    #[test_case("macro 1", Ok(Command::EditMacro('1'));                 "edit")]
+    fn parse_macro(input: &str, expected: Result<Command, String>) {
+        assert_eq!(expected, Command::parse(input));
+    }
+
+    #[test]
+    fn parse_new() {
+        assert_eq!(Ok(Command::New), Command::parse("new"));
+    }
+
+    #[test_case("drafts", false;  "restore")]
+    #[test_case("drafts!", true;  "discard")]
+    fn parse_drafts(input: &str, force: bool) {
+        assert_eq!(Ok(Command::Drafts { force }), Command::parse(input));
+    }
+
+    #[test_case("help", None;           "without_topic")]
+    #[test_case("h :sort", Some(":sort"); "with_topic")]
+    fn parse_help(input: &str, topic: Option<&str>) {
+        assert_eq!(
+            Ok(Command::Help(topic.map(String::from))),
+            Command::parse(input)
+        );
+    }
+
+    #[test]
+    fn parse_recent() {
+        assert_eq!(Ok(Command::Recent), Command::parse("oldfiles"));
+        assert_eq!(Ok(Command::Buffers), Command::parse("ls"));
+    }
+
+    #[test_case("upper", Case::Upper;   "upper")]
+    #[test_case(":title", Case::Title;  "title_with_colon")]
+    fn parse_change_case(input: &str, case: Case) {
+        assert_eq!(Ok(Command::ChangeCase(case)), Command::parse(input));
+    }
+
+    #[test_case("sort", SortOptions::default();  "sort")]
+    #[test_case("sort! n i", SortOptions { ignore_case: true, numeric: true, descending: true }; "sort_with_options")]
+    fn parse_sort(input: &str, options: SortOptions) {
+        assert_eq!(Ok(Command::Sort(options)), Command::parse(input));
+    }
+
+    #[test_case("align =", Ok(Command::Align(String::from("="))); "delimiter")]
+    #[test_case("align  | ", Ok(Command::Align(String::from("|"))); "trimmed_delimiter")]
+    #[test_case("align", Err(String::from("No delimiter entered")); "missing_delimiter")]
+    fn parse_align(input: &str, expected: Result<Command, String>) {
+        assert_eq!(expected, Command::parse(input));
+    }
+
+    #[test_case("42", 42; "line")]
+    #[test_case(":1", 1;  "line_with_colon")]
+    fn parse_goto(input: &str, line: u32) {
+        assert_eq!(Ok(Command::Goto(line)), Command::parse(input));
+    }
+
+    #[test_case("s/a/b/", Ok(Command::Substitute(Substitution::parse("/a/b/").unwrap())); "substitute")]
+    #[test_case("s#a#b#", Ok(Command::Substitute(Substitution::parse("#a#b#").unwrap())); "other_delimiter")]
+    #[test_case("s", Err(String::from("No pattern entered")); "missing_pattern")]
+    #[test_case("sort", Ok(Command::Sort(SortOptions::default())); "other_command")]
+    fn parse_substitute(input: &str, expected: Result<Command, String>) {
+        assert_eq!(expected, Command::parse(input));
+    }
+
+    #[test_case("d", Ok(Command::DeleteLines(None));  "delete")]
+    #[test_case("d A", Ok(Command::DeleteLines(Some('A'))); "delete_appending")]
+    #[test_case("y", Ok(Command::Yank(None));         "yank")]
+    #[test_case("y \"", Ok(Command::Yank(None));      "yank_unnamed")]
+    #[test_case("d 3", Err(String::from("Register 3 can't be written to")); "delete_numbered")]
+    #[test_case("pu \"", Ok(Command::Put(Some('"'))); "put")]
+    #[test_case("put ab", Err(String::from("Invalid register: ab")); "invalid")]
+    fn parse_registers(input: &str, expected: Result<Command, String>) {
+        assert_eq!(expected, Command::parse(input));
+    }
+
+    #[test_case("rename src/b.rs", Ok(Command::Rename { path: PathBuf::from("src/b.rs"), force: false }); "rename")]
+    #[test_case("rename! b.rs", Ok(Command::Rename { path: PathBuf::from("b.rs"), force: true }); "rename_forced")]
+    #[test_case("rename", Err(String::from("No new path entered"));                 "rename_without_path")]
+    #[test_case("delete-file", Ok(Command::DeleteFile { force: false });            "delete_file")]
+    #[test_case("ro", Ok(Command::ReadOnly(true));                                  "readonly")]
+    #[test_case("noreadonly", Ok(Command::ReadOnly(false));                         "editable")]
+    fn parse_file_commands(input: &str, expected: Result<Command, String>) {
+        assert_eq!(expected, Command::parse(input));
+    }
+
+    #[test_case("complete dict", Ok(Command::Complete(Source::Dictionary)); "dictionary")]
+    #[test_case("complete file", Ok(Command::Complete(Source::File));       "file")]
+    #[test_case("complete", Err(String::from("No completion source entered")); "missing")]
+    fn parse_complete(input: &str, expected: Result<Command, String>) {
+        assert_eq!(expected, Command::parse(input));
+    }
+
+    #[test_case("select i(", Ok(Command::Select(TextObject { around: false, kind: Kind::Bracket('(', ')') })); "brackets")]
+    #[test_case("select aw", Ok(Command::Select(TextObject { around: true, kind: Kind::Word }));  "word")]
+    #[test_case("select", Err(String::from("No text object entered"));                          "missing")]
+    fn parse_select(input: &str, expected: Result<Command, String>) {
+        assert_eq!(expected, Command::parse(input));
+    }
+
+    fn pair(open: &str, close: &str) -> Pair {
+        Pair {
+            open: open.to_string(),
+            close: close.to_string(),
+        }
+    }
+
+    #[test_case("surround (", Ok(Command::Surround { pair: pair("(", ")"), object: None }); "selection")]
+    #[test_case("ys \" iw", Ok(Command::Surround { pair: pair("\"", "\""), object: Some(TextObject { around: false, kind: Kind::Word }) }); "object")]
+    #[test_case("ys <p class=\"x\"> ip", Ok(Command::Surround { pair: pair("<p class=\"x\">", "</p>"), object: Some(TextObject { around: false, kind: Kind::Paragraph }) }); "tag")]
+    #[test_case("surround", Err(String::from("No pair entered"));               "missing")]
+    #[test_case("ds b", Ok(Command::Unsurround(TextObject { around: true, kind: Kind::Bracket('(', ')') })); "delete")]
+    #[test_case("cs ' \"", Ok(Command::Resurround { target: TextObject { around: true, kind: Kind::Quote('\'') }, pair: pair("\"", "\"") }); "change")]
+    #[test_case("cs t [", Ok(Command::Resurround { target: TextObject { around: true, kind: Kind::Tag }, pair: pair("[", "]") }); "change_tag")]
+    #[test_case("cs (", Err(String::from("No pair entered"));                   "change_missing_pair")]
+    fn parse_surrounding_pairs(input: &str, expected: Result<Command, String>) {
+        assert_eq!(expected, Command::parse(input));
+    }
+
+    #[test_case("mark A", Ok(Command::Mark('A'));    "mark")]
+    #[test_case("k Z", Ok(Command::Mark('Z'));       "mark_short")]
+    #[test_case("delm A", Ok(Command::DeleteMark('A')); "delete")]
+    #[test_case("mark a", Err(String::from("Invalid mark: a (marks are named A to Z)")); "lowercase")]
+    fn parse_mark(input: &str, expected: Result<Command, String>) {
+        assert_eq!(expected, Command::parse(input));
+    }
+
+    fn ranged(lines: LineRange, command: Option<Command>) -> Result<Command, String> {
+        Ok(Command::Ranged {
+            lines,
+            command: command.map(Box::new),
+        })
+    }
+
+    fn lines(start: u32, end: u32) -> LineRange {
+        let spec = |line| range::LineSpec::new(Address::Line(line));
+        LineRange::Lines(spec(start), spec(end))
+    }
+
+    #[test_case("10,20d", ranged(lines(10, 20), Some(Command::DeleteLines(None))); "delete")]
+    #[test_case("%y a", ranged(LineRange::All, Some(Command::Yank(Some('a')))); "yank")]
+    #[test_case("%sort", ranged(LineRange::All, Some(Command::Sort(SortOptions::default()))); "sort_all")]
+    #[test_case("%=", ranged(LineRange::All, Some(Command::Reindent)); "reindent_all")]
+    #[test_case("3,5 !rev", ranged(lines(3, 5), Some(Command::Filter { lines: FilterLines::Range, command: String::from("rev") })); "filter")]
+    #[test_case("3,5", ranged(lines(3, 5), None); "goto_end")]
+    #[test_case("$", ranged(LineRange::Lines(range::LineSpec::new(Address::Last), range::LineSpec::new(Address::Last)), None); "goto_last")]
+    #[test_case("%s/a/b/g", ranged(LineRange::All, Some(Command::Substitute(Substitution::parse("/a/b/g").unwrap()))); "substitute")]
+    #[test_case("'A", Ok(Command::JumpToMark('A')); "global_mark")]
+    #[test_case("'A,'B", ranged(LineRange::Lines(range::LineSpec::new(Address::Mark('A')), range::LineSpec::new(Address::Mark('B'))), None); "between_global_marks")]
+    #[test_case("3,5w", Err(String::from("The command doesn't take a range")); "no_range")]
+    #[test_case("3,5nope", Err(String::from("Unknown command: nope")); "unknown")]
+    fn parse_range(input: &str, expected: Result<Command, String>) {
+        assert_eq!(expected, Command::parse(input));
+    }
+
+    #[test_case("/two words", "two words", true;  "forward")]
+    #[test_case(":?back", "back", false;         "backward")]
+    #[test_case("/?", "?", true;                 "question_mark")]
+    fn parse_find(input: &str, pattern: &str, forward: bool) {
+        let expected = Command::Find {
+            pattern: pattern.to_string(),
+            forward,
+        };
+        assert_eq!(Ok(expected), Command::parse(input));
+    }
+
+    #[test_case("fold",        FoldAction::Close;   "close")]
+    #[test_case("foldopen",    FoldAction::Open;    "open")]
+    #[test_case("foldopen!",   FoldAction::OpenAll; "open_all")]
+    #[test_case("foldtoggle",  FoldAction::Toggle;  "toggle")]
+    fn parse_fold(input: &str, action: FoldAction) {
+        assert_eq!(Ok(Command::Fold(action)), Command::parse(input));
+    }
+
+    #[test]
+    fn parse_find_without_text() {
+        assert!(Command::parse("/").is_err());
+    }
+
+    #[test]
+    fn parse_goto_out_of_range() {
+        assert!(Command::parse("99999999999").is_err());
+    }
+
+    #[test]
+    fn parse_unknown() {
+        let expected = Err(String::from("Unknown command: foo"));
+        assert_eq!(expected, Command::parse("foo bar"));
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert!(Command::parse("  ").is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use std::path::{self, PathBuf};
+
+use crate::config;
+
+/// Where the completions for the text before the cursor come from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    /// The words in the configured word lists.
+    Dictionary,
+    /// The files the path before the cursor could lead to.
+    File,
+}
+
+impl Source {
+    pub fn parse(name: &str) -> Result<Source, String> {
+        match name {
+            "dict" | "dictionary" => Ok(Source::Dictionary),
+            "file" => Ok(Source::File),
+            "" => Err(String::from("No completion source entered")),
+            name => Err(format!("Unknown completion source: {}", name)),
+        }
+    }
+
+    /// Finds the partly typed text at the end of a line which a completion replaces, returning
+    /// the column it starts at along with the text. Words are made up of letters, and paths of
+    /// anything other than whitespace, quotes and brackets.
+    ///
+    /// # Arguments
+    ///
+    /// * 'before' - The text of the line before the cursor.
+    pub fn partial(self, before: &str) -> (u32, &str) {
+        let is_part = |c: char| match self {
+            Source::Dictionary => c.is_alphabetic() || c == '\'',
+            Source::File => !c.is_whitespace() && !"\"'`()[]{}<>,;".contains(c),
+        };
+        let start = before
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| is_part(*c))
+            .last()
+            .map_or(before.len(), |(i, _)| i);
+        let column = before[..start].chars().count() as u32;
+        (column, &before[start..])
+    }
+}
+
+/// Reads the words from word lists with one word on each line, in alphabetical order without
+/// any repeats. Word lists which can't be read are skipped.
+pub fn load_words(paths: &[PathBuf]) -> Vec<String> {
+    let mut words: Vec<String> = paths
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(expand_home(&path.to_string_lossy())).ok())
+        .flat_map(|contents| {
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|word| !word.is_empty())
+                .map(String::from)
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    words.sort();
+    words.dedup();
+    words
+}
+
+/// Lists the words which could complete a partly typed word. Words match ignoring case, and
+/// keep the case of the letters already typed.
+///
+/// # Arguments
+///
+/// * 'words' - The words to choose from.
+/// * 'partial' - The word typed so far.
+pub fn complete_word(words: &[String], partial: &str) -> Vec<String> {
+    let typed = partial.chars().count();
+    let mut candidates: Vec<String> = words
+        .iter()
+        .filter(|word| {
+            word.chars().count() > typed
+                && word
+                    .chars()
+                    .zip(partial.chars())
+                    .all(|(a, b)| a.to_lowercase().eq(b.to_lowercase()))
+        })
+        .map(|word| {
+            format!(
+                "{}{}",
+                partial,
+                word.chars().skip(typed).collect::<String>()
+            )
+        })
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    candidates
+}
+
+/// Expands a leading `~` in a path to the home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), config::home_dir()) {
+        (Some(""), Some(home)) => home,
+        (Some(rest), Some(home)) if rest.starts_with(path::is_separator) => home.join(&rest[1..]),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Lists the files which could complete a partially typed path, in alphabetical order.
+/// Directories end with a `/` so completing them again descends into them. Either separator can
+/// be typed on Windows. Hidden files are only included when asked for, or when the partial file
+/// name starts with a `.`.
+///
+/// # Arguments
+///
+/// * 'partial' - The path typed so far, which can start with `~`.
+/// * 'hidden' - Whether to include hidden files.
+pub fn complete_path(partial: &str, hidden: bool) -> Vec<String> {
+    let (dir, prefix) = match partial.rfind(path::is_separator) {
+        Some(i) => partial.split_at(i + 1),
+        None => ("", partial),
+    };
+    let search = match dir {
+        "" => PathBuf::from("."),
+        dir => expand_home(dir),
+    };
+    let Ok(entries) = std::fs::read_dir(search) else {
+        return vec![];
+    };
+
+    let hidden = hidden || prefix.starts_with('.');
+    let mut candidates: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !hidden) {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+/// The longest text which all of the candidates start with.
+pub fn common_prefix(candidates: &[String]) -> &str {
+    let Some(first) = candidates.first() else {
+        return "";
+    };
+    let len = candidates[1..].iter().fold(first.len(), |len, candidate| {
+        first
+            .char_indices()
+            .zip(candidate.chars())
+            .take_while(|((i, a), b)| *i < len && a == b)
+            .last()
+            .map_or(0, |((i, a), _)| i + a.len_utf8())
+    });
+    &first[..len]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use test_case::test_case;
+
+    use super::*;
+
+    fn setup(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        for file in ["lib.rs", "license", ".hidden"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        dir
+    }
+
+    fn complete(dir: &Path, partial: &str, hidden: bool) -> Vec<String> {
+        let prefix = format!("{}/", dir.display());
+        complete_path(&format!("{}{}", prefix, partial), hidden)
+            .into_iter()
+            .map(|candidate| candidate[prefix.len()..].to_string())
+            .collect()
+    }
+
+    #[test_case("li", false, vec!["lib.rs", "license"];                 "prefix")]
+    #[test_case("s", false, vec!["src/"];                               "directory")]
+    #[test_case("", false, vec!["lib.rs", "license", "src/"];           "without_hidden")]
+    #[test_case("", true, vec![".hidden", "lib.rs", "license", "src/"]; "with_hidden")]
+    #[test_case(".h", false, vec![".hidden"];                           "hidden_prefix")]
+    #[test_case("x", false, vec![];                                     "no_match")]
+    fn complete_files(partial: &str, hidden: bool, expected: Vec<&str>) {
+        let name = format!("texteditor_complete_files_{}_{}", partial, hidden);
+        let dir = setup(&name.replace('.', "dot"));
+        let actual = complete(&dir, partial, hidden);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test_case(Source::Dictionary, "let x = hel", 8, "hel";       "word")]
+    #[test_case(Source::Dictionary, "it's don't", 5, "don't";      "apostrophe")]
+    #[test_case(Source::Dictionary, "x = ", 4, "";                 "no_word")]
+    #[test_case(Source::File, "open(\"~/src/ma", 6, "~/src/ma";   "path")]
+    #[test_case(Source::File, "é src/", 2, "src/";                 "column_in_chars")]
+    fn partial_text(source: Source, before: &str, column: u32, expected: &str) {
+        assert_eq!((column, expected), source.partial(before));
+    }
+
+    #[test_case("dict", Ok(Source::Dictionary);                        "dictionary")]
+    #[test_case("file", Ok(Source::File);                              "file")]
+    #[test_case("", Err(String::from("No completion source entered")); "missing")]
+    #[test_case("line", Err(String::from("Unknown completion source: line")); "unknown")]
+    fn parse_source(name: &str, expected: Result<Source, String>) {
+        assert_eq!(expected, Source::parse(name));
+    }
+
+    #[test_case("hel", vec!["held", "hello", "helm"]; "prefix")]
+    #[test_case("HEL", vec!["HELd", "HELlo", "HELm"]; "keeps_case")]
+    #[test_case("help", vec![];                       "no_match")]
+    #[test_case("held", vec![];                       "already_complete")]
+    fn complete_words(partial: &str, expected: Vec<&str>) {
+        let words: Vec<String> = ["Hello", "held", "hello", "helm", "world"]
+            .iter()
+            .map(|w| w.to_string())
+            .collect();
+        assert_eq!(expected, complete_word(&words, partial));
+    }
+
+    #[test]
+    fn load_word_lists() {
+        let dir = std::env::temp_dir().join("texteditor_load_word_lists");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("one"), "zebra\napple\n\n").unwrap();
+        fs::write(dir.join("two"), "apple\nmango\n").unwrap();
+        let paths = [dir.join("one"), dir.join("missing"), dir.join("two")];
+        let words = load_words(&paths);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(vec!["apple", "mango", "zebra"], words);
+    }
+
+    #[test_case(&["lib.rs", "license"], "li";  "shared")]
+    #[test_case(&["src/"], "src/";             "single")]
+    #[test_case(&["a", "b"], "";               "nothing_shared")]
+    #[test_case(&[], "";                       "empty")]
+    fn common(candidates: &[&str], expected: &str) {
+        let candidates: Vec<String> = candidates.iter().map(|c| c.to_string()).collect();
+        assert_eq!(expected, common_prefix(&candidates));
+    }
+
+    #[test]
+    fn expand_home_directory() {
+        let Some(home) = config::home_dir() else {
+            return;
+        };
+        assert_eq!(home.join("notes.txt"), expand_home("~/notes.txt"));
+        assert_eq!(PathBuf::from("a/~b"), expand_home("a/~b"));
+    }
+}
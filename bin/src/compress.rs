@@ -0,0 +1,131 @@
+use std::path::Path;
+
+use crate::error::Result;
+use crate::shell;
+
+/// The level gzip compresses at by default.
+const GZIP_DEFAULT_LEVEL: u32 = 6;
+/// The level zstd compresses at by default.
+const ZSTD_DEFAULT_LEVEL: u32 = 3;
+
+/// A compression format which documents are decompressed from when they're loaded and
+/// compressed with again when they're saved, using the `gzip` and `zstd` commands.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    Gzip { level: u32 },
+    Zstd { level: u32 },
+}
+
+impl Compression {
+    /// The format a file with this path is expected to use, at the default level.
+    pub fn from_path(path: &Path) -> Option<Compression> {
+        match path.extension()?.to_str()? {
+            "gz" => Some(Compression::Gzip {
+                level: GZIP_DEFAULT_LEVEL,
+            }),
+            "zst" => Some(Compression::Zstd {
+                level: ZSTD_DEFAULT_LEVEL,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Detects the format of a compressed file from its extension and the data at its start.
+    /// Files which don't start with the format's magic number aren't treated as compressed.
+    ///
+    /// gzip records roughly how hard it tried to compress a file, so files compressed with the
+    /// fastest or best compression are saved with that level again. zstd doesn't record its
+    /// level, so zstd files are saved at the default level.
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - The path of the file.
+    /// * 'bytes' - The contents of the file.
+    pub fn detect(path: &Path, bytes: &[u8]) -> Option<Compression> {
+        match Compression::from_path(path)? {
+            Compression::Gzip { level } if bytes.starts_with(&[0x1f, 0x8b]) => {
+                // The extra flags byte is 2 for the best compression and 4 for the fastest.
+                let level = match bytes.get(8) {
+                    Some(2) => 9,
+                    Some(4) => 1,
+                    _ => level,
+                };
+                Some(Compression::Gzip { level })
+            }
+            zstd @ Compression::Zstd { .. } if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) => {
+                Some(zstd)
+            }
+            _ => None,
+        }
+    }
+
+    /// The name of the format.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compression::Gzip { .. } => "gzip",
+            Compression::Zstd { .. } => "zstd",
+        }
+    }
+
+    /// Whether the formats are the same, regardless of the level.
+    pub fn same_format(&self, other: &Compression) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    pub fn decompress(&self, bytes: Vec<u8>) -> Result<Vec<u8>> {
+        let command = match self {
+            Compression::Gzip { .. } => "gzip -dc",
+            Compression::Zstd { .. } => "zstd -dcq",
+        };
+        shell::pipe(command, bytes)
+    }
+
+    pub fn compress(&self, bytes: Vec<u8>) -> Result<Vec<u8>> {
+        let command = match self {
+            Compression::Gzip { level } => format!("gzip -c -{}", level),
+            Compression::Zstd { level } => format!("zstd -cq -{}", level),
+        };
+        shell::pipe(&command, bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    const GZIP_HEADER: [u8; 10] = [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 3];
+
+    fn gzip_header(extra_flags: u8) -> Vec<u8> {
+        let mut header = GZIP_HEADER.to_vec();
+        header[8] = extra_flags;
+        header
+    }
+
+    #[test_case("log.gz", gzip_header(0), Some(Compression::Gzip { level: 6 }); "gzip_default")]
+    #[test_case("log.gz", gzip_header(2), Some(Compression::Gzip { level: 9 }); "gzip_best")]
+    #[test_case("log.gz", gzip_header(4), Some(Compression::Gzip { level: 1 }); "gzip_fast")]
+    #[test_case("log.zst", vec![0x28, 0xb5, 0x2f, 0xfd, 0], Some(Compression::Zstd { level: 3 }); "zstd")]
+    #[test_case("log.gz", b"Lorem".to_vec(), None;                             "not_compressed")]
+    #[test_case("log.txt", gzip_header(0), None;                               "other_extension")]
+    fn detect_format(path: &str, bytes: Vec<u8>, expected: Option<Compression>) {
+        assert_eq!(expected, Compression::detect(Path::new(path), &bytes));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn gzip_round_trip() {
+        let compression = Compression::Gzip { level: 9 };
+        let compressed = compression.compress(b"Lorem ipsum\n".to_vec()).unwrap();
+
+        assert_eq!(
+            Some(compression),
+            Compression::detect(Path::new("a.gz"), &compressed)
+        );
+        assert_eq!(
+            b"Lorem ipsum\n".to_vec(),
+            compression.decompress(compressed).unwrap()
+        );
+    }
+}
@@ -0,0 +1,615 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use log::info;
+use serde::Deserialize;
+
+use crate::colors::ColorSupport;
+use crate::completion::expand_home;
+use crate::error::EditorError;
+use crate::filetype;
+use crate::fold::FoldMethod;
+
+/// The name of the file holding a project's settings, in the project's root directory.
+pub const PROJECT_FILE: &str = ".texteditor.toml";
+
+/// User settings, read from `config.toml` in the editor's config directory. Any settings missing
+/// from the file use their default values.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Documents larger than this many bytes are opened in large file mode.
+    pub large_file_threshold: u64,
+    /// The maximum number of entries kept in each prompt history.
+    pub history_size: usize,
+    /// The number of cells between tab stops when displaying tabs.
+    pub tab_width: usize,
+    /// The minimum number of lines kept visible above and below the cursor when scrolling.
+    pub scrolloff: u32,
+    /// The number of lines scrolled by each step of the mouse wheel.
+    pub scroll_step: u32,
+    /// The number of columns each level of indentation takes up.
+    pub indent_width: usize,
+    /// Whether to indent with spaces rather than tabs.
+    pub expand_tabs: bool,
+    /// Whether to remove trailing whitespace from edited lines when saving.
+    pub trim_trailing_whitespace: bool,
+    /// Whether to add a line break to the end of the document when saving, if it's missing.
+    pub insert_final_newline: bool,
+    /// The number of cells paragraphs are wrapped to when reflowing them.
+    pub text_width: usize,
+    /// How the lines a fold covers are found.
+    pub fold_method: FoldMethod,
+    /// Whether word motions stop at the parts of camelCase and snake_case words.
+    pub subword_motion: bool,
+    /// Whether pasted lines are shifted to the indentation at the cursor, keeping their
+    /// indentation relative to the first pasted line.
+    pub reindent_paste: bool,
+    /// Whether to show an overview of the document down the right-hand side of the screen.
+    pub minimap: bool,
+    /// Whether to show a scrollbar down the right edge of the screen.
+    pub scrollbar: bool,
+    /// Whether to draw guides at each level of indentation.
+    pub indent_guides: bool,
+    /// Whether to colour nested brackets by their depth.
+    pub rainbow_brackets: bool,
+    /// Whether to dim the text outside the innermost pair of brackets around the cursor.
+    pub bracket_scope: bool,
+    /// The number of cells the text column is narrowed to in zen mode.
+    pub zen_width: usize,
+    /// Whether the status line is shown in zen mode. Messages and prompts are always shown.
+    pub zen_status_line: bool,
+    /// Whether to save every modified document when the terminal loses focus.
+    pub save_on_focus_lost: bool,
+    /// Whether large documents are indexed while the editor is idle, so they can be searched
+    /// quickly.
+    pub search_index: bool,
+    /// Whether searches carry on from the other end of the document when they reach the end.
+    pub wrap_search: bool,
+    /// Whether files are locked while they're open, so other instances of the editor can warn
+    /// about them being edited twice.
+    pub lock_files: bool,
+    /// The colours the terminal supports, which styles are mapped down to. Detected from the
+    /// environment when it isn't set.
+    pub colors: Option<ColorSupport>,
+    /// Word lists, with one word on each line, which words are completed from.
+    pub dictionaries: Vec<PathBuf>,
+    /// The shell command run by `make` to build the project, whose output is searched for
+    /// error locations.
+    pub make_command: String,
+    /// The names of files or directories which mark a project's root directory, in order of
+    /// preference. Builds are run from the root, and its `.texteditor.toml` is read.
+    pub root_markers: Vec<String>,
+    /// Projects whose settings files are allowed to set shell commands, such as `make_command`.
+    pub trusted_projects: Vec<PathBuf>,
+    /// Settings for individual filetypes, from the `[filetype.<name>]` tables.
+    pub filetype: HashMap<String, FiletypeConfig>,
+    /// How to decrypt and encrypt files with each extension, from the `[encryption.<extension>]`
+    /// tables.
+    pub encryption: HashMap<String, EncryptionConfig>,
+}
+
+/// The shell commands which decrypt and encrypt files with an extension. Each command reads from
+/// stdin and writes to stdout, so the plaintext never has to be written to disk.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct EncryptionConfig {
+    pub decrypt: String,
+    pub encrypt: String,
+}
+
+/// Settings which override the global settings for a single filetype. Any settings missing from
+/// the table use the filetype's defaults, falling back to the global settings.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct FiletypeConfig {
+    pub comment_prefix: Option<String>,
+    pub tab_width: Option<usize>,
+    pub indent_width: Option<usize>,
+    pub expand_tabs: Option<bool>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+    pub text_width: Option<usize>,
+    pub fold_method: Option<FoldMethod>,
+    pub subword_motion: Option<bool>,
+    pub reindent_paste: Option<bool>,
+}
+
+/// Settings which override the global settings for the files under a project's root directory,
+/// read from the project's `.texteditor.toml` file. Anyone can put one in a repository, so shell
+/// commands are only used if the project is in `trusted_projects`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ProjectConfig {
+    pub tab_width: Option<usize>,
+    pub indent_width: Option<usize>,
+    pub expand_tabs: Option<bool>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+    pub text_width: Option<usize>,
+    pub fold_method: Option<FoldMethod>,
+    pub subword_motion: Option<bool>,
+    pub reindent_paste: Option<bool>,
+    pub make_command: Option<String>,
+    pub filetype: HashMap<String, FiletypeConfig>,
+}
+
+/// The settings in effect for a document, combining the global settings with the settings for
+/// the document's filetype.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// The prefix used for line comments, if the filetype has them.
+    pub comment_prefix: Option<String>,
+    pub tab_width: usize,
+    pub indent_width: usize,
+    pub expand_tabs: bool,
+    pub trim_trailing_whitespace: bool,
+    pub insert_final_newline: bool,
+    pub text_width: usize,
+    pub fold_method: FoldMethod,
+    pub subword_motion: bool,
+    pub reindent_paste: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            large_file_threshold: 32 * 1024 * 1024,
+            history_size: 100,
+            tab_width: 4,
+            scrolloff: 0,
+            scroll_step: 3,
+            indent_width: 4,
+            expand_tabs: true,
+            trim_trailing_whitespace: false,
+            insert_final_newline: false,
+            text_width: 80,
+            fold_method: FoldMethod::Indent,
+            subword_motion: false,
+            reindent_paste: false,
+            minimap: false,
+            scrollbar: false,
+            indent_guides: false,
+            rainbow_brackets: false,
+            bracket_scope: false,
+            zen_width: 80,
+            zen_status_line: false,
+            save_on_focus_lost: false,
+            search_index: true,
+            wrap_search: true,
+            lock_files: true,
+            colors: None,
+            dictionaries: vec![PathBuf::from("/usr/share/dict/words")],
+            make_command: String::from("make"),
+            root_markers: [PROJECT_FILE, ".git", "Cargo.toml"]
+                .map(String::from)
+                .to_vec(),
+            trusted_projects: Vec::new(),
+            filetype: HashMap::new(),
+            encryption: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config file, falling back to the default settings if it doesn't exist or can't
+    /// be read. A config file which can't be parsed is an error, so it can be reported.
+    pub fn load() -> Result<Config, EditorError> {
+        let Some(path) = config_dir().map(|dir| dir.join("config.toml")) else {
+            return Ok(Config::default());
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => match Config::parse(&contents) {
+                Ok(config) => {
+                    info!("Loaded config from {:?}", path);
+                    Ok(config)
+                }
+                Err(e) => Err(EditorError::Config {
+                    path,
+                    message: e.message().to_string(),
+                }),
+            },
+            Err(_) => Ok(Config::default()),
+        }
+    }
+
+    pub fn parse(contents: &str) -> Result<Config, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    /// Applies a project's settings over these ones, for the files under the project's root.
+    /// The project's shell commands are left out unless it's trusted.
+    ///
+    /// # Arguments
+    ///
+    /// * 'root' - The project's root directory.
+    /// * 'project' - The settings from the project's settings file.
+    pub fn with_project(&self, root: &Path, project: &ProjectConfig) -> Config {
+        let mut config = self.clone();
+        let project = project.clone();
+        config.tab_width = project.tab_width.unwrap_or(config.tab_width);
+        config.indent_width = project.indent_width.unwrap_or(config.indent_width);
+        config.expand_tabs = project.expand_tabs.unwrap_or(config.expand_tabs);
+        config.trim_trailing_whitespace = project
+            .trim_trailing_whitespace
+            .unwrap_or(config.trim_trailing_whitespace);
+        config.insert_final_newline = project
+            .insert_final_newline
+            .unwrap_or(config.insert_final_newline);
+        config.text_width = project.text_width.unwrap_or(config.text_width);
+        config.fold_method = project.fold_method.unwrap_or(config.fold_method);
+        config.subword_motion = project.subword_motion.unwrap_or(config.subword_motion);
+        config.reindent_paste = project.reindent_paste.unwrap_or(config.reindent_paste);
+        if self.is_trusted(root) {
+            config.make_command = project.make_command.unwrap_or(config.make_command);
+        }
+        for (name, settings) in project.filetype {
+            let global = config.filetype.remove(&name).unwrap_or_default();
+            config.filetype.insert(name, settings.or(global));
+        }
+        config
+    }
+
+    /// Whether a project's settings file is allowed to set shell commands.
+    ///
+    /// # Arguments
+    ///
+    /// * 'root' - The project's root directory.
+    pub fn is_trusted(&self, root: &Path) -> bool {
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        self.trusted_projects.iter().any(|trusted| {
+            let trusted = expand_home(&trusted.to_string_lossy());
+            trusted.canonicalize().unwrap_or(trusted) == root
+        })
+    }
+
+    /// The commands which decrypt and encrypt a file, if its extension is configured as an
+    /// encrypted format.
+    pub fn encryption(&self, path: &Path) -> Option<&EncryptionConfig> {
+        let extension = path.extension()?.to_str()?;
+        self.encryption.get(extension)
+    }
+
+    /// Resolves the settings for a filetype. Settings configured for the filetype take priority,
+    /// followed by the filetype's defaults, then the global settings.
+    ///
+    /// # Arguments
+    ///
+    /// * 'name' - The name of the filetype, or None if the filetype isn't known.
+    pub fn settings(&self, name: Option<&str>) -> Settings {
+        let defaults = name.and_then(filetype::find);
+        let config = name
+            .and_then(|name| self.filetype.get(name))
+            .cloned()
+            .unwrap_or_default();
+
+        Settings {
+            comment_prefix: config
+                .comment_prefix
+                .or_else(|| defaults.and_then(|d| d.comment_prefix).map(String::from)),
+            tab_width: config.tab_width.unwrap_or(self.tab_width),
+            indent_width: config
+                .indent_width
+                .or(defaults.map(|d| d.indent_width))
+                .unwrap_or(self.indent_width),
+            expand_tabs: config
+                .expand_tabs
+                .or(defaults.map(|d| d.expand_tabs))
+                .unwrap_or(self.expand_tabs),
+            trim_trailing_whitespace: config
+                .trim_trailing_whitespace
+                .unwrap_or(self.trim_trailing_whitespace),
+            insert_final_newline: config
+                .insert_final_newline
+                .unwrap_or(self.insert_final_newline),
+            text_width: config.text_width.unwrap_or(self.text_width),
+            fold_method: config.fold_method.unwrap_or(self.fold_method),
+            subword_motion: config.subword_motion.unwrap_or(self.subword_motion),
+            reindent_paste: config.reindent_paste.unwrap_or(self.reindent_paste),
+        }
+    }
+}
+
+impl FiletypeConfig {
+    /// Combines these settings with another filetype's, preferring these ones.
+    fn or(self, other: FiletypeConfig) -> FiletypeConfig {
+        FiletypeConfig {
+            comment_prefix: self.comment_prefix.or(other.comment_prefix),
+            tab_width: self.tab_width.or(other.tab_width),
+            indent_width: self.indent_width.or(other.indent_width),
+            expand_tabs: self.expand_tabs.or(other.expand_tabs),
+            trim_trailing_whitespace: self
+                .trim_trailing_whitespace
+                .or(other.trim_trailing_whitespace),
+            insert_final_newline: self.insert_final_newline.or(other.insert_final_newline),
+            text_width: self.text_width.or(other.text_width),
+            fold_method: self.fold_method.or(other.fold_method),
+            subword_motion: self.subword_motion.or(other.subword_motion),
+            reindent_paste: self.reindent_paste.or(other.reindent_paste),
+        }
+    }
+}
+
+impl ProjectConfig {
+    /// Loads a project's settings file, if it has one. A settings file which can't be read or
+    /// parsed is an error, so it can be reported.
+    ///
+    /// # Arguments
+    ///
+    /// * 'root' - The project's root directory.
+    pub fn load(root: &Path) -> Option<Result<ProjectConfig, EditorError>> {
+        let path = root.join(PROJECT_FILE);
+        if !path.is_file() {
+            return None;
+        }
+        let result = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| ProjectConfig::parse(&contents).map_err(|e| e.message().into()))
+            .map_err(|message| EditorError::Config { path, message });
+        Some(result)
+    }
+
+    pub fn parse(contents: &str) -> Result<ProjectConfig, toml::de::Error> {
+        toml::from_str(contents)
+    }
+}
+
+/// The directory containing the editor's config files, following the XDG base directory
+/// specification.
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// The directory containing state which persists across invocations, such as prompt history,
+/// following the XDG base directory specification.
+pub fn state_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_STATE_HOME", ".local/state")
+}
+
+/// Resolves an XDG base directory from its environment variable, falling back to the given path
+/// relative to the home directory.
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    let dir = std::env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| home_dir().map(|home| home.join(fallback)))?;
+
+    Some(dir.join("texteditor"))
+}
+
+/// The user's home directory. Windows doesn't set `HOME`, so `USERPROFILE` is used there instead.
+pub fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE").filter(|_| cfg!(windows)))
+        .map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_empty() {
+        let expected = Config::default();
+        let actual = Config::parse("").unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_large_file_threshold() {
+        let actual = Config::parse("large_file_threshold = 1024").unwrap();
+        assert_eq!(1024, actual.large_file_threshold);
+    }
+
+    #[test]
+    fn parse_history_size() {
+        let actual = Config::parse("history_size = 10").unwrap();
+        assert_eq!(10, actual.history_size);
+    }
+
+    #[test]
+    fn parse_tab_width() {
+        let actual = Config::parse("tab_width = 8").unwrap();
+        assert_eq!(8, actual.tab_width);
+    }
+
+    #[test]
+    fn parse_scrolloff() {
+        let actual = Config::parse("scrolloff = 5").unwrap();
+        assert_eq!(5, actual.scrolloff);
+    }
+
+    #[test]
+    fn parse_scroll_step() {
+        let actual = Config::parse("scroll_step = 1").unwrap();
+        assert_eq!(1, actual.scroll_step);
+    }
+
+    #[test]
+    fn parse_save_settings() {
+        let contents = "trim_trailing_whitespace = true\ninsert_final_newline = true";
+        let actual = Config::parse(contents).unwrap();
+        assert!(actual.trim_trailing_whitespace);
+        assert!(actual.insert_final_newline);
+    }
+
+    #[test]
+    fn parse_text_width() {
+        let contents = "text_width = 100\n[filetype.markdown]\ntext_width = 72";
+        let config = Config::parse(contents).unwrap();
+        assert_eq!(100, config.settings(None).text_width);
+        assert_eq!(72, config.settings(Some("markdown")).text_width);
+    }
+
+    #[test]
+    fn parse_fold_method() {
+        let contents = "[filetype.markdown]\nfold_method = \"marker\"";
+        let config = Config::parse(contents).unwrap();
+        assert_eq!(FoldMethod::Indent, config.settings(None).fold_method);
+        assert_eq!(
+            FoldMethod::Marker,
+            config.settings(Some("markdown")).fold_method
+        );
+    }
+
+    #[test]
+    fn parse_reindent_paste() {
+        let contents = "reindent_paste = true\n[filetype.markdown]\nreindent_paste = false";
+        let config = Config::parse(contents).unwrap();
+        assert!(!Config::default().settings(None).reindent_paste);
+        assert!(config.settings(Some("rust")).reindent_paste);
+        assert!(!config.settings(Some("markdown")).reindent_paste);
+    }
+
+    #[test]
+    fn parse_minimap_and_scrollbar() {
+        let actual = Config::parse("minimap = true\nscrollbar = true").unwrap();
+        assert!(actual.minimap);
+        assert!(actual.scrollbar);
+        assert!(!actual.indent_guides);
+    }
+
+    #[test]
+    fn parse_bracket_settings() {
+        let actual = Config::parse("rainbow_brackets = true\nbracket_scope = true").unwrap();
+        assert!(actual.rainbow_brackets);
+        assert!(actual.bracket_scope);
+    }
+
+    #[test]
+    fn parse_wrap_search() {
+        assert!(Config::default().wrap_search);
+        assert!(!Config::parse("wrap_search = false").unwrap().wrap_search);
+    }
+
+    #[test]
+    fn parse_zen_settings() {
+        let actual = Config::parse("zen_width = 100\nzen_status_line = true").unwrap();
+        assert_eq!(100, actual.zen_width);
+        assert!(actual.zen_status_line);
+    }
+
+    #[test]
+    fn parse_lock_files() {
+        assert!(Config::default().lock_files);
+        assert!(!Config::parse("lock_files = false").unwrap().lock_files);
+    }
+
+    #[test]
+    fn parse_dictionaries() {
+        let actual = Config::parse("dictionaries = [\"~/words.txt\", \"/tmp/names\"]").unwrap();
+        assert_eq!(
+            vec![PathBuf::from("~/words.txt"), PathBuf::from("/tmp/names")],
+            actual.dictionaries
+        );
+    }
+
+    #[test]
+    fn parse_make_command() {
+        let actual = Config::parse("make_command = \"cargo build\"").unwrap();
+        assert_eq!("cargo build", actual.make_command);
+    }
+
+    #[test]
+    fn parse_colors() {
+        assert_eq!(None, Config::parse("").unwrap().colors);
+        let actual = Config::parse("colors = \"256\"").unwrap();
+        assert_eq!(Some(ColorSupport::Extended), actual.colors);
+        assert!(Config::parse("colors = \"8\"").is_err());
+    }
+
+    #[test]
+    fn parse_encryption() {
+        let contents = "[encryption.age]\n\
+                        decrypt = \"age -d -i key.txt\"\n\
+                        encrypt = \"age -r age1example\"";
+        let config = Config::parse(contents).unwrap();
+        let encryption = config.encryption(Path::new("notes.md.age")).unwrap();
+        assert_eq!("age -d -i key.txt", encryption.decrypt);
+        assert_eq!("age -r age1example", encryption.encrypt);
+        assert_eq!(None, config.encryption(Path::new("notes.md")));
+    }
+
+    #[test]
+    fn settings_without_filetype() {
+        let config = Config::default();
+        let actual = config.settings(None);
+        assert_eq!(None, actual.comment_prefix);
+        assert_eq!(config.indent_width, actual.indent_width);
+        assert_eq!(config.expand_tabs, actual.expand_tabs);
+    }
+
+    #[test]
+    fn settings_use_filetype_defaults() {
+        let actual = Config::default().settings(Some("go"));
+        assert_eq!(Some(String::from("//")), actual.comment_prefix);
+        assert!(!actual.expand_tabs);
+    }
+
+    #[test]
+    fn settings_use_filetype_config() {
+        let contents = "indent_width = 3\n\
+                        [filetype.go]\n\
+                        expand_tabs = true\n\
+                        [filetype.custom]\n\
+                        comment_prefix = \";\"";
+        let config = Config::parse(contents).unwrap();
+
+        let go = config.settings(Some("go"));
+        assert!(go.expand_tabs);
+        assert_eq!(4, go.indent_width);
+
+        let custom = config.settings(Some("custom"));
+        assert_eq!(Some(String::from(";")), custom.comment_prefix);
+        assert_eq!(3, custom.indent_width);
+    }
+
+    #[test]
+    fn parse_trusted_projects() {
+        let actual = Config::parse("trusted_projects = [\"/src/editor\"]").unwrap();
+        assert_eq!(vec![PathBuf::from("/src/editor")], actual.trusted_projects);
+    }
+
+    #[test]
+    fn parse_root_markers() {
+        assert_eq!(
+            vec![".texteditor.toml", ".git", "Cargo.toml"],
+            Config::default().root_markers
+        );
+        let actual = Config::parse("root_markers = [\"package.json\"]").unwrap();
+        assert_eq!(vec!["package.json"], actual.root_markers);
+    }
+
+    #[test]
+    fn project_overrides_settings() {
+        let contents = "indent_width = 2\n\
+                        [filetype.go]\n\
+                        tab_width = 8";
+        let project = ProjectConfig::parse(contents).unwrap();
+        let config = Config::parse("[filetype.go]\nindent_width = 3\ntab_width = 4").unwrap();
+        let actual = config.with_project(Path::new("/src/editor"), &project);
+
+        assert_eq!(2, actual.settings(None).indent_width);
+        let go = actual.settings(Some("go"));
+        assert_eq!(3, go.indent_width);
+        assert_eq!(8, go.tab_width);
+    }
+
+    #[test]
+    fn project_commands_need_trust() {
+        let project = ProjectConfig::parse("make_command = \"./build.sh\"").unwrap();
+        let root = Path::new("/src/editor");
+
+        let untrusted = Config::default().with_project(root, &project);
+        assert_eq!("make", untrusted.make_command);
+
+        let config = Config::parse("trusted_projects = [\"/src/editor\"]").unwrap();
+        let trusted = config.with_project(root, &project);
+        assert_eq!("./build.sh", trusted.make_command);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(Config::parse("large_file_threshold = \"big\"").is_err());
+    }
+}
@@ -0,0 +1,163 @@
+use std::ops::RangeInclusive;
+
+/// The marker starting a conflict, followed by our side's lines.
+const OURS: &str = "<<<<<<<";
+/// The marker starting the common ancestor's lines, in the diff3 style.
+const BASE: &str = "|||||||";
+/// The marker between the two sides.
+const SEPARATOR: &str = "=======";
+/// The marker ending a conflict, after their side's lines.
+const THEIRS: &str = ">>>>>>>";
+
+/// A conflict left in a file by a merge, found by its markers. Each field is the line of a
+/// marker. Lines start from 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conflict {
+    pub start: u32,
+    /// The marker before the common ancestor's lines, if the conflict shows them.
+    pub base: Option<u32>,
+    pub separator: u32,
+    pub end: u32,
+}
+
+/// Which side of a conflict to keep when resolving it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Keep {
+    Ours,
+    Theirs,
+    /// Both sides, ours first.
+    Both,
+}
+
+impl Conflict {
+    /// The lines the conflict covers, including its markers.
+    pub fn lines(&self) -> RangeInclusive<u32> {
+        self.start..=self.end
+    }
+
+    /// The lines which replace the conflict when it's resolved.
+    ///
+    /// # Arguments
+    ///
+    /// * 'lines' - The lines the conflict covers, including its markers.
+    /// * 'keep' - The side to keep.
+    pub fn resolve(&self, lines: &[String], keep: Keep) -> Vec<String> {
+        let index = |line: u32| (line - self.start) as usize;
+        let ours = &lines[1..index(self.base.unwrap_or(self.separator))];
+        let theirs = &lines[index(self.separator) + 1..index(self.end)];
+        match keep {
+            Keep::Ours => ours.to_vec(),
+            Keep::Theirs => theirs.to_vec(),
+            Keep::Both => [ours, theirs].concat(),
+        }
+    }
+}
+
+/// Whether a line is a conflict marker of a kind, which is the marker alone or followed by a
+/// space and a label such as a branch name.
+fn is_marker(line: &str, marker: &str) -> bool {
+    line.strip_prefix(marker)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+}
+
+/// Finds the conflicts in a document's lines. Markers which don't make up a whole conflict are
+/// ignored.
+///
+/// # Arguments
+///
+/// * 'lines' - The document's lines, starting from line 1.
+pub fn find(lines: &[String]) -> Vec<Conflict> {
+    let mut conflicts = vec![];
+    let mut start = None;
+    let mut base = None;
+    let mut separator = None;
+
+    for (line, text) in (1..).zip(lines) {
+        if is_marker(text, OURS) {
+            (start, base, separator) = (Some(line), None, None);
+        } else if is_marker(text, BASE) && start.is_some() && separator.is_none() {
+            base = Some(line);
+        } else if is_marker(text, SEPARATOR) && start.is_some() {
+            separator = Some(line);
+        } else if is_marker(text, THEIRS) {
+            if let (Some(start), Some(separator)) = (start, separator) {
+                conflicts.push(Conflict {
+                    start,
+                    base,
+                    separator,
+                    end: line,
+                });
+            }
+            (start, base, separator) = (None, None, None);
+        }
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    const MERGED: &str = "\
+fn main() {
+<<<<<<< HEAD
+    ours();
+=======
+    theirs();
+    more();
+>>>>>>> feature
+}
+<<<<<<< HEAD
+a
+||||||| base
+b
+=======
+c
+>>>>>>> feature";
+
+    #[test]
+    fn find_conflicts() {
+        assert_eq!(
+            vec![
+                Conflict {
+                    start: 2,
+                    base: None,
+                    separator: 4,
+                    end: 7
+                },
+                Conflict {
+                    start: 9,
+                    base: Some(11),
+                    separator: 13,
+                    end: 15
+                },
+            ],
+            find(&lines(MERGED))
+        );
+    }
+
+    #[test_case("<<<<<<< HEAD\n=======\n";      "unfinished")]
+    #[test_case("<<<<<<<<\n=======\n>>>>>>>\n"; "longer_marker")]
+    #[test_case("=======\n>>>>>>> feature\n";   "missing_start")]
+    fn incomplete_conflicts(text: &str) {
+        assert_eq!(Vec::<Conflict>::new(), find(&lines(text)));
+    }
+
+    #[test_case(Keep::Ours, 1, vec!["    ours();"];                              "ours")]
+    #[test_case(Keep::Theirs, 1, vec!["    theirs();", "    more();"];          "theirs")]
+    #[test_case(Keep::Both, 1, vec!["    ours();", "    theirs();", "    more();"]; "both")]
+    #[test_case(Keep::Ours, 2, vec!["a"];                                        "ours_with_base")]
+    #[test_case(Keep::Theirs, 2, vec!["c"];                                      "theirs_with_base")]
+    fn resolve_conflicts(keep: Keep, index: usize, expected: Vec<&str>) {
+        let lines = lines(MERGED);
+        let conflict = find(&lines)[index - 1];
+        let covered = &lines[conflict.start as usize - 1..conflict.end as usize];
+        assert_eq!(expected, conflict.resolve(covered, keep));
+    }
+}
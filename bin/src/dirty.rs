@@ -0,0 +1,70 @@
+use std::ops::Range;
+
+/// The parts of the screen which need to be redrawn. The status line and cursor are always
+/// redrawn, as they change with almost every event.
+#[derive(Debug, Default, PartialEq)]
+pub enum Dirty {
+    /// Only the status line and cursor have changed.
+    #[default]
+    Cursor,
+    /// The given document lines have changed, along with the status line and cursor.
+    Lines(Range<u32>),
+    /// The whole screen needs to be redrawn.
+    All,
+}
+
+impl Dirty {
+    /// Marks a single document line as needing to be redrawn.
+    ///
+    /// # Arguments
+    ///
+    /// * 'line' - The line which has changed. Line numbers start from 1.
+    pub fn mark_line(&mut self, line: u32) {
+        self.mark_lines(line..line + 1);
+    }
+
+    /// Marks a range of document lines as needing to be redrawn, merging it with any lines
+    /// already marked.
+    pub fn mark_lines(&mut self, lines: Range<u32>) {
+        *self = match std::mem::take(self) {
+            Dirty::Cursor => Dirty::Lines(lines),
+            Dirty::Lines(marked) => {
+                Dirty::Lines(marked.start.min(lines.start)..marked.end.max(lines.end))
+            }
+            Dirty::All => Dirty::All,
+        };
+    }
+
+    /// Marks the whole screen as needing to be redrawn.
+    pub fn mark_all(&mut self) {
+        *self = Dirty::All;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mark_line() {
+        let mut dirty = Dirty::default();
+        dirty.mark_line(5);
+        assert_eq!(Dirty::Lines(5..6), dirty);
+    }
+
+    #[test]
+    fn mark_lines_merges_ranges() {
+        let mut dirty = Dirty::default();
+        dirty.mark_line(5);
+        dirty.mark_lines(2..4);
+        assert_eq!(Dirty::Lines(2..6), dirty);
+    }
+
+    #[test]
+    fn mark_lines_after_all() {
+        let mut dirty = Dirty::default();
+        dirty.mark_all();
+        dirty.mark_line(5);
+        assert_eq!(Dirty::All, dirty);
+    }
+}
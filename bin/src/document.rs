@@ -1,112 +1,1772 @@
-use std::error::Error;
+use std::collections::BTreeSet;
 use std::ffi::OsString;
-use std::fs::{self};
-use std::ops::Range;
-use std::path::PathBuf;
+use std::fs::{self, File, Permissions};
+use std::io::{Read, Write};
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
 
-use log::info;
+use log::{error, info};
 use text_buffer::TextBuffer;
 
+use crate::case::Case;
+use crate::compress::Compression;
+use crate::config::{Config, EncryptionConfig};
+use crate::error::{EditorError, Result};
+use crate::filetype;
+use crate::fold::{self, FoldMethod, Folds};
+use crate::hex;
+use crate::search::{self, Count, Found, SearchIndex};
+use crate::selection::Position;
+use crate::shell;
+use crate::signs::Signs;
+use crate::undo::{Change, Distance, Step, UndoTree};
+use crate::virtual_text::VirtualText;
+
+/// The number of bytes read from disk at a time when loading a large document.
+const CHUNK_SIZE: usize = 1024 * 1024;
+/// The number of pieces a document's piece table can be split into by editing before it's
+/// compacted while the editor is idle.
+const COMPACT_PIECES: usize = 1000;
+/// The largest document compacted while the editor is idle, in bytes, so compacting doesn't
+/// keep input waiting.
+const COMPACT_SIZE: usize = 4 * 1024 * 1024;
+/// The smallest document given a search index, in bytes. Smaller documents are quick enough to
+/// search in full.
+const INDEX_SIZE: usize = 8 * 1024 * 1024;
+
 pub struct Document {
     buffer: TextBuffer,
     path: Option<PathBuf>,
     debug: Option<PathBuf>,
     name: Option<OsString>,
+    readonly: bool,
+    large: bool,
+    loader: Option<Loader>,
+    bytes: Option<Vec<u8>>,
+    /// The lines edited since the document was last saved.
+    modified: BTreeSet<u32>,
+    filetype: Option<String>,
+    history: UndoTree,
+    /// The changes made since they were last sent to the other participants, while the
+    /// document is shared.
+    outbox: Option<Vec<Change>>,
+    /// The commands the document's file is decrypted and encrypted with, if it's encrypted.
+    encryption: Option<EncryptionConfig>,
+    /// The format the document's file is compressed with, if it's compressed.
+    compression: Option<Compression>,
+    /// When the document's file was last changed, as of loading or saving it.
+    disk_time: Option<SystemTime>,
+    /// The size of the document's file as of loading or saving it, once it's been read in full.
+    disk_len: Option<u64>,
+    /// Whether the document's file was changed by another program while the document had
+    /// unsaved changes, which saving the document overwrites.
+    outdated: bool,
+    /// The index searches of the document use, once it's large enough to have one.
+    index: Option<SearchIndex>,
+    /// The annotations drawn among the document's lines.
+    virtual_text: VirtualText,
+    /// The closed folds hiding the document's lines.
+    folds: Folds,
+    /// The signs drawn in the gutter next to the document's lines.
+    signs: Signs,
+    /// The number of edits made to the document, which tells what's worked out from its text
+    /// when it's out of date.
+    edits: u64,
+}
+
+/// The line break at the end of each line of a document.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    /// The line break used by documents which don't have one yet, which is CRLF on Windows.
+    pub const NATIVE: LineEnding = if cfg!(windows) {
+        LineEnding::Crlf
+    } else {
+        LineEnding::Lf
+    };
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
+/// Reads a document from disk in chunks as it's needed, so large documents can be opened without
+/// reading the entire file up front.
+struct Loader {
+    file: File,
+    pending: Vec<u8>,
+    /// The number of bytes read from the file so far.
+    read: u64,
+    /// The size of the file when it was opened.
+    size: u64,
+    /// Whether invalid UTF-8 has been found in the file so far.
+    lossy: bool,
+}
+
+impl Loader {
+    /// Reads the next chunk of the file. Any bytes at the end of the chunk which don't form a
+    /// complete UTF-8 character are kept and prepended to the following chunk. Invalid UTF-8 is
+    /// replaced, as when a document is loaded in full. Returns None once the whole file has been
+    /// read.
+    fn next_chunk(&mut self) -> Result<Option<String>> {
+        let mut bytes = std::mem::take(&mut self.pending);
+        let read = (&mut self.file)
+            .take(CHUNK_SIZE as u64)
+            .read_to_end(&mut bytes)?;
+        self.read += read as u64;
+
+        if bytes.is_empty() {
+            return Ok(None);
+        }
+
+        let mut text = String::with_capacity(bytes.len());
+        let mut chunks = bytes.utf8_chunks().peekable();
+        while let Some(chunk) = chunks.next() {
+            text.push_str(chunk.valid());
+            let invalid = chunk.invalid();
+            // A character cut off at the end of the chunk may be completed by the next one.
+            let incomplete = read > 0
+                && chunks.peek().is_none()
+                && std::str::from_utf8(invalid).is_err_and(|e| e.error_len().is_none());
+            match incomplete {
+                true => self.pending = invalid.to_vec(),
+                false if !invalid.is_empty() => {
+                    text.push(char::REPLACEMENT_CHARACTER);
+                    self.lossy = true;
+                }
+                false => {}
+            }
+        }
+        Ok(Some(text))
+    }
+}
+
+impl Default for Document {
+    fn default() -> Document {
+        Document::new()
+    }
 }
 
-impl Document {
-    pub fn new() -> Document {
-        Document {
-            buffer: TextBuffer::new(None),
-            path: None,
-            debug: None,
-            name: None,
-        }
+impl Document {
+    pub fn new() -> Document {
+        Document {
+            buffer: TextBuffer::new(None),
+            path: None,
+            debug: None,
+            name: None,
+            readonly: false,
+            large: false,
+            loader: None,
+            bytes: None,
+            modified: BTreeSet::new(),
+            filetype: None,
+            history: UndoTree::new(),
+            outbox: None,
+            encryption: None,
+            compression: None,
+            disk_time: None,
+            disk_len: None,
+            outdated: false,
+            index: None,
+            virtual_text: VirtualText::new(),
+            folds: Folds::new(),
+            signs: Signs::new(),
+            edits: 0,
+        }
+    }
+
+    /// Constructs an untitled document containing text which hasn't been saved, such as a
+    /// restored draft, so it counts as modified.
+    pub fn unsaved(text: &str) -> Document {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(text.to_string()));
+        document.modified.insert(1);
+        document
+    }
+
+    /// Constructs an untitled document containing some text, such as the output of a command.
+    /// The document is shown with the given name until it's saved.
+    pub fn scratch(name: &str, text: &str) -> Document {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(text.to_string()));
+        document.name = Some(name.into());
+        document
+    }
+
+    /// Constructs a document for a file on disk, marking it as read-only if the file can't be
+    /// written to.
+    fn from_file(file: PathBuf, buffer: TextBuffer) -> Document {
+        let readonly = !is_writable(&file);
+        if readonly {
+            info!("Document {:?} is not writable, opening as read-only", file);
+        }
+
+        Document {
+            buffer,
+            disk_time: modified_time(&file),
+            disk_len: None,
+            outdated: false,
+            debug: Some(debug_path(&file)),
+            name: file.file_name().map(|f| f.to_owned()),
+            path: Some(file),
+            readonly,
+            large: false,
+            loader: None,
+            bytes: None,
+            modified: BTreeSet::new(),
+            filetype: None,
+            history: UndoTree::new(),
+            outbox: None,
+            encryption: None,
+            compression: None,
+            index: None,
+            virtual_text: VirtualText::new(),
+            folds: Folds::new(),
+            signs: Signs::new(),
+            edits: 0,
+        }
+    }
+
+    /// Loads a document from disk. Files containing binary data are opened in the hex view, while
+    /// text files containing a small amount of invalid UTF-8 are loaded with the invalid bytes
+    /// replaced and marked as read-only, so saving can't corrupt them. Compressed files are
+    /// decompressed, and compressed again when they're saved.
+    pub fn load(file: PathBuf) -> Result<Document> {
+        let mut bytes = fs::read(&file)?;
+        let len = bytes.len() as u64;
+        let compression = Compression::detect(&file, &bytes);
+        if let Some(compression) = compression {
+            info!("Decompressing document {:?} as {:?}", file, compression);
+            bytes = compression.decompress(bytes)?;
+        }
+
+        let mut document = Document::load_bytes(file, bytes);
+        document.disk_len = Some(len);
+        document.compression = compression;
+        document.detect_filetype();
+        Ok(document)
+    }
+
+    /// Constructs a document from the contents of a file.
+    fn load_bytes(file: PathBuf, bytes: Vec<u8>) -> Document {
+        if hex::is_binary(&bytes) {
+            info!("Document {:?} contains binary data, opening as hex", file);
+            let mut document = Document::from_file(file, TextBuffer::new(None));
+            document.bytes = Some(bytes);
+            return document;
+        }
+
+        let (contents, lossy) = match String::from_utf8(bytes) {
+            Ok(contents) => (contents, false),
+            Err(e) => (String::from_utf8_lossy(e.as_bytes()).into_owned(), true),
+        };
+
+        let len = contents.len();
+        let buffer = TextBuffer::new(Some(contents));
+        debug_assert_eq!(len, buffer.doc_len());
+
+        info!("Loaded {} characters from document {:?}", len, file);
+        let mut document = Document::from_file(file, buffer);
+        if lossy {
+            info!("Document contains invalid UTF-8, opening as read-only");
+            document.readonly = true;
+        }
+        document
+    }
+
+    /// Loads an encrypted document from disk, decrypting it with the configured command. The
+    /// decrypted text is only kept in memory, so no debug file is written for the document. The
+    /// terminal should be restored while it's loaded or saved, as the commands may prompt for a
+    /// passphrase.
+    pub fn load_encrypted(file: PathBuf, encryption: EncryptionConfig) -> Result<Document> {
+        let ciphertext = fs::read(&file)?;
+        let len = ciphertext.len() as u64;
+        let plaintext = shell::pipe(&encryption.decrypt, ciphertext)?;
+        let contents = String::from_utf8(plaintext).map_err(|_| EditorError::InvalidUtf8)?;
+        info!("Decrypted document {:?}", file);
+
+        let mut document = Document::from_file(file, TextBuffer::new(Some(contents)));
+        document.disk_len = Some(len);
+        document.set_encryption(Some(encryption));
+        document.detect_filetype();
+        Ok(document)
+    }
+
+    /// Opens a document, switching to large file mode if the file is larger than the configured
+    /// threshold. In large file mode the document is loaded lazily as lines are requested.
+    pub fn open(file: PathBuf, config: &Config) -> Result<Document> {
+        if let Some(encryption) = config.encryption(&file) {
+            return Document::load_encrypted(file, encryption.clone());
+        }
+
+        // Compressed files can't be loaded lazily, so they're decompressed in full.
+        let size = fs::metadata(&file)?.len();
+        if size <= config.large_file_threshold || Compression::from_path(&file).is_some() {
+            return Document::load(file);
+        }
+
+        // Binary files can't be loaded lazily as text, so they're loaded in full.
+        let mut sample = vec![];
+        File::open(&file)?
+            .take(CHUNK_SIZE as u64)
+            .read_to_end(&mut sample)?;
+        if hex::is_binary(&sample) {
+            return Document::load(file);
+        }
+
+        info!(
+            "Document {:?} is {} bytes, opening in large file mode",
+            file, size
+        );
+        let loader = Loader {
+            file: File::open(&file)?,
+            pending: vec![],
+            read: 0,
+            size,
+            lossy: false,
+        };
+
+        let mut document = Document::from_file(file, TextBuffer::new(None));
+        document.large = true;
+        document.loader = Some(loader);
+        document.load_chunk()?;
+        document.detect_filetype();
+        Ok(document)
+    }
+
+    /// Constructs an empty document for a file which doesn't exist yet. The file will be created
+    /// when the document is first saved, encrypted or compressed if its extension calls for it.
+    pub fn create(file: PathBuf, config: &Config) -> Document {
+        info!("Creating new document {:?}", file);
+        let encryption = config.encryption(&file).cloned();
+        let compression = Compression::from_path(&file);
+        let mut document = Document::from_file(file, TextBuffer::new(None));
+        document.readonly = false;
+        document.set_encryption(encryption);
+        document.compression = compression;
+        document.detect_filetype();
+        document
+    }
+
+    /// The file name of the document, or a placeholder if the document hasn't been saved.
+    pub fn name(&self) -> String {
+        self.name
+            .as_ref()
+            .map_or(String::from("[No Name]"), |name| {
+                name.to_string_lossy().into_owned()
+            })
+    }
+
+    /// The name of the document's filetype, if it could be detected.
+    pub fn filetype(&self) -> Option<&str> {
+        self.filetype.as_deref()
+    }
+
+    /// Detects the filetype from the document's path and the lines at its start and end. The
+    /// end of a lazily loaded document isn't checked until it has been read, so a modeline there
+    /// doesn't force the whole file to be loaded.
+    fn detect_filetype(&mut self) {
+        if self.is_binary() {
+            self.filetype = None;
+            return;
+        }
+
+        let count = self.line_count();
+        let head = filetype::MODELINE_LINES.min(count);
+        let mut lines = self.get_lines(1..head + 1);
+        if self.loader.is_none() && count > head {
+            let start = (count - filetype::MODELINE_LINES).max(head) + 1;
+            lines.extend(self.get_lines(start..count + 1));
+        }
+
+        // Encrypted and compressed files are detected by the extension before the encrypted or
+        // compressed one.
+        let path = match self.encryption.is_some() || self.compression.is_some() {
+            true => self.path.as_ref().map(|path| path.with_extension("")),
+            false => self.path.clone(),
+        };
+        self.filetype = filetype::detect(path.as_deref(), &lines);
+        info!("Detected filetype {:?} for {:?}", self.filetype, self.path);
+    }
+
+    pub fn is_readonly(&self) -> bool {
+        self.readonly
+    }
+
+    pub fn set_readonly(&mut self, readonly: bool) {
+        self.readonly = readonly;
+    }
+
+    /// Whether the document's file was changed by another program while the document had
+    /// unsaved changes, until the document is saved.
+    pub fn is_outdated(&self) -> bool {
+        self.outdated
+    }
+
+    pub fn set_outdated(&mut self, outdated: bool) {
+        self.outdated = outdated;
+    }
+
+    /// Whether the document has changes which haven't been saved.
+    pub fn is_modified(&self) -> bool {
+        !self.modified.is_empty()
+    }
+
+    /// Whether the document was opened in large file mode.
+    pub fn is_large(&self) -> bool {
+        self.large
+    }
+
+    /// The percentage of a lazily loaded document which has been read so far, or None if the
+    /// whole document has been loaded.
+    pub fn load_progress(&self) -> Option<u8> {
+        self.loader.as_ref().map(|loader| match loader.size {
+            0 => 0,
+            size => (loader.read.min(size) * 100 / size) as u8,
+        })
+    }
+
+    /// Loads the next chunk of a lazily loaded document.
+    pub fn load_chunk(&mut self) -> Result<()> {
+        if let Some(loader) = self.loader.as_mut() {
+            let lossy = loader.lossy;
+            match loader.next_chunk()? {
+                Some(text) => {
+                    self.buffer.extend_original(&text);
+                    if loader.lossy && !lossy {
+                        info!("Document contains invalid UTF-8, opening as read-only");
+                        self.readonly = true;
+                    }
+                }
+                None => {
+                    info!("Finished loading document {:?}", self.path);
+                    self.disk_len = Some(loader.read);
+                    self.loader = None;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads a lazily loaded document until the given line is available, or until the whole
+    /// document has been read.
+    pub fn load_until(&mut self, line: u32) -> Result<()> {
+        while self.loader.is_some() && self.buffer.get_line_count() <= line {
+            self.load_chunk()?;
+        }
+        Ok(())
+    }
+
+    /// Loads the remainder of a lazily loaded document, returning the full text.
+    fn load_all(&mut self) -> Result<String> {
+        while self.loader.is_some() {
+            self.load_chunk()?;
+        }
+        Ok(self.buffer.text())
+    }
+
+    /// The full contents of the document as they should be written to disk.
+    fn contents(&mut self) -> Result<Vec<u8>> {
+        let mut contents = match &self.bytes {
+            Some(bytes) => bytes.clone(),
+            None => self.load_all()?.into_bytes(),
+        };
+        if let Some(compression) = &self.compression {
+            contents = compression.compress(contents)?;
+        }
+        match &self.encryption {
+            Some(encryption) => shell::pipe(&encryption.encrypt, contents),
+            None => Ok(contents),
+        }
+    }
+
+    /// Sets the commands the document is decrypted and encrypted with, so it's encrypted when
+    /// saved. Encrypted documents don't write a debug file, which would contain the plaintext.
+    pub fn set_encryption(&mut self, encryption: Option<EncryptionConfig>) {
+        if encryption.is_some() {
+            self.debug = None;
+        }
+        self.encryption = encryption;
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.encryption.is_some()
+    }
+
+    /// The format the document's file is compressed with, if it's compressed.
+    pub fn compression(&self) -> Option<Compression> {
+        self.compression
+    }
+
+    /// Whether the document contains binary data, which is displayed in the hex view with one
+    /// line per row of bytes.
+    pub fn is_binary(&self) -> bool {
+        self.bytes.is_some()
+    }
+
+    /// Sets one nibble of a byte in a binary document.
+    ///
+    /// # Arguments
+    ///
+    /// * 'row' - The row of the hex view containing the byte. Rows start from 1.
+    /// * 'column' - The index of the byte within the row.
+    /// * 'value' - The value of the nibble, from 0 to 15.
+    /// * 'low' - Whether to set the low nibble rather than the high nibble.
+    pub fn set_nibble(&mut self, row: u32, column: u32, value: u8, low: bool) {
+        let offset = (row as usize - 1) * hex::BYTES_PER_ROW + column as usize;
+        if let Some(byte) = self.bytes.as_mut().and_then(|b| b.get_mut(offset)) {
+            *byte = match low {
+                true => (*byte & 0xf0) | (value & 0x0f),
+                false => (*byte & 0x0f) | (value << 4),
+            };
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        let contents = self.contents()?;
+        let path = self.path.as_ref().ok_or(EditorError::NoFileName)?;
+        fs::write(path, &contents)?;
+        self.disk_len = Some(contents.len() as u64);
+        self.mark_saved();
+        Ok(())
+    }
+
+    /// Saves the document to a new path, which the document will refer to from then on. The
+    /// document is encrypted if the new path's extension is configured as encrypted.
+    pub fn save_as(&mut self, path: PathBuf, config: &Config) -> Result<()> {
+        // Keep the compression level when saving to a path with the same format.
+        let compression = match (Compression::from_path(&path), self.compression) {
+            (Some(new), Some(old)) if new.same_format(&old) => Some(old),
+            (new, _) => new,
+        };
+        let previous = (
+            std::mem::replace(&mut self.encryption, config.encryption(&path).cloned()),
+            std::mem::replace(&mut self.compression, compression),
+        );
+        let result = self.contents().and_then(|contents| {
+            fs::write(&path, &contents)?;
+            Ok(contents.len())
+        });
+        let len = match result {
+            Ok(len) => len,
+            Err(e) => {
+                (self.encryption, self.compression) = previous;
+                return Err(e);
+            }
+        };
+        info!("Saved document as {:?}", path);
+
+        self.debug = self.encryption.is_none().then(|| debug_path(&path));
+        self.name = path.file_name().map(|f| f.to_owned());
+        self.path = Some(path);
+        self.readonly = false;
+        self.disk_len = Some(len as u64);
+        self.mark_saved();
+        self.detect_filetype();
+        Ok(())
+    }
+
+    /// Moves the document's file to a new path, which the document will refer to from then on.
+    /// A document whose file hasn't been created yet just takes on the new path.
+    pub fn rename(&mut self, path: PathBuf) -> Result<()> {
+        let from = self.path.as_ref().ok_or(EditorError::NoFileName)?;
+        if from.exists() {
+            fs::rename(from, &path)?;
+        }
+        info!("Renamed {:?} to {:?}", from, path);
+
+        self.debug = self.encryption.is_none().then(|| debug_path(&path));
+        self.name = path.file_name().map(|f| f.to_owned());
+        self.path = Some(path);
+        self.acknowledge_disk_change();
+        self.detect_filetype();
+        Ok(())
+    }
+
+    /// Deletes the document's file, leaving the document's text as it is.
+    pub fn delete_file(&self) -> Result<()> {
+        let path = self.path.as_ref().ok_or(EditorError::NoFileName)?;
+        fs::remove_file(path)?;
+        info!("Deleted {:?}", path);
+        Ok(())
+    }
+
+    /// Saves the document even if the file is marked as read-only, by temporarily making it
+    /// writable and restoring the original permissions afterwards.
+    pub fn force_save(&mut self) -> Result<()> {
+        let contents = self.contents()?;
+        let path = self.path.as_ref().ok_or(EditorError::NoFileName)?;
+        let permissions = fs::metadata(path).map(|m| m.permissions()).ok();
+
+        match permissions {
+            Some(original) if original.readonly() => {
+                fs::set_permissions(path, writable_permissions(&original))?;
+                let result = fs::write(path, &contents);
+                fs::set_permissions(path, original)?;
+                result?;
+            }
+            _ => fs::write(path, &contents)?,
+        }
+
+        self.readonly = false;
+        self.disk_len = Some(contents.len() as u64);
+        self.mark_saved();
+        Ok(())
+    }
+
+    /// Saves the document by piping its contents through `sudo tee`, for files the current user
+    /// doesn't have permission to write to. The terminal should be restored beforehand as sudo
+    /// may prompt for a password.
+    pub fn save_elevated(&mut self) -> Result<()> {
+        let contents = self.contents()?;
+        let path = self.path.as_ref().ok_or(EditorError::NoFileName)?;
+        info!("Attempting to save {:?} with elevated permissions", path);
+
+        let mut child = Command::new("sudo")
+            .arg("tee")
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(&contents)?;
+        }
+
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(EditorError::Elevated(status));
+        }
+
+        self.readonly = false;
+        self.disk_len = Some(contents.len() as u64);
+        self.mark_saved();
+        Ok(())
+    }
+
+    /// Whether the document's piece table has been split up enough by editing to be worth
+    /// compacting. Documents still loading and large documents are left alone.
+    pub fn needs_compaction(&self) -> bool {
+        self.loader.is_none()
+            && self.buffer.piece_count() > COMPACT_PIECES
+            && self.buffer.doc_len() <= COMPACT_SIZE
+    }
+
+    /// The number of pieces in the document's piece table.
+    pub fn piece_count(&self) -> usize {
+        self.buffer.piece_count()
+    }
+
+    /// The number of bytes of inserted text the document's piece table holds.
+    pub fn add_buffer_len(&self) -> usize {
+        self.buffer.add_len()
+    }
+
+    /// Rebuilds the document's piece table as a single piece, dropping the deleted text it
+    /// still holds.
+    pub fn compact(&mut self) {
+        info!("Compacting document {:?}", self.path);
+        self.buffer.compact();
+    }
+
+    /// Whether the document is large enough to have a search index, and it isn't fully built.
+    /// Documents are only indexed once they've finished loading.
+    pub fn needs_index(&self) -> bool {
+        self.loader.is_none()
+            && !self.is_binary()
+            && self.buffer.doc_len() >= INDEX_SIZE
+            && self.index.as_ref().is_none_or(|index| !index.is_complete())
+    }
+
+    /// Builds the next block of the document's search index, returning whether there was one
+    /// to build.
+    pub fn build_index(&mut self) -> bool {
+        if !self.needs_index() {
+            return false;
+        }
+        let buffer = &self.buffer;
+        self.index
+            .get_or_insert_with(|| SearchIndex::new(buffer.get_line_count()))
+            .build_next(|lines| read_text(buffer, lines))
+    }
+
+    /// Finds the next occurrence of some text after a position, or the previous one before it,
+    /// optionally wrapping around the end of the document. The whole document is loaded first.
+    /// Only the lines the search index can't rule out are read, if the document has one.
+    ///
+    /// # Arguments
+    ///
+    /// * 'pattern' - The text to find.
+    /// * 'from' - The position to search from.
+    /// * 'forward' - Whether to search towards the end of the document.
+    /// * 'wrap' - Whether to carry on from the other end of the document.
+    pub fn find(
+        &mut self,
+        pattern: &str,
+        from: Position,
+        forward: bool,
+        wrap: bool,
+    ) -> Result<Option<Found>> {
+        if self.is_binary() {
+            return Ok(None);
+        }
+        self.load_until(u32::MAX)?;
+        let ranges = self.search_ranges(pattern);
+        let read = |lines| self.read_lines(lines);
+        Ok(search::find(&ranges, read, pattern, from, forward, wrap))
+    }
+
+    /// Counts the occurrences of some text, and finds which of them is at a position. Like
+    /// `find`, only the lines the search index can't rule out are read.
+    ///
+    /// # Arguments
+    ///
+    /// * 'pattern' - The text to count.
+    /// * 'at' - The position of the occurrence to find the place of.
+    pub fn count(&mut self, pattern: &str, at: Position) -> Result<Count> {
+        self.load_until(u32::MAX)?;
+        let ranges = match self.is_binary() {
+            true => vec![],
+            false => self.search_ranges(pattern),
+        };
+        let read = |lines| self.read_lines(lines);
+        Ok(search::count(&ranges, read, pattern, at))
+    }
+
+    /// The ranges of lines which might contain some text, according to the search index.
+    fn search_ranges(&self, pattern: &str) -> Vec<Range<u32>> {
+        let all = 1..self.buffer.get_line_count() + 1;
+        match &self.index {
+            Some(index) => index.candidates(pattern),
+            None => vec![all],
+        }
+    }
+
+    fn read_lines(&self, lines: Range<u32>) -> Vec<String> {
+        read_text(&self.buffer, lines)
+            .split('\n')
+            .map(String::from)
+            .collect()
+    }
+
+    /// The annotations drawn among the document's lines.
+    pub fn virtual_text(&self) -> &VirtualText {
+        &self.virtual_text
+    }
+
+    pub fn virtual_text_mut(&mut self) -> &mut VirtualText {
+        &mut self.virtual_text
+    }
+
+    /// The lines covered by the fold at a line, found by indentation or by markers.
+    pub fn fold_range(&self, line: u32, method: FoldMethod) -> Option<RangeInclusive<u32>> {
+        let last = self.line_count();
+        match method {
+            FoldMethod::Indent => fold::indent_range(line, last, |line| {
+                (!self.is_blank(line)).then(|| self.indentation(line))
+            }),
+            FoldMethod::Marker => fold::marker_range(line, &self.get_lines(1..last + 1)),
+        }
+    }
+
+    /// The number of edits made to the document since it was opened.
+    pub fn edits(&self) -> u64 {
+        self.edits
+    }
+
+    pub fn folds(&self) -> &Folds {
+        &self.folds
+    }
+
+    pub fn folds_mut(&mut self) -> &mut Folds {
+        &mut self.folds
+    }
+
+    pub fn signs(&self) -> &Signs {
+        &self.signs
+    }
+
+    pub fn signs_mut(&mut self) -> &mut Signs {
+        &mut self.signs
+    }
+
+    /// The size of the document's file as of loading or saving it, or None if the file hasn't
+    /// been read in full.
+    pub fn disk_len(&self) -> Option<u64> {
+        self.disk_len
+    }
+
+    /// Marks the document as matching its file, once it's been written.
+    pub fn mark_saved(&mut self) {
+        self.modified.clear();
+        self.outdated = false;
+        self.disk_time = self.path.as_deref().and_then(modified_time);
+    }
+
+    /// Whether the document's file has been changed by another program since the document was
+    /// loaded or saved.
+    pub fn changed_on_disk(&self) -> bool {
+        let Some(path) = &self.path else {
+            return false;
+        };
+        modified_time(path).is_some_and(|time| Some(time) != self.disk_time)
+    }
+
+    /// Accepts the file's current contents as known, so a change is only reported once.
+    pub fn acknowledge_disk_change(&mut self) {
+        self.disk_time = self.path.as_deref().and_then(modified_time);
+    }
+
+    /// Adds text read from the end of the document's file as it grows while it's followed,
+    /// replacing the whole document if the file was truncated. The text isn't an edit, so it
+    /// can't be undone and doesn't leave the document modified.
+    ///
+    /// # Arguments
+    ///
+    /// * 'text' - The text added to the file.
+    /// * 'truncated' - Whether the file was truncated, so the text is all of it.
+    pub fn extend_from_disk(&mut self, text: &str, truncated: bool) {
+        let len = self.buffer.doc_len();
+        if truncated && len > 0 {
+            let deleted = self.buffer.text_range(0, len);
+            self.buffer.delete(0, len);
+            self.track_edit(0, &deleted, "");
+        }
+        if !text.is_empty() {
+            let end = self.buffer.doc_len();
+            self.buffer.insert(end, text);
+            self.track_edit(end, "", text);
+        }
+        self.acknowledge_disk_change();
+    }
+
+    pub fn text(&self) -> String {
+        self.buffer.text()
+    }
+
+    pub fn len(&self) -> u32 {
+        match &self.bytes {
+            Some(bytes) => bytes.len() as u32,
+            None => self.buffer.doc_len() as u32,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of lines in the document. A trailing new line character terminates the
+    /// final line rather than starting a new one.
+    pub fn line_count(&self) -> u32 {
+        if let Some(bytes) = &self.bytes {
+            return hex::row_count(bytes.len());
+        }
+
+        let count = self.buffer.get_line_count();
+        match self.buffer.last_char() {
+            Some('\n') => count - 1,
+            _ => count,
+        }
+    }
+
+    /// The line break the document uses, detected from the end of its first line. Documents
+    /// without a line break yet use the platform's.
+    pub fn line_ending(&self) -> LineEnding {
+        if self.is_binary() {
+            return LineEnding::Lf;
+        }
+        if self.buffer.get_line_count() < 2 {
+            return LineEnding::NATIVE;
+        }
+        match self.buffer.get_line_content(1) {
+            Some(line) if line.ends_with('\r') => LineEnding::Crlf,
+            _ => LineEnding::Lf,
+        }
+    }
+
+    pub fn get_lines(&self, lines: Range<u32>) -> Vec<String> {
+        let mut results = vec![];
+        info!("Fetching lines from document with range {:?}", lines);
+
+        let line_count = self.line_count();
+        for line in lines {
+            if line > line_count {
+                break;
+            }
+            if let Some(bytes) = &self.bytes {
+                results.push(hex::render_row(bytes, line as usize - 1));
+                continue;
+            }
+            if let Some(content) = self.buffer.get_line_content(line) {
+                results.push(content);
+            }
+        }
+
+        results
+    }
+
+    /// Returns the number of characters on a line, excluding the new line character. For binary
+    /// documents this is the index of the last byte on the row, as the cursor can't be placed
+    /// after it.
+    pub fn line_len(&self, line: u32) -> u32 {
+        if let Some(bytes) = &self.bytes {
+            let start = (line as usize).saturating_sub(1) * hex::BYTES_PER_ROW;
+            let len = bytes.len().saturating_sub(start).min(hex::BYTES_PER_ROW);
+            return len.saturating_sub(1) as u32;
+        }
+
+        self.buffer
+            .get_line_content(line)
+            .map_or(0, |content| content.chars().count() as u32)
+    }
+
+    /// Returns the columns of the word at the given position, or None if there's no word there.
+    /// A position just after the end of a word is counted as part of it. Words are made up of
+    /// alphanumeric characters and underscores.
+    pub fn word_bounds(&self, line: u32, column: u32) -> Option<Range<u32>> {
+        if self.is_binary() {
+            return None;
+        }
+
+        let chars: Vec<char> = self.buffer.get_line_content(line)?.chars().collect();
+        let is_word = |i: usize| {
+            chars
+                .get(i)
+                .is_some_and(|c| c.is_alphanumeric() || *c == '_')
+        };
+        let column = column as usize;
+        let within = match is_word(column) {
+            true => column,
+            false if column > 0 && is_word(column - 1) => column - 1,
+            false => return None,
+        };
+        let start = (0..=within).rev().take_while(|i| is_word(*i)).last()?;
+        let end = (within..chars.len())
+            .find(|i| !is_word(*i))
+            .unwrap_or(chars.len());
+        Some(start as u32..end as u32)
+    }
+
+    /// Returns the start of the next word after a position, or the end of the document if there
+    /// isn't one. Blank lines count as words, so they aren't skipped over.
+    ///
+    /// # Arguments
+    ///
+    /// * 'position' - The position to move from.
+    /// * 'subwords' - Whether to stop at each part of camelCase and snake_case words.
+    pub fn next_word(&self, position: Position, subwords: bool) -> Position {
+        for row in position.row..=self.line_count() {
+            let starts = self.word_starts(row, subwords);
+            let start = match row == position.row {
+                true => starts.into_iter().find(|column| *column > position.column),
+                false => starts.first().copied(),
+            };
+            if let Some(column) = start {
+                return Position { row, column };
+            }
+        }
+        let row = self.line_count().max(1);
+        Position {
+            row,
+            column: self.line_len(row),
+        }
+    }
+
+    /// Returns the start of the word before a position, or the start of the document if there
+    /// isn't one.
+    ///
+    /// # Arguments
+    ///
+    /// * 'position' - The position to move from.
+    /// * 'subwords' - Whether to stop at each part of camelCase and snake_case words.
+    pub fn previous_word(&self, position: Position, subwords: bool) -> Position {
+        for row in (1..=position.row).rev() {
+            let starts = self.word_starts(row, subwords);
+            let start = match row == position.row {
+                true => starts
+                    .into_iter()
+                    .rev()
+                    .find(|column| *column < position.column),
+                false => starts.last().copied(),
+            };
+            if let Some(column) = start {
+                return Position { row, column };
+            }
+        }
+        Position { row: 1, column: 0 }
+    }
+
+    /// Returns the columns where words start on a line. A blank line has a single word at its
+    /// start.
+    fn word_starts(&self, line: u32, subwords: bool) -> Vec<u32> {
+        if self.is_binary() {
+            return vec![];
+        }
+        let chars: Vec<char> = self
+            .buffer
+            .get_line_content(line)
+            .unwrap_or_default()
+            .chars()
+            .collect();
+        if chars.iter().all(|c| c.is_whitespace()) {
+            return vec![0];
+        }
+        (0..chars.len())
+            .filter(|i| starts_word(&chars, *i, subwords))
+            .map(|i| i as u32)
+            .collect()
+    }
+
+    /// Returns the lines of the paragraph containing the given line. Paragraphs are separated
+    /// by blank lines, and a run of blank lines counts as a paragraph of its own.
+    pub fn paragraph_bounds(&self, line: u32) -> Range<u32> {
+        let blank = self.is_blank(line);
+        let start = (1..line)
+            .rev()
+            .take_while(|l| self.is_blank(*l) == blank)
+            .last()
+            .unwrap_or(line);
+        let end = (line + 1..=self.line_count())
+            .take_while(|l| self.is_blank(*l) == blank)
+            .last()
+            .unwrap_or(line);
+        start..end + 1
+    }
+
+    /// Returns the first blank line after the current paragraph, or the last line if there isn't
+    /// one. From a blank line, the blank lines are skipped and the following paragraph is used.
+    pub fn next_paragraph(&self, line: u32) -> u32 {
+        let count = self.line_count().max(1);
+        let blank = self.is_blank(line);
+        (line + 1..=count)
+            .skip_while(|l| blank && self.is_blank(*l))
+            .find(|l| self.is_blank(*l))
+            .unwrap_or(count)
+    }
+
+    /// Returns the last blank line before the current paragraph, or the first line if there isn't
+    /// one. From a blank line, the blank lines are skipped and the preceding paragraph is used.
+    pub fn previous_paragraph(&self, line: u32) -> u32 {
+        let blank = self.is_blank(line);
+        (1..line)
+            .rev()
+            .skip_while(|l| blank && self.is_blank(*l))
+            .find(|l| self.is_blank(*l))
+            .unwrap_or(1)
+    }
+
+    /// Returns the start of the next sentence after the given position, or the end of the
+    /// document if there isn't one.
+    pub fn next_sentence(&self, position: Position) -> Position {
+        let mut next = None;
+        self.scan_sentences(self.paragraph_bounds(position.row).start, |start| {
+            if start > position {
+                next = Some(start);
+            }
+            next.is_none()
+        });
+        next.unwrap_or_else(|| {
+            let row = self.line_count().max(1);
+            Position {
+                row,
+                column: self.line_len(row),
+            }
+        })
+    }
+
+    /// Returns the start of the sentence before the given position, or the start of the document
+    /// if there isn't one.
+    pub fn previous_sentence(&self, position: Position) -> Position {
+        let mut line = self.paragraph_bounds(position.row).start;
+        loop {
+            let mut previous = None;
+            self.scan_sentences(line, |start| {
+                if start < position {
+                    previous = Some(start);
+                }
+                start < position
+            });
+            if let Some(previous) = previous {
+                return previous;
+            }
+            if line <= 1 {
+                return Position { row: 1, column: 0 };
+            }
+            line = self.paragraph_bounds(line - 1).start;
+        }
+    }
+
+    /// Visits the start of each sentence from the given line onwards, until the visitor returns
+    /// false. A sentence ends with a full stop, question mark or exclamation mark followed by
+    /// whitespace, and blank lines separate sentences too. The line should be the start of a
+    /// paragraph, as its first character is taken to start a sentence.
+    fn scan_sentences(&self, line: u32, mut visit: impl FnMut(Position) -> bool) {
+        if self.is_binary() {
+            return;
+        }
+
+        // Whether the next non-whitespace character starts a sentence.
+        let mut ended = true;
+        // Whether the characters so far end a sentence, if followed by whitespace.
+        let mut terminated = false;
+        for row in line..=self.line_count() {
+            let content = self.buffer.get_line_content(row).unwrap_or_default();
+            if content.trim().is_empty() {
+                ended = true;
+                continue;
+            }
+
+            for (column, c) in content.chars().enumerate() {
+                if c.is_whitespace() {
+                    ended |= terminated;
+                    continue;
+                }
+                if ended {
+                    let column = column as u32;
+                    if !visit(Position { row, column }) {
+                        return;
+                    }
+                    ended = false;
+                }
+                terminated = match c {
+                    '.' | '!' | '?' => true,
+                    // Closing quotes and brackets can follow the end of a sentence.
+                    ')' | ']' | '"' | '\'' => terminated,
+                    _ => false,
+                };
+            }
+            ended |= terminated;
+        }
+    }
+
+    /// Whether a line is empty or contains only whitespace.
+    pub fn is_blank(&self, line: u32) -> bool {
+        self.buffer
+            .get_line_content(line)
+            .is_none_or(|content| content.trim().is_empty())
+    }
+
+    /// Returns the number of whitespace characters at the start of a line. Binary documents have
+    /// no indentation.
+    pub fn indentation(&self, line: u32) -> u32 {
+        if self.is_binary() {
+            return 0;
+        }
+
+        self.buffer.get_line_content(line).map_or(0, |content| {
+            content.chars().take_while(|c| c.is_whitespace()).count() as u32
+        })
+    }
+
+    pub fn insert(&mut self, line: u32, column: u32, c: char) {
+        if self.is_binary() {
+            return;
+        }
+        if let Some(pos) = self.get_doc_pos(line, column) {
+            self.buffer_insert_char(pos, c);
+            self.modified.insert(line);
+        }
+    }
+
+    /// Inserts a block of text at the given position as a single edit, rather than one character
+    /// at a time, so large pastes stay fast.
+    pub fn insert_text(&mut self, line: u32, column: u32, text: &str) {
+        if self.is_binary() || text.is_empty() {
+            return;
+        }
+        if let Some(pos) = self.get_doc_pos(line, column) {
+            self.buffer_insert(pos, text);
+            let lines = text.matches('\n').count() as u32;
+            self.modified.extend(line..=line + lines);
+        }
+    }
+
+    /// Deletes the text between two positions as a single edit.
+    pub fn delete(&mut self, start: Position, end: Position) {
+        if self.is_binary() {
+            return;
+        }
+
+        let from = self.get_doc_pos(start.row, start.column);
+        let to = self.get_doc_pos(end.row, end.column);
+        if let (Some(from), Some(to)) = (from, to) {
+            self.buffer_delete(from, to);
+            self.modified.insert(start.row);
+        }
+    }
+
+    /// Returns the byte and character offsets of a position from the start of the document.
+    pub fn offsets(&self, position: Position) -> Option<(usize, usize)> {
+        if self.is_binary() {
+            return None;
+        }
+
+        let bytes = self.get_doc_pos(position.row, position.column)?;
+        let chars = self.buffer.text_range(0, bytes).chars().count();
+        Some((bytes, chars))
+    }
+
+    /// Deletes the text between two positions as a single edit, returning the deleted text.
+    pub fn cut(&mut self, start: Position, end: Position) -> String {
+        if self.is_binary() {
+            return String::new();
+        }
+
+        let from = self.get_doc_pos(start.row, start.column);
+        let to = self.get_doc_pos(end.row, end.column);
+        let text = match (from, to) {
+            (Some(from), Some(to)) => self.buffer.text_range(from, to),
+            _ => String::new(),
+        };
+        self.delete(start, end);
+        text
+    }
+
+    /// Replaces a range of lines with new lines as a single edit.
+    ///
+    /// # Arguments
+    ///
+    /// * 'lines' - The first and last lines to replace. Lines start from 1.
+    /// * 'replacement' - The new lines, without line breaks.
+    pub fn replace_lines(&mut self, lines: RangeInclusive<u32>, replacement: &[String]) {
+        let start = Position {
+            row: *lines.start(),
+            column: 0,
+        };
+        let end = Position {
+            row: *lines.end(),
+            column: self.line_len(*lines.end()),
+        };
+        self.delete(start, end);
+        self.insert_text(start.row, 0, &replacement.join("\n"));
+    }
+
+    /// Changes the case of the text between two positions as a single edit, returning the new end
+    /// of the text, which moves if the conversion changed its length.
+    pub fn convert_case(&mut self, start: Position, end: Position, case: Case) -> Position {
+        if self.is_binary() {
+            return end;
+        }
+
+        let original = self.cut(start, end);
+        let converted = case.convert(&original);
+        self.insert_text(start.row, start.column, &converted);
+
+        let last_line = converted.rsplit('\n').next().unwrap_or_default();
+        let column = match start.row == end.row {
+            true => start.column + last_line.chars().count() as u32,
+            false => last_line.chars().count() as u32,
+        };
+        Position {
+            row: end.row,
+            column,
+        }
+    }
+
+    /// Replaces the character at the given position, as a single edit. At the end of a line the
+    /// character is inserted instead, so the line break isn't replaced.
+    pub fn replace(&mut self, line: u32, column: u32, c: char) {
+        if self.is_binary() {
+            return;
+        }
+        let Some(pos) = self.get_doc_pos(line, column) else {
+            return;
+        };
+
+        let existing = self
+            .buffer
+            .get_line_content(line)
+            .and_then(|content| content.chars().nth(column as usize));
+        if let Some(existing) = existing {
+            self.buffer_delete(pos, pos + existing.len_utf8());
+        }
+        self.buffer_insert_char(pos, c);
+        self.modified.insert(line);
+    }
+
+    /// Comments out a line by inserting the prefix and a space before its first non-whitespace
+    /// character, or uncomments it if it already starts with the prefix. Blank lines are left
+    /// alone. Returns the column the change was made at and the number of characters added, which
+    /// is negative when the line was uncommented.
+    ///
+    /// # Arguments
+    ///
+    /// * 'line' - The line to toggle. Lines start from 1.
+    /// * 'prefix' - The prefix which starts a line comment.
+    pub fn toggle_comment(&mut self, line: u32, prefix: &str) -> Option<(u32, i64)> {
+        if self.is_binary() || prefix.is_empty() {
+            return None;
+        }
+
+        let content = self.buffer.get_line_content(line)?;
+        let text = content.trim_end_matches(['\r', '\n']);
+        let indent = text.len() - text.trim_start().len();
+        if indent == text.len() {
+            return None;
+        }
+
+        let column = text[..indent].chars().count() as u32;
+        let pos = self.buffer.get_doc_pos(line, indent as u32)? as usize;
+        let delta = match text[indent..].strip_prefix(prefix) {
+            Some(rest) => {
+                let len = prefix.len() + usize::from(rest.starts_with(' '));
+                self.buffer_delete(pos, pos + len);
+                -(text[indent..indent + len].chars().count() as i64)
+            }
+            None => {
+                let comment = format!("{} ", prefix);
+                self.buffer_insert(pos, &comment);
+                comment.chars().count() as i64
+            }
+        };
+        self.modified.insert(line);
+        Some((column, delta))
+    }
+
+    /// Removes spaces and tabs from the end of each line edited since the document was last
+    /// saved. Lines which haven't been edited are left alone, so saving doesn't change them.
+    pub fn trim_trailing_whitespace(&mut self) {
+        if self.is_binary() {
+            return;
+        }
+
+        let lines: Vec<u32> = self.modified.iter().copied().collect();
+        for line in lines {
+            let Some(content) = self.buffer.get_line_content(line) else {
+                continue;
+            };
+            let trimmed = content.trim_end_matches([' ', '\t']).len();
+            if trimmed == content.len() {
+                continue;
+            }
+            if let Some(pos) = self.buffer.get_doc_pos(line, trimmed as u32) {
+                let pos = pos as usize;
+                self.buffer_delete(pos, pos + content.len() - trimmed);
+            }
+        }
+    }
+
+    /// Adds a line break to the end of the document if it doesn't already end with one. Empty
+    /// documents are left empty.
+    pub fn ensure_final_newline(&mut self) -> Result<()> {
+        if self.is_binary() {
+            return Ok(());
+        }
+
+        self.load_until(u32::MAX)?;
+        if self.buffer.last_char().is_some_and(|c| c != '\n') {
+            let line_ending = self.line_ending().as_str();
+            self.buffer_insert(self.buffer.doc_len(), line_ending);
+        }
+        Ok(())
+    }
+
+    /// Commits the changes made since the last commit as a single step in the document's
+    /// history, so they're undone together.
+    pub fn commit_undo(&mut self) {
+        self.history.commit();
+    }
+
+    /// The document's history of changes.
+    pub fn history(&self) -> &UndoTree {
+        &self.history
+    }
+
+    /// Reverts the last step in the document's history, returning where the change was made, or
+    /// None if there's nothing to undo.
+    pub fn undo(&mut self) -> Option<Position> {
+        let step = self.history.undo()?;
+        self.apply_step(step)
+    }
+
+    /// Makes the most recently undone step again, returning where the change was made, or None
+    /// if there's nothing to redo.
+    pub fn redo(&mut self) -> Option<Position> {
+        let step = self.history.redo()?;
+        self.apply_step(step)
+    }
+
+    /// Moves the document to any revision in its history, returning where the last change was
+    /// made.
+    ///
+    /// # Arguments
+    ///
+    /// * 'revision' - The revision to move to, as numbered by the undo tree.
+    pub fn jump_to_revision(&mut self, revision: usize) -> Option<Position> {
+        let steps = self.history.jump(revision);
+        self.apply_steps(steps)
+    }
+
+    /// Moves the document back through its history by a number of revisions or an amount of
+    /// time, returning where the last change was made, or None if it was already there.
+    pub fn earlier(&mut self, distance: Distance) -> Option<Position> {
+        let steps = self.history.earlier(distance);
+        self.apply_steps(steps)
+    }
+
+    /// Moves the document forward through its history by a number of revisions or an amount of
+    /// time, returning where the last change was made, or None if it was already there.
+    pub fn later(&mut self, distance: Distance) -> Option<Position> {
+        let steps = self.history.later(distance);
+        self.apply_steps(steps)
     }
 
-    pub fn load(file: PathBuf) -> Result<Document, Box<dyn Error>> {
-        let file_name = file.file_name().map(|f| f.to_owned());
-        let contents = std::fs::read_to_string(&file)?;
-        let len = contents.len();
-        let buffer = TextBuffer::new(Some(contents));
+    fn apply_steps(&mut self, steps: Vec<Step>) -> Option<Position> {
+        steps
+            .into_iter()
+            .fold(None, |position, step| self.apply_step(step).or(position))
+    }
 
-        debug_assert_eq!(len, buffer.doc_len());
-        debug_assert!(file_name.is_some());
+    /// Applies the changes from moving through the document's history directly to the buffer,
+    /// without recording them again.
+    fn apply_step(&mut self, step: Step) -> Option<Position> {
+        let mut first = None;
+        let mut apply = |document: &mut Document, pos: usize, remove: &str, add: &str| {
+            document.buffer.delete(pos, pos + remove.len());
+            if !add.is_empty() {
+                document.buffer.insert(pos, add);
+            }
+            document.track_edit(pos, remove, add);
+            let (line, _) = document.buffer.get_line_pos(pos);
+            let lines = add.matches('\n').count() as u32;
+            document.modified.extend(line..=line + lines);
+            if let Some(outbox) = &mut document.outbox {
+                outbox.push(Change {
+                    pos,
+                    deleted: remove.to_string(),
+                    inserted: add.to_string(),
+                });
+            }
+            first = Some(pos);
+        };
 
-        let mut debug = file.to_owned();
-        if let Some(os_file_name) = file.file_name() {
-            let mut debug_file_name = os_file_name.to_os_string();
-            debug_file_name.push(".debug");
-            debug.set_file_name(debug_file_name);
-        }
-        if let Some(extension) = file.extension() {
-            debug.set_extension(extension);
+        match step {
+            Step::Undo(changes) => changes
+                .iter()
+                .rev()
+                .for_each(|c| apply(self, c.pos, &c.inserted, &c.deleted)),
+            Step::Redo(changes) => changes
+                .iter()
+                .for_each(|c| apply(self, c.pos, &c.deleted, &c.inserted)),
         }
 
-        info!("Loaded {} characters from document {:?}", len, file);
-        Ok(Document {
-            buffer,
-            path: Some(file),
-            debug: Some(debug),
-            name: file_name,
-        })
+        let (row, offset) = self.buffer.get_line_pos(first?);
+        let content = self.buffer.get_line_content(row).unwrap_or_default();
+        let column = content
+            .get(..offset as usize)
+            .map_or(0, |text| text.chars().count() as u32);
+        Some(Position { row, column })
     }
 
-    pub fn save(&self) -> Result<(), Box<dyn Error>> {
-        if let Some(path) = &self.path {
-            fs::write(path, self.buffer.text())?;
-        }
+    /// Starts recording the changes made to the document so they can be sent to the other
+    /// participants in a shared editing session. The whole document is loaded first, as it's
+    /// shared in full.
+    pub fn share(&mut self) -> Result<()> {
+        self.load_until(u32::MAX)?;
+        self.outbox = Some(vec![]);
         Ok(())
     }
 
-    pub fn len(&self) -> u32 {
-        self.buffer.doc_len() as u32
+    pub fn is_shared(&self) -> bool {
+        self.outbox.is_some()
     }
 
-    pub fn line_count(&self) -> u32 {
-        self.buffer.get_line_count()
+    /// Takes the changes made since this was last called, while the document is shared.
+    pub fn take_changes(&mut self) -> Vec<Change> {
+        self.outbox.as_mut().map(std::mem::take).unwrap_or_default()
     }
 
-    pub fn get_lines(&self, lines: Range<u32>) -> Vec<String> {
-        let mut results = vec![];
-        info!("Fetching lines from document with range {:?}", lines);
+    /// Applies an edit made by another participant, returning whether it could be applied. Edits
+    /// which don't fit the document, as they were made to an older version of it, are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * 'pos' - The byte position of the edit.
+    /// * 'deleted' - The number of bytes deleted at the position.
+    /// * 'inserted' - The text inserted at the position.
+    pub fn apply_change(&mut self, pos: usize, deleted: usize, inserted: &str) -> bool {
+        let text = self.text();
+        let end = pos.saturating_add(deleted);
+        if self.is_binary() || !text.is_char_boundary(pos) || !text.is_char_boundary(end) {
+            return false;
+        }
 
-        for line in lines {
-            if let Some(content) = self.buffer.get_line_content(line) {
-                results.push(content);
-            }
+        self.buffer_delete(pos, end);
+        if !inserted.is_empty() {
+            self.buffer_insert(pos, inserted);
+        }
+        let (line, _) = self.buffer.get_line_pos(pos);
+        let lines = inserted.matches('\n').count() as u32;
+        self.modified.extend(line..=line + lines);
+        true
+    }
+
+    /// Replaces the text of the document with the host's copy in a shared editing session,
+    /// returning whether it changed. Only the part between the text the two have in common at
+    /// the start and end is replaced, as a single edit which isn't sent back to the host.
+    pub fn sync_text(&mut self, text: &str) -> bool {
+        let current = self.text();
+        let prefix = common_prefix_len(current.chars(), text.chars());
+        let suffix = common_prefix_len(
+            current[prefix..].chars().rev(),
+            text[prefix..].chars().rev(),
+        );
+        if prefix == current.len() && prefix == text.len() {
+            return false;
         }
 
-        results
+        let outbox = self.outbox.take();
+        self.buffer_delete(prefix, current.len() - suffix);
+        let inserted = &text[prefix..text.len() - suffix];
+        if !inserted.is_empty() {
+            self.buffer_insert(prefix, inserted);
+        }
+        self.outbox = outbox;
+
+        let (line, _) = self.buffer.get_line_pos(prefix);
+        let lines = inserted.matches('\n').count() as u32;
+        self.modified.extend(line..=line + lines);
+        true
     }
 
-    pub fn insert(&mut self, line: u32, column: u32, c: char) {
-        if let Some(pos) = self.buffer.get_doc_pos(line, column) {
-            self.buffer.insert_char(pos as usize, c);
-            /*info!(
-                "Inserting text {} at position {}, line {} column {}",
-                text, pos, line, column
-            );*/
+    /// Inserts text into the buffer, recording the change in the document's history.
+    fn buffer_insert(&mut self, pos: usize, text: &str) {
+        self.buffer.insert(pos, text);
+        self.track_edit(pos, "", text);
+        self.record(Change {
+            pos,
+            deleted: String::new(),
+            inserted: text.to_string(),
+        });
+    }
+
+    /// Inserts a character into the buffer, recording the change in the document's history.
+    fn buffer_insert_char(&mut self, pos: usize, c: char) {
+        self.buffer.insert_char(pos, c);
+        self.track_edit(pos, "", &c.to_string());
+        self.record(Change {
+            pos,
+            deleted: String::new(),
+            inserted: c.to_string(),
+        });
+    }
+
+    /// Deletes text from the buffer, recording the change in the document's history.
+    fn buffer_delete(&mut self, start: usize, end: usize) {
+        if start >= end {
+            return;
+        }
+        let deleted = self.buffer.text_range(start, end);
+        self.buffer.delete(start, end);
+        self.track_edit(start, &deleted, "");
+        self.record(Change {
+            pos: start,
+            deleted,
+            inserted: String::new(),
+        });
+    }
+
+    /// Counts an edit and updates what's kept by line for it. The part of the search index it
+    /// touched is marked as stale, and the anchors of the virtual text, the folds, the signs and
+    /// the modified lines are moved with the lines around them.
+    fn track_edit(&mut self, pos: usize, deleted: &str, inserted: &str) {
+        self.edits += 1;
+        if self.index.is_none()
+            && self.virtual_text.is_empty()
+            && self.folds.is_empty()
+            && self.signs.is_empty()
+            && self.modified.is_empty()
+        {
+            return;
         }
+        let (line, _) = self.buffer.get_line_pos(pos);
+        let count = |text: &str| text.matches('\n').count() as u32;
+        let (removed, inserted) = (count(deleted), count(inserted));
+        if let Some(index) = &mut self.index {
+            index.edit(line, removed, inserted);
+        }
+        self.virtual_text.edit(line, removed, inserted);
+        self.folds.edit(line, removed, inserted);
+        self.signs.edit(line, removed, inserted);
+        if removed != inserted {
+            self.modified = shift_lines(&self.modified, line, removed, inserted);
+        }
+    }
+
+    fn record(&mut self, change: Change) {
+        if let Some(outbox) = &mut self.outbox {
+            outbox.push(change.clone());
+        }
+        self.history.record(change);
+    }
+
+    /// Converts a line and a character column into a byte position in the document.
+    fn get_doc_pos(&self, line: u32, column: u32) -> Option<usize> {
+        let content = self.buffer.get_line_content(line).unwrap_or_default();
+        let offset = content
+            .char_indices()
+            .nth(column as usize)
+            .map_or(content.len(), |(i, _)| i);
+
+        self.buffer
+            .get_doc_pos(line, offset as u32)
+            .map(|pos| pos as usize)
     }
 
     pub fn debug(&self) {
         if let Some(debug_file) = &self.debug {
             info!("Writing debug file to {:?}", debug_file);
-            fs::write(debug_file, format!("{}", self.buffer));
+            if let Err(e) = fs::write(debug_file, format!("{}", self.buffer)) {
+                error!("Unable to write debug file {:?}: {}", debug_file, e);
+            }
+        }
+    }
+}
+
+/// The kinds of character which make up words. A word is a run of characters of the same kind.
+#[derive(Debug, PartialEq)]
+pub enum CharKind {
+    Whitespace,
+    /// Alphanumeric characters and underscores.
+    Word,
+    Punctuation,
+}
+
+impl CharKind {
+    pub fn of(c: char) -> CharKind {
+        match c {
+            c if c.is_whitespace() => CharKind::Whitespace,
+            c if c.is_alphanumeric() || c == '_' => CharKind::Word,
+            _ => CharKind::Punctuation,
         }
     }
 }
 
+/// Whether a word starts at a character of a line. With subwords, words are also split after
+/// underscores, before a lowercase letter is followed by an uppercase one, and before the last
+/// of a run of uppercase letters followed by a lowercase one, so `parseHTTPRequest` is split into
+/// `parse`, `HTTP` and `Request`.
+fn starts_word(chars: &[char], i: usize, subwords: bool) -> bool {
+    let c = chars[i];
+    let kind = CharKind::of(c);
+    if kind == CharKind::Whitespace {
+        return false;
+    }
+    let Some(&previous) = i.checked_sub(1).and_then(|i| chars.get(i)) else {
+        return true;
+    };
+    if CharKind::of(previous) != kind {
+        return true;
+    }
+    let next = chars.get(i + 1);
+    subwords
+        && kind == CharKind::Word
+        && ((previous == '_' && c != '_')
+            || ((previous.is_lowercase() || previous.is_numeric()) && c.is_uppercase())
+            || (previous.is_uppercase()
+                && c.is_uppercase()
+                && next.is_some_and(|n| n.is_lowercase())))
+}
+
+/// The length in bytes of the characters two pieces of text start with in common.
+fn common_prefix_len(a: impl Iterator<Item = char>, b: impl Iterator<Item = char>) -> usize {
+    a.zip(b)
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum()
+}
+
+/// Reads the text of a range of lines from a buffer at once, which is much quicker than reading
+/// the lines one at a time.
+fn read_text(buffer: &TextBuffer, lines: Range<u32>) -> String {
+    let start = buffer.get_doc_pos(lines.start, 0).unwrap_or(0) as usize;
+    let end = buffer
+        .get_doc_pos(lines.end, 0)
+        .map_or(buffer.doc_len(), |pos| pos as usize - 1);
+    buffer.text_range(start, end)
+}
+
+/// Moves a set of lines for an edit. Lines below the edit move up or down with it, and lines
+/// joined onto the edited line are dropped.
+///
+/// # Arguments
+///
+/// * 'lines' - The lines to move.
+/// * 'line' - The line the edit was made on.
+/// * 'removed' - The number of line breaks the edit removed.
+/// * 'inserted' - The number of line breaks the edit inserted.
+fn shift_lines(lines: &BTreeSet<u32>, line: u32, removed: u32, inserted: u32) -> BTreeSet<u32> {
+    lines
+        .iter()
+        .filter(|&&l| l <= line || l > line + removed)
+        .map(|&l| match l > line {
+            true => l + inserted - removed,
+            false => l,
+        })
+        .collect()
+}
+
+fn modified_time(file: &Path) -> Option<SystemTime> {
+    fs::metadata(file).and_then(|m| m.modified()).ok()
+}
+
+/// Checks whether the current user can write to an existing file.
+fn is_writable(file: &Path) -> bool {
+    fs::OpenOptions::new().write(true).open(file).is_ok()
+}
+
+/// Adds write permission for the owner of a file, leaving the other permissions untouched.
+#[cfg(unix)]
+fn writable_permissions(permissions: &Permissions) -> Permissions {
+    use std::os::unix::fs::PermissionsExt;
+    Permissions::from_mode(permissions.mode() | 0o200)
+}
+
+#[cfg(not(unix))]
+fn writable_permissions(permissions: &Permissions) -> Permissions {
+    let mut permissions = permissions.clone();
+    permissions.set_readonly(false);
+    permissions
+}
+
+/// Builds the path of the file the piece table is written to for debugging, which sits alongside
+/// the document with a `.debug` suffix before the extension.
+fn debug_path(file: &Path) -> PathBuf {
+    let mut debug = file.to_owned();
+    if let Some(os_file_name) = file.file_name() {
+        let mut debug_file_name = os_file_name.to_os_string();
+        debug_file_name.push(".debug");
+        debug.set_file_name(debug_file_name);
+    }
+    if let Some(extension) = file.extension() {
+        debug.set_extension(extension);
+    }
+    debug
+}
+
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
     use test_case::test_case;
 
     use super::*;
+    use crate::signs::{Group, Sign};
 
     fn setup(file: &str) -> PathBuf {
         Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
             .join("tests")
             .join(file)
     }
@@ -161,6 +1821,583 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn load_file_and_get_text() {
+        let path = setup("multiple_paragraphs");
+        let document = Document::load(path.to_owned()).unwrap();
+
+        let expected = std::fs::read_to_string(&path).unwrap();
+        let actual = document.text();
+        assert_eq!(expected, actual);
+    }
+
+    #[test_case(1, 0, "Xorem ipsum"; "start_of_line")]
+    #[test_case(1, 4, "LoreX ipsum"; "end_of_word")]
+    #[test_case(1, 11, "Lorem ipsumX"; "end_of_line")]
+    #[test_case(2, 1, "dXlor"; "second_line")]
+    fn replace_character(line: u32, column: u32, expected: &str) {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("Lorem ipsum\ndolor")));
+        document.replace(line, column, 'X');
+
+        let actual = document.get_lines(Range {
+            start: line,
+            end: line + 1,
+        });
+        assert_eq!(vec![expected], actual);
+        assert_eq!(2, document.line_count());
+    }
+
+    #[test]
+    fn extend_from_disk() {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("one\n")));
+        document.extend_from_disk("two\n", false);
+        assert_eq!("one\ntwo\n", document.text());
+        assert!(!document.is_modified());
+        assert_eq!(None, document.undo());
+
+        document.extend_from_disk("new\n", true);
+        assert_eq!("new\n", document.text());
+    }
+
+    #[test_case("Lorem ipsum", "// Lorem ipsum", Some((0, 3));     "comment")]
+    #[test_case("    Lorem", "    // Lorem", Some((4, 3));         "comment_indented")]
+    #[test_case("\t// Lorem", "\tLorem", Some((1, -3));           "uncomment")]
+    #[test_case("//Lorem", "Lorem", Some((0, -2));                 "uncomment_without_space")]
+    #[test_case("   ", "   ", None;                                "blank")]
+    fn toggle_comment(text: &str, expected: &str, change: Option<(u32, i64)>) {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(format!("{}\nipsum", text)));
+
+        assert_eq!(change, document.toggle_comment(1, "//"));
+        assert_eq!(format!("{}\nipsum", expected), document.text());
+    }
+
+    #[test]
+    fn detect_filetype_from_modeline_at_end() {
+        let path = std::env::temp_dir().join("texteditor_detect_filetype_from_modeline_at_end");
+        let mut contents = String::from("Lorem ipsum\n").repeat(20);
+        contents.push_str("# vim: ft=python\n");
+        std::fs::write(&path, contents).unwrap();
+
+        let document = Document::load(path.to_owned()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(Some("python"), document.filetype());
+    }
+
+    #[test]
+    fn insert_text_across_lines() {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("Lorem ipsum\ndolor")));
+        document.insert_text(1, 6, "sit\namet ");
+
+        assert_eq!("Lorem sit\namet ipsum\ndolor", document.text());
+        assert_eq!(3, document.line_count());
+    }
+
+    #[test]
+    fn delete_across_lines() {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("Lorem ipsum\ndolor sit\namet")));
+        document.delete(
+            Position { row: 1, column: 6 },
+            Position { row: 2, column: 6 },
+        );
+
+        assert_eq!("Lorem sit\namet", document.text());
+    }
+
+    #[test_case(0, Some(0..5);     "start_of_word")]
+    #[test_case(8, Some(6..11);    "middle_of_word")]
+    #[test_case(5, Some(0..5);     "end_of_word")]
+    #[test_case(12, None;          "punctuation")]
+    #[test_case(14, Some(13..22);  "underscore")]
+    fn word_bounds(column: u32, expected: Option<Range<u32>>) {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("Lorem ipsum, dolor_sit")));
+
+        assert_eq!(expected, document.word_bounds(1, column));
+    }
+
+    const WORDS: &str = "let parseHTTPRequest = read_line();\n\n  x2Y";
+
+    #[test_case((1, 0), false, (1, 4);     "next_word")]
+    #[test_case((1, 4), false, (1, 21);    "punctuation")]
+    #[test_case((1, 28), false, (1, 32);   "brackets")]
+    #[test_case((1, 32), false, (2, 0);    "blank_line")]
+    #[test_case((2, 0), false, (3, 2);     "indentation")]
+    #[test_case((3, 2), false, (3, 5);     "end_of_document")]
+    #[test_case((1, 4), true, (1, 9);      "camel_case")]
+    #[test_case((1, 9), true, (1, 13);     "acronym")]
+    #[test_case((1, 23), true, (1, 28);    "snake_case")]
+    #[test_case((3, 2), true, (3, 4);      "after_digit")]
+    fn next_word(from: (u32, u32), subwords: bool, expected: (u32, u32)) {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from(WORDS)));
+        let position = |(row, column)| Position { row, column };
+
+        assert_eq!(
+            position(expected),
+            document.next_word(position(from), subwords)
+        );
+    }
+
+    #[test_case((1, 6), false, (1, 4);     "start_of_word")]
+    #[test_case((3, 2), false, (2, 0);     "blank_line")]
+    #[test_case((1, 21), true, (1, 13);    "camel_case")]
+    #[test_case((1, 28), true, (1, 23);    "snake_case")]
+    #[test_case((1, 0), true, (1, 0);      "start_of_document")]
+    fn previous_word(from: (u32, u32), subwords: bool, expected: (u32, u32)) {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from(WORDS)));
+        let position = |(row, column)| Position { row, column };
+
+        assert_eq!(
+            position(expected),
+            document.previous_word(position(from), subwords)
+        );
+    }
+
+    #[test_case(1, 1..3; "first")]
+    #[test_case(2, 1..3; "end_of_first")]
+    #[test_case(4, 3..5; "blank_lines")]
+    #[test_case(6, 5..7; "last")]
+    fn paragraph_bounds(line: u32, expected: Range<u32>) {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("Lorem\nipsum\n\n \ndolor\nsit")));
+
+        assert_eq!(expected, document.paragraph_bounds(line));
+    }
+
+    #[test_case(1, 3;  "to_blank_line")]
+    #[test_case(3, 6;  "skips_blank_lines")]
+    #[test_case(5, 6;  "to_last_line")]
+    fn next_paragraph(line: u32, expected: u32) {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("Lorem\nipsum\n\n\ndolor\nsit")));
+
+        assert_eq!(expected, document.next_paragraph(line));
+    }
+
+    #[test_case(6, 4;  "to_blank_line")]
+    #[test_case(4, 1;  "skips_blank_lines")]
+    #[test_case(5, 4;  "from_start_of_paragraph")]
+    #[test_case(2, 1;  "to_first_line")]
+    fn previous_paragraph(line: u32, expected: u32) {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("Lorem\nipsum\n\n\ndolor\nsit")));
+
+        assert_eq!(expected, document.previous_paragraph(line));
+    }
+
+    #[test_case((1, 0), (1, 13);  "same_line")]
+    #[test_case((1, 13), (2, 6);  "next_line")]
+    #[test_case((2, 6), (4, 0);   "next_paragraph")]
+    #[test_case((4, 0), (4, 8);   "closing_quote")]
+    #[test_case((4, 8), (4, 14);  "end_of_document")]
+    fn next_sentence(from: (u32, u32), expected: (u32, u32)) {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from(
+            "Lorem ipsum. Dolor sit\namet? Consectetur\n\n\"Elit!\" Sed do",
+        )));
+        let position = |(row, column)| Position { row, column };
+
+        assert_eq!(position(expected), document.next_sentence(position(from)));
+    }
+
+    #[test_case((1, 15), (1, 13);  "same_line")]
+    #[test_case((2, 6), (1, 13);   "previous_line")]
+    #[test_case((4, 0), (2, 6);    "previous_paragraph")]
+    #[test_case((1, 0), (1, 0);    "start_of_document")]
+    fn previous_sentence(from: (u32, u32), expected: (u32, u32)) {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from(
+            "Lorem ipsum. Dolor sit\namet? Consectetur\n\n\"Elit!\" Sed do",
+        )));
+        let position = |(row, column)| Position { row, column };
+
+        assert_eq!(
+            position(expected),
+            document.previous_sentence(position(from))
+        );
+    }
+
+    #[test]
+    fn cut_returns_deleted_text() {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("Lorem ipsum\ndolor")));
+        let text = document.cut(
+            Position { row: 1, column: 6 },
+            Position { row: 2, column: 0 },
+        );
+
+        assert_eq!("ipsum\n", text);
+        assert_eq!("Lorem dolor", document.text());
+    }
+
+    #[test]
+    fn convert_case_returns_new_end() {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("Eine straße")));
+        let end = document.convert_case(
+            Position { row: 1, column: 5 },
+            Position { row: 1, column: 11 },
+            Case::Upper,
+        );
+
+        assert_eq!("Eine STRASSE", document.text());
+        assert_eq!(Position { row: 1, column: 12 }, end);
+    }
+
+    #[test]
+    fn offsets_count_bytes_and_chars() {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("héllo\nwörld")));
+        let position = Position { row: 2, column: 2 };
+
+        assert_eq!(Some((10, 8)), document.offsets(position));
+    }
+
+    #[test]
+    fn replace_lines_in_middle() {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("Lorem\nipsum\ndolor\nsit")));
+        document.replace_lines(2..=3, &[String::from("amet")]);
+
+        assert_eq!("Lorem\namet\nsit", document.text());
+    }
+
+    #[test]
+    fn undo_and_redo_replacement() {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("Lorem\nipsum\ndolor\nsit")));
+        document.replace_lines(2..=3, &[String::from("amét")]);
+        document.commit_undo();
+        document.insert(1, 5, '!');
+
+        assert_eq!(Some(Position { row: 1, column: 5 }), document.undo());
+        assert_eq!(Some(Position { row: 2, column: 0 }), document.undo());
+        assert_eq!("Lorem\nipsum\ndolor\nsit", document.text());
+        assert_eq!(None, document.undo());
+        assert_eq!(Some(Position { row: 2, column: 0 }), document.redo());
+        assert_eq!("Lorem\namét\nsit", document.text());
+    }
+
+    #[test]
+    fn jump_between_branches() {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("Lorem")));
+        document.insert_text(1, 5, " ipsum");
+        document.undo();
+        document.insert_text(1, 0, "é ");
+        document.commit_undo();
+
+        assert_eq!(
+            Some(Position { row: 1, column: 5 }),
+            document.jump_to_revision(1)
+        );
+        assert_eq!("Lorem ipsum", document.text());
+        document.jump_to_revision(2);
+        assert_eq!("é Lorem", document.text());
+    }
+
+    #[test_case("Lorem ipsum", "Lorem dolor ipsum";  "insertion")]
+    #[test_case("Lorem ipsum", "Lorem";              "deletion")]
+    #[test_case("héllo wörld", "héllo wörld";        "unchanged")]
+    #[test_case("aaa", "aaaa";                       "repeated")]
+    #[test_case("", "Lorem\nipsum";                  "from_empty")]
+    fn sync_text(text: &str, host: &str) {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(text.to_string()));
+        document.share().unwrap();
+
+        assert_eq!(text != host, document.sync_text(host));
+        assert_eq!(host, document.text());
+        assert!(document.take_changes().is_empty());
+    }
+
+    #[test]
+    fn apply_remote_change() {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("héllo")));
+        document.share().unwrap();
+
+        assert!(!document.apply_change(2, 1, "e"));
+        assert!(document.apply_change(1, 2, "e"));
+        assert_eq!("hello", document.text());
+        assert_eq!(2, document.take_changes().len());
+    }
+
+    #[test_case("Lorem", 0;      "none")]
+    #[test_case("  \tLorem", 3;  "mixed")]
+    #[test_case("    ", 4;       "blank")]
+    fn indentation(text: &str, expected: u32) {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(format!("{}\nipsum", text)));
+
+        assert_eq!(expected, document.indentation(1));
+    }
+
+    #[test]
+    fn trim_trailing_whitespace_on_edited_lines() {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("Lorem  \nipsum \t\ndolor ")));
+        document.insert(2, 0, 'X');
+        document.insert(3, 0, 'Y');
+        document.trim_trailing_whitespace();
+
+        assert_eq!("Lorem  \nXipsum\nYdolor", document.text());
+    }
+
+    #[test]
+    fn signs_move_with_lines_inserted_above() {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("Lorem\nipsum\ndolor")));
+        document.signs_mut().place(3, Sign::bookmark());
+        document.insert_text(1, 0, "new\nlines\n");
+
+        assert!(document.signs().contains(5, Group::Bookmark));
+        assert!(!document.signs().contains(3, Group::Bookmark));
+    }
+
+    #[test]
+    fn trim_trailing_whitespace_after_lines_inserted_above() {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("Lorem\nipsum \ndolor ")));
+        document.insert(3, 0, 'X');
+        document.insert_text(1, 5, "\nnew");
+        document.trim_trailing_whitespace();
+        assert_eq!("Lorem\nnew\nipsum \nXdolor", document.text());
+
+        let start = Position { row: 1, column: 5 };
+        document.delete(start, Position { row: 2, column: 3 });
+        document.trim_trailing_whitespace();
+        assert_eq!("Lorem\nipsum \nXdolor", document.text());
+    }
+
+    #[test_case("Lorem\nipsum", "Lorem\nipsum\n"; "without_newline")]
+    #[test_case("Lorem ipsum\n", "Lorem ipsum\n"; "with_newline")]
+    #[test_case("Lorem\r\nipsum", "Lorem\r\nipsum\r\n"; "crlf")]
+    #[test_case("", ""; "empty")]
+    fn ensure_final_newline(text: &str, expected: &str) {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from(text)));
+        document.ensure_final_newline().unwrap();
+
+        assert_eq!(expected, document.text());
+    }
+
+    #[test_case("Lorem\nipsum\r\n", LineEnding::Lf;     "lf")]
+    #[test_case("Lorem\r\nipsum\n", LineEnding::Crlf;   "crlf")]
+    #[test_case("Lorem ipsum", LineEnding::NATIVE;       "single_line")]
+    #[test_case("", LineEnding::NATIVE;                  "empty")]
+    fn detect_line_ending(text: &str, expected: LineEnding) {
+        let document = Document::scratch("test", text);
+        assert_eq!(expected, document.line_ending());
+    }
+
+    #[test]
+    fn save_as_writes_to_new_path() {
+        let path = setup("single_line");
+        let mut document = Document::load(path.to_owned()).unwrap();
+        document.set_readonly(true);
+
+        let new_path = std::env::temp_dir().join("texteditor_save_as_writes_to_new_path");
+        document
+            .save_as(new_path.to_owned(), &Config::default())
+            .unwrap();
+
+        let expected = std::fs::read_to_string(&path).unwrap();
+        let actual = std::fs::read_to_string(&new_path).unwrap();
+        std::fs::remove_file(&new_path).unwrap();
+
+        assert_eq!(expected, actual);
+        assert_eq!(Some(new_path.as_path()), document.path());
+        assert!(!document.is_readonly());
+    }
+
+    #[test]
+    fn detect_change_on_disk() {
+        let path = std::env::temp_dir().join("texteditor_detect_change_on_disk");
+        std::fs::write(&path, "Lorem\n").unwrap();
+        let mut document = Document::load(path.to_owned()).unwrap();
+        assert!(!document.changed_on_disk());
+
+        let later = SystemTime::now() + std::time::Duration::from_secs(10);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(document.changed_on_disk());
+
+        document.acknowledge_disk_change();
+        assert!(!document.changed_on_disk());
+        document.set_outdated(true);
+        document.save().unwrap();
+        assert!(!document.is_outdated());
+        std::fs::remove_file(&path).unwrap();
+        assert!(!document.changed_on_disk());
+    }
+
+    #[test]
+    fn disk_len_is_what_was_read() {
+        let path = std::env::temp_dir().join("texteditor_disk_len_is_what_was_read");
+        std::fs::write(&path, "Lorem\n").unwrap();
+        let mut document = Document::load(path.to_owned()).unwrap();
+        std::fs::write(&path, "Lorem\nipsum\n").unwrap();
+        assert_eq!(Some(6), document.disk_len());
+
+        document.insert_text(1, 0, "> ");
+        document.save().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(Some(8), document.disk_len());
+    }
+
+    /// A config which "encrypts" `.rot` files by shifting each lowercase letter by one.
+    #[cfg(unix)]
+    fn rot_config() -> Config {
+        Config::parse("[encryption.rot]\ndecrypt = \"tr b-za a-z\"\nencrypt = \"tr a-z b-za\"")
+            .unwrap()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn open_and_save_encrypted_file() {
+        let path = std::env::temp_dir().join("texteditor_encrypted_file.md.rot");
+        std::fs::write(&path, "mpsfn jqtvn\n").unwrap();
+
+        let mut document = Document::open(path.to_owned(), &rot_config()).unwrap();
+        assert_eq!("lorem ipsum\n", document.text());
+        assert_eq!(Some("markdown"), document.filetype());
+        assert!(document.is_encrypted());
+
+        document.insert_text(1, 0, "dolor ");
+        document.save().unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!("epmps mpsfn jqtvn\n", saved);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn save_as_encrypted_file() {
+        let mut document = Document::new();
+        document.buffer = TextBuffer::new(Some(String::from("lorem")));
+
+        let path = std::env::temp_dir().join("texteditor_save_as_encrypted_file.rot");
+        document.save_as(path.to_owned(), &rot_config()).unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!("mpsfn", saved);
+        assert!(document.is_encrypted());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn open_and_save_compressed_file() {
+        let path = std::env::temp_dir().join("texteditor_compressed_file.py.gz");
+        let compression = Compression::Gzip { level: 9 };
+        let compressed = compression.compress(b"import os\n".to_vec()).unwrap();
+        std::fs::write(&path, compressed).unwrap();
+
+        let mut document = Document::open(path.to_owned(), &Config::default()).unwrap();
+        assert_eq!("import os\n", document.text());
+        assert_eq!(Some("python"), document.filetype());
+
+        document.insert_text(2, 0, "import sys\n");
+        document.save().unwrap();
+        let saved = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(Some(compression), Compression::detect(&path, &saved));
+        assert_eq!(
+            b"import os\nimport sys\n".to_vec(),
+            compression.decompress(saved).unwrap()
+        );
+    }
+
+    #[test]
+    fn open_large_file_loads_lazily() {
+        let path = setup("document");
+        let config = Config {
+            large_file_threshold: 0,
+            ..Config::default()
+        };
+        let mut document = Document::open(path.to_owned(), &config).unwrap();
+        assert!(document.is_large());
+
+        assert_eq!(Some(100), document.load_progress());
+
+        document.load_until(10).unwrap();
+        let expected = &read_lines(&path)[9..10];
+        let actual = document.get_lines(Range { start: 10, end: 11 });
+        assert_eq!(expected, actual);
+
+        assert_eq!(None, document.disk_len());
+        let expected = std::fs::read_to_string(&path).unwrap();
+        let actual = document.load_all().unwrap();
+        assert_eq!(expected, actual);
+        assert_eq!(None, document.load_progress());
+        assert_eq!(Some(expected.len() as u64), document.disk_len());
+    }
+
+    #[test]
+    fn open_large_file_with_invalid_utf8() {
+        let path = std::env::temp_dir().join("texteditor_open_large_file_with_invalid_utf8");
+        // The first character is split between the first two chunks.
+        let mut bytes = "a".repeat(CHUNK_SIZE - 1).into_bytes();
+        bytes.extend_from_slice("é\n".as_bytes());
+        bytes.extend_from_slice(b"b\xffc\n\xe2\x82");
+        std::fs::write(&path, &bytes).unwrap();
+        let config = Config {
+            large_file_threshold: 0,
+            ..Config::default()
+        };
+
+        let mut document = Document::open(path.to_owned(), &config).unwrap();
+        assert!(!document.is_readonly());
+        let expected = format!("{}é\nb\u{fffd}c\n\u{fffd}", "a".repeat(CHUNK_SIZE - 1));
+        assert_eq!(expected, document.load_all().unwrap());
+        assert!(document.is_readonly());
+        assert_eq!(Some(bytes.len() as u64), document.disk_len());
+    }
+
+    #[test]
+    fn load_binary_file_as_hex() {
+        let path = std::env::temp_dir().join("texteditor_load_binary_file_as_hex");
+        std::fs::write(&path, b"Lorem\0ipsum dolor sit amet").unwrap();
+        let mut document = Document::load(path.to_owned()).unwrap();
+        assert!(document.is_binary());
+        assert_eq!(2, document.line_count());
+        assert_eq!(9, document.line_len(2));
+
+        document.set_nibble(1, 5, 0x2, false);
+        document.set_nibble(1, 5, 0x0, true);
+        document.save().unwrap();
+
+        let actual = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(b"Lorem ipsum dolor sit amet".to_vec(), actual);
+    }
+
+    #[test]
+    fn load_invalid_utf8_as_readonly() {
+        let path = std::env::temp_dir().join("texteditor_load_invalid_utf8_as_readonly");
+        std::fs::write(&path, b"L\xf6rem ipsum dolor sit amet").unwrap();
+        let document = Document::load(path.to_owned()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(!document.is_binary());
+        assert!(document.is_readonly());
+        assert_eq!("L\u{fffd}rem ipsum dolor sit amet", document.text());
+    }
+
     #[test_case(1, 2;   "single_line_at_start")]
     #[test_case(24, 25; "single_line_at_end")]
     #[test_case(10, 11; "single_line_in_middle")]
@@ -0,0 +1,112 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// The text of unnamed buffers which were left with unsaved changes when the editor exited,
+/// kept as numbered files in a directory until they're restored or discarded.
+#[derive(Debug)]
+pub struct Drafts {
+    dir: PathBuf,
+}
+
+impl Drafts {
+    /// Constructs the drafts kept in a directory, which is created when a draft is first saved.
+    pub fn new(dir: PathBuf) -> Drafts {
+        Drafts { dir }
+    }
+
+    /// The draft files in the directory, oldest first.
+    fn files(&self) -> Vec<(u32, PathBuf)> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return vec![];
+        };
+        let mut files: Vec<(u32, PathBuf)> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let name = path.file_name()?.to_str()?;
+                let number = name.strip_prefix("draft-")?.strip_suffix(".txt")?;
+                Some((number.parse().ok()?, path))
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// The number of drafts waiting to be restored.
+    pub fn count(&self) -> usize {
+        self.files().len()
+    }
+
+    /// Saves the text of each buffer as a new draft, after any drafts which haven't been
+    /// restored yet.
+    pub fn save(&self, texts: &[String]) -> io::Result<()> {
+        if texts.is_empty() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+        let next = self.files().last().map_or(1, |(number, _)| number + 1);
+        for (number, text) in (next..).zip(texts) {
+            fs::write(self.dir.join(format!("draft-{}.txt", number)), text)?;
+        }
+        Ok(())
+    }
+
+    /// Reads every draft, oldest first, and removes them.
+    pub fn take(&self) -> io::Result<Vec<String>> {
+        let mut texts = vec![];
+        for (_, path) in self.files() {
+            texts.push(fs::read_to_string(&path)?);
+            fs::remove_file(&path)?;
+        }
+        Ok(texts)
+    }
+
+    /// Removes every draft without restoring them, returning how many there were.
+    pub fn discard(&self) -> io::Result<usize> {
+        let files = self.files();
+        for (_, path) in &files {
+            fs::remove_file(path)?;
+        }
+        Ok(files.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(name: &str) -> Drafts {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        Drafts::new(dir)
+    }
+
+    #[test]
+    fn drafts_are_restored_in_order() {
+        let drafts = setup("texteditor_drafts_restored");
+        assert_eq!(0, drafts.count());
+        drafts.save(&[String::from("first")]).unwrap();
+        drafts
+            .save(&[String::from("second"), String::from("third\n")])
+            .unwrap();
+        assert_eq!(3, drafts.count());
+
+        assert_eq!(vec!["first", "second", "third\n"], drafts.take().unwrap());
+        assert_eq!(0, drafts.count());
+        fs::remove_dir_all(&drafts.dir).unwrap();
+    }
+
+    #[test]
+    fn drafts_are_discarded() {
+        let drafts = setup("texteditor_drafts_discarded");
+        drafts
+            .save(&[String::from("a"), String::from("b")])
+            .unwrap();
+        fs::write(drafts.dir.join("notes.txt"), "kept").unwrap();
+
+        assert_eq!(2, drafts.discard().unwrap());
+        assert_eq!(0, drafts.count());
+        assert!(drafts.dir.join("notes.txt").exists());
+        fs::remove_dir_all(&drafts.dir).unwrap();
+    }
+}
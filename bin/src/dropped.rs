@@ -0,0 +1,130 @@
+use std::path::PathBuf;
+
+use crate::completion::expand_home;
+
+/// The files in pasted text which is nothing but paths to existing files, as terminals paste
+/// the paths of files dropped onto them. Paths can be quoted or have their spaces escaped, as
+/// shells expect, or be `file://` URLs. Returns None if anything else is pasted.
+pub fn paths(text: &str) -> Option<Vec<PathBuf>> {
+    let words = split_words(text);
+    if words.is_empty() {
+        return None;
+    }
+    words
+        .iter()
+        .map(|word| {
+            let path = match word.strip_prefix("file://") {
+                Some(url) => PathBuf::from(percent_decode(url)?),
+                None => expand_home(word),
+            };
+            (path.is_absolute() && path.is_file()).then_some(path)
+        })
+        .collect()
+}
+
+/// Splits text into words as a shell would: at whitespace, apart from whitespace inside quotes
+/// or escaped by a backslash.
+pub fn split_words(text: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') => {
+                let next = chars.next();
+                let word = word.get_or_insert_with(String::new);
+                match next {
+                    Some(next @ ('"' | '\\' | '$' | '`')) => word.push(next),
+                    Some(next) => {
+                        word.push('\\');
+                        word.push(next);
+                    }
+                    None => word.push('\\'),
+                }
+            }
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, '\\') => {
+                if let Some(next) = chars.next() {
+                    word.get_or_insert_with(String::new).push(next);
+                }
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+/// Decodes the `%xx` escapes in a URL's path, or returns None if they don't decode to UTF-8.
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes.get(i + 1..i + 3).filter(|_| bytes[i] == b'%');
+        match escape.and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()) {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("a b\n c", vec!["a", "b", "c"];                 "whitespace")]
+    #[test_case("/tmp/my\\ file.txt", vec!["/tmp/my file.txt"]; "escaped_space")]
+    #[test_case("'/tmp/a b' \"c \\\"d\\\"\"", vec!["/tmp/a b", "c \"d\""]; "quotes")]
+    #[test_case("'' x", vec!["", "x"];                          "empty_quotes")]
+    #[test_case("  ", vec![];                                   "blank")]
+    fn split(text: &str, expected: Vec<&str>) {
+        assert_eq!(expected, split_words(text));
+    }
+
+    #[test_case("a%20b%C3%A9", Some("a bé");  "escapes")]
+    #[test_case("100%", Some("100%");         "unfinished_escape")]
+    #[test_case("%FF", None;                  "invalid_utf8")]
+    fn decode(text: &str, expected: Option<&str>) {
+        assert_eq!(expected.map(String::from), percent_decode(text));
+    }
+
+    #[test]
+    fn dropped_paths() {
+        let dir = std::env::temp_dir().join("texteditor_dropped");
+        fs::create_dir_all(&dir).unwrap();
+        let (a, b) = (dir.join("a file.txt"), dir.join("b.txt"));
+        fs::write(&a, "").unwrap();
+        fs::write(&b, "").unwrap();
+
+        let text = format!(
+            "'{}' file://{}\n",
+            a.display(),
+            b.display().to_string().replace(' ', "%20")
+        );
+        assert_eq!(Some(vec![a.clone(), b.clone()]), paths(&text));
+        assert_eq!(None, paths(&format!("{} and more", a.display())));
+        assert_eq!(None, paths(&dir.display().to_string()));
+        assert_eq!(None, paths("b.txt"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
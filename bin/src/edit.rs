@@ -0,0 +1,91 @@
+/// A change made to a document by typing, which can be replayed at another position.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    /// Text typed in insert mode.
+    Insert(String),
+    /// Text typed in overwrite mode, replacing the characters under the cursor.
+    Replace(String),
+}
+
+/// Groups consecutive typed characters into edits, remembering the last complete edit so it can
+/// be repeated.
+#[derive(Debug, Default)]
+pub struct EditRecorder {
+    pending: Option<Edit>,
+    last: Option<Edit>,
+}
+
+impl EditRecorder {
+    pub fn new() -> EditRecorder {
+        EditRecorder::default()
+    }
+
+    /// Records a typed character, adding it to the current edit if it was typed in the same mode.
+    ///
+    /// # Arguments
+    ///
+    /// * 'c' - The character typed.
+    /// * 'overwrite' - Whether the character replaced the one under the cursor.
+    pub fn record(&mut self, c: char, overwrite: bool) {
+        match (&mut self.pending, overwrite) {
+            (Some(Edit::Insert(text)), false) | (Some(Edit::Replace(text)), true) => text.push(c),
+            _ => {
+                self.finish();
+                self.pending = Some(match overwrite {
+                    true => Edit::Replace(c.to_string()),
+                    false => Edit::Insert(c.to_string()),
+                });
+            }
+        }
+    }
+
+    /// Completes the current edit, making it the edit which is repeated.
+    pub fn finish(&mut self) {
+        if let Some(edit) = self.pending.take() {
+            self.last = Some(edit);
+        }
+    }
+
+    /// The last complete edit.
+    pub fn last(&self) -> Option<&Edit> {
+        self.last.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_typed_characters() {
+        let mut recorder = EditRecorder::new();
+        for c in "Lorem".chars() {
+            recorder.record(c, false);
+        }
+        assert_eq!(None, recorder.last());
+
+        recorder.finish();
+        assert_eq!(Some(&Edit::Insert(String::from("Lorem"))), recorder.last());
+    }
+
+    #[test]
+    fn mode_change_starts_new_edit() {
+        let mut recorder = EditRecorder::new();
+        recorder.record('a', false);
+        recorder.record('b', true);
+        recorder.record('c', true);
+        recorder.finish();
+
+        assert_eq!(Some(&Edit::Replace(String::from("bc"))), recorder.last());
+    }
+
+    #[test]
+    fn finish_without_edit_keeps_last() {
+        let mut recorder = EditRecorder::new();
+        recorder.record('a', false);
+        recorder.finish();
+        recorder.finish();
+
+        assert_eq!(Some(&Edit::Insert(String::from("a"))), recorder.last());
+    }
+}
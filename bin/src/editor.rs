@@ -1,302 +1,6072 @@
 use console::style;
-use crossterm::cursor;
-use crossterm::event;
-use crossterm::event::Event as TerminalEvent;
-use crossterm::terminal;
-use log::info;
-use std::error::Error;
+use crossterm::event::{Event as TerminalEvent, KeyEvent};
+use log::error;
+use std::collections::BTreeSet;
 use std::io;
-use std::ops::Range;
-use std::path::PathBuf;
-use unicode_width::UnicodeWidthStr;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::document::Document;
+use crate::align;
+use crate::ansi;
+use crate::brackets::{BracketScan, ScanKey};
+use crate::case::Case;
+use crate::chord::{self, Chord};
+use crate::collab::{self, Message, Session};
+use crate::command::{Command, FilterLines, FoldAction};
+use crate::completion;
+use crate::config::{self, Config, ProjectConfig, Settings};
+use crate::conflict::{self, Conflict, Keep};
+use crate::dirty::Dirty;
+use crate::document::{Document, LineEnding};
+use crate::drafts::Drafts;
+use crate::dropped;
+use crate::edit::{Edit, EditRecorder};
+use crate::error::{self, EditorError};
+use crate::export::{self, Format};
+use crate::fold::FoldStore;
+use crate::follow::Follower;
+use crate::help;
+use crate::hex;
+use crate::history::History;
+use crate::hover::{self, Hover};
+use crate::idle::{self, Scheduler, Task};
+use crate::indent::{self, Rules};
+use crate::info::Info;
+use crate::input::InputPoller;
+use crate::inspect;
+use crate::jumplist::{Jump, JumpList};
 use crate::keymaps::KeyMaps;
-use crate::terminal::CursorPosition;
+use crate::killring::KillRing;
+use crate::layout;
+use crate::literal::{Literal, Step};
+use crate::lock::{Acquired, Lock};
+use crate::logging;
+use crate::macros::{self, Macros};
+use crate::marks::{Mark, MarkStore};
+use crate::minimap;
+use crate::overlay::{self, Overlay};
+use crate::perf::{self, Perf};
+use crate::picker::Picker;
+use crate::project;
+use crate::prompt::{Kind, Prompt};
+use crate::quickfix::{self, QuickFix};
+use crate::range::LineRange;
+use crate::reflow;
+use crate::registers::{self, Register, Registers};
+use crate::scrollbar;
+use crate::selection::{Position, Selection};
+use crate::shell;
+use crate::signs::{self, Sign};
+use crate::sort;
+use crate::status::{Segments, Subsystem};
+use crate::substitute::{Confirmation, Substitution};
+use crate::surround::Pair;
 use crate::terminal::Terminal;
+use crate::terminal::{CursorPosition, CursorShape};
+use crate::textobject::{self, TextObject};
+use crate::virtual_text::{self, Annotation, Placement, Row, Source};
+use crate::welcome;
 
 pub struct Editor {
-    column: u16,
+    column: u32,
     row: u32,
-    document: Option<Document>,
+    top: u32,
+    /// Where the selection was started, if text is being selected.
+    anchor: Option<Position>,
+    buffers: Vec<Buffer>,
+    command_history: History,
+    /// The paths of documents which have been opened, kept across invocations for the start
+    /// screen.
+    recent_files: History,
+    /// The closed folds of each file, restored when the file is opened again.
+    fold_store: FoldStore,
+    /// The directory lock files are kept in, or None if files aren't locked.
+    lock_dir: Option<PathBuf>,
+    /// The drafts saved from unnamed buffers with unsaved changes on exit, or None if there's
+    /// no state directory to keep them in.
+    drafts: Option<Drafts>,
+    /// The global marks, which are kept between sessions.
+    mark_store: MarkStore,
+    /// Whether the start screen is shown, which it is from launching without a document until
+    /// the first edit or document is opened.
+    welcome: bool,
+    /// The title last given to the terminal's window.
+    title: Option<String>,
+    /// The text previously entered at the find prompt.
+    search_history: History,
+    /// The completions of the file path at the prompt, while Tab is being pressed repeatedly.
+    completion: Option<Completion>,
+    config: Config,
+    current: usize,
+    dirty: Dirty,
+    edits: EditRecorder,
+    events: Receiver<Event>,
     exit: bool,
+    /// Whether the terminal has focus. Documents aren't loaded in the background while it
+    /// doesn't.
+    focused: bool,
+    /// Where the last recenter placed the current line, so pressing it again moves on to the
+    /// next position.
+    recenter: Option<Recenter>,
+    /// Whether the minimap is shown down the right-hand side of the screen.
+    minimap: bool,
+    /// Whether the scrollbar is shown down the right edge of the screen.
+    scrollbar: bool,
+    /// Whether guides are drawn at each level of indentation.
+    indent_guides: bool,
+    /// The panels shown before entering zen mode, which are restored on leaving it, or None if
+    /// zen mode is off.
+    zen: Option<Panels>,
+    /// The terminal cell the cursor returns to when moving up and down, so passing through
+    /// shorter lines doesn't lose the horizontal position.
+    goal_column: Option<usize>,
+    input: Option<InputPoller>,
+    jumps: JumpList,
     keymaps: KeyMaps,
-    lines: Vec<String>,
+    kills: KillRing,
+    /// The text yanked and deleted into registers, to be put back.
+    registers: Registers,
+    /// The recorded keyboard macros, and the one being recorded.
+    macros: Macros,
+    /// The keys typed after Ctrl+V, while a character is being entered by its codepoint or a
+    /// digraph.
+    literal: Option<Literal>,
+    /// The chord waiting for its next key, if its prefix has been pressed.
+    chord: Option<PendingChord>,
+    /// Where the mouse is resting, and the tooltip shown for it.
+    hover: Option<Hover>,
+    /// The timings of recent iterations of the event loop.
+    perf: Perf,
+    /// The work done while waiting for input.
+    idle: Scheduler,
+    /// What subsystems working in the background report in the status line.
+    segments: Segments,
+    /// The timings of the current iteration of the event loop, so far.
+    frame: perf::Frame,
+    /// Whether the last frame's timings are shown in the status line.
+    show_perf: bool,
+    /// Whether every frame's timings are logged.
+    profile_events: bool,
+    /// The file messages are logged to, if logging is enabled.
+    log_file: Option<PathBuf>,
+    /// Whether the last event was a kill, so the next kill is added to the same entry.
+    killing: bool,
+    low_nibble: bool,
+    message: Option<String>,
+    /// Whether the message was too long for the status line, so it was drawn over several rows
+    /// which stay until a key is pressed.
+    message_expanded: bool,
+    /// Whether typing replaces the character under the cursor rather than inserting before it.
+    overwrite: bool,
+    /// Whether Enter and Tab are inserted as they're typed, so text pasted into a terminal
+    /// without bracketed paste arrives as it was copied.
+    paste_mode: bool,
+    /// The list being picked from, if one is open, and what to do with the picked item.
+    picker: Option<(Picker, PickerAction)>,
+    /// The shared editing session, when hosting or connected to one.
+    session: Option<Session>,
+    /// The file being followed as it grows, if one is.
+    follower: Option<Follower>,
+    /// The prompt being entered on the status line, if one is open.
+    prompt: Option<Prompt>,
+    /// Where the cursor was when a prompt which moves it while typing was opened.
+    prompt_preview: Option<PromptPreview>,
+    /// The lines given by a range before the command being run, which it acts on in place of
+    /// the selection.
+    range: Option<RangeInclusive<u32>>,
+    /// The error locations found by the last build.
+    quickfix: QuickFix,
+    /// The text last searched for, which can be found again with a key.
+    search: Option<String>,
+    /// The substitution being confirmed a match at a time.
+    substitution: Option<Confirmation>,
+    /// The replacements the substitution being entered at the prompt would make.
+    preview: Option<SubstitutePreview>,
+    /// The question asked before running a command which can't be undone.
+    question: Option<Question>,
+    /// The rows of the document info panel, while it's shown.
+    info: Option<Vec<String>>,
+    /// Whether hidden files are included when completing file paths at the prompt.
+    show_hidden: bool,
+    /// The words in the configured word lists, read the first time a word is completed.
+    dictionary: Option<Vec<String>>,
+    sender: Sender<Event>,
     should_render: bool,
-    status: String,
     terminal: Terminal,
 }
 
+/// An open document, along with the cursor and scroll position to restore when it's next
+/// switched to.
+struct Buffer {
+    document: Document,
+    column: u32,
+    row: u32,
+    top: u32,
+    /// Whether the rest of a lazily loaded document is being loaded while the editor is idle.
+    loading: bool,
+    /// The settings for the document's filetype.
+    settings: Settings,
+    /// Whether escape sequences in the document are displayed as the colours they set, rather
+    /// than as raw text. The document can't be edited while they are.
+    ansi: bool,
+    /// The register whose macro the document shows as text, which is stored back into the
+    /// register when the document is saved.
+    register: Option<char>,
+    /// The brackets matched on the visible lines, kept until the document is edited or
+    /// scrolled.
+    brackets: Option<BracketScan>,
+    /// The rows of the minimap last drawn for the document, kept until it's edited or scrolled.
+    minimap: Option<(minimap::Key, Vec<String>)>,
+    /// The lock telling other instances of the editor that the document's file is open, which
+    /// is released when the buffer is closed.
+    lock: Option<Lock>,
+    /// The merge conflicts left in the document, while in merge mode.
+    merge: Option<Merge>,
+}
+
+impl Buffer {
+    /// Makes the configured changes to the document before it's saved, such as trimming
+    /// trailing whitespace. The changes are made as edits to the document itself.
+    fn apply_save_edits(&mut self) -> error::Result<()> {
+        if self.settings.trim_trailing_whitespace {
+            self.document.trim_trailing_whitespace();
+        }
+        if self.settings.insert_final_newline {
+            self.document.ensure_final_newline()?;
+        }
+        Ok(())
+    }
+
+    /// Shows the messages of the errors from the last build in the document. The first error on
+    /// a line is drawn after the end of the line, and any others on rows of their own below it.
+    fn annotate_errors(&mut self, quickfix: &QuickFix) {
+        let Some(path) = self.document.path().map(PathBuf::from) else {
+            return;
+        };
+        let virtual_text = self.document.virtual_text_mut();
+        virtual_text.clear(Source::Errors);
+        let mut annotated = BTreeSet::new();
+        for entry in quickfix.entries_in(&path) {
+            let placement = match annotated.insert(entry.line) {
+                true => Placement::EndOfLine,
+                false => Placement::Below,
+            };
+            virtual_text.place(
+                entry.line,
+                Annotation {
+                    source: Source::Errors,
+                    placement,
+                    text: entry.message.clone(),
+                    color: console::Color::Red,
+                },
+            );
+        }
+    }
+}
+
+/// The file paths the argument at the prompt could be completed to, and which is being shown.
+struct Completion {
+    candidates: Vec<String>,
+    index: usize,
+}
+
+/// What happens to the item chosen from a picker.
+#[derive(Debug, Clone, PartialEq)]
+enum PickerAction {
+    /// Pastes the kill ring entry with the item's index.
+    Paste,
+    /// Moves the document to one of its revisions, listed in the same order as the items.
+    Revision(Vec<usize>),
+    /// Opens one of the documents, listed in the same order as the items.
+    Open(Vec<PathBuf>),
+    /// Switches to the buffer with the item's index.
+    Switch,
+    /// Jumps to one of the global marks, listed in the same order as the items.
+    Mark(Vec<char>),
+    /// Puts one of the registers, listed in the same order as the items.
+    Put(Vec<char>),
+    /// Replaces the text before the cursor from a column with one of the completions, listed in
+    /// the same order as the items.
+    Complete(u32, Vec<String>),
+}
+
+/// The most rows a picker takes up.
+const PICKER_ROWS: u32 = 10;
+
+/// The popups drawn over the text area, bottom layer first. Keys go to the top layer while any
+/// are open, apart from tooltips, which any key hides.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Layer {
+    Tooltip,
+    Picker,
+    Question,
+    Info,
+    ChordHint,
+}
+
+/// The number of lines at the start of a document checked for escape sequences when it's opened.
+const ANSI_DETECTION_LINES: u32 = 100;
+
+/// The colour indent guides are drawn in, a dark grey which stays in the background.
+const INDENT_GUIDE_COLOR: console::Color = console::Color::Color256(240);
+/// The colour of the replacements shown while a substitution is previewed or confirmed.
+const SUBSTITUTE_COLOR: console::Color = console::Color::Green;
+/// The question asked before replacing each match of a substitution with the confirm flag.
+const CONFIRM_SUBSTITUTION: &str = "Replace this match? (y/n/a/l/q)";
+/// Shown in the status line below a message which is too long for it.
+const CONTINUE: &str = "Press any key to continue";
+
+/// Where in the viewport a recenter places the current line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Recenter {
+    Center,
+    Top,
+    Bottom,
+}
+
+impl Recenter {
+    /// The position to use when recentering again, cycling from center to top to bottom.
+    fn next(self) -> Recenter {
+        match self {
+            Recenter::Center => Recenter::Top,
+            Recenter::Top => Recenter::Bottom,
+            Recenter::Bottom => Recenter::Center,
+        }
+    }
+}
+
+/// A chord whose prefix has been pressed, waiting for the next key.
+struct PendingChord {
+    chord: &'static Chord,
+    started: Instant,
+    /// Whether the keys which can follow the prefix are shown.
+    hint: bool,
+}
+
+/// The replacements a substitution entered at the prompt would make, which are shown in place of
+/// the lines they'd be made on until the prompt is submitted.
+struct SubstitutePreview {
+    /// The prompt's text the preview was made for.
+    input: String,
+    lines: RangeInclusive<u32>,
+    substitution: Substitution,
+}
+
+/// Where the cursor was when a prompt which moves it while typing was opened, returned to if
+/// the prompt is cancelled.
+struct PromptPreview {
+    origin: Position,
+    top: u32,
+    /// The prompt's input the cursor was last moved for.
+    input: String,
+}
+
+/// The merge conflicts found in a document, kept until the document is edited.
+struct Merge {
+    /// The number of edits made to the document when the conflicts were found.
+    edits: u64,
+    conflicts: Vec<Conflict>,
+}
+
+/// A question waiting for a yes or no answer before a command is run.
+struct Question {
+    /// The question, shown as a message until it's answered.
+    text: String,
+    /// The command run if the answer is yes.
+    command: Command,
+    /// The index of the buffer the command acts on, which is switched back to if it's no
+    /// longer current.
+    buffer: usize,
+    /// Text pasted if the answer is no, when the question offered to do something else with
+    /// pasted text.
+    paste: Option<String>,
+}
+
+/// The parts of the screen hidden by zen mode, as they were before entering it.
+#[derive(Debug, Clone, Copy)]
+struct Panels {
+    minimap: bool,
+    scrollbar: bool,
+}
+
+/// How often the event loop wakes up when no events have been posted.
+const TICK: Duration = Duration::from_millis(250);
+
 pub enum Event {
+    /// Input read from the terminal, which is mapped to an event by the keymaps.
+    Input(TerminalEvent),
+    /// A message from another participant in a shared editing session.
+    Remote(u32, Message),
+    ParticipantJoined(u32),
+    ParticipantLeft(u32),
+    /// Sets or clears a subsystem's segment of the status line.
+    Status(Subsystem, Option<String>),
+    /// Text added to the file being followed, which is all of it if the file was truncated.
+    Followed {
+        text: String,
+        truncated: bool,
+    },
     KeyPress(char),
+    RepeatEdit,
     Exit,
-    MoveCursor(CursorPosition),
-    MoveCursorUp(u16),
-    MoveCursorDown(u16),
-    MoveCursorLeft(u16),
-    MoveCursorRight(u16),
+    MoveCursorUp(u32),
+    MoveCursorDown(u32),
+    MoveCursorLeft(u32),
+    MoveCursorRight(u32),
+    MoveCursorHome,
+    NextParagraph,
+    PreviousParagraph,
+    NextSentence,
+    PreviousSentence,
+    NextWord,
+    PreviousWord,
+    /// Deletes from the cursor back to the start of the word, or the selection if there is one.
+    DeleteWordBack,
+    /// Deletes from the cursor to the start of the next word, or the selection if there is one.
+    DeleteWordForward,
+    /// Moves the cursor while extending the selection, starting a selection if there isn't one.
+    SelectUp(u32),
+    SelectDown(u32),
+    SelectLeft(u32),
+    SelectRight(u32),
+    SelectAll,
+    SelectLine,
+    /// Grows the selection to the next larger unit of text: the word at the cursor, the line,
+    /// the paragraph, then the whole document.
+    ExpandSelection,
+    ChangeCase(Case),
+    /// Re-wraps the selected paragraphs, or the paragraph at the cursor, to the text width.
+    Reflow,
+    FindNext,
+    FindPrevious,
+    NextError,
+    PreviousError,
+    /// Scrolls the viewport down by a number of lines without moving the cursor, unless it would
+    /// go off screen.
+    ScrollDown(u32),
+    ScrollUp(u32),
+    Recenter,
+    /// Scrolls the viewport by the configured step, leaving the cursor where it is even if it
+    /// goes off screen.
+    MouseScrollDown,
+    MouseScrollUp,
+    /// A click of the left mouse button at a column and row of the screen.
+    MouseClick(u16, u16),
+    /// The mouse moving to a column and row of the screen without a button pressed.
+    MouseMove(u16, u16),
     NewLine,
+    /// Cuts from the cursor to the end of the line into the kill ring. At the end of a line the
+    /// line break is cut instead.
+    KillLine,
+    DeleteLine,
+    /// Inserts the most recently killed text.
+    Yank,
+    /// Opens a picker to choose which killed or pasted text to insert.
+    PasteHistory,
+    PickerInsert(char),
+    PickerBackspace,
+    PickerNext,
+    PickerPrevious,
+    PickerSubmit,
+    PickerCancel,
+    /// Text pasted into the terminal, which is inserted in one go.
+    Paste(String),
+    Indent,
+    ToggleComment,
+    ToggleOverwrite,
+    Cancel,
+    NextBuffer,
+    PreviousBuffer,
+    JumpBack,
+    JumpForward,
+    Save,
+    Suspend,
+    Undo,
+    Redo,
+    /// Starts entering a character by its codepoint or a digraph.
+    InsertLiteral,
+    /// Waits for another key, which picks one of the chord's commands to run.
+    StartChord(&'static Chord),
+    ChordKey(char),
+    ChordCancel,
+    /// Closes the document info panel.
+    CloseInfo,
+    LiteralKey(char),
+    LiteralSubmit,
+    LiteralCancel,
+    /// Answers whether to replace the match being confirmed by a substitution.
+    ConfirmKey(char),
+    ConfirmCancel,
+    OpenPrompt,
+    /// Opens a prompt for a line to go to, moving to it while it's typed.
+    OpenGotoPrompt,
+    /// Opens a prompt for text to find, moving to its next match while it's typed.
+    OpenSearchPrompt,
+    PromptInsert(char),
+    PromptBackspace,
+    PromptHistoryPrevious,
+    PromptHistoryNext,
+    /// Completes the file path being entered at the prompt, cycling through the possible
+    /// completions when pressed again.
+    PromptComplete,
+    /// Toggles whether hidden files are included when completing file paths.
+    PromptToggleHidden,
+    PromptSubmit,
+    PromptCancel,
 }
 
 impl Editor {
-    pub fn new() -> Editor {
+    pub fn new(config: Config) -> Editor {
+        let mut terminal = Terminal::new();
+        if let Some(colors) = config.colors {
+            terminal.set_colors(colors);
+        }
+        Editor::with_terminal(config, terminal, config::state_dir())
+    }
+
+    /// Constructs an editor which draws to the given terminal, such as a headless one.
+    ///
+    /// # Arguments
+    ///
+    /// * 'config' - The configuration to use.
+    /// * 'terminal' - The terminal to draw to.
+    /// * 'state_dir' - The directory history, marks, folds, locks and drafts are kept in, or
+    ///   None to keep them in memory only.
+    pub fn with_terminal(config: Config, terminal: Terminal, state_dir: Option<PathBuf>) -> Editor {
+        let command_history = match &state_dir {
+            Some(dir) => History::load(dir.join("command_history"), config.history_size),
+            None => History::new(config.history_size),
+        };
+        let search_history = match &state_dir {
+            Some(dir) => History::load(dir.join("search_history"), config.history_size),
+            None => History::new(config.history_size),
+        };
+        let recent_files = match &state_dir {
+            Some(dir) => History::load(dir.join("recent_files"), config.history_size),
+            None => History::new(config.history_size),
+        };
+        let fold_store = match &state_dir {
+            Some(dir) => FoldStore::load(dir.join("folds.json")),
+            None => FoldStore::new(),
+        };
+        let mark_store = match &state_dir {
+            Some(dir) => MarkStore::load(dir.join("marks.json")),
+            None => MarkStore::new(),
+        };
+        let lock_dir = state_dir
+            .as_ref()
+            .filter(|_| config.lock_files)
+            .map(|dir| dir.join("locks"));
+        let drafts = state_dir.map(|dir| Drafts::new(dir.join("drafts")));
+
+        let (sender, events) = mpsc::channel();
+
         Editor {
             column: 0,
             row: 1,
-            document: None,
+            top: 1,
+            anchor: None,
+            buffers: vec![],
+            command_history,
+            recent_files,
+            search_history,
+            fold_store,
+            lock_dir,
+            drafts,
+            mark_store,
+            welcome: false,
+            title: None,
+            completion: None,
+            minimap: config.minimap,
+            scrollbar: config.scrollbar,
+            indent_guides: config.indent_guides,
+            zen: None,
+            config,
+            current: 0,
+            dirty: Dirty::All,
+            edits: EditRecorder::new(),
+            events,
             exit: false,
+            focused: true,
+            goal_column: None,
+            recenter: None,
+            input: None,
+            jumps: JumpList::new(),
             keymaps: KeyMaps {},
-            lines: vec![],
+            kills: KillRing::new(),
+            registers: Registers::new(),
+            macros: Macros::new(),
+            killing: false,
+            literal: None,
+            chord: None,
+            hover: None,
+            perf: Perf::new(),
+            idle: Scheduler::new(vec![Task::Load, Task::Compact, Task::Index]),
+            segments: Segments::new(),
+            frame: perf::Frame::default(),
+            show_perf: false,
+            profile_events: false,
+            log_file: None,
+            low_nibble: false,
+            message: None,
+            message_expanded: false,
+            overwrite: false,
+            paste_mode: false,
+            picker: None,
+            prompt: None,
+            prompt_preview: None,
+            range: None,
+            session: None,
+            follower: None,
+            quickfix: QuickFix::default(),
+            search: None,
+            substitution: None,
+            preview: None,
+            question: None,
+            info: None,
+            show_hidden: false,
+            dictionary: None,
+            sender,
             should_render: true,
-            status: String::from("Document"),
-            terminal: Terminal::new(),
+            terminal,
         }
     }
 
-    pub fn run(&mut self) -> io::Result<()> {
-        /*queue!(
-            stdout(),
-            style::ResetColor,
-            terminal::Clear(ClearType::All),
-            style::Print(format!("This is some text!"))
-        )?;*/
+    pub fn terminal(&self) -> &Terminal {
+        &self.terminal
+    }
+
+    /// Draws the whole screen again, loading any lines which are about to be drawn first.
+    pub fn redraw(&mut self) -> io::Result<()> {
+        self.load_visible_lines();
+        self.dirty.mark_all();
+        self.render()
+    }
 
-        if self.document.is_none() {
-            self.document = Some(Document::new());
+    pub fn run(&mut self) -> io::Result<()> {
+        if self.buffers.is_empty() {
+            self.open(Document::new());
+            self.welcome = true;
         }
+        self.offer_drafts();
         self.terminal.startup()?;
+        self.input = Some(InputPoller::start(self.sender.clone()));
+        self.load_visible_lines();
+        self.render()?;
 
         while !self.exit {
-            self.handle_event()?;
+            self.tick()?;
         }
 
+        if let Some(input) = self.input.take() {
+            input.stop();
+        }
         self.terminal.shutdown()?;
 
         Ok(())
     }
 
-    pub fn exit(&mut self) {
-        self.exit = true;
-        if let Some(document) = &self.document {
-            document.debug();
-        }
+    /// Whether any idle task has work to do, in which case the event loop doesn't wait for input
+    /// before running it.
+    fn has_idle_work(&self) -> bool {
+        self.focused && self.buffers.iter().any(|b| b.loading)
+            || self.buffers.iter().any(|b| b.document.needs_compaction())
+            || self.config.search_index && self.buffers.iter().any(|b| b.document.needs_index())
     }
 
-    pub fn load(&mut self, file: Option<PathBuf>) -> Result<(), Box<dyn Error>> {
-        if let Some(path) = file {
-            let document = Document::load(path)?;
+    /// Runs one slice of idle work, returning whether there was anything to do.
+    fn run_idle(&mut self) -> bool {
+        let mut idle = std::mem::take(&mut self.idle);
+        let worked = idle.run_slice(idle::BUDGET, |task| match task {
+            Task::Load => self.focused && self.load_in_background(),
+            Task::Compact => self.compact_document(),
+            Task::Index => {
+                self.config.search_index
+                    && self.buffers.iter_mut().any(|b| b.document.build_index())
+            }
+        });
+        self.idle = idle;
+        worked
+    }
 
-            let size = self.terminal.size();
-            self.lines = document.get_lines(std::ops::Range {
-                start: 1,
-                end: (size.height) as u32,
-            });
+    /// Compacts the first document whose piece table has been split up by editing, returning
+    /// whether there was one to compact.
+    fn compact_document(&mut self) -> bool {
+        let Some(buffer) = self
+            .buffers
+            .iter_mut()
+            .find(|b| b.document.needs_compaction())
+        else {
+            return false;
+        };
+        buffer.document.compact();
+        true
+    }
 
-            self.document = Some(document);
-            self.terminal.move_cursor_to(CursorPosition { x: 0, y: 0 });
+    /// Loads the next chunk of the first document which is still loading, returning whether
+    /// there was anything to load.
+    fn load_in_background(&mut self) -> bool {
+        let Some(buffer) = self.buffers.iter_mut().find(|b| b.loading) else {
+            return false;
+        };
 
-            self.render()?;
+        if let Err(e) = buffer.document.load_chunk() {
+            buffer.loading = false;
+            self.message = Some(e.to_string());
+        } else if buffer.document.load_progress().is_none() {
+            buffer.loading = false;
         }
-        Ok(())
+        self.should_render = true;
+        true
     }
 
-    fn handle_event(&mut self) -> std::io::Result<()> {
-        let a = match event::read()? {
-            TerminalEvent::FocusGained => None,
-            TerminalEvent::FocusLost => None,
-            TerminalEvent::Key(e) => self.keymaps.map_key_press_to_event(e),
-            TerminalEvent::Mouse(_) => None,
-            TerminalEvent::Paste(_) => None,
-            TerminalEvent::Resize(_, _) => None,
+    /// Checks whether the current document was changed by another program while the terminal
+    /// didn't have focus. An unmodified document is reloaded, while a modified one is left as
+    /// it is with a warning, so no changes are lost.
+    fn focus_gained(&mut self) {
+        self.focused = true;
+        let Some(buffer) = self.buffers.get_mut(self.current) else {
+            return;
+        };
+        if !buffer.document.changed_on_disk() {
+            return;
+        }
+        buffer.document.acknowledge_disk_change();
+        if buffer.document.is_modified() {
+            self.message = Some(format!(
+                "\"{}\" changed on disk (:e! to discard your changes and reload)",
+                buffer.document.name()
+            ));
+            buffer.document.set_outdated(true);
+        } else {
+            self.reload(false);
+        }
+        self.should_render = true;
+    }
+
+    /// Saves the modified documents if configured to, as the user is switching to another
+    /// program which may read them.
+    fn focus_lost(&mut self) {
+        self.focused = false;
+        if self.config.save_on_focus_lost && self.buffers.iter().any(|b| b.document.is_modified()) {
+            self.save_all();
+            self.should_render = true;
+        }
+    }
+
+    /// Stops loading the current document in the background. The rest of the document is still
+    /// loaded as it's needed.
+    fn cancel_loading(&mut self) {
+        if let Some(buffer) = self.buffers.get_mut(self.current) {
+            if buffer.loading {
+                buffer.loading = false;
+                self.message = Some(String::from("Loading cancelled"));
+            }
+        }
+    }
+
+    /// Runs one iteration of the event loop: waits for an event to be posted, handles it along
+    /// with any others already queued, then renders the result once.
+    fn tick(&mut self) -> io::Result<()> {
+        // Don't wait for events while there's idle work, such as documents loading, so it
+        // continues until the user does something.
+        let timeout = match self.has_idle_work() {
+            true => Duration::ZERO,
+            false => TICK,
         };
 
-        if let Some(event) = a {
-            self.process_event(event)?;
-            if self.should_render {
-                self.should_render = false;
-                self.render()?;
+        match self.events.recv_timeout(timeout) {
+            Ok(event) => {
+                let started = Instant::now();
+                if let Err(e) = self.handle_event(event) {
+                    self.report(e);
+                }
+                self.frame.events += started.elapsed();
+                self.frame.event_count += 1;
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                let started = Instant::now();
+                let busy = self.show_chord_hint() || self.show_tooltip() || self.run_idle();
+                self.frame.buffer += started.elapsed();
+                if !busy {
+                    return Ok(());
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                self.exit = true;
+                return Ok(());
             }
         }
+        let started = Instant::now();
+        while let Ok(event) = self.events.try_recv() {
+            if self.exit {
+                break;
+            }
+            if let Err(e) = self.handle_event(event) {
+                self.report(e);
+            }
+            self.frame.event_count += 1;
+        }
+        self.frame.events += started.elapsed();
+
+        let started = Instant::now();
+        self.load_visible_lines();
+        self.frame.buffer += started.elapsed();
+        if let Some(e) = self.terminal.take_error() {
+            self.report(e.into());
+        }
+        if self.should_render {
+            self.should_render = false;
+            let started = Instant::now();
+            self.render()?;
+            self.frame.render = started.elapsed().saturating_sub(self.frame.flush);
+        }
+
+        let frame = std::mem::take(&mut self.frame);
+        if self.profile_events {
+            frame.log();
+        }
+        self.perf.record(frame);
         Ok(())
     }
 
-    fn process_event(&mut self, event: Event) -> std::io::Result<()> {
+    /// Writes to the terminal, adding how long it took to the current frame's timings.
+    fn flush(&mut self, write: impl FnOnce(&Terminal) -> io::Result<()>) -> io::Result<()> {
+        let started = Instant::now();
+        let result = write(&self.terminal);
+        self.frame.flush += started.elapsed();
+        result
+    }
+
+    /// Shows an error in the message area, so the editor keeps running rather than exiting when
+    /// an operation fails.
+    ///
+    /// # Arguments
+    ///
+    /// * 'error' - The error to show, which is also logged.
+    pub fn report(&mut self, error: EditorError) {
+        error!("{}", error);
+        self.message = Some(error.to_string());
         self.should_render = true;
-        match event {
-            Event::KeyPress(c) => self.handle_key_press(c)?,
-            Event::Exit => self.exit(),
-            Event::MoveCursor(pos) => self.terminal.move_cursor_to(pos),
-            Event::MoveCursorUp(o) => self.move_cursor_up(o)?,
-            Event::MoveCursorDown(o) => self.move_cursor_down(o)?,
-            Event::MoveCursorLeft(o) => self.move_cursor_left(o)?,
-            Event::MoveCursorRight(o) => self.move_cursor_right(o)?,
-            Event::NewLine => self.handle_new_line(),
+    }
+
+    /// Closes the editor, keeping unnamed buffers with unsaved changes as drafts. The editor
+    /// stays open if the drafts can't be saved, so their text isn't lost.
+    pub fn exit(&mut self) {
+        if let Err(e) = self.save_drafts() {
+            error!("Unable to save drafts: {}", e);
+            self.message = Some(format!(
+                "Unable to save drafts: {} (:qa! to quit without them)",
+                e
+            ));
+            return;
+        }
+        self.exit = true;
+        for buffer in &self.buffers {
+            buffer.document.debug();
+        }
+    }
+
+    /// Whether a buffer's changes are kept as a draft on exit, rather than needing to be saved
+    /// first. Without a state directory there's nowhere to keep drafts.
+    fn is_draft(&self, buffer: &Buffer) -> bool {
+        let document = &buffer.document;
+        self.drafts.is_some()
+            && document.path().is_none()
+            && document.is_modified()
+            && !document.is_binary()
+    }
+
+    /// Saves the text of each unnamed buffer with unsaved changes as a draft, to be restored
+    /// the next time the editor starts.
+    fn save_drafts(&self) -> io::Result<()> {
+        let Some(drafts) = &self.drafts else {
+            return Ok(());
         };
-        Ok(())
+        let texts: Vec<String> = self
+            .buffers
+            .iter()
+            .filter(|b| self.is_draft(b))
+            .map(|b| b.document.text())
+            .collect();
+        drafts.save(&texts)
     }
 
-    fn handle_key_press(&mut self, c: char) -> std::io::Result<()> {
-        //print!("{}", c);
-        if let Some(document) = self.document.as_mut() {
-            document.insert(self.row, self.column as u32, c);
-            self.move_cursor_right(1)?;
-            self.should_render = true;
+    /// Offers to open the drafts left from unnamed buffers the last time the editor exited.
+    fn offer_drafts(&mut self) {
+        let count = self.drafts.as_ref().map_or(0, Drafts::count);
+        if count > 0 {
+            self.ask(
+                format!("Open the {} draft(s) kept from unnamed buffers?", count),
+                Command::Drafts { force: false },
+            );
         }
-        Ok(())
     }
 
-    fn get_document_window(&self) -> (u32, u32) {
-        let size = self.terminal.size();
-        let pos = self.terminal.cursor_pos();
+    /// Opens the drafts kept from unnamed buffers in new buffers, or discards them.
+    fn restore_drafts(&mut self, force: bool) {
+        let Some(drafts) = &self.drafts else {
+            return;
+        };
+        if force {
+            self.message = Some(match drafts.discard() {
+                Ok(count) => format!("{} draft(s) discarded", count),
+                Err(e) => format!("Unable to discard drafts: {}", e),
+            });
+            return;
+        }
+        match drafts.take() {
+            Ok(texts) if texts.is_empty() => {
+                self.message = Some(String::from("No drafts to open"));
+            }
+            Ok(texts) => {
+                for text in &texts {
+                    self.open(Document::unsaved(text));
+                }
+                self.message = Some(format!("{} draft(s) opened", texts.len()));
+            }
+            Err(e) => self.message = Some(format!("Unable to open drafts: {}", e)),
+        }
+    }
 
-        let cursor_offset = (size.height - pos.y) as u32;
-        (
-            self.row - (size.height as u32 - cursor_offset),
-            self.row + cursor_offset - 1,
-        )
+    /// Opens a document in a new buffer and switches to it.
+    pub fn open(&mut self, document: Document) {
+        if let Some(path) = document.path() {
+            self.recent_files.push(&path.display().to_string());
+            if let Err(e) = self.recent_files.save() {
+                error!("Unable to save recent files: {}", e);
+            }
+        }
+        // Only the start of a lazily loaded document is checked for escape sequences.
+        let ansi = !document.is_binary()
+            && document
+                .get_lines(1..document.line_count().min(ANSI_DETECTION_LINES) + 1)
+                .iter()
+                .any(|line| ansi::contains_escapes(line));
+        let settings = self
+            .project_config(document.path())
+            .settings(document.filetype());
+        self.buffers.push(Buffer {
+            loading: document.load_progress().is_some(),
+            ansi,
+            register: None,
+            brackets: None,
+            minimap: None,
+            lock: None,
+            merge: None,
+            settings,
+            document,
+            column: 0,
+            row: 1,
+            top: 1,
+        });
+        if let Some(buffer) = self.buffers.last_mut() {
+            buffer.annotate_errors(&self.quickfix);
+            if let Some(path) = buffer.document.path() {
+                for range in self.fold_store.get(path) {
+                    buffer.document.folds_mut().close(range);
+                }
+            }
+        }
+        self.switch_buffer(self.buffers.len() - 1);
+        self.lock_file();
+        self.offer_merge();
     }
 
-    fn move_cursor_up(&mut self, offset: u16) -> std::io::Result<()> {
-        let pos = self.terminal.cursor_pos();
+    /// Suggests merge mode if the current document has merge conflicts in it. Documents opened
+    /// in large file mode aren't searched for conflicts.
+    fn offer_merge(&mut self) {
+        let Some(document) = self.document().filter(|d| !d.is_large() && !d.is_binary()) else {
+            return;
+        };
+        let count = conflict::find(&document.get_lines(1..document.line_count() + 1)).len();
+        if count > 0 {
+            self.message = Some(format!(
+                "{} merge conflict(s) found (:merge to resolve them)",
+                count
+            ));
+        }
+    }
 
-        if pos.y > 0 {
-            self.terminal.move_cursor_up(offset)?;
-            self.check_cursor_pos()?;
-            self.row -= 1;
-        } else {
-            if self.row != 1 {
-                self.row -= 1;
-                if let Some(document) = &self.document {
-                    let size = self.terminal.size();
-                    self.lines = document.get_lines(Range {
-                        start: self.row,
-                        end: self.row + size.height as u32,
-                    });
-                    self.check_cursor_pos()?;
+    /// The merge conflicts in the current document, found again if it's been edited since they
+    /// were last found. Empty unless in merge mode.
+    fn conflicts(&mut self) -> &[Conflict] {
+        let Some(buffer) = self.buffers.get_mut(self.current) else {
+            return &[];
+        };
+        let Some(merge) = &mut buffer.merge else {
+            return &[];
+        };
+        let document = &buffer.document;
+        if merge.edits != document.edits() {
+            merge.edits = document.edits();
+            merge.conflicts = conflict::find(&document.get_lines(1..document.line_count() + 1));
+        }
+        &merge.conflicts
+    }
+
+    /// Starts merge mode, jumping to the first merge conflict from the cursor, or stops it.
+    fn toggle_merge(&mut self) {
+        let Some(buffer) = self.buffers.get_mut(self.current) else {
+            return;
+        };
+        if buffer.merge.take().is_some() {
+            self.message = Some(String::from("Merge mode off"));
+            return;
+        }
+        // The edit count never matches, so the conflicts are found straight away.
+        buffer.merge = Some(Merge {
+            edits: u64::MAX,
+            conflicts: vec![],
+        });
+        match self.conflicts().len() {
+            0 => {
+                self.message = Some(String::from("No merge conflicts"));
+                if let Some(buffer) = self.buffers.get_mut(self.current) {
+                    buffer.merge = None;
                 }
             }
+            _ => {
+                self.jump_to_conflict(true);
+                // The status line gains the count of conflicts.
+                self.dirty.mark_all();
+            }
         }
-        Ok(())
     }
 
-    fn move_cursor_down(&mut self, offset: u16) -> std::io::Result<()> {
-        let size = self.terminal.size();
-        let pos = self.terminal.cursor_pos();
-        if pos.y < size.height - 2 {
-            if pos.y as usize <= self.lines.len() - 1 {
-                self.row += 1;
-                self.terminal.move_cursor_down(offset)?;
-                self.check_cursor_pos()?;
+    /// Jumps to the next or previous merge conflict, wrapping around the document.
+    fn jump_to_conflict(&mut self, forward: bool) {
+        let row = self.row;
+        let starts: Vec<u32> = match self
+            .buffers
+            .get(self.current)
+            .and_then(|b| b.merge.as_ref())
+        {
+            Some(_) => self.conflicts().iter().map(|c| c.start).collect(),
+            None => {
+                let document = self.document();
+                document.map_or(vec![], |d| {
+                    conflict::find(&d.get_lines(1..d.line_count() + 1))
+                        .iter()
+                        .map(|c| c.start)
+                        .collect()
+                })
             }
-        } else {
-            if let Some(document) = &self.document {
-                let line_count = document.line_count();
-                let size = self.terminal.size();
-
-                if self.row < line_count {
-                    self.row += 1;
-                    self.lines = document.get_lines(Range {
-                        start: self.row - size.height as u32,
-                        end: self.row,
-                    });
-                    self.check_cursor_pos()?;
+        };
+        let line = match forward {
+            true => starts.iter().find(|start| **start > row).or(starts.first()),
+            false => starts
+                .iter()
+                .rev()
+                .find(|start| **start < row)
+                .or(starts.last()),
+        };
+        match line {
+            Some(line) => {
+                self.record_jump();
+                self.goto(*line, 0);
+            }
+            None => self.message = Some(String::from("No merge conflicts")),
+        }
+    }
+
+    /// Resolves the merge conflict at the cursor by replacing it with one or both of its sides,
+    /// leaving the cursor at the start of the kept lines.
+    fn resolve_conflict(&mut self, keep: Keep) {
+        if !self.is_editable() {
+            return;
+        }
+        let row = self.row;
+        let found = self.document().and_then(|d| {
+            let lines = d.get_lines(1..d.line_count() + 1);
+            let conflict = conflict::find(&lines)
+                .into_iter()
+                .find(|c| c.lines().contains(&row))?;
+            let covered = &lines[conflict.start as usize - 1..conflict.end as usize];
+            Some((conflict, conflict.resolve(covered, keep)))
+        });
+        let Some((conflict, kept)) = found else {
+            self.message = Some(String::from("The cursor isn't in a merge conflict"));
+            return;
+        };
+
+        match kept.is_empty() {
+            true => self.remove_lines(conflict.lines()),
+            false => {
+                if let Some(document) = self.document_mut() {
+                    document.replace_lines(conflict.lines(), &kept);
                 }
+                self.anchor = None;
+                self.goto(conflict.start, 0);
+                self.dirty.mark_all();
+            }
+        }
+        if self
+            .buffers
+            .get(self.current)
+            .is_some_and(|b| b.merge.is_some())
+        {
+            match self.conflicts().len() {
+                0 => {
+                    self.message = Some(String::from("All merge conflicts resolved"));
+                    if let Some(buffer) = self.buffers.get_mut(self.current) {
+                        buffer.merge = None;
+                    }
+                }
+                _ => self.jump_to_conflict(true),
             }
         }
-        Ok(())
     }
 
-    fn move_cursor_left(&mut self, offset: u16) -> std::io::Result<()> {
-        self.terminal.move_cursor_left(offset)?;
-        self.column = self.terminal.cursor_pos().x + 1;
-        Ok(())
+    /// Locks the current document's file so other instances of the editor know it's open,
+    /// releasing any lock the buffer already holds. If another instance already has the file
+    /// open, asks whether to make the document read-only. Read-only documents aren't locked.
+    fn lock_file(&mut self) {
+        let Some(dir) = self.lock_dir.clone() else {
+            return;
+        };
+        let Some(buffer) = self.buffers.get_mut(self.current) else {
+            return;
+        };
+        buffer.lock = None;
+        let Some(path) = buffer.document.path().map(PathBuf::from) else {
+            return;
+        };
+        if buffer.document.is_readonly() {
+            return;
+        }
+
+        match Lock::acquire(&dir, &path) {
+            Ok(Acquired::Locked(lock)) => buffer.lock = Some(lock),
+            Ok(Acquired::HeldBy(pid)) => {
+                let process = match pid {
+                    Some(pid) => format!(" (process {})", pid),
+                    None => String::new(),
+                };
+                let text = format!(
+                    "{} is open elsewhere{}. Make it read-only?",
+                    buffer.document.name(),
+                    process
+                );
+                self.ask(text, Command::ReadOnly(true));
+            }
+            Err(e) => error!("Unable to lock {}: {}", path.display(), e),
+        }
     }
 
-    fn move_cursor_right(&mut self, offset: u16) -> std::io::Result<()> {
-        let pos = self.terminal.cursor_pos();
+    /// Makes the current document read-only or editable, locking its file while it's editable.
+    fn set_readonly(&mut self, readonly: bool) {
+        let Some(buffer) = self.buffers.get_mut(self.current) else {
+            return;
+        };
+        buffer.document.set_readonly(readonly);
+        self.lock_file();
+        self.message = Some(String::from(match readonly {
+            true => "Document is read-only",
+            false => "Document can be edited",
+        }));
+        self.dirty.mark_all();
+    }
 
-        if (pos.x as usize) < (self.lines[pos.y as usize].len()) {
-            self.terminal.move_cursor_right(offset)?;
-            self.column = self.terminal.cursor_pos().x + 1;
+    /// Switches to the buffer at the given index, saving the cursor position of the current
+    /// buffer so it can be restored when switching back.
+    pub fn switch_buffer(&mut self, index: usize) {
+        if index >= self.buffers.len() {
+            return;
         }
-        Ok(())
+
+        if let Some(buffer) = self.buffers.get_mut(self.current) {
+            buffer.column = self.column;
+            buffer.row = self.row;
+            buffer.top = self.top;
+        }
+
+        let buffer = &self.buffers[index];
+        self.anchor = None;
+        self.column = buffer.column;
+        self.row = buffer.row;
+        self.top = buffer.top;
+        self.current = index;
     }
 
-    fn check_cursor_pos(&mut self) -> std::io::Result<()> {
-        let pos = self.terminal.cursor_pos();
+    fn next_buffer(&mut self) {
+        if !self.buffers.is_empty() {
+            self.record_jump();
+            self.switch_buffer((self.current + 1) % self.buffers.len());
+        }
+    }
 
-        let y_index = pos.y as usize;
-        if pos.x != self.column && self.column as usize <= (self.lines[y_index].len()) {
-            self.terminal.move_cursor_to(CursorPosition {
-                x: self.column,
-                y: pos.y,
-            });
+    fn previous_buffer(&mut self) {
+        if !self.buffers.is_empty() {
+            self.record_jump();
+            let count = self.buffers.len();
+            self.switch_buffer((self.current + count - 1) % count);
         }
-        if self.column as usize > (self.lines[y_index].len()) {
-            self.terminal.move_cursor_to(CursorPosition {
-                x: self.lines[pos.y as usize].len() as u16,
-                y: pos.y,
-            });
+    }
+
+    /// The current cursor position, as recorded in the jump list.
+    fn position(&self) -> Jump {
+        Jump {
+            buffer: self.current,
+            row: self.row,
+            column: self.column,
         }
-        Ok(())
     }
 
-    fn handle_new_line(&mut self) {
-        if self.terminal.cursor_pos().y < self.terminal.size().height - 2 {
-            self.terminal.move_cursor_to(CursorPosition {
-                x: 0,
-                y: self.terminal.cursor_pos().y + 1,
-            });
+    /// Records the cursor position in the jump list before making a significant jump.
+    fn record_jump(&mut self) {
+        self.jumps.push(self.position());
+    }
+
+    fn jump_back(&mut self) {
+        if let Some(jump) = self.jumps.back(self.position()) {
+            self.jump_to(jump);
         }
     }
 
-    fn render_status_line(&self) -> String {
-        // Cursor position
-        let (x, y) = cursor::position().expect("");
-        let pos = format!("{}, {}", x + 1, self.row);
+    fn jump_forward(&mut self) {
+        if let Some(jump) = self.jumps.forward() {
+            self.jump_to(jump);
+        }
+    }
 
-        let (width, _) = terminal::size().expect("");
-        let space_length = width as usize - self.status.len() - pos.len();
-        let spaces = std::iter::repeat(' ')
-            .take(space_length)
-            .collect::<String>();
+    /// Moves the cursor to a position from the jump list, switching buffers if needed.
+    fn jump_to(&mut self, jump: Jump) {
+        if jump.buffer != self.current {
+            self.switch_buffer(jump.buffer);
+        }
+        self.goto(jump.row, jump.column);
+    }
 
-        format!("{}{}{}", style(&self.status).bold().green(), spaces, pos)
+    /// Sets a global mark at the cursor. Marks can only be set in documents with a path.
+    fn set_mark(&mut self, name: char) {
+        let Some(path) = self.document().and_then(|d| d.path()).map(PathBuf::from) else {
+            self.message = Some(String::from("Marks can't be set in untitled documents"));
+            return;
+        };
+        let mark = Mark {
+            path,
+            row: self.row,
+            column: self.column,
+        };
+        self.mark_store.set(name, mark);
+        self.save_marks();
+        self.message = Some(format!("Mark {} set", name));
     }
 
-    pub fn render(&self) -> std::io::Result<()> {
-        let mut buffer = String::new();
-        let size = self.terminal.size();
+    fn delete_mark(&mut self, name: char) {
+        match self.mark_store.remove(name) {
+            true => {
+                self.save_marks();
+                self.message = Some(format!("Mark {} deleted", name));
+            }
+            false => self.message = Some(format!("Mark not set: {}", name)),
+        }
+    }
 
-        if let Some(document) = &self.document {
-            let (line_start, line_end) = self.get_document_window();
-            let lines = document.get_lines(Range {
-                start: line_start,
-                end: line_end,
-            });
+    fn save_marks(&mut self) {
+        if let Err(e) = self.mark_store.save() {
+            error!("Unable to save marks: {}", e);
+        }
+    }
 
-            for row in 0..size.height {
-                if row == size.height - 1 {
-                    buffer += self.render_status_line().as_str();
-                } else {
-                    /*let line = lines[row as usize].as_str();
-                    info!(
-                        "Unicode Width: {}, Normal Width: {}",
-                        UnicodeWidthStr::width_cjk(line),
-                        line.len()
-                    );*/
-                    if (row as usize) < lines.len() {
-                        if self.lines[row as usize].len() > size.width as usize {
-                            buffer += &lines[row as usize][0..size.width as usize];
-                        } else {
-                            buffer += &lines[row as usize];
-                        }
-                    }
-                    buffer += "\r\n";
-                }
+    /// Opens the file a global mark was set in, if it isn't the current document, and moves the
+    /// cursor to the mark.
+    fn jump_to_mark(&mut self, name: char) {
+        let Some(mark) = self.mark_store.get(name).cloned() else {
+            self.message = Some(format!("Mark not set: {}", name));
+            return;
+        };
+        let current = self.document().and_then(|d| d.path()) == Some(mark.path.as_path());
+        if !current && !mark.path.exists() {
+            self.message = Some(format!("File not found: {}", mark.path.display()));
+            return;
+        }
+
+        self.record_jump();
+        if current || self.edit(mark.path) {
+            self.goto(mark.row, mark.column);
+        }
+    }
+
+    /// Opens a picker listing the global marks with where they were set.
+    fn open_marks(&mut self) {
+        let (names, items): (Vec<char>, Vec<String>) = self
+            .mark_store
+            .iter()
+            .map(|(name, mark)| {
+                let item = format!("{}  {}:{}", name, mark.path.display(), mark.row);
+                (name, item)
+            })
+            .unzip();
+        if items.is_empty() {
+            self.message = Some(String::from("No marks set"));
+            return;
+        }
+        self.picker = Some((Picker::new("Marks", items), PickerAction::Mark(names)));
+    }
+
+    pub fn set_profile_events(&mut self, enabled: bool) {
+        self.profile_events = enabled;
+    }
+
+    pub fn set_log_file(&mut self, path: Option<PathBuf>) {
+        self.log_file = path;
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The settings for the files in the project a document is in, applying the project's
+    /// settings file over the global settings. A settings file which can't be parsed is reported
+    /// and ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - The document's path, or None if it's untitled.
+    fn project_config(&mut self, path: Option<&Path>) -> Config {
+        let root = path.and_then(|path| project::root(path, &self.config.root_markers));
+        match root
+            .as_deref()
+            .map(|root| (root, ProjectConfig::load(root)))
+        {
+            Some((root, Some(Ok(project)))) => self.config.with_project(root, &project),
+            Some((_, Some(Err(e)))) => {
+                self.message = Some(e.to_string());
+                self.config.clone()
             }
+            _ => self.config.clone(),
+        }
+    }
+
+    /// The settings for the current document's filetype.
+    fn settings(&self) -> Settings {
+        self.buffers
+            .get(self.current)
+            .map_or_else(|| self.config.settings(None), |b| b.settings.clone())
+    }
+
+    /// Whether the current document's escape sequences are displayed as colours.
+    fn is_ansi(&self) -> bool {
+        self.buffers.get(self.current).is_some_and(|b| b.ansi)
+    }
+
+    /// Switches between displaying the current document's escape sequences as colours and as
+    /// raw text which can be edited.
+    fn toggle_ansi(&mut self) {
+        if let Some(buffer) = self.buffers.get_mut(self.current) {
+            buffer.ansi = !buffer.ansi;
+            self.dirty.mark_all();
+        }
+    }
+
+    /// The number of cells taken up by the gutter, which is only shown while the current
+    /// document has signs.
+    fn gutter_width(&self) -> usize {
+        match self.buffers.get(self.current) {
+            Some(buffer) if !buffer.document.signs().is_empty() && self.zen.is_none() => {
+                signs::GUTTER_WIDTH
+            }
+            _ => 0,
+        }
+    }
+
+    /// The number of cells taken up by the minimap, if it's shown.
+    fn minimap_width(&self) -> usize {
+        match self.minimap && self.document().is_some() {
+            true => minimap::WIDTH,
+            false => 0,
+        }
+    }
+
+    /// The number of cells taken up by the scrollbar, if it's shown.
+    fn scrollbar_width(&self) -> usize {
+        match self.scrollbar && self.document().is_some() {
+            true => scrollbar::WIDTH,
+            false => 0,
+        }
+    }
+
+    /// The number of cells available to display the document's text.
+    fn text_width(&self) -> usize {
+        let margins = self.gutter_width() + self.minimap_width() + self.scrollbar_width();
+        let width = (self.terminal.size().width as usize).saturating_sub(margins);
+        match self.zen {
+            Some(_) => width.min(self.config.zen_width),
+            None => width,
+        }
+    }
+
+    /// The number of cells before the document's text: the gutter, and in zen mode the space
+    /// centering the text.
+    fn text_offset(&self) -> usize {
+        let centering = match self.zen {
+            Some(_) => (self.terminal.size().width as usize).saturating_sub(self.text_width()) / 2,
+            None => 0,
+        };
+        centering + self.gutter_width()
+    }
+
+    /// Enters zen mode, hiding the gutter, minimap and scrollbar and centering the text, or
+    /// leaves it, showing the panels which were shown before.
+    fn toggle_zen(&mut self) {
+        match self.zen.take() {
+            Some(panels) => {
+                self.minimap = panels.minimap;
+                self.scrollbar = panels.scrollbar;
+            }
+            None => {
+                self.zen = Some(Panels {
+                    minimap: std::mem::take(&mut self.minimap),
+                    scrollbar: std::mem::take(&mut self.scrollbar),
+                });
+            }
+        }
+        self.dirty.mark_all();
+    }
+
+    /// Handles a click on the screen. Clicking the minimap or scrollbar jumps to the lines shown
+    /// by the row clicked on, centering them in the viewport.
+    fn click(&mut self, column: u16, row: u16) {
+        let (column, height) = (column as usize, self.text_height());
+        let Some(line_count) = self.document().map(|d| d.line_count()) else {
+            return;
+        };
+        if row as u32 >= height {
+            return;
         }
 
-        self.terminal.render(buffer)
+        let minimap_column = self.text_offset() + self.text_width();
+        let scrollbar_column = minimap_column + self.minimap_width();
+        let line = if self.scrollbar_width() > 0 && column >= scrollbar_column {
+            scrollbar::lines_at(row as usize, line_count, height as usize).start
+        } else if self.minimap_width() > 0 && column >= minimap_column {
+            minimap::line_at(row as usize, line_count, height as usize)
+        } else {
+            return;
+        };
+        self.record_jump();
+        self.goto(line, 0);
+        self.top = line.saturating_sub(height / 2).clamp(1, self.max_top());
+        self.dirty.mark_all();
+    }
+
+    /// Draws the scrollbar rows for the current document, or nothing if it's hidden. Error
+    /// locations from the last build and lines with signs are marked along the track.
+    fn render_scrollbar(&self, document: &Document, height: u32) -> Vec<String> {
+        if self.scrollbar_width() == 0 {
+            return vec![];
+        }
+        let errors = document
+            .path()
+            .map_or(vec![], |p| self.quickfix.lines_in(p));
+        let mut marks: Vec<(u32, console::Color)> = errors
+            .into_iter()
+            .map(|line| (line, console::Color::Red))
+            .collect();
+        if let Some(buffer) = self.buffers.get(self.current) {
+            marks.extend(
+                buffer
+                    .document
+                    .signs()
+                    .iter()
+                    .map(|(line, sign)| (line, sign.color)),
+            );
+        }
+        let viewport = self.top..self.top + height;
+        scrollbar::render(height as usize, document.line_count(), viewport, &marks)
+    }
+
+    /// Draws the minimap and scrollbar for a row of the screen, after the row's text.
+    fn render_right_columns(&self, row: u32, minimap: &[String], scrollbar: &[String]) -> String {
+        let mut rendered = String::new();
+        let column = self.text_offset() + self.text_width();
+        if let Some(minimap) = minimap.get(row as usize) {
+            rendered += &layout::move_to_column(column);
+            rendered += minimap;
+        }
+        if let Some(scrollbar) = scrollbar.get(row as usize) {
+            rendered += &layout::move_to_column(column + self.minimap_width());
+            rendered += scrollbar;
+        }
+        rendered
+    }
+
+    /// Draws the minimap for the current document again if it's been edited or scrolled since it
+    /// was last drawn. Returns whether the minimap looks different, so it needs to be redrawn.
+    fn update_minimap(&mut self) -> bool {
+        if self.minimap_width() == 0 {
+            return false;
+        }
+        let (top, height) = (self.top, self.text_height());
+        let tab_width = self.settings().tab_width;
+        let Some(buffer) = self.buffers.get_mut(self.current) else {
+            return false;
+        };
+        let key = minimap::Key {
+            edits: buffer.document.edits(),
+            top,
+            height,
+            tab_width,
+        };
+        if buffer
+            .minimap
+            .as_ref()
+            .is_some_and(|(drawn, _)| *drawn == key)
+        {
+            return false;
+        }
+        let document = &buffer.document;
+        let lines = document.get_lines(1..document.line_count() + 1);
+        let rows = minimap::render(&lines, height as usize, top..top + height, tab_width);
+        let changed = buffer
+            .minimap
+            .as_ref()
+            .is_none_or(|(_, drawn)| *drawn != rows);
+        buffer.minimap = Some((key, rows));
+        changed
+    }
+
+    /// The minimap rows for the current document, or nothing if it's hidden.
+    fn minimap_rows(&self) -> &[String] {
+        match self
+            .buffers
+            .get(self.current)
+            .and_then(|b| b.minimap.as_ref())
+        {
+            Some((_, rows)) if self.minimap_width() > 0 => rows,
+            _ => &[],
+        }
+    }
+
+    /// Places a sign on the current line, or every selected line, removing it instead if every
+    /// line already has one.
+    ///
+    /// # Arguments
+    ///
+    /// * 'sign' - The sign to place.
+    fn toggle_sign(&mut self, sign: Sign) {
+        let lines = self.given_lines().or(Some(self.row..=self.row));
+        let (Some(lines), Some(buffer)) = (lines, self.buffers.get_mut(self.current)) else {
+            return;
+        };
+
+        let count = lines.clone().count();
+        let signed = lines
+            .clone()
+            .all(|line| buffer.document.signs().contains(line, sign.group));
+        for line in lines {
+            if signed {
+                buffer.document.signs_mut().remove(line, sign.group);
+            } else {
+                buffer.document.signs_mut().place(line, sign);
+            }
+        }
+        let action = if signed { "Removed" } else { "Placed" };
+        self.message = Some(format!("{} sign on {}L", action, count));
+        self.dirty.mark_all();
+    }
+
+    /// Moves the cursor to the next or previous line with a sign, wrapping around the document.
+    fn jump_to_sign(&mut self, forward: bool) {
+        let line = self.buffers.get(self.current).and_then(|b| match forward {
+            true => b.document.signs().next(self.row),
+            false => b.document.signs().previous(self.row),
+        });
+        match line {
+            Some(line) => {
+                self.record_jump();
+                self.goto(line, 0);
+            }
+            None => self.message = Some(String::from("No signs")),
+        }
+    }
+
+    fn document(&self) -> Option<&Document> {
+        self.buffers
+            .get(self.current)
+            .map(|buffer| &buffer.document)
+    }
+
+    fn document_mut(&mut self) -> Option<&mut Document> {
+        self.buffers
+            .get_mut(self.current)
+            .map(|buffer| &mut buffer.document)
+    }
+
+    /// Moves the cursor to the given line and column, clamping both to the bounds of the
+    /// document. If the line isn't currently visible, the viewport is centered on it.
+    ///
+    /// # Arguments
+    ///
+    /// * 'line' - The line to move to. Line numbers start from 1.
+    /// * 'column' - The column to move to. Columns start from 0.
+    pub fn goto(&mut self, line: u32, column: u32) {
+        if let Some(Err(e)) = self.document_mut().map(|d| d.load_until(line)) {
+            self.message = Some(e.to_string());
+        }
+
+        let Some(document) = self.document() else {
+            return;
+        };
+        let row = line.clamp(1, document.line_count().max(1));
+        let column = column.min(document.line_len(row));
+
+        self.row = row;
+        self.column = column;
+        self.anchor = None;
+
+        let height = self.text_height();
+        if self.row < self.top || self.row >= self.top + height {
+            self.top = self.row.saturating_sub(height / 2).max(1);
+        }
+    }
+
+    /// Moves the cursor to the next occurrence of some text after it, or the previous one before
+    /// it, wrapping around the end of the document unless wrapping is turned off. The match's
+    /// place among the matches is shown, along with whether the search wrapped. The text is
+    /// remembered to be found again.
+    ///
+    /// # Arguments
+    ///
+    /// * 'pattern' - The text to find.
+    /// * 'forward' - Whether to search towards the end of the document.
+    fn find(&mut self, pattern: String, forward: bool) {
+        let from = Position {
+            row: self.row,
+            column: self.column,
+        };
+        let wrap = self.config.wrap_search;
+        let Some(document) = self.document_mut() else {
+            return;
+        };
+        match document.find(&pattern, from, forward, wrap) {
+            Ok(Some(found)) => {
+                let count = document.count(&pattern, found.position);
+                self.record_jump();
+                self.goto(found.position.row, found.position.column);
+                let mut message = count.map_or(String::new(), |count| count.describe());
+                if found.wrapped {
+                    message += match forward {
+                        true => ", wrapped to the top",
+                        false => ", wrapped to the bottom",
+                    };
+                }
+                self.message = Some(message);
+            }
+            Ok(None) if wrap => self.message = Some(format!("Pattern not found: {}", pattern)),
+            Ok(None) => {
+                self.message = Some(format!(
+                    "Search hit the {} without finding: {}",
+                    if forward { "bottom" } else { "top" },
+                    pattern
+                ))
+            }
+            Err(e) => self.report(e),
+        }
+        self.search = Some(pattern);
+    }
+
+    /// Replaces matches of a pattern on the current line, or the lines given by a range or the
+    /// selection, as a single edit. A substitution with the confirm flag asks before replacing
+    /// each match instead.
+    fn substitute(&mut self, substitution: Substitution) {
+        if !self.is_editable() || self.document().is_some_and(|d| d.is_binary()) {
+            return;
+        }
+        let lines = self.given_lines().unwrap_or(self.row..=self.row);
+        if substitution.confirm {
+            self.anchor = None;
+            self.substitution = Some(Confirmation::new(substitution, lines));
+            self.next_substitution();
+            return;
+        }
+
+        let Some(content) = self
+            .document()
+            .map(|d| d.get_lines(*lines.start()..*lines.end() + 1))
+        else {
+            return;
+        };
+        let mut count = 0;
+        let replaced: Vec<String> = content
+            .into_iter()
+            .flat_map(|line| match substitution.replace(&line) {
+                Some((replaced, columns)) => {
+                    count += columns.len();
+                    replaced.split('\n').map(String::from).collect()
+                }
+                None => vec![line],
+            })
+            .collect();
+        self.report_substitutions(count, substitution.pattern());
+        if count > 0 {
+            self.transform_range(lines, |content| *content = replaced);
+        }
+    }
+
+    /// Moves the cursor to the next match of the substitution being confirmed and asks whether
+    /// to replace it, or finishes the substitution if there are no more matches.
+    fn next_substitution(&mut self) {
+        let (Some(confirmation), Some(buffer)) =
+            (&mut self.substitution, self.buffers.get(self.current))
+        else {
+            return;
+        };
+        let document = &buffer.document;
+        let found =
+            confirmation.next(|row| document.get_lines(row..row + 1).pop().unwrap_or_default());
+        match found {
+            Some((row, range)) => {
+                let line = document.get_lines(row..row + 1).pop().unwrap_or_default();
+                let column = line[..range.start].chars().count() as u32;
+                self.goto(row, column);
+                self.message = Some(String::from(CONFIRM_SUBSTITUTION));
+            }
+            None => self.finish_substitution(),
+        }
+        self.dirty.mark_all();
+    }
+
+    /// Answers whether to replace the match being confirmed. Yes replaces it and no leaves it,
+    /// moving on to the next match, all replaces it and every match after it, last replaces it
+    /// and stops, and quit stops without replacing it.
+    ///
+    /// # Arguments
+    ///
+    /// * 'answer' - The key pressed, or None if a key other than a character was pressed, which
+    ///   stops substituting.
+    fn confirm_substitution(&mut self, answer: Option<char>) {
+        match answer {
+            Some('y') => {
+                self.replace_match();
+                self.next_substitution();
+            }
+            Some('n') => {
+                let line = self.current_match_line();
+                if let Some(confirmation) = &mut self.substitution {
+                    confirmation.skip(&line);
+                }
+                self.next_substitution();
+            }
+            Some('a') => {
+                while self
+                    .substitution
+                    .as_ref()
+                    .is_some_and(|c| c.current().is_some())
+                {
+                    self.replace_match();
+                    self.next_substitution();
+                }
+            }
+            Some('l') => {
+                self.replace_match();
+                self.finish_substitution();
+            }
+            Some('q') | None => self.finish_substitution(),
+            Some(_) => self.message = Some(String::from(CONFIRM_SUBSTITUTION)),
+        }
+    }
+
+    /// The text of the line with the match being confirmed.
+    fn current_match_line(&self) -> String {
+        let row = self
+            .substitution
+            .as_ref()
+            .and_then(|c| c.current())
+            .map(|(row, _)| row);
+        match (row, self.document()) {
+            (Some(row), Some(document)) => {
+                document.get_lines(row..row + 1).pop().unwrap_or_default()
+            }
+            _ => String::new(),
+        }
+    }
+
+    /// Replaces the match being confirmed.
+    fn replace_match(&mut self) {
+        let line = self.current_match_line();
+        let Some((row, replaced)) = self.substitution.as_mut().and_then(|c| c.accept(&line)) else {
+            return;
+        };
+        if let Some(document) = self.document_mut() {
+            document.replace_lines(row..=row, &replaced);
+        }
+    }
+
+    fn finish_substitution(&mut self) {
+        if let Some(confirmation) = self.substitution.take() {
+            let pattern = confirmation.substitution.pattern().to_string();
+            self.report_substitutions(confirmation.count, &pattern);
+        }
+        self.clamp_cursor_column();
+        self.dirty.mark_all();
+    }
+
+    fn report_substitutions(&mut self, count: usize, pattern: &str) {
+        self.message = Some(match count {
+            0 => format!("Pattern not found: {}", pattern),
+            1 => String::from("1 substitution"),
+            _ => format!("{} substitutions", count),
+        });
+    }
+
+    /// Previews the replacements the substitution being entered at the prompt would make, if
+    /// the prompt holds one, redrawing the lines when the preview changes.
+    fn update_preview(&mut self) {
+        let Some(input) = self
+            .prompt
+            .as_ref()
+            .filter(|p| p.kind == Kind::Command)
+            .map(|p| p.input().to_string())
+        else {
+            if self.preview.take().is_some() {
+                self.dirty.mark_all();
+            }
+            return;
+        };
+        if self.preview.as_ref().is_some_and(|p| p.input == input) {
+            return;
+        }
+
+        let (lines, substitution) = match Command::parse(&input) {
+            Ok(Command::Substitute(substitution)) => (None, substitution),
+            Ok(Command::Ranged {
+                lines,
+                command: Some(command),
+            }) => match *command {
+                Command::Substitute(substitution) => (Some(lines), substitution),
+                _ => return self.clear_preview(),
+            },
+            _ => return self.clear_preview(),
+        };
+        let lines = match lines {
+            // The whole document isn't loaded for a preview, which only shows the visible lines.
+            Some(lines) => {
+                let last = self.document().map_or(1, |d| d.line_count().max(1));
+                lines.resolve(self.row, last, |name| self.mark_line(name))
+            }
+            None => Ok(self.selection_lines().unwrap_or(self.row..=self.row)),
+        };
+        let Ok(lines) = lines else {
+            return self.clear_preview();
+        };
+        self.preview = Some(SubstitutePreview {
+            input,
+            lines,
+            substitution,
+        });
+        self.dirty.mark_all();
+    }
+
+    fn clear_preview(&mut self) {
+        if self.preview.take().is_some() {
+            self.dirty.mark_all();
+        }
+    }
+
+    /// Opens a prompt on the status line, noting where the cursor is for prompts which move it
+    /// while they're typed.
+    fn open_prompt(&mut self, prompt: Prompt) {
+        if let Some(history) = self.prompt_history(prompt.kind) {
+            history.reset();
+        }
+        self.prompt_preview =
+            matches!(prompt.kind, Kind::Goto | Kind::Search).then(|| PromptPreview {
+                origin: self.cursor(),
+                top: self.top,
+                input: String::new(),
+            });
+        self.prompt = Some(prompt);
+    }
+
+    /// The history of the text entered at a kind of prompt, if it keeps one.
+    fn prompt_history(&mut self, kind: Kind) -> Option<&mut History> {
+        match kind {
+            Kind::Command => Some(&mut self.command_history),
+            Kind::Search => Some(&mut self.search_history),
+            Kind::Goto | Kind::SaveAs { .. } => None,
+        }
+    }
+
+    /// Replaces the prompt's input with an earlier or later entry from its history.
+    fn recall_history(&mut self, previous: bool) {
+        let Some(prompt) = &self.prompt else {
+            return;
+        };
+        let (kind, input) = (prompt.kind, prompt.input().to_string());
+        let entry = self
+            .prompt_history(kind)
+            .and_then(|history| match previous {
+                true => history.previous(&input),
+                false => history.next(),
+            })
+            .map(String::from);
+        if let (Some(entry), Some(prompt)) = (entry, &mut self.prompt) {
+            prompt.set_input(&entry);
+        }
+    }
+
+    /// Moves the cursor to the line or match being entered at the prompt, or back to where it
+    /// was while the input doesn't give one.
+    fn preview_prompt(&mut self) {
+        let (Some(prompt), Some(preview)) = (&self.prompt, &mut self.prompt_preview) else {
+            return;
+        };
+        if preview.input == prompt.input() {
+            return;
+        }
+        preview.input = prompt.input().to_string();
+        let (kind, input, valid) = (prompt.kind, preview.input.clone(), prompt.validate());
+        let origin = preview.origin;
+        self.return_to_origin();
+
+        let wrap = self.config.wrap_search;
+        let target = match kind {
+            _ if input.is_empty() || valid.is_err() => None,
+            Kind::Goto => input.parse().ok().map(|row| Position { row, column: 0 }),
+            Kind::Search => self
+                .document_mut()
+                .and_then(|d| d.find(&input, origin, true, wrap).ok().flatten())
+                .map(|found| found.position),
+            Kind::Command | Kind::SaveAs { .. } => None,
+        };
+        if let Some(target) = target {
+            self.goto(target.row, target.column);
+        }
+        self.dirty.mark_all();
+    }
+
+    /// Moves the cursor back to where it was when the prompt was opened.
+    fn return_to_origin(&mut self) {
+        if let Some(preview) = &self.prompt_preview {
+            let (origin, top) = (preview.origin, preview.top);
+            self.goto(origin.row, origin.column);
+            self.top = top;
+        }
+    }
+
+    /// Closes the prompt without running it, moving the cursor back if it was moved while
+    /// typing.
+    fn cancel_prompt(&mut self) {
+        self.prompt = None;
+        self.return_to_origin();
+        if self.prompt_preview.take().is_some() {
+            self.dirty.mark_all();
+        }
+    }
+
+    /// Closes, opens or toggles the fold at the cursor. Closing a fold moves the cursor to its
+    /// first line. The document's folds are saved so they're restored when it's opened again.
+    fn fold(&mut self, action: FoldAction) {
+        let (row, method) = (self.row, self.settings().fold_method);
+        let Some(document) = self.document_mut() else {
+            return;
+        };
+        let opened = match action {
+            FoldAction::Close => false,
+            FoldAction::Open | FoldAction::Toggle => document.folds_mut().open(row),
+            FoldAction::OpenAll => {
+                document.folds_mut().open_all();
+                true
+            }
+        };
+        let closed = match opened || action == FoldAction::Open {
+            true => None,
+            false => document.fold_range(row, method).inspect(|range| {
+                document.folds_mut().close(range.clone());
+            }),
+        };
+        match (opened, closed) {
+            (true, _) => {}
+            (false, Some(range)) => {
+                self.row = *range.start();
+                self.clamp_cursor_column();
+            }
+            (false, None) if action == FoldAction::Open => {
+                self.message = Some(String::from("No closed fold here"));
+            }
+            (false, None) => self.message = Some(String::from("No fold here")),
+        }
+        self.save_folds();
+        self.dirty.mark_all();
+    }
+
+    /// Records the current document's closed folds in the fold store and saves it.
+    fn save_folds(&mut self) {
+        let Some(buffer) = self.buffers.get(self.current) else {
+            return;
+        };
+        let Some(path) = buffer.document.path() else {
+            return;
+        };
+        self.fold_store.set(path, buffer.document.folds());
+        if let Err(e) = self.fold_store.save() {
+            error!("Unable to save folds: {}", e);
+        }
+    }
+
+    /// Keeps the cursor out of the lines hidden by closed folds. Moving up and down lands on a
+    /// fold's first line, while anything else which moves the cursor into a fold, such as a
+    /// search or a jump, opens it.
+    ///
+    /// # Arguments
+    ///
+    /// * 'vertical' - Whether the cursor was moved up or down.
+    fn reveal_cursor(&mut self, vertical: bool) {
+        let row = self.row;
+        let Some(document) = self.document_mut() else {
+            return;
+        };
+        let visible = document.folds().visible_line(row);
+        if visible == row {
+            return;
+        }
+        match vertical {
+            true => {
+                self.row = visible;
+                self.clamp_cursor_column();
+            }
+            false => {
+                document.folds_mut().open_around(row);
+                self.dirty.mark_all();
+            }
+        }
+    }
+
+    /// Plays the macro in a register. With a selection, the macro is played once on each selected
+    /// line, starting from the start of the line. The lines are visited from the bottom up, so
+    /// lines the macro adds or removes don't move the lines still to be visited.
+    fn play_macro(&mut self, register: char) {
+        let Some(text) = self.registers.get(register).map(|r| r.text.clone()) else {
+            self.message = Some(format!("Register @{} is empty", register));
+            return;
+        };
+        let keys = match macros::parse(&text) {
+            Ok(keys) => keys,
+            Err(e) => {
+                self.message = Some(e);
+                return;
+            }
+        };
+        if self.macros.depth >= macros::MAX_DEPTH {
+            self.message = Some(String::from("Macros are nested too deeply"));
+            return;
+        }
+
+        match self.given_lines() {
+            Some(lines) => {
+                self.anchor = None;
+                for line in lines.rev() {
+                    self.goto(line, 0);
+                    if !self.replay(&keys) {
+                        break;
+                    }
+                }
+            }
+            None => {
+                self.replay(&keys);
+            }
+        }
+        self.dirty.mark_all();
+    }
+
+    /// Handles each key in turn as if it had been typed, returning whether they were all handled
+    /// without an error. Keys played back aren't recorded into the macro being recorded.
+    fn replay(&mut self, keys: &[KeyEvent]) -> bool {
+        self.macros.depth += 1;
+        let result = keys
+            .iter()
+            .try_for_each(|key| self.handle_event(Event::Input(TerminalEvent::Key(*key))));
+        self.macros.depth -= 1;
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                self.report(e);
+                false
+            }
+        }
+    }
+
+    /// Opens the macro in a register as text in a new buffer. Saving the buffer stores the text
+    /// back into the register.
+    fn edit_macro(&mut self, register: char) {
+        let text = self.registers.get(register).map(|r| r.text.clone());
+        let document =
+            Document::scratch(&format!("[Macro @{}]", register), &text.unwrap_or_default());
+        self.open(document);
+        if let Some(buffer) = self.buffers.last_mut() {
+            buffer.register = Some(register);
+        }
+        self.message = Some(String::from("Save to store the macro"));
+    }
+
+    /// Stores the current document's text as the macro in its register.
+    fn store_macro(&mut self, register: char) {
+        let Some(document) = self.document_mut() else {
+            return;
+        };
+        match macros::parse(&document.text()) {
+            Ok(keys) => {
+                document.mark_saved();
+                self.message = Some(format!("Stored {} keys into @{}", keys.len(), register));
+                let text = macros::to_text(&keys);
+                self.registers.set(register, Register::new(text, false));
+            }
+            Err(e) => self.message = Some(e),
+        }
+    }
+
+    /// Finds the text last searched for again.
+    fn find_again(&mut self, forward: bool) {
+        match self.search.clone() {
+            Some(pattern) => self.find(pattern, forward),
+            None => self.message = Some(String::from("No previous search")),
+        }
+    }
+
+    pub(crate) fn handle_event(&mut self, event: Event) -> error::Result<()> {
+        match event {
+            Event::Input(TerminalEvent::FocusGained) => {
+                self.focus_gained();
+                Ok(())
+            }
+            Event::Input(TerminalEvent::FocusLost) => {
+                self.focus_lost();
+                Ok(())
+            }
+            // The key which dismisses a message drawn over several rows does nothing else, so it
+            // can't act on text the user couldn't see.
+            Event::Input(TerminalEvent::Key(_))
+                if self.message_expanded && self.message.is_some() =>
+            {
+                self.message = None;
+                self.message_expanded = false;
+                self.dirty.mark_all();
+                self.should_render = true;
+                Ok(())
+            }
+            Event::Input(input) => {
+                if let TerminalEvent::Key(key) = input {
+                    let in_document = self.prompt.is_none()
+                        && self.picker.is_none()
+                        && self.chord.is_none()
+                        && self.literal.is_none()
+                        && self.substitution.is_none();
+                    self.macros.record(key, in_document);
+                }
+                match self.map_input(input) {
+                    // Moving the mouse only shows and hides tooltips, so it leaves the message
+                    // and everything else alone.
+                    Some(Event::MouseMove(column, row)) => {
+                        self.mouse_moved(column, row);
+                        Ok(())
+                    }
+                    Some(event) => self.process_event(event),
+                    None => Ok(()),
+                }
+            }
+            Event::Remote(id, message) => {
+                self.receive(id, message);
+                Ok(())
+            }
+            Event::ParticipantJoined(id) => {
+                self.participant_joined(id);
+                Ok(())
+            }
+            Event::ParticipantLeft(id) => {
+                self.participant_left(id);
+                Ok(())
+            }
+            Event::Status(subsystem, text) => {
+                self.segments.set(subsystem, text);
+                self.should_render = true;
+                Ok(())
+            }
+            Event::Followed { text, truncated } => {
+                self.followed(&text, truncated);
+                Ok(())
+            }
+            event => self.process_event(event),
+        }
+    }
+
+    fn map_input(&self, input: TerminalEvent) -> Option<Event> {
+        if let (TerminalEvent::Key(e), Some(layer)) = (&input, self.layers().last()) {
+            match layer {
+                Layer::Picker => return self.keymaps.map_picker_key_press_to_event(*e),
+                Layer::Question => return self.keymaps.map_confirm_key_press_to_event(*e),
+                Layer::Info => return self.keymaps.map_info_key_press_to_event(*e),
+                Layer::ChordHint => return self.keymaps.map_chord_key_press_to_event(*e),
+                // Tooltips are the bottom layer and don't take keys, which are mapped as usual.
+                Layer::Tooltip => {}
+            }
+        }
+        match input {
+            TerminalEvent::FocusGained => None,
+            TerminalEvent::FocusLost => None,
+            TerminalEvent::Key(e) if self.literal.is_some() => {
+                self.keymaps.map_literal_key_press_to_event(e)
+            }
+            TerminalEvent::Key(e) if self.substitution.is_some() => {
+                self.keymaps.map_confirm_key_press_to_event(e)
+            }
+            TerminalEvent::Key(e) if self.chord.is_some() => {
+                self.keymaps.map_chord_key_press_to_event(e)
+            }
+            TerminalEvent::Key(e) if self.prompt.is_some() => {
+                self.keymaps.map_prompt_key_press_to_event(e)
+            }
+            TerminalEvent::Key(e) => self.keymaps.map_key_press_to_event(e),
+            TerminalEvent::Mouse(e) => self.keymaps.map_mouse_to_event(e),
+            TerminalEvent::Paste(text) => Some(Event::Paste(text)),
+            TerminalEvent::Resize(_, _) => None,
+        }
+    }
+
+    fn process_event(&mut self, event: Event) -> error::Result<()> {
+        let view = self.view();
+        self.should_render = true;
+        self.message = None;
+        if self.hover.take().is_some_and(|hover| hover.is_shown()) {
+            self.dirty.mark_all();
+        }
+        let typing = matches!(event, Event::KeyPress(_));
+        if !typing {
+            self.low_nibble = false;
+            self.edits.finish();
+            // Typing is undone a run at a time, so only other events start a new revision.
+            self.document_mut().iter_mut().for_each(|d| d.commit_undo());
+        }
+        let vertical = matches!(
+            event,
+            Event::MoveCursorUp(_)
+                | Event::MoveCursorDown(_)
+                | Event::SelectUp(_)
+                | Event::SelectDown(_)
+                | Event::ScrollUp(_)
+                | Event::ScrollDown(_)
+        );
+        if !vertical {
+            self.goal_column = None;
+        }
+        if !matches!(event, Event::Recenter) {
+            self.recenter = None;
+        }
+        if !matches!(event, Event::KillLine) {
+            self.killing = false;
+        }
+        if !matches!(event, Event::PromptComplete) {
+            self.completion = None;
+        }
+        match event {
+            Event::SelectUp(_)
+            | Event::SelectDown(_)
+            | Event::SelectLeft(_)
+            | Event::SelectRight(_) => {
+                self.anchor.get_or_insert(self.cursor());
+            }
+            Event::MoveCursorUp(_)
+            | Event::MoveCursorDown(_)
+            | Event::MoveCursorLeft(_)
+            | Event::MoveCursorRight(_)
+            | Event::MoveCursorHome
+            | Event::NextParagraph
+            | Event::PreviousParagraph
+            | Event::NextSentence
+            | Event::NextWord
+            | Event::PreviousWord
+            | Event::PreviousSentence
+            | Event::Cancel
+            | Event::MouseClick(..) => self.anchor = None,
+            _ => {}
+        }
+        // Scrolling with the mouse leaves the cursor behind, so anything else brings it back
+        // into view first.
+        if !matches!(event, Event::MouseScrollDown | Event::MouseScrollUp) && !self.cursor_visible()
+        {
+            self.scroll_to_cursor();
+        }
+        match event {
+            // Terminal input is mapped to other events before being processed.
+            Event::Input(_)
+            | Event::Remote(..)
+            | Event::ParticipantJoined(_)
+            | Event::ParticipantLeft(_)
+            | Event::Status(..)
+            | Event::Followed { .. }
+            | Event::MouseMove(..) => {}
+            Event::KeyPress(c) => self.handle_key_press(c),
+            Event::RepeatEdit => self.repeat_edit(),
+            Event::Exit => self.exit(),
+            Event::MoveCursorUp(o) => self.move_cursor_up(o),
+            Event::MoveCursorDown(o) => self.move_cursor_down(o),
+            Event::MoveCursorLeft(o) => self.move_cursor_left(o),
+            Event::MoveCursorRight(o) => self.move_cursor_right(o),
+            Event::MoveCursorHome => self.move_cursor_home(),
+            Event::NextParagraph => self.next_paragraph(),
+            Event::PreviousParagraph => self.previous_paragraph(),
+            Event::NextSentence => self.next_sentence(),
+            Event::NextWord => self.next_word(),
+            Event::PreviousWord => self.previous_word(),
+            Event::DeleteWordBack => self.delete_word(false),
+            Event::DeleteWordForward => self.delete_word(true),
+            Event::PreviousSentence => self.previous_sentence(),
+            Event::SelectUp(o) => self.move_cursor_up(o),
+            Event::SelectDown(o) => self.move_cursor_down(o),
+            Event::SelectLeft(o) => self.move_cursor_left(o),
+            Event::SelectRight(o) => self.move_cursor_right(o),
+            Event::SelectAll => self.select_all(),
+            Event::SelectLine => self.select_line(),
+            Event::ExpandSelection => self.expand_selection(),
+            Event::ChangeCase(case) => self.change_case(case),
+            Event::Reflow => self.reflow(),
+            Event::FindNext => self.find_again(true),
+            Event::FindPrevious => self.find_again(false),
+            Event::NextError => self.next_error(),
+            Event::PreviousError => self.previous_error(),
+            Event::ScrollDown(o) => self.scroll_down(o),
+            Event::ScrollUp(o) => self.scroll_up(o),
+            Event::Recenter => self.recenter(),
+            Event::MouseScrollDown => self.scroll_view(self.config.scroll_step as i64)?,
+            Event::MouseScrollUp => self.scroll_view(-(self.config.scroll_step as i64))?,
+            Event::MouseClick(column, row) => self.click(column, row),
+            Event::NewLine if self.paste_mode => self.type_literal('\n'),
+            Event::NewLine => self.handle_new_line(),
+            Event::KillLine => self.kill_line(),
+            Event::DeleteLine => self.delete_line(),
+            Event::Yank => {
+                if let Some(text) = self.kills.latest() {
+                    self.paste(text.to_string());
+                }
+            }
+            Event::Paste(text) => {
+                let into_prompt = self.prompt.is_some() || self.picker.is_some();
+                let paths = match into_prompt || self.question.is_some() {
+                    true => None,
+                    false => dropped::paths(&text),
+                };
+                match paths {
+                    Some(paths) => self.offer_to_open(text, paths),
+                    None => {
+                        if !into_prompt {
+                            self.kills.push(text.clone());
+                        }
+                        self.paste(text);
+                    }
+                }
+            }
+            Event::PasteHistory => self.open_paste_history(),
+            Event::PickerInsert(c) => self.picker.iter_mut().for_each(|(p, _)| p.push(c)),
+            Event::PickerBackspace => self.picker.iter_mut().for_each(|(p, _)| p.pop()),
+            Event::PickerNext => self.picker.iter_mut().for_each(|(p, _)| p.select_next()),
+            Event::PickerPrevious => self
+                .picker
+                .iter_mut()
+                .for_each(|(p, _)| p.select_previous()),
+            Event::PickerSubmit => self.submit_picker(),
+            Event::PickerCancel => {
+                self.picker = None;
+                self.dirty.mark_all();
+            }
+            Event::Indent if self.paste_mode => self.type_literal('\t'),
+            Event::Indent => self.indent(),
+            Event::ToggleComment => self.toggle_comment(),
+            Event::ToggleOverwrite => self.overwrite = !self.overwrite,
+            Event::Cancel => self.cancel_loading(),
+            Event::NextBuffer => self.next_buffer(),
+            Event::PreviousBuffer => self.previous_buffer(),
+            Event::JumpBack => self.jump_back(),
+            Event::JumpForward => self.jump_forward(),
+            Event::Save => self.save(false)?,
+            Event::Suspend => self.suspend()?,
+            Event::Undo => self.undo(),
+            Event::Redo => self.redo(),
+            Event::InsertLiteral => {
+                if self.is_editable() && !self.document().is_some_and(|d| d.is_binary()) {
+                    self.literal = Some(Literal::new());
+                }
+            }
+            Event::LiteralKey(c) => {
+                let step = self.literal.as_mut().map(|l| l.push(c));
+                self.insert_literal(step);
+            }
+            Event::LiteralSubmit => {
+                let step = self.literal.as_ref().map(|l| l.finish());
+                self.insert_literal(step);
+            }
+            Event::LiteralCancel => self.literal = None,
+            Event::ConfirmKey(c) if self.question.is_some() => self.answer(Some(c))?,
+            Event::ConfirmCancel if self.question.is_some() => self.answer(None)?,
+            Event::ConfirmKey(c) => self.confirm_substitution(Some(c)),
+            Event::ConfirmCancel => self.confirm_substitution(None),
+            Event::StartChord(chord) => {
+                self.chord = Some(PendingChord {
+                    chord,
+                    started: Instant::now(),
+                    hint: false,
+                })
+            }
+            Event::ChordKey(c) => self.finish_chord(Some(c))?,
+            Event::ChordCancel => self.finish_chord(None)?,
+            Event::CloseInfo => {
+                self.info = None;
+                self.dirty.mark_all();
+            }
+            Event::OpenPrompt => self.open_prompt(Prompt::new(Kind::Command)),
+            Event::OpenGotoPrompt => self.open_prompt(Prompt::new(Kind::Goto)),
+            Event::OpenSearchPrompt => {
+                let prompt = Prompt::new(Kind::Search).with_default(self.search.clone());
+                self.open_prompt(prompt);
+            }
+            Event::PromptInsert(c) => self.prompt.iter_mut().for_each(|p| p.push(c)),
+            Event::PromptBackspace => self.prompt.iter_mut().for_each(|p| p.pop()),
+            Event::PromptHistoryPrevious => self.recall_history(true),
+            Event::PromptHistoryNext => self.recall_history(false),
+            Event::PromptComplete => self.complete_prompt(),
+            Event::PromptToggleHidden => self.show_hidden = !self.show_hidden,
+            Event::PromptSubmit => self.submit_prompt()?,
+            Event::PromptCancel => self.cancel_prompt(),
+        };
+        self.update_preview();
+        self.preview_prompt();
+
+        self.reveal_cursor(vertical);
+        if !typing {
+            self.document_mut().iter_mut().for_each(|d| d.commit_undo());
+        }
+        self.share_changes();
+        if self.view() != view {
+            self.dirty.mark_all();
+        }
+        if self.welcome && !self.showing_welcome() {
+            self.welcome = false;
+            self.dirty.mark_all();
+        }
+        Ok(())
+    }
+
+    /// Sets the terminal's title to the current document's name, marked with `[+]` if it's been
+    /// modified. The title is only written when it changes.
+    fn update_title(&mut self) -> io::Result<()> {
+        let title = match self.document() {
+            Some(document) if !self.showing_welcome() => format!(
+                "{}{} - texteditor",
+                document.name(),
+                match document.is_modified() {
+                    true => " [+]",
+                    false => "",
+                }
+            ),
+            _ => String::from("texteditor"),
+        };
+        if self.title.as_ref() == Some(&title) {
+            return Ok(());
+        }
+        self.terminal.set_title(&title)?;
+        self.title = Some(title);
+        Ok(())
+    }
+
+    /// Whether the start screen is drawn in place of the untitled document opened at launch.
+    /// Editing the document or opening another one dismisses it.
+    fn showing_welcome(&self) -> bool {
+        self.welcome
+            && self.buffers.len() == 1
+            && self
+                .document()
+                .is_some_and(|d| d.path().is_none() && !d.is_modified())
+    }
+
+    /// Whether the current buffer is the help.
+    fn in_help(&self) -> bool {
+        self.document()
+            .is_some_and(|d| d.path().is_none() && d.name() == help::TITLE)
+    }
+
+    /// Opens the help in a read-only buffer, or switches to it if it's already open, and moves
+    /// the cursor to a topic.
+    fn help(&mut self, topic: Option<&str>) {
+        let text = help::text(&self.keymaps);
+        let line = match topic {
+            Some(topic) => match help::find_topic(&text, topic) {
+                Some(line) => line,
+                None => {
+                    self.message = Some(format!("No help for {}", topic));
+                    return;
+                }
+            },
+            None => 1,
+        };
+
+        self.record_jump();
+        match self
+            .buffers
+            .iter()
+            .position(|b| b.document.path().is_none() && b.document.name() == help::TITLE)
+        {
+            Some(index) => self.switch_buffer(index),
+            None => {
+                let mut document = Document::scratch(help::TITLE, &text);
+                document.set_readonly(true);
+                self.open(document);
+            }
+        }
+        self.goto(line, 0);
+        self.top = line;
+    }
+
+    /// Opens a picker listing recently opened files, to open one of them.
+    fn open_recent_files(&mut self) {
+        let items: Vec<String> = self.recent_files.newest_first().map(String::from).collect();
+        if items.is_empty() {
+            self.message = Some(String::from("No recent files"));
+            return;
+        }
+        let paths = items.iter().map(PathBuf::from).collect();
+        self.picker = Some((Picker::new("Recent", items), PickerAction::Open(paths)));
+    }
+
+    /// Opens a picker listing the open buffers, to switch to one of them. Modified buffers are
+    /// marked, and the highlighted buffer is previewed alongside the list.
+    fn pick_buffer(&mut self) {
+        let items = self
+            .buffers
+            .iter()
+            .enumerate()
+            .map(|(i, buffer)| {
+                format!(
+                    "{} {}{}",
+                    i + 1,
+                    buffer.document.name(),
+                    match buffer.document.is_modified() {
+                        true => " [+]",
+                        false => "",
+                    }
+                )
+            })
+            .collect();
+        let mut picker = Picker::new("Buffers", items);
+        picker.select(self.current);
+        self.picker = Some((picker, PickerAction::Switch));
+    }
+
+    /// The lines of a buffer shown in the buffer picker's preview, starting from the top of the
+    /// screen when the buffer was last shown.
+    ///
+    /// # Arguments
+    ///
+    /// * 'index' - The buffer's index.
+    /// * 'rows' - The number of lines to show.
+    /// * 'width' - The number of cells each line is given.
+    fn preview_buffer(&self, index: usize, rows: usize, width: usize) -> Vec<String> {
+        let Some(buffer) = self.buffers.get(index) else {
+            return vec![];
+        };
+        let top = match index == self.current {
+            true => self.top,
+            false => buffer.top,
+        };
+        buffer
+            .document
+            .get_lines(top..top + rows as u32)
+            .iter()
+            .map(|line| layout::render_line(line, width, buffer.settings.tab_width))
+            .collect()
+    }
+
+    /// The state which, when changed, moves or redraws every line on screen: the current buffer,
+    /// the first visible line, the number of lines in the document and the selection.
+    fn view(&self) -> (usize, u32, Option<u32>, Option<Selection>) {
+        (
+            self.current,
+            self.top,
+            self.document().map(|d| d.line_count()),
+            self.selection(),
+        )
+    }
+
+    fn cursor(&self) -> Position {
+        Position {
+            row: self.row,
+            column: self.column,
+        }
+    }
+
+    /// The selected text, if there is any. Text can't be selected in the hex view.
+    fn selection(&self) -> Option<Selection> {
+        self.anchor
+            .map(|anchor| Selection::new(anchor, self.cursor()))
+            .filter(|selection| !selection.is_empty())
+            .filter(|_| self.document().is_some_and(|d| !d.is_binary()))
+    }
+
+    /// Selects the text between two positions, leaving the cursor at the end.
+    fn select(&mut self, start: Position, end: Position) {
+        self.anchor = Some(start);
+        self.row = end.row;
+        self.column = end.column;
+        self.scroll_to_cursor();
+    }
+
+    /// Finds a text object around the cursor, searching the lines around it.
+    fn find_object(&self, object: TextObject) -> Option<(Position, Position)> {
+        let cursor = self.cursor();
+        let document = self.document().filter(|d| !d.is_binary())?;
+        let first = cursor.row.saturating_sub(textobject::SCAN_LINES).max(1);
+        let lines = document.get_lines(first..cursor.row + textobject::SCAN_LINES + 1);
+        object.find(&lines, first, cursor)
+    }
+
+    /// Selects a text object around the cursor, searching the lines around it.
+    fn select_object(&mut self, object: TextObject) {
+        match self.find_object(object) {
+            Some((start, end)) => {
+                self.select(start, end);
+                self.dirty.mark_all();
+            }
+            None => self.message = Some(String::from("No text object at the cursor")),
+        }
+    }
+
+    /// Surrounds the selection, or a text object around the cursor, with a pair such as
+    /// brackets or tags, then selects the surrounded text along with the pair.
+    fn surround(&mut self, pair: Pair, object: Option<TextObject>) {
+        if !self.is_editable() || self.document().is_some_and(|d| d.is_binary()) {
+            return;
+        }
+        let found = match object {
+            Some(object) => self.find_object(object),
+            None => self.selection().map(|s| (s.start(), s.end())),
+        };
+        let Some((start, mut end)) = found else {
+            self.message = Some(String::from(match object {
+                Some(_) => "No text object at the cursor",
+                None => "Nothing selected to surround",
+            }));
+            return;
+        };
+
+        if let Some(document) = self.document_mut() {
+            // The closing side goes in first so the start doesn't move.
+            document.insert_text(end.row, end.column, &pair.close);
+            document.insert_text(start.row, start.column, &pair.open);
+        }
+        if end.row == start.row {
+            end.column += pair.open.chars().count() as u32;
+        }
+        end.column += pair.close.chars().count() as u32;
+        self.select(start, end);
+        self.dirty.mark_all();
+    }
+
+    /// Deletes the pair around the cursor, such as the brackets or tags around it, or replaces
+    /// it with another pair.
+    ///
+    /// # Arguments
+    ///
+    /// * 'target' - The text object taking in the pair around the cursor.
+    /// * 'replacement' - The pair to put in its place, if it's being changed.
+    fn unsurround(&mut self, target: TextObject, replacement: Option<Pair>) {
+        if !self.is_editable() || self.document().is_some_and(|d| d.is_binary()) {
+            return;
+        }
+        let inner = TextObject {
+            around: false,
+            ..target
+        };
+        let (Some((start, end)), Some((inner_start, inner_end))) =
+            (self.find_object(target), self.find_object(inner))
+        else {
+            self.message = Some(String::from("No surrounding pair at the cursor"));
+            return;
+        };
+
+        if let Some(document) = self.document_mut() {
+            // The closing side is changed first so the opening side doesn't move.
+            document.delete(inner_end, end);
+            if let Some(pair) = &replacement {
+                document.insert_text(inner_end.row, inner_end.column, &pair.close);
+            }
+            document.delete(start, inner_start);
+            if let Some(pair) = &replacement {
+                document.insert_text(start.row, start.column, &pair.open);
+            }
+        }
+        self.anchor = None;
+        self.row = start.row;
+        self.column = start.column;
+        self.scroll_to_cursor();
+        self.dirty.mark_all();
+    }
+
+    /// The position just after the last character of a line, including its line break unless
+    /// it's the last line of the document.
+    fn end_of_line(&self, line: u32) -> Position {
+        match self.document() {
+            Some(document) if line < document.line_count() => Position {
+                row: line + 1,
+                column: 0,
+            },
+            Some(document) => Position {
+                row: line,
+                column: document.line_len(line),
+            },
+            None => self.cursor(),
+        }
+    }
+
+    fn select_all(&mut self) {
+        if let Some(Err(e)) = self.document_mut().map(|d| d.load_until(u32::MAX)) {
+            self.message = Some(e.to_string());
+            return;
+        }
+        let Some(line_count) = self.document().map(|d| d.line_count().max(1)) else {
+            return;
+        };
+        self.select(Position { row: 1, column: 0 }, self.end_of_line(line_count));
+    }
+
+    /// Selects the current line. If whole lines are already selected, the selection is extended
+    /// to include the next line.
+    fn select_line(&mut self) {
+        let (first, last) = match self.selection() {
+            Some(s) if s.start().column == 0 && s.end().column == 0 => (s.start().row, s.end().row),
+            _ => (self.row, self.row),
+        };
+        let last = last.min(self.document().map_or(1, |d| d.line_count().max(1)));
+        self.select(
+            Position {
+                row: first,
+                column: 0,
+            },
+            self.end_of_line(last),
+        );
+    }
+
+    fn expand_selection(&mut self) {
+        let Some(document) = self.document() else {
+            return;
+        };
+        let (start, end) = self
+            .selection()
+            .map_or((self.cursor(), self.cursor()), |s| (s.start(), s.end()));
+        // A selection ending at the start of a line doesn't include any of that line.
+        let last_row = match end.column == 0 && end.row > start.row {
+            true => end.row - 1,
+            false => end.row,
+        };
+
+        let word = match start.row == end.row {
+            true => document
+                .word_bounds(start.row, start.column)
+                .map(|columns| {
+                    let position = |column| Position {
+                        row: start.row,
+                        column,
+                    };
+                    (position(columns.start), position(columns.end))
+                }),
+            false => None,
+        };
+        let line = (
+            Position {
+                row: start.row,
+                column: 0,
+            },
+            self.end_of_line(last_row),
+        );
+        let paragraph = (
+            Position {
+                row: document.paragraph_bounds(start.row).start,
+                column: 0,
+            },
+            self.end_of_line(document.paragraph_bounds(last_row).end - 1),
+        );
+        let whole = (
+            Position { row: 1, column: 0 },
+            self.end_of_line(document.line_count().max(1)),
+        );
+
+        let expanded = [word, Some(line), Some(paragraph), Some(whole)]
+            .into_iter()
+            .flatten()
+            .find(|(s, e)| *s <= start && *e >= end && (*s, *e) != (start, end));
+        if let Some((start, end)) = expanded {
+            self.select(start, end);
+        }
+    }
+
+    fn kill_line(&mut self) {
+        if !self.is_editable() || self.document().is_some_and(|d| d.is_binary()) {
+            return;
+        }
+        let Some(document) = self.document() else {
+            return;
+        };
+
+        let start = self.cursor();
+        let end = match self.column < document.line_len(self.row) {
+            true => Position {
+                row: self.row,
+                column: document.line_len(self.row),
+            },
+            false => self.end_of_line(self.row),
+        };
+        if end == start {
+            return;
+        }
+
+        let text = self
+            .document_mut()
+            .map_or(String::new(), |d| d.cut(start, end));
+        match self.killing {
+            true => {
+                self.kills.append(&text);
+                self.registers.append_delete(&text);
+            }
+            false => {
+                self.kills.push(text.clone());
+                self.registers.delete(None, Register::new(text, false));
+            }
+        }
+        self.killing = true;
+        self.anchor = None;
+        self.dirty.mark_line(self.row);
+    }
+
+    /// Deletes the current line, including its line break.
+    fn delete_line(&mut self) {
+        self.delete_lines(self.row..=self.row, None);
+    }
+
+    /// Deletes lines, including their line breaks, leaving the cursor on the line after them.
+    /// The lines are stored in the registers.
+    ///
+    /// # Arguments
+    ///
+    /// * 'lines' - The first and last lines to delete.
+    /// * 'register' - The named register to store the lines in, if one was given.
+    fn delete_lines(&mut self, lines: RangeInclusive<u32>, register: Option<char>) {
+        if !self.is_editable() || self.document().is_some_and(|d| d.is_binary()) {
+            return;
+        }
+        if let Some(text) = self.line_text(lines.clone()) {
+            self.kills.push(text.clone());
+            self.registers.delete(register, Register::new(text, true));
+        }
+        self.remove_lines(lines);
+    }
+
+    /// Removes lines, including their line breaks, leaving the cursor on the line after them.
+    fn remove_lines(&mut self, lines: RangeInclusive<u32>) {
+        let Some(line_count) = self.document().map(|d| d.line_count()) else {
+            return;
+        };
+        let (first, last) = (*lines.start(), *lines.end());
+
+        // The last line has no line break after it, so the one before the lines is deleted
+        // instead.
+        let start = match last == line_count && first > 1 {
+            true => Position {
+                row: first - 1,
+                column: self.document().map_or(0, |d| d.line_len(first - 1)),
+            },
+            false => Position {
+                row: first,
+                column: 0,
+            },
+        };
+        let end = self.end_of_line(last);
+        if let Some(document) = self.document_mut() {
+            document.delete(start, end);
+        }
+
+        self.anchor = None;
+        self.row = first.min(self.document().map_or(1, |d| d.line_count().max(1)));
+        self.clamp_cursor_column();
+        self.scroll_to_cursor();
+        self.dirty.mark_all();
+    }
+
+    /// The text of whole lines, with a line break after each, as it's stored in a register.
+    fn line_text(&self, lines: RangeInclusive<u32>) -> Option<String> {
+        let document = self.document()?;
+        let lines = document.get_lines(*lines.start()..*lines.end() + 1);
+        Some(lines.into_iter().map(|line| line + "\n").collect())
+    }
+
+    /// Copies the current line, or the lines given by a range or the selection, into the
+    /// registers.
+    fn yank_lines(&mut self, register: Option<char>) {
+        let lines = self.given_lines().unwrap_or(self.row..=self.row);
+        let count = lines.clone().count();
+        if let Some(text) = self.line_text(lines) {
+            self.kills.push(text.clone());
+            self.registers.yank(register, Register::new(text, true));
+            self.message = Some(match count {
+                1 => String::from("1 line yanked"),
+                _ => format!("{} lines yanked", count),
+            });
+        }
+    }
+
+    /// Puts the text in a register after the cursor, or on the lines below the current line if
+    /// it's whole lines, leaving the cursor at the start of the first line put.
+    ///
+    /// # Arguments
+    ///
+    /// * 'name' - The register to put, or the unnamed register if none was given.
+    fn put(&mut self, name: Option<char>) {
+        let name = name.unwrap_or('"');
+        let Some(register) = self.registers.get(name).cloned() else {
+            self.message = Some(format!("Register \"{} is empty", name));
+            return;
+        };
+        self.anchor = None;
+        if !register.linewise {
+            self.paste(register.text);
+            return;
+        }
+
+        let row = self.row;
+        self.column = self.document().map_or(0, |d| d.line_len(row));
+        let text = register.text.strip_suffix('\n').unwrap_or(&register.text);
+        self.paste(format!("\n{}", text));
+        self.goto(row + 1, 0);
+    }
+
+    /// Opens a picker listing the registers with text in them, to put one.
+    fn open_registers(&mut self) {
+        let (names, items): (Vec<char>, Vec<String>) = self
+            .registers
+            .list()
+            .into_iter()
+            .map(|(name, register)| {
+                (
+                    name,
+                    format!("\"{}  {}", name, registers::summary(&register.text)),
+                )
+            })
+            .unzip();
+        if items.is_empty() {
+            self.message = Some(String::from("Nothing has been yanked or deleted"));
+            return;
+        }
+        self.picker = Some((Picker::new("Registers", items), PickerAction::Put(names)));
+    }
+
+    /// Completes the partly typed word or path before the cursor. A single completion is
+    /// inserted straight away, otherwise a picker lists them to choose from.
+    fn complete(&mut self, source: completion::Source) {
+        if !self.is_editable() || self.document().is_some_and(|d| d.is_binary()) {
+            return;
+        }
+        let row = self.row;
+        let line = self
+            .document()
+            .and_then(|d| d.get_lines(row..row + 1).pop())
+            .unwrap_or_default();
+        let before: String = line.chars().take(self.column as usize).collect();
+        let (start, partial) = source.partial(&before);
+
+        let candidates = match source {
+            completion::Source::Dictionary if partial.is_empty() => {
+                self.message = Some(String::from("No word before the cursor to complete"));
+                return;
+            }
+            completion::Source::Dictionary => {
+                let config = &self.config;
+                let words = self
+                    .dictionary
+                    .get_or_insert_with(|| completion::load_words(&config.dictionaries));
+                completion::complete_word(words, partial)
+            }
+            completion::Source::File => completion::complete_path(partial, self.show_hidden),
+        };
+        match candidates.len() {
+            0 => self.message = Some(format!("No completions for \"{}\"", partial)),
+            1 => self.insert_completion(start, candidates.into_iter().next().unwrap_or_default()),
+            _ => {
+                self.picker = Some((
+                    Picker::new("Complete", candidates.clone()),
+                    PickerAction::Complete(start, candidates),
+                ));
+            }
+        }
+    }
+
+    /// Replaces the text before the cursor on the current line with a completion.
+    ///
+    /// # Arguments
+    ///
+    /// * 'start' - The column the completed text starts at.
+    /// * 'completion' - The text to put in its place.
+    fn insert_completion(&mut self, start: u32, completion: String) {
+        self.anchor = Some(Position {
+            row: self.row,
+            column: start,
+        });
+        self.paste(completion);
+    }
+
+    /// Changes the case of the selected text, keeping it selected.
+    fn change_case(&mut self, case: Case) {
+        let Some(selection) = self.selection() else {
+            self.message = Some(String::from("No text selected"));
+            return;
+        };
+        if !self.is_editable() {
+            return;
+        }
+
+        let (start, end) = (selection.start(), selection.end());
+        let Some(end) = self
+            .document_mut()
+            .map(|d| d.convert_case(start, end, case))
+        else {
+            return;
+        };
+        match selection.cursor == start {
+            true => self.anchor = Some(end),
+            false => self.select(start, end),
+        }
+        self.dirty.mark_all();
+    }
+
+    /// The lines given by a range before the command, or else the first and last lines covered
+    /// by the selection, or every line in the document if nothing is selected.
+    fn selected_lines(&mut self) -> Option<RangeInclusive<u32>> {
+        if let Some(range) = &self.range {
+            return Some(range.clone());
+        }
+        if let Some(lines) = self.selection_lines() {
+            return Some(lines);
+        }
+
+        if let Some(Err(e)) = self.document_mut().map(|d| d.load_until(u32::MAX)) {
+            self.message = Some(e.to_string());
+            return None;
+        }
+        self.document().map(|d| 1..=d.line_count().max(1))
+    }
+
+    /// The first and last lines covered by the selection, if there is one. A selection ending at
+    /// the start of a line doesn't cover that line.
+    fn selection_lines(&self) -> Option<RangeInclusive<u32>> {
+        let selection = self.selection()?;
+        let (start, end) = (selection.start(), selection.end());
+        match end.column == 0 && end.row > start.row {
+            true => Some(start.row..=end.row - 1),
+            false => Some(start.row..=end.row),
+        }
+    }
+
+    /// The line of a mark given in a range. The first and last lines of the selection are marked
+    /// `'<` and `'>`, and global marks can be given if they're set in the current document.
+    fn mark_line(&self, name: char) -> Option<u32> {
+        match name {
+            '<' => self.selection_lines().map(|lines| *lines.start()),
+            '>' => self.selection_lines().map(|lines| *lines.end()),
+            name => {
+                let path = self.document().and_then(|d| d.path())?;
+                self.mark_store.line_in(name, path)
+            }
+        }
+    }
+
+    /// The lines given by a range before the command, or covered by the selection. None if
+    /// neither was given, for commands which act on the current line without them.
+    fn given_lines(&mut self) -> Option<RangeInclusive<u32>> {
+        match self.range.is_some() || self.selection().is_some() {
+            true => self.selected_lines(),
+            false => None,
+        }
+    }
+
+    /// Rearranges the selected lines, or every line in the document if nothing is selected, as
+    /// a single edit.
+    fn transform_lines(&mut self, transform: impl FnOnce(&mut Vec<String>)) {
+        if let Some(lines) = self.selected_lines() {
+            self.transform_range(lines, transform);
+        }
+    }
+
+    /// Shows the character under the cursor, its codepoints, encoding and width, along with the
+    /// cursor's position in the document.
+    fn inspect(&mut self) {
+        let cursor = self.cursor();
+        let Some(document) = self.document() else {
+            return;
+        };
+        let Some((bytes, chars)) = document.offsets(cursor) else {
+            self.message = Some(String::from("Not available in the hex view"));
+            return;
+        };
+
+        let line = document
+            .get_lines(cursor.row..cursor.row + 1)
+            .pop()
+            .unwrap_or_default();
+        let character = match inspect::grapheme_at(&line, cursor.column) {
+            Some(grapheme) => inspect::describe(grapheme),
+            None => String::from("End of line"),
+        };
+        self.message = Some(format!(
+            "{}  byte {}  char {}  line {}  column {}",
+            character,
+            bytes,
+            chars,
+            cursor.row,
+            cursor.column + 1
+        ));
+    }
+
+    /// Works out the indentation of the selected lines, or every line in the document if nothing
+    /// is selected, from the filetype's indent rules as a single edit.
+    fn reindent(&mut self) {
+        let Some(rules) = self.indent_rules() else {
+            self.message = Some(String::from("No indent rules for this filetype"));
+            return;
+        };
+        if !rules.reindents {
+            self.message = Some(String::from(
+                "Indentation is part of the syntax, so it's left as is",
+            ));
+            return;
+        }
+        let Some(lines) = self.selected_lines() else {
+            return;
+        };
+        let settings = self.settings();
+        let previous = self.previous_line(*lines.start());
+        self.transform_range(lines, |lines| {
+            *lines = indent::reindent(rules, previous.as_deref(), lines, &settings);
+        });
+    }
+
+    /// Re-wraps the selected paragraphs, or the paragraph at the cursor, to the text width as a
+    /// single edit.
+    fn reflow(&mut self) {
+        let lines = match self.given_lines() {
+            Some(lines) => Some(lines),
+            None => self
+                .document()
+                .filter(|d| !d.is_blank(self.row))
+                .map(|d| d.paragraph_bounds(self.row))
+                .map(|bounds| bounds.start..=bounds.end - 1),
+        };
+        let Some(lines) = lines else {
+            return;
+        };
+
+        let settings = self.settings();
+        self.transform_range(lines, |content| {
+            *content = reflow::reflow(
+                content,
+                settings.text_width,
+                settings.comment_prefix.as_deref(),
+                settings.tab_width,
+            );
+        });
+    }
+
+    /// Replaces a range of lines with the result of a transform as a single edit. Any selection
+    /// is extended to cover the whole of the lines afterwards.
+    fn transform_range(
+        &mut self,
+        lines: RangeInclusive<u32>,
+        transform: impl FnOnce(&mut Vec<String>),
+    ) {
+        if !self.is_editable() || self.document().is_some_and(|d| d.is_binary()) {
+            return;
+        }
+        let selected = self.selection().is_some();
+        let Some(document) = self.document_mut() else {
+            return;
+        };
+
+        let mut content = document.get_lines(*lines.start()..*lines.end() + 1);
+        transform(&mut content);
+        document.replace_lines(lines.clone(), &content);
+
+        let last = *lines.start() + content.len().max(1) as u32 - 1;
+        if selected {
+            let end = Position {
+                row: last,
+                column: document.line_len(last),
+            };
+            self.select(
+                Position {
+                    row: *lines.start(),
+                    column: 0,
+                },
+                end,
+            );
+        } else {
+            let line_count = document.line_count().max(1);
+            self.row = self.row.min(line_count);
+            self.clamp_cursor_column();
+            self.scroll_to_cursor();
+        }
+        self.dirty.mark_all();
+    }
+
+    /// Deletes the selected text so it can be replaced by whatever is being typed or pasted,
+    /// leaving the cursor where the selection started.
+    fn delete_selection(&mut self) {
+        let Some(selection) = self.selection() else {
+            self.anchor = None;
+            return;
+        };
+
+        if let Some(document) = self.document_mut() {
+            document.delete(selection.start(), selection.end());
+        }
+        self.anchor = None;
+        self.row = selection.start().row;
+        self.column = selection.start().column;
+        self.scroll_to_cursor();
+        self.dirty.mark_all();
+    }
+
+    fn handle_key_press(&mut self, c: char) {
+        if !self.is_editable() {
+            return;
+        }
+        if self.document().is_some_and(|d| d.is_binary()) {
+            self.edit_hex(c);
+            return;
+        }
+        self.delete_selection();
+        self.type_char(c, self.overwrite);
+        self.edits.record(c, self.overwrite);
+
+        // Typing a token such as `}` at the start of a line dedents it straight away.
+        let dedents = self
+            .indent_rules()
+            .is_some_and(|rules| rules.dedent.contains(&c.to_string().as_str()));
+        if dedents && self.text_before_cursor().trim() == c.to_string() {
+            self.dedent_closing_line();
+        }
+    }
+
+    /// The indent rules for the current document's filetype, if it has any.
+    fn indent_rules(&self) -> Option<&'static Rules> {
+        self.document()
+            .and_then(|d| d.filetype())
+            .and_then(indent::rules)
+    }
+
+    /// The text on the current line before the cursor.
+    fn text_before_cursor(&self) -> String {
+        self.document()
+            .and_then(|d| d.get_lines(self.row..self.row + 1).pop())
+            .unwrap_or_default()
+            .chars()
+            .take(self.column as usize)
+            .collect()
+    }
+
+    /// The closest line above a line which isn't blank, which a line's indentation is worked out
+    /// from.
+    fn previous_line(&self, row: u32) -> Option<String> {
+        let document = self.document()?;
+        let line = (1..row).rev().find(|line| !document.is_blank(*line))?;
+        document.get_lines(line..line + 1).pop()
+    }
+
+    /// Dedents the current line if it starts by closing a block, such as with `}`, following
+    /// the filetype's indent rules. Lines are only ever dedented, so indentation added by hand
+    /// is kept.
+    fn dedent_closing_line(&mut self) {
+        let Some(rules) = self.indent_rules() else {
+            return;
+        };
+        let row = self.row;
+        let Some(line) = self
+            .document()
+            .and_then(|d| d.get_lines(row..row + 1).pop())
+        else {
+            return;
+        };
+        if !rules.closes(&line) {
+            return;
+        }
+
+        let settings = self.settings();
+        let previous = self.previous_line(row);
+        let columns = indent::columns(Some(rules), previous.as_deref(), &line, &settings);
+        if columns >= indent::width(&line, settings.tab_width) {
+            return;
+        }
+        let dedented = indent::reindent_line(&line, columns, &settings);
+        let removed = line.chars().count() - dedented.chars().count();
+        if let Some(document) = self.document_mut() {
+            document.replace_lines(row..=row, &[dedented]);
+        }
+        self.column = self.column.saturating_sub(removed as u32);
+        self.dirty.mark_line(row);
+    }
+
+    /// Splits the line at the cursor, replacing the selection, and indents the new line like the
+    /// one before it, adjusted by the filetype's indent rules.
+    fn break_line(&mut self) {
+        self.delete_selection();
+        self.dedent_closing_line();
+        let (row, column) = (self.row, self.column);
+        let Some(line) = self
+            .document()
+            .and_then(|d| d.get_lines(row..row + 1).pop())
+        else {
+            return;
+        };
+        let before: String = line.chars().take(column as usize).collect();
+        let after: String = line.chars().skip(column as usize).collect();
+
+        let settings = self.settings();
+        let columns = indent::columns(
+            self.indent_rules(),
+            Some(&before),
+            after.trim_start(),
+            &settings,
+        );
+        let indentation = indent::whitespace(columns, &settings);
+        if let Some(document) = self.document_mut() {
+            let text = String::from(document.line_ending().as_str()) + &indentation;
+            document.insert_text(row, column, &text);
+        }
+        self.row += 1;
+        self.column = indentation.chars().count() as u32;
+        self.scroll_to_cursor();
+        self.dirty.mark_all();
+    }
+
+    /// Inserts a line break or tab as it's typed in paste mode. Line breaks match the document's
+    /// line endings.
+    fn type_literal(&mut self, c: char) {
+        match c {
+            '\n' => self.paste(String::from("\n")),
+            c => self.handle_key_press(c),
+        }
+    }
+
+    /// Inserts the character entered after Ctrl+V once it's complete, or shows why it's
+    /// invalid.
+    fn insert_literal(&mut self, step: Option<Step>) {
+        match step {
+            Some(Step::Pending) | None => return,
+            Some(Step::Insert(text)) => text.chars().for_each(|c| self.handle_key_press(c)),
+            Some(Step::Invalid(message)) => self.message = Some(message),
+        }
+        self.literal = None;
+    }
+
+    /// Checks whether the current document can be edited, showing a message if it's read-only.
+    fn is_editable(&mut self) -> bool {
+        match self.document() {
+            Some(document) if document.is_readonly() => {
+                self.message = Some(String::from("Document is read-only"));
+                false
+            }
+            Some(_) if self.is_ansi() => {
+                self.message = Some(String::from("Colours are shown (:ansi to edit raw text)"));
+                false
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    /// Inserts a character at the cursor, or replaces the character under the cursor, then
+    /// moves the cursor past it.
+    fn type_char(&mut self, c: char, overwrite: bool) {
+        let (row, column) = (self.row, self.column);
+        if let Some(document) = self.document_mut() {
+            match overwrite {
+                true => document.replace(row, column, c),
+                false => document.insert(row, column, c),
+            }
+            self.dirty.mark_line(row);
+            self.move_cursor_right(1);
+        }
+    }
+
+    /// Inserts pasted text at the cursor as a single edit and moves the cursor to the end of it,
+    /// shifting pasted lines to the cursor's indentation if `reindent_paste` is set. Text pasted
+    /// into the prompt is added up to the first line break.
+    fn paste(&mut self, text: String) {
+        if let Some((picker, _)) = &mut self.picker {
+            text.lines()
+                .next()
+                .unwrap_or_default()
+                .chars()
+                .for_each(|c| picker.push(c));
+            return;
+        }
+        if let Some(prompt) = &mut self.prompt {
+            prompt.push_str(text.lines().next().unwrap_or_default());
+            return;
+        }
+        if !self.is_editable() {
+            return;
+        }
+        if self.document().is_some_and(|d| d.is_binary()) {
+            self.message = Some(String::from("Text can't be pasted into the hex view"));
+            return;
+        }
+
+        // Terminals send line breaks in pasted text as carriage returns.
+        let mut text = text.replace("\r\n", "\n").replace('\r', "\n");
+        self.delete_selection();
+        let (row, column) = (self.row, self.column);
+        let settings = self.settings();
+        if let Some(document) = self.document() {
+            if settings.reindent_paste {
+                let line = document.get_lines(row..row + 1).pop().unwrap_or_default();
+                let line = line.trim_end_matches(['\r', '\n']);
+                let before: String = line.chars().take(column as usize).collect();
+                text = indent::adjust_paste(&text, &before, line, &settings);
+            }
+            if document.line_ending() == LineEnding::Crlf {
+                text = text.replace('\n', "\r\n");
+            }
+        }
+        if let Some(document) = self.document_mut() {
+            document.insert_text(row, column, &text);
+        }
+
+        match text.rsplit_once('\n') {
+            Some((before, after)) => {
+                self.row += before.matches('\n').count() as u32 + 1;
+                self.column = after.chars().count() as u32;
+                self.dirty.mark_all();
+            }
+            None => {
+                self.column += text.chars().count() as u32;
+                self.dirty.mark_line(row);
+            }
+        }
+        self.scroll_to_cursor();
+    }
+
+    /// Inserts one level of indentation at the cursor, as spaces up to the next indent stop or as
+    /// a tab, depending on the filetype's settings.
+    fn indent(&mut self) {
+        if !self.is_editable() || self.document().is_some_and(|d| d.is_binary()) {
+            return;
+        }
+
+        self.delete_selection();
+        let settings = self.settings();
+        let indent = match settings.expand_tabs {
+            true => {
+                let cell = self
+                    .document()
+                    .and_then(|d| d.get_lines(self.row..self.row + 1).pop())
+                    .map_or(0, |line| {
+                        layout::display_column(&line, self.column, settings.tab_width)
+                    });
+                let width = settings.indent_width.max(1);
+                " ".repeat(width - cell % width)
+            }
+            false => String::from("\t"),
+        };
+        for c in indent.chars() {
+            self.type_char(c, self.overwrite);
+            self.edits.record(c, self.overwrite);
+        }
+    }
+
+    /// Comments out the current line using the filetype's comment prefix, or uncomments it if
+    /// it's already commented, keeping the cursor on the same character.
+    fn toggle_comment(&mut self) {
+        if !self.is_editable() || self.document().is_some_and(|d| d.is_binary()) {
+            return;
+        }
+        let Some(prefix) = self.settings().comment_prefix else {
+            self.message = Some(String::from("No comment prefix for this filetype"));
+            return;
+        };
+
+        let row = self.row;
+        let change = self
+            .document_mut()
+            .and_then(|d| d.toggle_comment(row, &prefix));
+        if let Some((column, added)) = change {
+            if self.column >= column {
+                self.column = (self.column as i64 + added).max(column as i64) as u32;
+            }
+            self.dirty.mark_line(row);
+        }
+    }
+
+    /// Applies the last edit again at the cursor.
+    fn repeat_edit(&mut self) {
+        let Some(edit) = self.edits.last().cloned() else {
+            self.message = Some(String::from("No edit to repeat"));
+            return;
+        };
+        if !self.is_editable() || self.document().is_some_and(|d| d.is_binary()) {
+            return;
+        }
+
+        let (text, overwrite) = match edit {
+            Edit::Insert(text) => (text, false),
+            Edit::Replace(text) => (text, true),
+        };
+        self.delete_selection();
+        for c in text.chars() {
+            self.type_char(c, overwrite);
+        }
+    }
+
+    /// Overwrites the nibble under the cursor in the hex view, moving on to the next byte once
+    /// both nibbles have been entered.
+    fn edit_hex(&mut self, c: char) {
+        let Some(value) = c.to_digit(16) else {
+            self.message = Some(String::from(
+                "Only hex digits can be entered in the hex view",
+            ));
+            return;
+        };
+
+        let (row, column, low) = (self.row, self.column, self.low_nibble);
+        let Some(document) = self.document_mut() else {
+            return;
+        };
+        document.set_nibble(row, column, value as u8, low);
+        let last_column = document.line_len(row);
+        self.dirty.mark_line(row);
+
+        self.low_nibble = !low;
+        if low {
+            if column < last_column {
+                self.move_cursor_right(1);
+            } else {
+                self.handle_new_line();
+            }
+        }
+    }
+
+    /// Follows the current document's file as it grows, like `tail -f`. The document is made
+    /// read-only and loaded in full, and the cursor moves to the end of it, where it stays as
+    /// text is added unless it's moved away.
+    pub fn follow(&mut self) -> error::Result<()> {
+        let Some(document) = self.document_mut() else {
+            return Err(EditorError::NoDocument);
+        };
+        let path = match document.path() {
+            Some(path) if !document.is_binary() && !document.is_encrypted() => path.to_path_buf(),
+            _ => return Err(EditorError::NotFollowable),
+        };
+        if document.compression().is_some() {
+            return Err(EditorError::NotFollowable);
+        }
+        document.set_readonly(true);
+        document.load_until(u32::MAX)?;
+        // Follow from the end of what was read, so text added since isn't missed.
+        let offset = document.disk_len().ok_or(EditorError::NotFollowable)?;
+        document.acknowledge_disk_change();
+
+        let line_count = document.line_count().max(1);
+        self.goto(line_count, 0);
+        self.follower = Some(Follower::start(path, offset, self.sender.clone()));
+        self.post_status(Subsystem::Follow, Some(String::from("Following")));
+        Ok(())
+    }
+
+    /// Adds text added to the followed file to its buffer. The cursor is kept at the end if it
+    /// was already there, so the view sticks to the bottom unless the user has moved away.
+    fn followed(&mut self, text: &str, truncated: bool) {
+        let Some(follower) = &self.follower else {
+            return;
+        };
+        let path = Some(follower.path());
+        let Some(index) = self.buffers.iter().position(|b| b.document.path() == path) else {
+            // The buffer was closed, so there's nothing left to follow the file into.
+            if let Some(follower) = self.follower.take() {
+                follower.stop();
+            }
+            self.post_status(Subsystem::Follow, None);
+            return;
+        };
+
+        let current = index == self.current;
+        let line_count = self.buffers[index].document.line_count();
+        let at_end = current && self.row >= line_count && self.cursor_visible();
+        self.buffers[index]
+            .document
+            .extend_from_disk(text, truncated);
+        if current {
+            let line_count = self.document().map_or(1, |d| d.line_count().max(1));
+            if at_end || self.row > line_count {
+                self.row = line_count;
+                self.clamp_cursor_column();
+                self.scroll_to_cursor();
+            }
+        }
+        self.dirty.mark_all();
+        self.should_render = true;
+    }
+
+    /// Shares the current document with other editors, which connect with `--connect`.
+    ///
+    /// # Arguments
+    ///
+    /// * 'address' - The address to listen on, such as `127.0.0.1:7878`.
+    /// * 'allow_remote' - Whether to listen on addresses other machines can connect to. Anyone
+    ///   who can connect can read and edit the document, as connections aren't authenticated.
+    pub fn serve(&mut self, address: &str, allow_remote: bool) -> error::Result<()> {
+        let addresses: Vec<SocketAddr> = address.to_socket_addrs()?.collect();
+        if !allow_remote && !addresses.iter().all(|a| a.ip().is_loopback()) {
+            return Err(EditorError::RemoteAddress(address.to_string()));
+        }
+        if self.buffers.is_empty() {
+            self.open(Document::new());
+        }
+        let session = Session::serve(&addresses[..], self.sender.clone())?;
+        if let Some(document) = self.document_mut() {
+            document.share()?;
+        }
+        self.message = session.address().map(|a| format!("Sharing on {}", a));
+        self.session = Some(session);
+        self.post_status(Subsystem::Session, Some(String::from("Sharing")));
+        Ok(())
+    }
+
+    /// Connects to another editor which is sharing a document with `--serve`, opening the
+    /// document in a new buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * 'address' - The address the other editor is listening on.
+    pub fn connect(&mut self, address: &str) -> error::Result<()> {
+        let session = Session::connect(address, self.sender.clone())?;
+        let mut document = Document::scratch(&format!("[{}]", address), "");
+        document.share()?;
+        self.open(document);
+        self.message = Some(format!("Connected to {}", address));
+        self.session = Some(session);
+        self.post_status(Subsystem::Session, Some(String::from("Connected")));
+        Ok(())
+    }
+
+    /// Sends the changes made to the shared document, along with the cursor if it's in the
+    /// shared document. Clients send each edit to the host, while the host sends out the whole
+    /// document, so every participant ends up with the host's copy.
+    fn share_changes(&mut self) {
+        let cursor = self.cursor();
+        let Some(session) = &mut self.session else {
+            return;
+        };
+        let Some(index) = self.buffers.iter().position(|b| b.document.is_shared()) else {
+            return;
+        };
+
+        let document = &mut self.buffers[index].document;
+        let changes = document.take_changes();
+        if session.is_host() {
+            if !changes.is_empty() {
+                session.send(&Message::Text(document.text()));
+            }
+        } else {
+            for change in changes {
+                session.send(&Message::Change {
+                    pos: change.pos,
+                    deleted: change.deleted.len(),
+                    inserted: change.inserted,
+                });
+            }
+        }
+        if index == self.current {
+            session.send_cursor(cursor);
+        }
+    }
+
+    /// Handles a message from another participant in the shared editing session.
+    fn receive(&mut self, from: u32, message: Message) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+        let host = session.is_host();
+        let Some(index) = self.buffers.iter().position(|b| b.document.is_shared()) else {
+            return;
+        };
+        let document = &mut self.buffers[index].document;
+
+        match message {
+            Message::Text(text) if !host => {
+                document.commit_undo();
+                document.sync_text(&text);
+                document.commit_undo();
+            }
+            Message::Change {
+                pos,
+                deleted,
+                inserted,
+            } if host => {
+                document.commit_undo();
+                document.apply_change(pos, deleted, &inserted);
+                document.commit_undo();
+            }
+            Message::Cursor { id, position } => {
+                // Clients don't know their own id, so the host adds it before passing it on.
+                let id = if host { from } else { id };
+                session.set_cursor(id, position);
+                if host {
+                    session.send_except(from, &Message::Cursor { id, position });
+                }
+            }
+            Message::Leave { id } => session.remove(id),
+            _ => {}
+        }
+
+        if index == self.current {
+            let document = &self.buffers[index].document;
+            self.row = self.row.min(document.line_count().max(1));
+            self.column = self.column.min(document.line_len(self.row));
+        }
+        self.share_changes();
+        self.dirty.mark_all();
+        self.should_render = true;
+    }
+
+    /// Sends the shared document and the cursors of everyone already editing it to a client
+    /// which has just connected.
+    fn participant_joined(&mut self, id: u32) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+        if let Some(buffer) = self.buffers.iter().find(|b| b.document.is_shared()) {
+            session.send_to(id, &Message::Text(buffer.document.text()));
+        }
+        let cursors: Vec<(u32, Position)> =
+            session.cursors().iter().map(|(i, p)| (*i, *p)).collect();
+        for (other, position) in cursors {
+            session.send_to(
+                id,
+                &Message::Cursor {
+                    id: other,
+                    position,
+                },
+            );
+        }
+        session.resend_cursor();
+        self.share_changes();
+        self.message = Some(format!("Participant {} joined", id));
+        self.should_render = true;
+    }
+
+    fn participant_left(&mut self, id: u32) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+        session.remove(id);
+        if session.is_host() {
+            session.send_except(id, &Message::Leave { id });
+            self.message = Some(format!("Participant {} left", id));
+        } else {
+            self.session = None;
+            self.message = Some(String::from("Disconnected from the host"));
+            self.post_status(Subsystem::Session, None);
+        }
+        self.dirty.mark_all();
+        self.should_render = true;
+    }
+
+    /// Opens a picker listing the killed and pasted text, most recent first, to choose which to
+    /// insert.
+    fn open_paste_history(&mut self) {
+        let items: Vec<String> = self.kills.entries().map(registers::summary).collect();
+        if items.is_empty() {
+            self.message = Some(String::from("Nothing has been killed or pasted"));
+            return;
+        }
+        self.picker = Some((Picker::new("Paste", items), PickerAction::Paste));
+    }
+
+    /// Closes the picker, acting on the highlighted item.
+    fn submit_picker(&mut self) {
+        let Some((picker, action)) = self.picker.take() else {
+            return;
+        };
+        self.dirty.mark_all();
+        let Some(index) = picker.selected() else {
+            return;
+        };
+
+        match action {
+            PickerAction::Paste => {
+                if let Some(text) = self.kills.get(index) {
+                    self.paste(text.to_string());
+                }
+            }
+            PickerAction::Revision(revisions) => {
+                if let Some(revision) = revisions.get(index).copied() {
+                    self.move_through_history(|d| d.jump_to_revision(revision), "");
+                }
+            }
+            PickerAction::Complete(start, mut candidates) => {
+                if index < candidates.len() {
+                    self.insert_completion(start, candidates.swap_remove(index));
+                }
+            }
+            PickerAction::Put(names) => {
+                if let Some(name) = names.get(index).copied() {
+                    self.put(Some(name));
+                }
+            }
+            PickerAction::Mark(names) => {
+                if let Some(name) = names.get(index).copied() {
+                    self.jump_to_mark(name);
+                }
+            }
+            PickerAction::Open(mut paths) => {
+                if index < paths.len() {
+                    self.record_jump();
+                    self.edit(paths.swap_remove(index));
+                }
+            }
+            PickerAction::Switch => {
+                if index != self.current {
+                    self.record_jump();
+                    self.switch_buffer(index);
+                }
+            }
+        }
+    }
+
+    fn undo(&mut self) {
+        self.move_through_history(Document::undo, "Nothing to undo");
+    }
+
+    fn redo(&mut self) {
+        self.move_through_history(Document::redo, "Nothing to redo");
+    }
+
+    /// Moves the document through its history, placing the cursor where the text changed.
+    ///
+    /// # Arguments
+    ///
+    /// * 'step' - Applies the change to the document, returning where it was made.
+    /// * 'message' - Shown when there's no change to make.
+    fn move_through_history(
+        &mut self,
+        step: impl FnOnce(&mut Document) -> Option<Position>,
+        message: &str,
+    ) {
+        if !self.is_editable() || self.document().is_some_and(|d| d.is_binary()) {
+            return;
+        }
+        let Some(document) = self.document_mut() else {
+            return;
+        };
+
+        match step(document) {
+            Some(position) => {
+                self.goto(position.row, position.column);
+                self.dirty.mark_all();
+            }
+            None if !message.is_empty() => self.message = Some(String::from(message)),
+            None => {}
+        }
+    }
+
+    /// Opens a picker showing the current document's undo tree, to choose a revision to move
+    /// to. The revisions are listed as the tree is walked, with branches indented.
+    fn open_undo_tree(&mut self) {
+        let Some(document) = self.document() else {
+            return;
+        };
+        let history = document.history();
+        let (revisions, items): (Vec<usize>, Vec<String>) =
+            history.describe(SystemTime::now()).into_iter().unzip();
+        let mut picker = Picker::new("Undo tree", items);
+        if let Some(current) = revisions.iter().position(|r| *r == history.current()) {
+            picker.select(current);
+        }
+        self.picker = Some((picker, PickerAction::Revision(revisions)));
+    }
+
+    /// Completes the file path being entered for commands which take one. A unique completion,
+    /// or the text shared by every completion, is filled in straight away. Otherwise each
+    /// press moves on to the next completion.
+    fn complete_prompt(&mut self) {
+        let Some(prompt) = &mut self.prompt else {
+            return;
+        };
+        let Some(argument) = prompt.path().map(String::from) else {
+            return;
+        };
+
+        let completion = match self.completion.take() {
+            Some(completion) => Completion {
+                index: (completion.index + 1) % completion.candidates.len(),
+                ..completion
+            },
+            None => {
+                let candidates = completion::complete_path(&argument, self.show_hidden);
+                let common = completion::common_prefix(&candidates);
+                if candidates.is_empty() {
+                    return;
+                }
+                if common.len() > argument.len() {
+                    prompt.set_path(common);
+                    return;
+                }
+                Completion {
+                    candidates,
+                    index: 0,
+                }
+            }
+        };
+
+        prompt.set_path(&completion.candidates[completion.index]);
+        self.completion = Some(completion);
+    }
+
+    /// Starts waiting for the mouse to rest where it has moved to, hiding the tooltip shown
+    /// where it was.
+    fn mouse_moved(&mut self, column: u16, row: u16) {
+        if self
+            .hover
+            .as_ref()
+            .is_some_and(|h| (h.column, h.row) == (column, row))
+        {
+            return;
+        }
+        if let Some(hover) = self.hover.replace(Hover::new(column, row)) {
+            if hover.is_shown() {
+                self.dirty.mark_all();
+                self.should_render = true;
+            }
+        }
+    }
+
+    /// Works out the tooltip for what's under the mouse once it has rested for a while,
+    /// returning whether there's a tooltip to draw.
+    fn show_tooltip(&mut self) -> bool {
+        let Some(hover) = self.hover.as_ref().filter(|h| h.is_due()) else {
+            return false;
+        };
+        let tooltip = self.tooltip_at(hover.column, hover.row);
+        let shown = !tooltip.is_empty();
+        if let Some(hover) = &mut self.hover {
+            hover.tooltip = Some(tooltip);
+        }
+        self.should_render |= shown;
+        shown
+    }
+
+    /// The lines of the tooltip for a cell of the screen: the messages of the build errors
+    /// there, whether over the text or over the errors drawn among it.
+    fn tooltip_at(&self, column: u16, row: u16) -> Vec<String> {
+        let height = self.text_height();
+        let Some(document) = self.document() else {
+            return vec![];
+        };
+        let Some(path) = document.path() else {
+            return vec![];
+        };
+        let Some(cell) = (column as usize).checked_sub(self.text_offset()) else {
+            return vec![];
+        };
+        if row as u32 >= height
+            || cell >= self.text_width()
+            || document.is_binary()
+            || !self.layers().is_empty()
+        {
+            return vec![];
+        }
+
+        let rows =
+            document
+                .virtual_text()
+                .rows(document.folds(), self.top, document.line_count(), height);
+        match rows.get(row as usize) {
+            Some(Row::Virtual(annotation)) if annotation.source == Source::Errors => {
+                annotation.text.lines().map(String::from).collect()
+            }
+            Some(Row::Line(line)) => {
+                let line = *line;
+                let text = document.get_lines(line..line + 1).pop().unwrap_or_default();
+                let tab_width = self.settings().tab_width;
+                let end = layout::display_column(&text, text.chars().count() as u32, tab_width);
+                let column = (cell < end).then(|| layout::column_at(&text, cell, tab_width));
+                let entries = self.quickfix.entries_in(path).filter(|e| e.line == line);
+                hover::diagnostics(entries, &text, column)
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Shows the keys which can follow a chord's prefix once the chord has been pending for a
+    /// while, returning whether the hint needs to be drawn.
+    fn show_chord_hint(&mut self) -> bool {
+        match &mut self.chord {
+            Some(pending) if !pending.hint && pending.started.elapsed() >= chord::HINT_DELAY => {
+                pending.hint = true;
+                self.should_render = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Ends the pending chord, running the command bound to the key pressed after its prefix.
+    fn finish_chord(&mut self, key: Option<char>) -> io::Result<()> {
+        let Some(pending) = self.chord.take() else {
+            return Ok(());
+        };
+        if pending.hint {
+            self.dirty.mark_all();
+        }
+        let Some(key) = key else {
+            return Ok(());
+        };
+
+        match pending.chord.command(key) {
+            Some(command) => match Command::parse(command) {
+                Ok(command) => self.execute(command)?,
+                Err(e) => self.message = Some(e),
+            },
+            None => {
+                self.message = Some(format!(
+                    "{} {} is not bound",
+                    pending.chord.prefix,
+                    chord::key_name(key)
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the prompt's command, adding its input to the prompt's history. Input which can't
+    /// be submitted is left to be corrected, with the reason shown beside it.
+    fn submit_prompt(&mut self) -> io::Result<()> {
+        let Some(prompt) = &self.prompt else {
+            return Ok(());
+        };
+        if prompt.validate().is_err() && !prompt.input().is_empty() {
+            return Ok(());
+        }
+        let Some(prompt) = self.prompt.take() else {
+            return Ok(());
+        };
+        // The cursor goes back first so jumping to the line or match records where it came from.
+        self.return_to_origin();
+        self.prompt_preview = None;
+
+        if let Some(history) = self.prompt_history(prompt.kind) {
+            history.push(prompt.value());
+            if let Err(e) = history.save() {
+                error!("Unable to save prompt history: {}", e);
+            }
+        }
+        match prompt.command() {
+            Ok(command) => self.execute(command)?,
+            Err(e) => self.message = Some(e),
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, command: Command) -> io::Result<()> {
+        match command {
+            Command::Write {
+                path: Some(path),
+                force,
+            } => self.save_as(path, force),
+            Command::Write { path: None, force } => self.save(force)?,
+            Command::WriteAll => self.save_all(),
+            Command::Quit => self.exit(),
+            Command::QuitAll { force } => self.quit_all(force),
+            Command::CloseOthers { force } => self.close_others(force),
+            Command::Edit {
+                path: Some(path),
+                force: _,
+            } => {
+                self.edit(path);
+            }
+            Command::Edit { path: None, force } => self.reload(force),
+            Command::Open(paths) => {
+                let count = paths.len();
+                let opened = paths
+                    .into_iter()
+                    .filter(|path| self.edit(path.clone()))
+                    .count();
+                if count > 1 && opened == count {
+                    self.message = Some(format!("Opened {} files", opened));
+                }
+            }
+            Command::Rename { path, force } => self.rename_file(path, force),
+            Command::DeleteFile { force } => self.delete_file(force),
+            Command::ReadOnly(readonly) => self.set_readonly(readonly),
+            Command::New => self.open(Document::new()),
+            Command::Drafts { force } => self.restore_drafts(force),
+            Command::Recent => self.open_recent_files(),
+            Command::Buffers => self.pick_buffer(),
+            Command::Help(topic) => self.help(topic.as_deref()),
+            Command::Goto(line) => {
+                self.record_jump();
+                self.goto(line, 0);
+            }
+            Command::Find { pattern, forward } => self.find(pattern, forward),
+            Command::Fold(action) => self.fold(action),
+            Command::ChangeCase(case) => self.change_case(case),
+            Command::Sort(options) => self.transform_lines(|lines| sort::sort(lines, options)),
+            Command::Unique => {
+                self.transform_lines(|lines| *lines = sort::unique(std::mem::take(lines)))
+            }
+            Command::Reverse => self.transform_lines(|lines| lines.reverse()),
+            Command::Reflow => self.reflow(),
+            Command::Reindent => self.reindent(),
+            Command::Inspect => self.inspect(),
+            Command::Info => {
+                // The counts cover the whole document, so the rest of it is loaded first.
+                if let Some(Err(e)) = self.document_mut().map(|d| d.load_until(u32::MAX)) {
+                    self.message = Some(e.to_string());
+                    return Ok(());
+                }
+                self.info = self
+                    .document()
+                    .map(|document| Info::of(document).render(SystemTime::now()));
+            }
+            Command::Make(arguments) => self.make(arguments)?,
+            Command::NextError => self.next_error(),
+            Command::PreviousError => self.previous_error(),
+            Command::ListErrors => self.list_errors(),
+            Command::Undo => self.undo(),
+            Command::Redo => self.redo(),
+            Command::UndoTree => self.open_undo_tree(),
+            Command::Earlier(distance) => {
+                self.move_through_history(|d| d.earlier(distance), "Already at the oldest change")
+            }
+            Command::Later(distance) => {
+                self.move_through_history(|d| d.later(distance), "Already at the newest change")
+            }
+            Command::Shell(command) => self.run_shell(&command)?,
+            Command::Filter { lines, command } => self.filter(lines, &command)?,
+            Command::Align(delimiter) => {
+                self.transform_lines(|lines| align::align(lines, &delimiter))
+            }
+            Command::ToggleAnsi => self.toggle_ansi(),
+            Command::ToggleMinimap => {
+                self.minimap = !self.minimap;
+                self.dirty.mark_all();
+            }
+            Command::ToggleScrollbar => {
+                self.scrollbar = !self.scrollbar;
+                self.dirty.mark_all();
+            }
+            Command::ToggleIndentGuides => {
+                self.indent_guides = !self.indent_guides;
+                self.dirty.mark_all();
+            }
+            Command::ToggleSubwords => {
+                if let Some(buffer) = self.buffers.get_mut(self.current) {
+                    let subwords = &mut buffer.settings.subword_motion;
+                    *subwords = !*subwords;
+                    self.message = Some(String::from(match subwords {
+                        true => "Subword motion on",
+                        false => "Subword motion off",
+                    }));
+                }
+            }
+            Command::Record(Some(register)) => {
+                self.macros.start(register);
+                self.message = Some(format!("Recording @{}", register));
+                self.post_recording();
+            }
+            Command::Record(None) => {
+                self.message = Some(match self.macros.stop() {
+                    Some((register, keys)) => {
+                        let text = macros::to_text(&keys);
+                        self.registers.set(register, Register::new(text, false));
+                        format!("Recorded {} keys into @{}", keys.len(), register)
+                    }
+                    None => String::from("Not recording"),
+                });
+                self.post_recording();
+            }
+            Command::Play(register) => self.play_macro(register),
+            Command::Substitute(substitution) => self.substitute(substitution),
+            Command::Mark(name) => self.set_mark(name),
+            Command::DeleteMark(name) => self.delete_mark(name),
+            Command::Marks => self.open_marks(),
+            Command::JumpToMark(name) => self.jump_to_mark(name),
+            Command::DeleteLines(register) => {
+                let lines = self.given_lines().unwrap_or(self.row..=self.row);
+                self.delete_lines(lines, register);
+            }
+            Command::Yank(register) => self.yank_lines(register),
+            Command::Put(register) => self.put(register),
+            Command::Registers => self.open_registers(),
+            Command::Complete(source) => self.complete(source),
+            Command::Select(object) => self.select_object(object),
+            Command::Surround { pair, object } => self.surround(pair, object),
+            Command::Unsurround(target) => self.unsurround(target, None),
+            Command::Resurround { target, pair } => self.unsurround(target, Some(pair)),
+            Command::Ranged { lines, command } => self.execute_ranged(lines, command)?,
+            Command::EditMacro(register) => self.edit_macro(register),
+            Command::TogglePaste => {
+                self.paste_mode = !self.paste_mode;
+                self.message = Some(String::from(match self.paste_mode {
+                    true => "Paste mode on",
+                    false => "Paste mode off",
+                }));
+            }
+            Command::ToggleZen => self.toggle_zen(),
+            Command::TogglePerf => self.show_perf = !self.show_perf,
+            Command::Messages => self.show_messages(),
+            Command::Bookmark => self.toggle_sign(Sign::bookmark()),
+            Command::Todo => self.toggle_sign(Sign::todo()),
+            Command::NextSign => self.jump_to_sign(true),
+            Command::PreviousSign => self.jump_to_sign(false),
+            Command::Merge => self.toggle_merge(),
+            Command::Resolve(keep) => self.resolve_conflict(keep),
+            Command::NextConflict => self.jump_to_conflict(true),
+            Command::PreviousConflict => self.jump_to_conflict(false),
+            Command::Export {
+                format,
+                path,
+                force,
+            } => self.export(format, path, force),
+        }
+        Ok(())
+    }
+
+    /// Runs a command on a range of lines, which it acts on in place of the selection. Without
+    /// a command, the cursor moves to the last line of the range.
+    ///
+    /// # Arguments
+    ///
+    /// * 'lines' - The range, as it was entered.
+    /// * 'command' - The command to run on the lines.
+    fn execute_ranged(
+        &mut self,
+        lines: LineRange,
+        command: Option<Box<Command>>,
+    ) -> io::Result<()> {
+        if let Some(Err(e)) = self.document_mut().map(|d| d.load_until(u32::MAX)) {
+            self.message = Some(e.to_string());
+            return Ok(());
+        }
+        let Some(last) = self.document().map(|d| d.line_count().max(1)) else {
+            return Ok(());
+        };
+        let range = match lines.resolve(self.row, last, |name| self.mark_line(name)) {
+            Ok(range) => range,
+            Err(e) => {
+                self.message = Some(e);
+                return Ok(());
+            }
+        };
+
+        match command {
+            Some(command) => {
+                self.range = Some(range);
+                let result = self.execute(*command);
+                self.range = None;
+                result?;
+            }
+            None => {
+                self.record_jump();
+                self.goto(*range.end(), 0);
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves every modified document, reporting how many were written. Untitled and read-only
+    /// documents are skipped, as they need to be saved individually.
+    fn save_all(&mut self) {
+        let (mut written, mut skipped) = (0, 0);
+        let mut errors = vec![];
+        for index in 0..self.buffers.len() {
+            let document = &self.buffers[index].document;
+            if !document.is_modified() {
+                continue;
+            }
+            if document.path().is_none() || document.is_readonly() {
+                skipped += 1;
+                continue;
+            }
+            let result = self
+                .with_encryption(document.is_encrypted(), |editor| {
+                    let buffer = &mut editor.buffers[index];
+                    buffer
+                        .apply_save_edits()
+                        .and_then(|_| buffer.document.save())
+                })
+                .unwrap_or_else(|e| Err(e.into()));
+            match result {
+                Ok(()) => written += 1,
+                Err(e) => errors.push(format!("{}: {}", self.buffers[index].document.name(), e)),
+            }
+        }
+        self.clamp_cursor_column();
+        self.dirty.mark_all();
+
+        let mut message = match written {
+            1 => String::from("1 file written"),
+            _ => format!("{} files written", written),
+        };
+        if skipped > 0 {
+            message += &format!(", {} untitled or read-only skipped", skipped);
+        }
+        if let Some(error) = errors.first() {
+            message += &format!(", {} failed ({})", errors.len(), error);
+        }
+        self.message = Some(message);
+    }
+
+    /// Closes the editor, as long as there are no unsaved changes or the quit is forced.
+    /// Unnamed buffers don't need saving, as they're kept as drafts.
+    fn quit_all(&mut self, force: bool) {
+        let modified = self
+            .buffers
+            .iter()
+            .filter(|b| b.document.is_modified() && !self.is_draft(b))
+            .count();
+        if modified > 0 && !force {
+            self.message = Some(format!(
+                "{} buffer(s) have unsaved changes (add ! to quit anyway)",
+                modified
+            ));
+            return;
+        }
+        self.exit();
+        // Forcing the quit discards the drafts along with any other changes if they can't be
+        // saved.
+        if force {
+            self.exit = true;
+        }
+    }
+
+    /// Closes every buffer except the current one. Buffers with unsaved changes are kept unless
+    /// the close is forced.
+    fn close_others(&mut self, force: bool) {
+        let closed: Vec<usize> = self
+            .buffers
+            .iter()
+            .enumerate()
+            .filter(|(index, buffer)| {
+                *index != self.current && (force || !buffer.document.is_modified())
+            })
+            .map(|(index, _)| index)
+            .collect();
+        self.current -= closed.iter().filter(|&&index| index < self.current).count();
+        self.remove_buffers(&closed);
+
+        let closed = closed.len();
+        let kept = self.buffers.len() - 1;
+        self.message = Some(match kept {
+            0 => format!("{} buffer(s) closed", closed),
+            _ => format!(
+                "{} buffer(s) closed, {} with unsaved changes kept (add ! to close them)",
+                closed, kept
+            ),
+        });
+    }
+
+    /// Removes buffers, along with the jumps made in them. The jumps made in the buffers which
+    /// remain are renumbered to match their new indices.
+    ///
+    /// # Arguments
+    ///
+    /// * 'closed' - The indices of the buffers to remove, in ascending order.
+    fn remove_buffers(&mut self, closed: &[usize]) {
+        let mut index = 0;
+        self.buffers.retain(|_| {
+            let keep = closed.binary_search(&index).is_err();
+            index += 1;
+            keep
+        });
+        self.jumps.remove_buffers(closed);
+    }
+
+    /// Asks a question, running a command if it's answered yes.
+    ///
+    /// # Arguments
+    ///
+    /// * 'text' - The question, which is followed by the keys to answer it with.
+    /// * 'command' - The command to run.
+    fn ask(&mut self, text: String, command: Command) {
+        self.question = Some(Question {
+            text: format!("{} (y/n)", text),
+            command,
+            buffer: self.current,
+            paste: None,
+        });
+    }
+
+    /// Offers to open the files whose paths were pasted, as terminals paste the paths of files
+    /// dropped onto them. The paths are pasted as text if the offer is turned down.
+    ///
+    /// # Arguments
+    ///
+    /// * 'text' - The pasted text.
+    /// * 'paths' - The files in the text.
+    fn offer_to_open(&mut self, text: String, paths: Vec<PathBuf>) {
+        let question = match paths.as_slice() {
+            [path] => format!("Open {}?", path.display()),
+            _ => format!("Open {} dropped files?", paths.len()),
+        };
+        self.ask(question, Command::Open(paths));
+        if let Some(question) = &mut self.question {
+            question.paste = Some(text);
+        }
+    }
+
+    /// Answers the question being asked, running its command if the answer is yes. Any key
+    /// other than y or n asks the question again.
+    ///
+    /// # Arguments
+    ///
+    /// * 'answer' - The key pressed, or None if a key other than a character was pressed, which
+    ///   answers no.
+    fn answer(&mut self, answer: Option<char>) -> io::Result<()> {
+        let Some(question) = self.question.take() else {
+            return Ok(());
+        };
+        self.dirty.mark_all();
+        match answer {
+            Some('y') => {
+                self.switch_buffer(question.buffer);
+                return self.execute(question.command);
+            }
+            Some('n') | None => match question.paste {
+                Some(text) => {
+                    self.kills.push(text.clone());
+                    self.paste(text);
+                }
+                None => self.message = Some(String::from("Cancelled")),
+            },
+            Some(_) => self.question = Some(question),
+        }
+        Ok(())
+    }
+
+    /// Moves the current document's file to a new path, keeping the document open. A path to
+    /// a directory moves the file into it. The file's recent files entry, global marks and
+    /// folds move along with it.
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - Where to move the file, relative to the working directory.
+    /// * 'force' - Whether to go ahead without asking first.
+    fn rename_file(&mut self, path: PathBuf, force: bool) {
+        let Some(from) = self.document().and_then(|d| d.path()).map(PathBuf::from) else {
+            self.message = Some(String::from("The document has no file to rename"));
+            return;
+        };
+        let mut to = match std::env::current_dir() {
+            Ok(dir) => dir.join(path),
+            Err(_) => path,
+        };
+        if let (true, Some(name)) = (to.is_dir(), from.file_name()) {
+            to.push(name);
+        }
+        if to == from {
+            self.message = Some(String::from("The document already has that path"));
+            return;
+        }
+        if !force {
+            let overwriting = match to.exists() {
+                true => ", replacing the file there",
+                false => "",
+            };
+            let text = format!("Rename to {}{}?", to.display(), overwriting);
+            return self.ask(
+                text,
+                Command::Rename {
+                    path: to,
+                    force: true,
+                },
+            );
+        }
+
+        let result = match self.buffers.get_mut(self.current) {
+            Some(buffer) => buffer.document.rename(to.clone()),
+            None => return,
+        };
+        if let Err(e) = result {
+            self.message = Some(format!("Unable to rename {}: {}", from.display(), e));
+            return;
+        }
+        // The new path may mean the document has a different filetype, or is in another project.
+        let config = self.project_config(Some(&to));
+        if let Some(buffer) = self.buffers.get_mut(self.current) {
+            buffer.settings = config.settings(buffer.document.filetype());
+        }
+        self.move_file_records(&from, Some(&to));
+        self.message = Some(format!("Renamed to {}", to.display()));
+        self.lock_file();
+        self.dirty.mark_all();
+    }
+
+    /// Deletes the current document's file and closes the document, switching to another
+    /// buffer. The file's recent files entry, global marks and folds are forgotten.
+    ///
+    /// # Arguments
+    ///
+    /// * 'force' - Whether to go ahead without asking first.
+    fn delete_file(&mut self, force: bool) {
+        let Some(path) = self.document().and_then(|d| d.path()).map(PathBuf::from) else {
+            self.message = Some(String::from("The document has no file to delete"));
+            return;
+        };
+        if !force {
+            let text = format!("Delete {} from disk?", path.display());
+            return self.ask(text, Command::DeleteFile { force: true });
+        }
+
+        if let Some(Err(e)) = self.document().map(Document::delete_file) {
+            self.message = Some(format!("Unable to delete {}: {}", path.display(), e));
+            return;
+        }
+        self.move_file_records(&path, None);
+        self.close_buffer();
+        self.message = Some(format!("Deleted {}", path.display()));
+    }
+
+    /// Updates what's remembered about a file between sessions once it's been renamed or
+    /// deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * 'from' - The file's old path.
+    /// * 'to' - The file's new path, or None if it was deleted.
+    fn move_file_records(&mut self, from: &Path, to: Option<&Path>) {
+        let to_entry = to.map(|to| to.display().to_string());
+        self.recent_files
+            .replace(&from.display().to_string(), to_entry.as_deref());
+        if let Err(e) = self.recent_files.save() {
+            error!("Unable to save recent files: {}", e);
+        }
+        self.mark_store.move_file(from, to);
+        self.save_marks();
+        self.fold_store.move_file(from, to);
+        if let Err(e) = self.fold_store.save() {
+            error!("Unable to save folds: {}", e);
+        }
+    }
+
+    /// Closes the current buffer without saving it, switching to the next buffer, or to a new
+    /// untitled document if it was the only one.
+    fn close_buffer(&mut self) {
+        if self.current >= self.buffers.len() {
+            return;
+        }
+        self.remove_buffers(&[self.current]);
+        let next = self.current.min(self.buffers.len().saturating_sub(1));
+        // The closed buffer's cursor isn't saved when switching away from it.
+        self.current = usize::MAX;
+        match self.buffers.is_empty() {
+            true => self.open(Document::new()),
+            false => self.switch_buffer(next),
+        }
+        self.dirty.mark_all();
+    }
+
+    /// Opens a document in a new buffer, or switches to it if it's already open, returning
+    /// whether it could be opened. Documents which don't exist yet are created when they're
+    /// first saved.
+    fn edit(&mut self, path: PathBuf) -> bool {
+        let path = match std::env::current_dir() {
+            Ok(dir) => dir.join(path),
+            Err(_) => path,
+        };
+        if let Some(index) = self
+            .buffers
+            .iter()
+            .position(|b| b.document.path() == Some(path.as_path()))
+        {
+            self.switch_buffer(index);
+            return true;
+        }
+
+        let encrypted = self.config.encryption(&path).is_some();
+        let document = self
+            .with_encryption(encrypted, |editor| match path.exists() {
+                true => Document::open(path, &editor.config),
+                false => Ok(Document::create(path, &editor.config)),
+            })
+            .unwrap_or_else(|e| Err(e.into()));
+        match document {
+            Ok(document) => {
+                self.open(document);
+                true
+            }
+            Err(e) => {
+                self.message = Some(e.to_string());
+                false
+            }
+        }
+    }
+
+    /// Sets or clears a subsystem's segment of the status line, by posting an event so it's
+    /// updated the same way as segments set by other threads.
+    ///
+    /// # Arguments
+    ///
+    /// * 'subsystem' - The subsystem the segment belongs to.
+    /// * 'text' - The segment's text, or `None` to clear it.
+    fn post_status(&self, subsystem: Subsystem, text: Option<String>) {
+        let _ = self.sender.send(Event::Status(subsystem, text));
+    }
+
+    /// Shows the register being recorded into in the status line, or clears it once recording
+    /// stops.
+    fn post_recording(&self) {
+        let text = self.macros.recording().map(|r| format!("Recording @{}", r));
+        self.post_status(Subsystem::Macro, text);
+    }
+
+    /// Discards the current document's changes and loads it from disk again, keeping the cursor
+    /// on the same line where possible. Modified documents are only reloaded when forced.
+    fn reload(&mut self, force: bool) {
+        let Some(document) = self.document() else {
+            return;
+        };
+        let Some(path) = document.path().map(PathBuf::from) else {
+            self.message = Some(String::from("No file name"));
+            return;
+        };
+        if document.is_modified() && !force {
+            self.message = Some(String::from(
+                "Document has unsaved changes (add ! to discard them)",
+            ));
+            return;
+        }
+
+        let readonly = document.is_readonly();
+        let encrypted = self.config.encryption(&path).is_some();
+        let document = self
+            .with_encryption(encrypted, |editor| Document::open(path, &editor.config))
+            .unwrap_or_else(|e| Err(e.into()));
+        let mut document = match document {
+            Ok(document) => document,
+            Err(e) => {
+                self.message = Some(e.to_string());
+                return;
+            }
+        };
+        // Keep documents opened with --readonly read-only.
+        if readonly {
+            document.set_readonly(true);
+        }
+        if let Err(e) = document.load_until(self.row) {
+            self.message = Some(e.to_string());
+        }
+
+        let settings = self
+            .project_config(document.path())
+            .settings(document.filetype());
+        let buffer = &mut self.buffers[self.current];
+        buffer.loading = document.load_progress().is_some();
+        buffer.settings = settings;
+        // Signs are placed by the user rather than worked out from the text, so they're kept.
+        *document.signs_mut() = std::mem::take(buffer.document.signs_mut());
+        buffer.document = document;
+
+        self.anchor = None;
+        let line_count = self.document().map_or(1, |d| d.line_count().max(1));
+        self.row = self.row.min(line_count);
+        self.clamp_cursor_column();
+        self.scroll_to_cursor();
+        self.dirty.mark_all();
+        self.message = Some(String::from("Reloaded from disk"));
+    }
+
+    /// Saves the current document. Read-only documents are only saved when forced, in which case
+    /// the file is made writable for the duration of the save, falling back to saving with sudo
+    /// if the current user doesn't have permission to write to it.
+    fn save(&mut self, force: bool) -> io::Result<()> {
+        if let Some(register) = self.buffers.get(self.current).and_then(|b| b.register) {
+            self.store_macro(register);
+            return Ok(());
+        }
+        // Untitled documents need a path, so a prompt is opened to enter one.
+        if self.document().is_some_and(|d| d.path().is_none()) {
+            self.open_prompt(Prompt::new(Kind::SaveAs { force }));
+            return Ok(());
+        }
+        if self.document().is_some_and(|d| d.is_readonly()) && !force {
+            self.message = Some(String::from("Document is read-only (add ! to override)"));
+            return Ok(());
+        }
+
+        if let Err(e) = self.apply_save_edits() {
+            self.message = Some(e.to_string());
+            return Ok(());
+        }
+
+        let encrypted = self.document().is_some_and(|d| d.is_encrypted());
+        let result = self.with_encryption(encrypted, |editor| match editor.document_mut() {
+            Some(document) if force => document.force_save(),
+            Some(document) => document.save(),
+            None => Ok(()),
+        })?;
+
+        match result {
+            Ok(()) => {
+                self.message = Some(self.written_message());
+                // Folds move as lines are edited, so they're saved with the document.
+                self.save_folds();
+            }
+            Err(e) if force && e.is_permission_denied() => self.save_elevated()?,
+            Err(e) if e.is_permission_denied() => {
+                self.message = Some(format!("{} (add ! to override)", e));
+            }
+            Err(e) => self.message = Some(e.to_string()),
+        }
+        Ok(())
+    }
+
+    /// Suspends the editor so the shell's job control can be used, redrawing the whole screen
+    /// once it's resumed.
+    #[cfg(unix)]
+    fn suspend(&mut self) -> io::Result<()> {
+        if let Some(input) = self.input.take() {
+            input.stop();
+        }
+        self.terminal.suspend()?;
+        self.input = Some(InputPoller::start(self.sender.clone()));
+        self.dirty.mark_all();
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn suspend(&mut self) -> io::Result<()> {
+        self.message = Some(String::from("Suspend isn't supported on this platform"));
+        Ok(())
+    }
+
+    /// Runs a shell command with the terminal restored, so the command can use it while it
+    /// runs.
+    fn with_terminal_restored<T>(&mut self, run: impl FnOnce(&mut Editor) -> T) -> io::Result<T> {
+        if let Some(input) = self.input.take() {
+            input.stop();
+        }
+        self.terminal.shutdown()?;
+        let result = run(self);
+        self.terminal.startup()?;
+        self.input = Some(InputPoller::start(self.sender.clone()));
+        self.dirty.mark_all();
+        Ok(result)
+    }
+
+    /// Loads or saves a document with the terminal restored if it's encrypted, as the commands
+    /// which decrypt and encrypt it may prompt for a passphrase.
+    ///
+    /// # Arguments
+    ///
+    /// * 'encrypted' - Whether the document is encrypted, or will be once it's saved.
+    /// * 'run' - Loads or saves the document.
+    fn with_encryption<T>(
+        &mut self,
+        encrypted: bool,
+        run: impl FnOnce(&mut Editor) -> T,
+    ) -> io::Result<T> {
+        match encrypted {
+            true => self.with_terminal_restored(run),
+            false => Ok(run(self)),
+        }
+    }
+
+    /// Runs the configured build command from the current document's project root, or the
+    /// working directory outside of a project, and collects the error locations from its output,
+    /// jumping to the first one.
+    fn make(&mut self, arguments: Option<String>) -> io::Result<()> {
+        let path = self.document().and_then(|d| d.path()).map(PathBuf::from);
+        let root = path
+            .as_deref()
+            .and_then(|path| project::root(path, &self.config.root_markers));
+        // Building with the global command instead would be surprising, so the project has to be
+        // trusted first.
+        if let Some((root, Some(Ok(project)))) =
+            root.as_deref().map(|r| (r, ProjectConfig::load(r)))
+        {
+            if project.make_command.is_some() && !self.config.is_trusted(root) {
+                self.message = Some(format!(
+                    "Add {} to trusted_projects to run its make_command",
+                    root.display()
+                ));
+                return Ok(());
+            }
+        }
+
+        let make_command = self.project_config(path.as_deref()).make_command;
+        let command = match arguments {
+            Some(arguments) => format!("{} {}", make_command, arguments),
+            None => make_command,
+        };
+        let dir = root
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_default();
+        let result = self.with_terminal_restored(|_| shell::run_in(&command, None, &dir))?;
+        let output = match result {
+            Ok(output) => output,
+            Err(e) => {
+                self.message = Some(e.to_string());
+                return Ok(());
+            }
+        };
+
+        let entries = quickfix::parse(&(output.stdout.clone() + &output.stderr));
+        self.quickfix = QuickFix::new(entries, dir);
+        for buffer in &mut self.buffers {
+            buffer.annotate_errors(&self.quickfix);
+        }
+        self.dirty.mark_all();
+        match self.quickfix.len() {
+            0 if output.status.success() => {
+                self.message = Some(String::from("Build succeeded"));
+            }
+            0 => self.message = Some(output.error()),
+            _ => self.next_error(),
+        }
+        Ok(())
+    }
+
+    fn next_error(&mut self) {
+        let entry = self.quickfix.next().cloned();
+        self.jump_to_error(entry);
+    }
+
+    fn previous_error(&mut self) {
+        let entry = self.quickfix.previous().cloned();
+        self.jump_to_error(entry);
+    }
+
+    /// Opens the error location's document and moves the cursor to it, showing the error.
+    fn jump_to_error(&mut self, entry: Option<quickfix::Entry>) {
+        let Some(entry) = entry else {
+            self.message = Some(match self.quickfix.len() {
+                0 => String::from("No errors"),
+                _ => String::from("No more errors"),
+            });
+            return;
+        };
+        let path = self.quickfix.path(&entry);
+        if !path.exists() {
+            self.message = Some(format!("File not found: {}", entry.path.display()));
+            return;
+        }
+
+        self.record_jump();
+        if self.edit(path) {
+            self.goto(entry.line, entry.column.unwrap_or(1).saturating_sub(1));
+            self.message = Some(format!(
+                "({}/{}) {}",
+                self.quickfix.current().map_or(0, |i| i + 1),
+                self.quickfix.len(),
+                entry.message
+            ));
+        }
+    }
+
+    /// Opens the most recent log messages in a new buffer, scrolled to the newest.
+    fn show_messages(&mut self) {
+        let Some(path) = &self.log_file else {
+            self.message = Some(String::from("Logging is disabled"));
+            return;
+        };
+        match logging::recent_messages(path, logging::MESSAGE_LINES) {
+            Ok(messages) => {
+                let mut document = Document::scratch("[Messages]", &messages);
+                document.set_readonly(true);
+                let last = document.line_count();
+                self.open(document);
+                self.goto(last, 0);
+            }
+            Err(e) => self.message = Some(format!("Unable to read {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Opens the error locations from the last build in a new buffer.
+    fn list_errors(&mut self) {
+        match self.quickfix.len() {
+            0 => self.message = Some(String::from("No errors")),
+            _ => self.open(Document::scratch("[Errors]", &self.quickfix.list())),
+        }
+    }
+
+    /// Runs a shell command, opening its output in a new buffer.
+    fn run_shell(&mut self, command: &str) -> io::Result<()> {
+        let result = self.with_terminal_restored(|_| shell::run(command, None))?;
+        let output = match result {
+            Ok(output) => output,
+            Err(e) => {
+                self.message = Some(e.to_string());
+                return Ok(());
+            }
+        };
+
+        let text = output.stdout.clone() + &output.stderr;
+        if !text.is_empty() {
+            self.open(Document::scratch(&format!("[!{}]", command), &text));
+        }
+        if !output.status.success() {
+            self.message = Some(output.error());
+        } else if text.is_empty() {
+            self.message = Some(String::from("Command finished with no output"));
+        }
+        Ok(())
+    }
+
+    /// Pipes lines through a shell command, replacing them with the command's output as a
+    /// single edit. The lines are left alone if the command fails.
+    fn filter(&mut self, lines: FilterLines, command: &str) -> io::Result<()> {
+        if !self.is_editable() || self.document().is_some_and(|d| d.is_binary()) {
+            return Ok(());
+        }
+        if lines == FilterLines::Selection && self.selection().is_none() {
+            self.message = Some(String::from("No selection"));
+            return Ok(());
+        }
+        if lines == FilterLines::All {
+            self.anchor = None;
+        }
+
+        let Some(range) = self.selected_lines() else {
+            return Ok(());
+        };
+
+        let input = self
+            .document()
+            .map(|d| d.get_lines(*range.start()..*range.end() + 1).join("\n") + "\n")
+            .unwrap_or_default();
+        let result = self.with_terminal_restored(|_| shell::run(command, Some(input)))?;
+        match result {
+            Ok(output) if output.status.success() => {
+                let stdout = output.stdout.strip_suffix('\n').unwrap_or(&output.stdout);
+                let replacement: Vec<String> = stdout.split('\n').map(String::from).collect();
+                self.transform_range(range, |content| *content = replacement);
+            }
+            Ok(output) => self.message = Some(output.error()),
+            Err(e) => self.message = Some(e.to_string()),
+        }
+        Ok(())
+    }
+
+    /// Saves the current document with sudo. The terminal is restored while sudo runs so it can
+    /// prompt for a password.
+    fn save_elevated(&mut self) -> io::Result<()> {
+        // Stop reading input so sudo can read the password from the terminal.
+        if let Some(input) = self.input.take() {
+            input.stop();
+        }
+        self.terminal.shutdown()?;
+        if let Some(path) = self.document().and_then(|d| d.path()) {
+            println!(
+                "Permission denied, attempting to write {:?} with sudo",
+                path
+            );
+        }
+
+        let result = match self.document_mut() {
+            Some(document) => document.save_elevated(),
+            None => Ok(()),
+        };
+        self.terminal.startup()?;
+        self.input = Some(InputPoller::start(self.sender.clone()));
+        self.dirty.mark_all();
+
+        self.message = Some(match result {
+            Ok(()) => self.written_message(),
+            Err(e) => e.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Saves the current document to a new path. Existing files are only overwritten when forced.
+    fn save_as(&mut self, path: PathBuf, force: bool) {
+        if path.exists() && !force {
+            self.message = Some(String::from("File exists (add ! to override)"));
+            return;
+        }
+        if let Err(e) = self.apply_save_edits() {
+            self.message = Some(e.to_string());
+            return;
+        }
+
+        let project = self.project_config(Some(&path));
+        let encrypted = self.config.encryption(&path).is_some();
+        let result = self
+            .with_encryption(encrypted, |editor| {
+                match editor.buffers.get_mut(editor.current) {
+                    Some(buffer) => buffer.document.save_as(path, &editor.config),
+                    None => Ok(()),
+                }
+            })
+            .unwrap_or_else(|e| Err(e.into()));
+
+        // The new path may mean the document has a different filetype, or is in another project.
+        if let Some(buffer) = self.buffers.get_mut(self.current) {
+            buffer.settings = project.settings(buffer.document.filetype());
+            self.dirty.mark_all();
+        }
+
+        self.message = Some(match result {
+            Ok(()) => self.written_message(),
+            Err(e) => e.to_string(),
+        });
+    }
+
+    /// Exports the selected lines, or the whole document, to a file. Without a path the file is
+    /// written next to the document, with the format's extension added to its name.
+    ///
+    /// # Arguments
+    ///
+    /// * 'format' - The format to export to.
+    /// * 'path' - Where to write the exported file.
+    /// * 'force' - Whether to replace an existing file.
+    fn export(&mut self, format: Format, path: Option<PathBuf>, force: bool) {
+        let Some(lines) = self.selected_lines() else {
+            return;
+        };
+        let tab_width = self.settings().tab_width;
+        let Some(document) = self.document() else {
+            return;
+        };
+
+        let path = match path.or_else(|| document.path().map(|p| p.to_path_buf())) {
+            Some(path) if path.extension().is_some_and(|e| e == format.extension()) => path,
+            Some(path) => {
+                let mut name = path.into_os_string();
+                name.push(".");
+                name.push(format.extension());
+                PathBuf::from(name)
+            }
+            None => {
+                self.message = Some(String::from("No file name"));
+                return;
+            }
+        };
+        if path.exists() && !force {
+            self.message = Some(String::from("File exists (add ! to override)"));
+            return;
+        }
+
+        let text = document.get_lines(*lines.start()..lines.end() + 1);
+        let exported = export::export(&text, *lines.start(), &document.name(), format, tab_width);
+        self.message = Some(match std::fs::write(&path, exported) {
+            Ok(()) => format!("Exported {}L to \"{}\"", text.len(), path.display()),
+            Err(e) => e.to_string(),
+        });
+    }
+
+    /// Makes the configured changes to the current document before it's saved, keeping the
+    /// cursor within the edited line.
+    fn apply_save_edits(&mut self) -> error::Result<()> {
+        if let Some(buffer) = self.buffers.get_mut(self.current) {
+            buffer.apply_save_edits()?;
+        }
+        self.clamp_cursor_column();
+        self.dirty.mark_all();
+        Ok(())
+    }
+
+    fn written_message(&self) -> String {
+        match self.document() {
+            Some(document) => format!(
+                "\"{}\" {}L, {}B written",
+                document.name(),
+                document.line_count(),
+                document.len()
+            ),
+            None => String::new(),
+        }
+    }
+
+    /// Ensures the lines in the viewport, and the line following it, have been loaded for
+    /// documents which are loaded lazily.
+    fn load_visible_lines(&mut self) {
+        let last_line = self.top + self.text_height();
+        if let Some(Err(e)) = self.document_mut().map(|d| d.load_until(last_line)) {
+            self.message = Some(e.to_string());
+        }
+    }
+
+    /// The number of terminal rows available for displaying the document.
+    fn text_height(&self) -> u32 {
+        self.terminal.size().height.saturating_sub(1) as u32
+    }
+
+    /// The number of lines to keep visible above and below the cursor, limited so the cursor can
+    /// still move within the viewport.
+    fn scrolloff(&self) -> u32 {
+        self.config
+            .scrolloff
+            .min(self.text_height().saturating_sub(1) / 2)
+    }
+
+    /// The last line which can be at the top of the viewport, keeping the end of the document at
+    /// the bottom of the screen.
+    fn max_top(&self) -> u32 {
+        let line_count = self.document().map_or(1, |d| d.line_count());
+        (line_count + 1).saturating_sub(self.text_height()).max(1)
+    }
+
+    /// Scrolls the viewport the minimum amount required for the cursor to be visible, along with
+    /// the configured number of lines around it.
+    fn scroll_to_cursor(&mut self) {
+        let height = self.text_height().max(1);
+        let scrolloff = self.scrolloff();
+        if self.row < self.top + scrolloff {
+            self.top = self.row.saturating_sub(scrolloff).max(1);
+        } else if self.row + scrolloff >= self.top + height {
+            let top = self.row + scrolloff + 1 - height;
+            self.top = top.min(self.max_top().max(self.top));
+        }
+        // Virtual text between lines can still push the cursor's line off the screen.
+        while self.top < self.row && self.cursor_row().is_none() {
+            self.top += 1;
+        }
+    }
+
+    /// Scrolls the viewport down, moving the cursor down only if it would otherwise leave the
+    /// screen.
+    fn scroll_down(&mut self, offset: u32) {
+        let top = (self.top + offset).min(self.max_top().max(self.top));
+        if top == self.top {
+            return;
+        }
+        self.top = top;
+
+        let min_row = self.top + self.scrolloff();
+        if self.row < min_row {
+            let cell = self.goal_cell();
+            let line_count = self.document().map_or(1, |d| d.line_count());
+            self.row = min_row.min(line_count);
+            self.move_to_cell(cell);
+        }
+    }
+
+    /// Scrolls the viewport up, moving the cursor up only if it would otherwise leave the screen.
+    fn scroll_up(&mut self, offset: u32) {
+        let top = self.top.saturating_sub(offset).max(1);
+        if top == self.top {
+            return;
+        }
+        self.top = top;
+
+        let max_row = (self.top + self.text_height().max(1) - 1).saturating_sub(self.scrolloff());
+        if self.row > max_row {
+            let cell = self.goal_cell();
+            self.row = max_row.max(self.top);
+            self.move_to_cell(cell);
+        }
+    }
+
+    /// Whether the current line is within the viewport.
+    fn cursor_visible(&self) -> bool {
+        self.cursor_row().is_some()
+    }
+
+    /// The row of the text area the current line is drawn on, counting any virtual text placed
+    /// between the lines above it, or None if it's outside the viewport.
+    fn cursor_row(&self) -> Option<u32> {
+        let height = self.text_height();
+        if self.row < self.top {
+            return None;
+        }
+        let Some(document) = self.document() else {
+            return Some(self.row - self.top).filter(|&row| row < height);
+        };
+        document
+            .virtual_text()
+            .rows(document.folds(), self.top, self.row, height)
+            .iter()
+            .position(|row| *row == Row::Line(self.row))
+            .map(|row| row as u32)
+    }
+
+    /// Scrolls the viewport one line at a time without moving the cursor, drawing each line as
+    /// it's scrolled so the movement is smooth.
+    ///
+    /// # Arguments
+    ///
+    /// * 'lines' - The number of lines to scroll, which is negative when scrolling up.
+    fn scroll_view(&mut self, lines: i64) -> io::Result<()> {
+        for _ in 0..lines.unsigned_abs() {
+            let top = match lines > 0 {
+                true => (self.top + 1).min(self.max_top().max(self.top)),
+                false => self.top.saturating_sub(1).max(1),
+            };
+            if top == self.top {
+                break;
+            }
+
+            self.top = top;
+            self.load_visible_lines();
+            self.dirty.mark_all();
+            self.render()?;
+            // The last step has already been drawn.
+            self.should_render = false;
+        }
+        Ok(())
+    }
+
+    /// Scrolls the viewport so the current line is in the center of the screen. Recentering
+    /// again moves the line to the top of the screen, then the bottom.
+    fn recenter(&mut self) {
+        let position = self.recenter.map_or(Recenter::Center, Recenter::next);
+        let height = self.text_height().max(1);
+        let scrolloff = self.scrolloff();
+
+        let top = match position {
+            Recenter::Center => self.row.saturating_sub(height / 2),
+            Recenter::Top => self.row.saturating_sub(scrolloff),
+            Recenter::Bottom => (self.row + scrolloff + 1).saturating_sub(height),
+        };
+        self.top = top.max(1);
+        self.recenter = Some(position);
+    }
+
+    /// Keeps the cursor within the bounds of the current line.
+    fn clamp_cursor_column(&mut self) {
+        if let Some(line_len) = self.document().map(|d| d.line_len(self.row)) {
+            self.column = self.column.min(line_len);
+        }
+    }
+
+    /// The terminal cell the cursor is displayed at within the current line. In the hex view this
+    /// is the index of the byte under the cursor.
+    fn cursor_cell(&self) -> usize {
+        match self.document() {
+            Some(document) if !document.is_binary() => document
+                .get_lines(self.row..self.row + 1)
+                .first()
+                .map_or(0, |line| match self.is_ansi() {
+                    true => layout::display_column(
+                        &ansi::strip(line),
+                        ansi::visible_column(line, self.column),
+                        self.settings().tab_width,
+                    ),
+                    false => layout::display_column(line, self.column, self.settings().tab_width),
+                }),
+            _ => self.column as usize,
+        }
+    }
+
+    /// Moves the cursor to the character displayed at a terminal cell on the current line, or
+    /// the end of the line if it's too short.
+    fn move_to_cell(&mut self, cell: usize) {
+        let Some(document) = self.document() else {
+            return;
+        };
+        let column =
+            match document.is_binary() {
+                true => cell as u32,
+                false => document
+                    .get_lines(self.row..self.row + 1)
+                    .first()
+                    .map_or(0, |line| match self.is_ansi() {
+                        true => {
+                            let visible = ansi::strip(line);
+                            let column =
+                                layout::column_at(&visible, cell, self.settings().tab_width);
+                            ansi::raw_column(line, column)
+                        }
+                        false => layout::column_at(line, cell, self.settings().tab_width),
+                    }),
+            };
+        self.column = column.min(document.line_len(self.row));
+    }
+
+    /// The cell to keep the cursor at while moving up and down, starting from where the cursor
+    /// is now.
+    fn goal_cell(&mut self) -> usize {
+        match self.goal_column {
+            Some(cell) => cell,
+            None => *self.goal_column.insert(self.cursor_cell()),
+        }
+    }
+
+    fn move_cursor_up(&mut self, offset: u32) {
+        let cell = self.goal_cell();
+        self.row = match self.document() {
+            Some(document) => document.folds().up(self.row, offset),
+            None => self.row.saturating_sub(offset).max(1),
+        };
+        self.move_to_cell(cell);
+        self.scroll_to_cursor();
+    }
+
+    fn move_cursor_down(&mut self, offset: u32) {
+        if let Some(line_count) = self.document().map(|d| d.line_count()) {
+            let cell = self.goal_cell();
+            self.row = match self.document() {
+                Some(document) => document.folds().down(self.row, offset, line_count.max(1)),
+                None => self.row,
+            };
+            self.move_to_cell(cell);
+            self.scroll_to_cursor();
+        }
+    }
+
+    /// Moves the cursor to the first non-whitespace character on the line, or to the start of
+    /// the line if it's already there.
+    fn move_cursor_home(&mut self) {
+        if let Some(indentation) = self.document().map(|d| d.indentation(self.row)) {
+            self.column = match self.column == indentation {
+                true => 0,
+                false => indentation,
+            };
+        }
+    }
+
+    fn move_cursor_left(&mut self, offset: u32) {
+        if self.is_ansi() {
+            return self.move_over_escapes(|column| column.saturating_sub(offset));
+        }
+        self.column = self.column.saturating_sub(offset);
+    }
+
+    fn move_cursor_right(&mut self, offset: u32) {
+        if self.is_ansi() {
+            return self.move_over_escapes(|column| column + offset);
+        }
+        if let Some(line_len) = self.document().map(|d| d.line_len(self.row)) {
+            self.column = (self.column + offset).min(line_len);
+        }
+    }
+
+    /// Moves the cursor within the current line by displayed characters, skipping over escape
+    /// sequences while they're shown as colours.
+    fn move_over_escapes(&mut self, step: impl FnOnce(u32) -> u32) {
+        let Some(line) = self
+            .document()
+            .and_then(|d| d.get_lines(self.row..self.row + 1).pop())
+        else {
+            return;
+        };
+        let line = line.trim_end_matches(['\r', '\n']);
+        let visible = ansi::strip(line).chars().count() as u32;
+        let column = step(ansi::visible_column(line, self.column)).min(visible);
+        self.column = ansi::raw_column(line, column);
+    }
+
+    /// Moves the cursor to the blank line after the current paragraph.
+    fn next_paragraph(&mut self) {
+        if let Some(row) = self.document().map(|d| d.next_paragraph(self.row)) {
+            self.move_to(Position { row, column: 0 });
+        }
+    }
+
+    /// Moves the cursor to the blank line before the current paragraph.
+    fn previous_paragraph(&mut self) {
+        if let Some(row) = self.document().map(|d| d.previous_paragraph(self.row)) {
+            self.move_to(Position { row, column: 0 });
+        }
+    }
+
+    fn next_sentence(&mut self) {
+        if let Some(position) = self.document().map(|d| d.next_sentence(self.cursor())) {
+            self.move_to(position);
+        }
+    }
+
+    fn previous_sentence(&mut self) {
+        if let Some(position) = self.document().map(|d| d.previous_sentence(self.cursor())) {
+            self.move_to(position);
+        }
+    }
+
+    fn next_word(&mut self) {
+        let subwords = self.settings().subword_motion;
+        if let Some(position) = self
+            .document()
+            .map(|d| d.next_word(self.cursor(), subwords))
+        {
+            self.move_to(position);
+        }
+    }
+
+    fn previous_word(&mut self) {
+        let subwords = self.settings().subword_motion;
+        if let Some(position) = self
+            .document()
+            .map(|d| d.previous_word(self.cursor(), subwords))
+        {
+            self.move_to(position);
+        }
+    }
+
+    /// Deletes from the cursor to the start of the next or previous word as a single edit, or
+    /// deletes the selection if there is one.
+    fn delete_word(&mut self, forward: bool) {
+        if !self.is_editable() || self.document().is_some_and(|d| d.is_binary()) {
+            return;
+        }
+        if self.selection().is_some() {
+            self.delete_selection();
+            return;
+        }
+        let cursor = self.cursor();
+        let subwords = self.settings().subword_motion;
+        let Some(document) = self.document_mut() else {
+            return;
+        };
+        let (start, end) = match forward {
+            true => (cursor, document.next_word(cursor, subwords)),
+            false => (document.previous_word(cursor, subwords), cursor),
+        };
+        document.delete(start, end);
+        self.move_to(start);
+        self.dirty.mark_all();
+    }
+
+    fn move_to(&mut self, position: Position) {
+        self.row = position.row;
+        self.column = position.column;
+        self.scroll_to_cursor();
+    }
+
+    fn handle_new_line(&mut self) {
+        if self.in_help() {
+            let line = self
+                .document()
+                .and_then(|d| d.get_lines(self.row..self.row + 1).pop())
+                .unwrap_or_default();
+            if let Some(topic) = help::link_at(&line, self.column as usize) {
+                self.anchor = None;
+                self.help(Some(topic));
+                return;
+            }
+        }
+        // Documents which can't be edited as text keep Enter for moving down.
+        if self.document().is_some_and(|d| !d.is_binary()) && self.is_editable() {
+            self.break_line();
+            return;
+        }
+        self.anchor = None;
+        self.column = 0;
+        self.move_cursor_down(1);
+    }
+
+    fn render_status_line(&self, document: &Document) -> String {
+        if let Some(prompt) = &self.prompt {
+            let text = prompt.label() + prompt.input();
+            return match prompt.validate() {
+                Err(e) if !prompt.input().is_empty() => format!("{}  {}", text, style(e).red()),
+                _ => text,
+            };
+        }
+        if let Some((picker, _)) = &self.picker {
+            return format!("{}> {}", picker.title(), picker.query());
+        }
+
+        if self.message_expanded {
+            return style(CONTINUE).bold().to_string();
+        }
+
+        if self.zen.is_some() && !self.config.zen_status_line {
+            return self.message.clone().unwrap_or_default();
+        }
+
+        let (status, pos) = self.status_parts(document);
+        let message = self
+            .message
+            .as_ref()
+            .map_or(String::new(), |message| format!("  {}", message));
+
+        // Long messages are cut short so the cursor position stays on screen.
+        let width = self.terminal.size().width as usize;
+        let available = width.saturating_sub(layout::display_width(&status) + pos.len());
+        let message = layout::truncate(&message, available);
+        let space_length = available - layout::display_width(message);
+        let spaces = " ".repeat(space_length);
+
+        format!(
+            "{}{}{}{}",
+            style(&status).bold().green(),
+            message,
+            spaces,
+            pos
+        )
+    }
+
+    /// The parts of the status line on either side of the message: the document's name followed
+    /// by the editor's state, and the filetype and cursor position.
+    fn status_parts(&self, document: &Document) -> (String, String) {
+        let mut status = document.name();
+        if document.is_readonly() {
+            status += " [RO]";
+        }
+        if document.is_encrypted() {
+            status += " [Encrypted]";
+        }
+        if self.is_ansi() {
+            status += " [ANSI]";
+        }
+        if self.overwrite {
+            status += " [Replace]";
+        }
+        if self.paste_mode {
+            status += " [Paste]";
+        }
+        if document.is_large() {
+            status += " [Large]";
+        }
+        if let Some(merge) = self
+            .buffers
+            .get(self.current)
+            .and_then(|b| b.merge.as_ref())
+        {
+            status += &match merge.conflicts.len() {
+                1 => String::from(" [1 conflict]"),
+                count => format!(" [{} conflicts]", count),
+            };
+        }
+        if document.is_outdated() {
+            status += " [Changed on disk]";
+        }
+        let loading = self.buffers.get(self.current).is_some_and(|b| b.loading);
+        if let Some(progress) = document.load_progress().filter(|_| loading) {
+            status += &format!(" [Loading {}%]", progress);
+        }
+        if let Some(literal) = &self.literal {
+            status += &format!(" [^V{}]", literal.keys());
+        }
+        if let Some(pending) = &self.chord {
+            status += &format!(" [{}-]", pending.chord.prefix);
+        }
+        status += &self.segments.render();
+        if self.show_perf {
+            status += &format!(" [{}]", self.perf.summary());
+        }
+        if self.buffers.len() > 1 {
+            status += &format!(" [{}/{}]", self.current + 1, self.buffers.len());
+        }
+
+        // Cursor position, or the offset of the byte under the cursor in the hex view.
+        let pos = match document.is_binary() {
+            true => format!(
+                "{:#010x}",
+                (self.row - 1) * hex::BYTES_PER_ROW as u32 + self.column
+            ),
+            false => format!("{}, {}", self.column + 1, self.row),
+        };
+        let pos = match document.filetype() {
+            Some(filetype) => format!("{}  {}", filetype, pos),
+            None => pos,
+        };
+        (status, pos)
+    }
+
+    /// The rows a message which doesn't fit in the status line is wrapped onto, drawn above the
+    /// status line, or nothing if it fits. There are no more rows than the text area has.
+    fn message_rows(&self, document: &Document) -> Vec<String> {
+        let Some(message) = &self.message else {
+            return vec![];
+        };
+        if self.prompt.is_some() || self.picker.is_some() {
+            return vec![];
+        }
+        let width = self.terminal.size().width as usize;
+        let (status, pos) = self.status_parts(document);
+        let available = width.saturating_sub(layout::display_width(&status) + pos.len());
+        if !message.contains('\n') && layout::display_width(message) + 2 <= available {
+            return vec![];
+        }
+
+        // Words too long for a row are split across rows.
+        let mut rows = vec![];
+        for row in message
+            .lines()
+            .flat_map(|line| reflow::reflow(&[line.to_string()], width, None, 1))
+        {
+            let mut rest = row.as_str();
+            loop {
+                let fits = layout::truncate(rest, width.max(1));
+                let fits = match fits.is_empty() {
+                    true => rest.chars().next().map_or("", |c| &rest[..c.len_utf8()]),
+                    false => fits,
+                };
+                rows.push(fits.to_string());
+                rest = &rest[fits.len()..];
+                if rest.is_empty() {
+                    break;
+                }
+            }
+        }
+        rows.truncate(self.text_height() as usize);
+        rows
+    }
+
+    /// Where to draw the cursor, or None if it has been scrolled off screen.
+    fn cursor_position(&self) -> Option<CursorPosition> {
+        if self.message_expanded {
+            return Some(CursorPosition {
+                x: CONTINUE.len() as u16,
+                y: self.terminal.size().height.saturating_sub(1),
+                shape: CursorShape::Default,
+            });
+        }
+        if let Some((picker, _)) = &self.picker {
+            let prompt = format!("{}> {}", picker.title(), picker.query());
+            return Some(CursorPosition {
+                x: layout::display_width(&prompt) as u16,
+                y: self.terminal.size().height.saturating_sub(1),
+                shape: CursorShape::Default,
+            });
+        }
+        if self.prompt.is_none() && (self.showing_welcome() || !self.cursor_visible()) {
+            return None;
+        }
+
+        Some(match &self.prompt {
+            Some(prompt) => CursorPosition {
+                x: layout::display_width(&(prompt.label() + prompt.input())) as u16,
+                y: self.terminal.size().height.saturating_sub(1),
+                shape: CursorShape::Default,
+            },
+            None => CursorPosition {
+                x: match self.document() {
+                    Some(document) if document.is_binary() => {
+                        hex::screen_column(self.column, self.low_nibble)
+                    }
+                    Some(_) => self.cursor_cell() as u16,
+                    None => 0,
+                } + self.text_offset() as u16,
+                y: self.cursor_row().unwrap_or(0) as u16,
+                shape: match self.overwrite {
+                    true => CursorShape::Underline,
+                    false => CursorShape::Default,
+                },
+            },
+        })
+    }
+
+    /// The popups open over the text area, bottom layer first.
+    fn layers(&self) -> Vec<Layer> {
+        [
+            (
+                Layer::Tooltip,
+                self.hover.as_ref().is_some_and(|h| h.is_shown()),
+            ),
+            (Layer::Picker, self.picker.is_some()),
+            (Layer::Question, self.question.is_some()),
+            (Layer::Info, self.info.is_some()),
+            (
+                Layer::ChordHint,
+                self.chord.as_ref().is_some_and(|c| c.hint),
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, open)| *open)
+        .map(|(layer, _)| layer)
+        .collect()
+    }
+
+    /// Lays out a popup to draw over the text area.
+    ///
+    /// # Arguments
+    ///
+    /// * 'layer' - The popup.
+    /// * 'width' - The number of cells across the text area.
+    /// * 'height' - The number of rows in the text area.
+    fn render_layer(&self, layer: Layer, width: usize, height: u32) -> Option<Overlay> {
+        Some(match layer {
+            Layer::Tooltip => {
+                let hover = self.hover.as_ref()?;
+                let placement = overlay::Placement::Below {
+                    row: hover.row as usize,
+                    column: hover.column as usize,
+                };
+                Overlay::new("", hover.tooltip.clone()?, placement)
+            }
+            Layer::Picker => {
+                let (picker, action) = self.picker.as_ref()?;
+                // Completions are listed by the cursor, and other pickers along the bottom.
+                let (placement, inner) = match (action, self.cursor_row()) {
+                    (PickerAction::Complete(..), Some(row)) => {
+                        let widest = picker
+                            .visible(PICKER_ROWS as usize)
+                            .iter()
+                            .map(|(item, _)| layout::display_width(item))
+                            .max()
+                            .unwrap_or(0);
+                        let column = self.cursor_cell() + self.text_offset();
+                        (
+                            overlay::Placement::Below {
+                                row: row as usize,
+                                column,
+                            },
+                            widest.min(width.saturating_sub(3)),
+                        )
+                    }
+                    _ => (overlay::Placement::Bottom, width.saturating_sub(2)),
+                };
+                let lines = self.render_picker(inner, height);
+                Overlay::new(picker.title(), lines, placement)
+            }
+            Layer::Question => {
+                let question = self.question.as_ref()?;
+                Overlay::new(
+                    "Confirm",
+                    vec![question.text.clone()],
+                    overlay::Placement::Center,
+                )
+            }
+            Layer::Info => Overlay::new(
+                "Document info",
+                self.info.clone()?,
+                overlay::Placement::Center,
+            ),
+            Layer::ChordHint => {
+                let pending = self.chord.as_ref()?;
+                let rows = pending.chord.render_hint(width.saturating_sub(2));
+                let (title, keys) = rows.split_first()?;
+                Overlay::new(title, keys.to_vec(), overlay::Placement::Bottom)
+            }
+        })
+    }
+
+    /// Lays out the items of the open picker, with the highlighted item in reverse video.
+    ///
+    /// # Arguments
+    ///
+    /// * 'width' - The number of cells each item is given.
+    /// * 'height' - The number of rows in the text area.
+    fn render_picker(&self, width: usize, height: u32) -> Vec<String> {
+        let Some((picker, action)) = &self.picker else {
+            return vec![];
+        };
+
+        let rows = PICKER_ROWS.min(height.saturating_sub(3)) as usize;
+        // The buffer picker gives the highlighted buffer's text two thirds of the width.
+        let preview = match (action, picker.selected()) {
+            (PickerAction::Switch, Some(index)) => {
+                let preview_width = (width - width / 3).saturating_sub(1);
+                Some(self.preview_buffer(index, rows, preview_width))
+            }
+            _ => None,
+        };
+        let width = match preview {
+            Some(_) => width / 3,
+            None => width,
+        };
+        let mut lines: Vec<String> = picker
+            .visible(rows)
+            .into_iter()
+            .map(|(item, selected)| {
+                let item = layout::truncate(item, width);
+                let padded = format!("{:<width$}", item, width = width);
+                match selected {
+                    true => style(padded).reverse().force_styling(true).to_string(),
+                    false => padded,
+                }
+            })
+            .collect();
+        if lines.is_empty() {
+            lines.push(String::from("No matches"));
+        }
+        if let Some(preview) = preview {
+            lines.resize(rows, " ".repeat(width));
+            let mut preview = preview.into_iter();
+            for line in &mut lines {
+                *line = format!("{}│{}", line, preview.next().unwrap_or_default());
+            }
+        }
+        lines
+    }
+
+    /// Lays out a line of the document for display, highlighting any part of it which is
+    /// selected. The line's sign is drawn before it while the gutter is shown, and in zen mode
+    /// the line is indented to center it.
+    fn render_text_line(&self, line: u32, content: &str, tab_width: usize) -> String {
+        let mut rendered = " ".repeat(self.text_offset() - self.gutter_width());
+        if let Some(buffer) = self
+            .buffers
+            .get(self.current)
+            .filter(|_| self.gutter_width() > 0)
+        {
+            rendered += &buffer.document.signs().render(line);
+        }
+        rendered += &self.render_text(line, content, self.text_width(), tab_width);
+
+        // A closed fold's first line is followed by the size of the fold, in place of any
+        // annotation at the end of the line.
+        let trailer = self
+            .document()
+            .and_then(|d| match d.folds().closed_at(line) {
+                Some(end) => Some((
+                    format!("··· {} lines", end - line + 1),
+                    console::Color::Cyan,
+                )),
+                None => d
+                    .virtual_text()
+                    .end_of_line(line)
+                    .map(|a| (a.text.clone(), a.color)),
+            });
+        if let Some((text, color)) = trailer {
+            let len = content.chars().count() as u32;
+            // A cell is kept free for the selection's line break.
+            let used = layout::display_column(content, len, tab_width) + 1 + virtual_text::GAP;
+            let room = self.text_width().saturating_sub(used);
+            if room > 0 {
+                rendered += &" ".repeat(virtual_text::GAP);
+                rendered += &style(layout::truncate(&text, room))
+                    .fg(color)
+                    .force_styling(true)
+                    .to_string();
+            }
+        }
+        rendered
+    }
+
+    /// Lays out a row of virtual text placed between lines, lined up with the text.
+    fn render_virtual_row(&self, annotation: &Annotation) -> String {
+        " ".repeat(self.text_offset()) + &annotation.render(self.text_width())
+    }
+
+    fn render_text(&self, line: u32, content: &str, width: usize, tab_width: usize) -> String {
+        if self.is_ansi() {
+            return layout::render_styled_line(&ansi::parse(content), width, tab_width);
+        }
+        if let Some((preview, columns)) = self.substitution_preview(line, content) {
+            // The preview stays on one line, so line breaks in the replacements are shown as
+            // a symbol.
+            let preview = preview.replace('\n', "↵");
+            let replaced = console::Style::new()
+                .fg(SUBSTITUTE_COLOR)
+                .underlined()
+                .force_styling(true);
+            return layout::render_decorated_line(&preview, width, tab_width, None, |column| {
+                match columns.iter().any(|c| c.contains(&column)) {
+                    true => replaced.clone(),
+                    false => console::Style::new(),
+                }
+            });
+        }
+        if let Some(columns) = self.selection().and_then(|s| s.columns(line)) {
+            return layout::render_highlighted_line(content, width, tab_width, columns);
+        }
+        let cursors = self.remote_cursors(line);
+        if !cursors.is_empty() {
+            return layout::render_line_with_cursors(content, width, tab_width, &cursors);
+        }
+
+        let (rainbow, dim) = (self.config.rainbow_brackets, self.config.bracket_scope);
+        let brackets = self
+            .buffers
+            .get(self.current)
+            .and_then(|b| b.brackets.as_ref())
+            .filter(|scan| scan.styles_line(line, dim));
+        if !self.indent_guides && brackets.is_none() {
+            return layout::render_line(content, width, tab_width);
+        }
+        let guides = self
+            .indent_guides
+            .then(|| (self.settings().indent_width, INDENT_GUIDE_COLOR));
+        layout::render_decorated_line(content, width, tab_width, guides, |column| match brackets {
+            Some(scan) => scan.style(Position { row: line, column }, rainbow, dim),
+            None => console::Style::new(),
+        })
+    }
+
+    /// A line as it would be after the substitution being previewed or confirmed, along with
+    /// the columns of its replacements, or None if the line isn't being substituted in.
+    fn substitution_preview(&self, line: u32, content: &str) -> Option<(String, Vec<Range<u32>>)> {
+        if let Some(confirmation) = &self.substitution {
+            return confirmation.preview(line, content);
+        }
+        let preview = self.preview.as_ref().filter(|p| p.lines.contains(&line))?;
+        preview.substitution.replace(content)
+    }
+
+    /// Matches the brackets on the visible lines again if the document has been edited or
+    /// scrolled since they were last matched, and finds the pair around the cursor, returning
+    /// whether the way the lines are drawn changed.
+    fn update_brackets(&mut self) -> bool {
+        let enabled = self.config.rainbow_brackets || self.config.bracket_scope;
+        let (height, top) = (self.text_height(), self.top);
+        let cursor = Position {
+            row: self.row,
+            column: self.column,
+        };
+        let Some(buffer) = self.buffers.get_mut(self.current) else {
+            return false;
+        };
+        let document = &buffer.document;
+        if !enabled || buffer.ansi || document.is_large() || document.is_binary() {
+            return buffer.brackets.take().is_some();
+        }
+
+        let last = document
+            .virtual_text()
+            .rows(document.folds(), top, document.line_count(), height)
+            .iter()
+            .rev()
+            .find_map(|row| match row {
+                Row::Line(line) => Some(*line),
+                Row::Virtual(_) => None,
+            })
+            .unwrap_or(top);
+        let key = ScanKey {
+            edits: document.edits(),
+            top,
+            last,
+        };
+        match &mut buffer.brackets {
+            Some(scan) if scan.is_current(key) => scan.set_cursor(cursor),
+            brackets => {
+                let mut scan = BracketScan::new(key, document.line_count(), |lines| {
+                    document.get_lines(lines)
+                });
+                scan.set_cursor(cursor);
+                *brackets = Some(scan);
+                true
+            }
+        }
+    }
+
+    /// The columns and colours of other participants' cursors on a line of the current document,
+    /// if it's shared.
+    fn remote_cursors(&self, line: u32) -> Vec<(u32, console::Color)> {
+        match (&self.session, self.document()) {
+            (Some(session), Some(document)) if document.is_shared() => session
+                .cursors()
+                .iter()
+                .filter(|(_, position)| position.row == line)
+                .map(|(id, position)| (position.column, collab::cursor_color(*id)))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Draws the parts of the screen which have changed since the last render. The status line
+    /// and cursor are always redrawn, but document lines are only redrawn when they're dirty.
+    pub fn render(&mut self) -> std::io::Result<()> {
+        self.update_title()?;
+        // Lines hidden by a closed fold can't be at the top of the screen, so the fold's first
+        // line is shown there instead.
+        if let Some(top) = self.document().map(|d| d.folds().visible_line(self.top)) {
+            self.top = top;
+        }
+        let message_rows = self
+            .document()
+            .map_or(vec![], |document| self.message_rows(document));
+        self.message_expanded = !message_rows.is_empty();
+        let mut dirty = std::mem::take(&mut self.dirty);
+        // The minimap runs down every row, so the whole screen is redrawn when it changes.
+        if self.update_brackets() | self.update_minimap() {
+            dirty = Dirty::All;
+        }
+        // Finds the conflicts again after an edit, for the count in the status line.
+        self.conflicts();
+        // Popups are drawn over the document, so the whole screen is redrawn while any are open.
+        if !self.layers().is_empty() || self.showing_welcome() {
+            dirty = Dirty::All;
+        }
+        // A long message moves the text up to make room for it.
+        if self.message_expanded {
+            dirty = Dirty::All;
+        }
+        let Some(document) = self.document() else {
+            let cursor = self.cursor_position();
+            return self.flush(|t| t.render(String::new(), cursor));
+        };
+
+        let size = self.terminal.size();
+        let height = self.text_height();
+        let rows =
+            document
+                .virtual_text()
+                .rows(document.folds(), self.top, document.line_count(), height);
+        // Rows of virtual text between lines move the lines below them, as do closed folds, so
+        // lines can't be redrawn on their own while any are shown.
+        let folded = rows.iter().any(
+            |row| matches!(row, Row::Line(line) if document.folds().closed_at(*line).is_some()),
+        );
+        if folded
+            || document
+                .virtual_text()
+                .has_rows(self.top..self.top + height)
+        {
+            dirty = Dirty::All;
+        }
+        let tab_width = self.settings().tab_width;
+        let status_line = self.render_status_line(document);
+
+        if dirty == Dirty::All {
+            let width = self.text_offset() + self.text_width();
+            let overlays: Vec<Overlay> = self
+                .layers()
+                .into_iter()
+                .filter_map(|layer| self.render_layer(layer, width, height))
+                .collect();
+            let minimap = self.minimap_rows();
+            let scrollbar = self.render_scrollbar(document, height);
+            let welcome = match self.showing_welcome() {
+                true => {
+                    let recent: Vec<String> =
+                        self.recent_files.newest_first().map(String::from).collect();
+                    welcome::render(&recent, size.width as usize, height as usize)
+                }
+                false => Vec::new(),
+            };
+            // Closed folds skip lines, so each row's line is fetched on its own.
+            let mut text_rows: Vec<String> = (0..height as usize)
+                .map(|row| match rows.get(row) {
+                    _ if self.showing_welcome() => welcome.get(row).cloned().unwrap_or_default(),
+                    Some(Row::Line(line)) => document
+                        .get_lines(*line..*line + 1)
+                        .first()
+                        .map(|text| self.render_text_line(*line, text, tab_width))
+                        .unwrap_or_default(),
+                    Some(Row::Virtual(annotation)) => self.render_virtual_row(annotation),
+                    None => String::new(),
+                })
+                .collect();
+            overlay::compose(&mut text_rows, &overlays, width);
+
+            let mut buffer = String::new();
+            for (row, text) in (0..).zip(text_rows).skip(message_rows.len()) {
+                buffer += &text;
+                if !self.showing_welcome() {
+                    buffer += &self.render_right_columns(row, minimap, &scrollbar);
+                }
+                buffer += "\r\n";
+            }
+            for row in &message_rows {
+                buffer += row;
+                buffer += "\r\n";
+            }
+            buffer += status_line.as_str();
+            let cursor = self.cursor_position();
+            return self.flush(|t| t.render(buffer, cursor));
+        }
+
+        let lines = match &dirty {
+            Dirty::Lines(lines) => lines.start.max(self.top)..lines.end.min(self.top + height),
+            Dirty::All | Dirty::Cursor => self.top..self.top,
+        };
+        let content = document.get_lines(lines.clone());
+        let scrollbar = self.render_scrollbar(document, height);
+        let mut rows: Vec<(u16, String)> = lines
+            .zip(content.iter())
+            .map(|(line, content)| {
+                let row = line - self.top;
+                let text = self.render_text_line(line, content, tab_width);
+                (
+                    row as u16,
+                    text + &self.render_right_columns(row, self.minimap_rows(), &scrollbar),
+                )
+            })
+            .collect();
+        rows.push((height as u16, status_line));
+        let cursor = self.cursor_position();
+        self.flush(|t| t.render_rows(rows, cursor))
     }
 }
@@ -0,0 +1,76 @@
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+
+use thiserror::Error;
+
+/// The errors the editor's operations can fail with. Each one describes itself well enough to be
+/// shown in the message area.
+#[derive(Debug, Error)]
+pub enum EditorError {
+    /// Reading or writing a file, the terminal or a connection failed.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// A document, or what a command printed for it, isn't valid UTF-8.
+    #[error("Document contains invalid UTF-8")]
+    InvalidUtf8,
+    /// A document given on the command line couldn't be opened.
+    #[error("Unable to open {}: {source}", path.display())]
+    Open {
+        path: PathBuf,
+        source: Box<EditorError>,
+    },
+    /// A document without a path was saved.
+    #[error("No file name")]
+    NoFileName,
+    /// The config file isn't valid TOML, or has settings of the wrong type.
+    #[error("Unable to parse {}: {message}", path.display())]
+    Config { path: PathBuf, message: String },
+    /// Nothing was given to print or export.
+    #[error("No document specified")]
+    NoDocument,
+    /// A shell command failed, described by the first line it printed to stderr.
+    #[error("{0}")]
+    Command(String),
+    /// A document given to `--follow` can't be read as its file grows.
+    #[error("Only uncompressed, unencrypted text files can be followed")]
+    NotFollowable,
+    /// `--serve` was given an address other machines can connect to, without `--allow-remote`.
+    #[error("{0} isn't a loopback address; add --allow-remote to share with other machines")]
+    RemoteAddress(String),
+    /// Saving with `sudo tee` failed.
+    #[error("sudo tee exited with {0}")]
+    Elevated(ExitStatus),
+}
+
+impl EditorError {
+    /// Whether the error is the operating system refusing access, which saving can try to get
+    /// around by forcing the save.
+    pub fn is_permission_denied(&self) -> bool {
+        matches!(self, EditorError::Io(e) if e.kind() == io::ErrorKind::PermissionDenied)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, EditorError>;
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(io::ErrorKind::PermissionDenied, true;  "permission_denied")]
+    #[test_case(io::ErrorKind::NotFound,         false; "not_found")]
+    fn permission_denied(kind: io::ErrorKind, expected: bool) {
+        let error = EditorError::from(io::Error::new(kind, "failed"));
+        assert_eq!(expected, error.is_permission_denied());
+        assert_eq!("failed", error.to_string());
+    }
+
+    #[test]
+    fn command_error_message() {
+        let error = EditorError::Command(String::from("Command failed: oops"));
+        assert_eq!("Command failed: oops", error.to_string());
+        assert!(!error.is_permission_denied());
+    }
+}
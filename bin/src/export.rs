@@ -0,0 +1,131 @@
+use console::style;
+
+/// A format documents can be exported to for sharing outside the editor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// A standalone HTML page.
+    Html,
+    /// Text with ANSI escape sequences, for display in a terminal.
+    Ansi,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Result<Format, String> {
+        match name.to_lowercase().as_str() {
+            "html" => Ok(Format::Html),
+            "ansi" => Ok(Format::Ansi),
+            "" => Err(String::from("No export format entered")),
+            _ => Err(format!("Unknown export format: {}", name)),
+        }
+    }
+
+    /// The extension added to a document's path when exporting it.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Html => "html",
+            Format::Ansi => "ans",
+        }
+    }
+}
+
+/// Exports lines of a document, numbering each line. Line numbers are dimmed in ANSI output and
+/// can't be selected in HTML output, so copying the text leaves them out.
+///
+/// # Arguments
+///
+/// * 'lines' - The lines to export, with or without their line breaks.
+/// * 'first_line' - The line number of the first line.
+/// * 'title' - The title of an HTML page, such as the document's name.
+/// * 'format' - The format to export to.
+/// * 'tab_width' - The number of cells between tab stops.
+pub fn export(
+    lines: &[String],
+    first_line: u32,
+    title: &str,
+    format: Format,
+    tab_width: usize,
+) -> String {
+    let last_line = first_line as usize + lines.len().saturating_sub(1);
+    let width = last_line.to_string().len();
+    let numbered = lines.iter().enumerate().map(|(i, line)| {
+        let number = format!("{:>width$} ", first_line as usize + i, width = width);
+        (number, line.trim_end_matches(['\r', '\n']))
+    });
+
+    match format {
+        Format::Ansi => numbered
+            .map(|(number, line)| format!("{}{}\n", style(number).dim().force_styling(true), line))
+            .collect(),
+        Format::Html => {
+            let body: String = numbered
+                .map(|(number, line)| {
+                    format!(
+                        "<span class=\"line-number\">{}</span>{}\n",
+                        number,
+                        escape_html(line)
+                    )
+                })
+                .collect();
+            format!(
+                "<!DOCTYPE html>\n\
+                 <html>\n\
+                 <head>\n\
+                 <meta charset=\"utf-8\">\n\
+                 <title>{}</title>\n\
+                 <style>\n\
+                 body {{ margin: 0; background: #1e1e1e; color: #d4d4d4; }}\n\
+                 pre {{ margin: 0; padding: 1em; font-family: monospace; tab-size: {}; }}\n\
+                 .line-number {{ color: #858585; user-select: none; }}\n\
+                 </style>\n\
+                 </head>\n\
+                 <body>\n\
+                 <pre>{}</pre>\n\
+                 </body>\n\
+                 </html>\n",
+                escape_html(title),
+                tab_width,
+                body
+            )
+        }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("html", Ok(Format::Html);                           "html")]
+    #[test_case("ANSI", Ok(Format::Ansi);                           "ansi")]
+    #[test_case("pdf", Err(String::from("Unknown export format: pdf")); "unknown")]
+    fn parse_format(name: &str, expected: Result<Format, String>) {
+        assert_eq!(expected, Format::parse(name));
+    }
+
+    #[test]
+    fn export_ansi() {
+        let lines = vec![String::from("Lorem\n"), String::from("ipsum")];
+        let expected = "\x1b[2m 9 \x1b[0mLorem\n\x1b[2m10 \x1b[0mipsum\n";
+        assert_eq!(expected, export(&lines, 9, "", Format::Ansi, 4));
+    }
+
+    #[test]
+    fn export_html_escapes_text() {
+        let lines = vec![String::from("if a < b && c > \"d\" {\n")];
+        let html = export(&lines, 1, "<main.rs>", Format::Html, 8);
+
+        assert!(html.contains("<title>&lt;main.rs&gt;</title>"));
+        assert!(html.contains("tab-size: 8;"));
+        assert!(html.contains(
+            "<span class=\"line-number\">1 </span>if a &lt; b &amp;&amp; c &gt; &quot;d&quot; {\n"
+        ));
+    }
+}
@@ -0,0 +1,313 @@
+use std::path::Path;
+
+/// The number of lines at the start and end of a document checked for a modeline.
+pub const MODELINE_LINES: u32 = 5;
+
+/// A filetype the editor knows how to detect, along with its default settings.
+#[derive(Debug, PartialEq)]
+pub struct Filetype {
+    pub name: &'static str,
+    extensions: &'static [&'static str],
+    filenames: &'static [&'static str],
+    interpreters: &'static [&'static str],
+    /// The prefix used for line comments, if the language has them.
+    pub comment_prefix: Option<&'static str>,
+    /// The number of columns each level of indentation takes up.
+    pub indent_width: usize,
+    /// Whether indentation is made up of spaces rather than tabs.
+    pub expand_tabs: bool,
+}
+
+const FILETYPES: &[Filetype] = &[
+    Filetype {
+        name: "rust",
+        extensions: &["rs"],
+        filenames: &[],
+        interpreters: &[],
+        comment_prefix: Some("//"),
+        indent_width: 4,
+        expand_tabs: true,
+    },
+    Filetype {
+        name: "c",
+        extensions: &["c", "h"],
+        filenames: &[],
+        interpreters: &[],
+        comment_prefix: Some("//"),
+        indent_width: 4,
+        expand_tabs: true,
+    },
+    Filetype {
+        name: "cpp",
+        extensions: &["cpp", "cc", "cxx", "hpp", "hh", "hxx"],
+        filenames: &[],
+        interpreters: &[],
+        comment_prefix: Some("//"),
+        indent_width: 4,
+        expand_tabs: true,
+    },
+    Filetype {
+        name: "go",
+        extensions: &["go"],
+        filenames: &[],
+        interpreters: &[],
+        comment_prefix: Some("//"),
+        indent_width: 4,
+        expand_tabs: false,
+    },
+    Filetype {
+        name: "python",
+        extensions: &["py", "pyi"],
+        filenames: &[],
+        interpreters: &["python"],
+        comment_prefix: Some("#"),
+        indent_width: 4,
+        expand_tabs: true,
+    },
+    Filetype {
+        name: "sh",
+        extensions: &["sh", "bash", "zsh"],
+        filenames: &[".bashrc", ".bash_profile", ".profile", ".zshrc"],
+        interpreters: &["sh", "bash", "zsh", "dash", "ksh"],
+        comment_prefix: Some("#"),
+        indent_width: 2,
+        expand_tabs: true,
+    },
+    Filetype {
+        name: "javascript",
+        extensions: &["js", "mjs", "cjs", "jsx"],
+        filenames: &[],
+        interpreters: &["node"],
+        comment_prefix: Some("//"),
+        indent_width: 2,
+        expand_tabs: true,
+    },
+    Filetype {
+        name: "typescript",
+        extensions: &["ts", "mts", "cts", "tsx"],
+        filenames: &[],
+        interpreters: &[],
+        comment_prefix: Some("//"),
+        indent_width: 2,
+        expand_tabs: true,
+    },
+    Filetype {
+        name: "ruby",
+        extensions: &["rb"],
+        filenames: &["Gemfile", "Rakefile"],
+        interpreters: &["ruby"],
+        comment_prefix: Some("#"),
+        indent_width: 2,
+        expand_tabs: true,
+    },
+    Filetype {
+        name: "perl",
+        extensions: &["pl", "pm"],
+        filenames: &[],
+        interpreters: &["perl"],
+        comment_prefix: Some("#"),
+        indent_width: 4,
+        expand_tabs: true,
+    },
+    Filetype {
+        name: "lua",
+        extensions: &["lua"],
+        filenames: &[],
+        interpreters: &["lua"],
+        comment_prefix: Some("--"),
+        indent_width: 2,
+        expand_tabs: true,
+    },
+    Filetype {
+        name: "make",
+        extensions: &["mk"],
+        filenames: &["Makefile", "makefile", "GNUmakefile"],
+        interpreters: &["make"],
+        comment_prefix: Some("#"),
+        indent_width: 8,
+        expand_tabs: false,
+    },
+    Filetype {
+        name: "toml",
+        extensions: &["toml"],
+        filenames: &["Cargo.lock"],
+        interpreters: &[],
+        comment_prefix: Some("#"),
+        indent_width: 4,
+        expand_tabs: true,
+    },
+    Filetype {
+        name: "yaml",
+        extensions: &["yml", "yaml"],
+        filenames: &[],
+        interpreters: &[],
+        comment_prefix: Some("#"),
+        indent_width: 2,
+        expand_tabs: true,
+    },
+    Filetype {
+        name: "json",
+        extensions: &["json"],
+        filenames: &[],
+        interpreters: &[],
+        comment_prefix: None,
+        indent_width: 2,
+        expand_tabs: true,
+    },
+    Filetype {
+        name: "markdown",
+        extensions: &["md", "markdown"],
+        filenames: &[],
+        interpreters: &[],
+        comment_prefix: None,
+        indent_width: 4,
+        expand_tabs: true,
+    },
+    Filetype {
+        name: "html",
+        extensions: &["html", "htm"],
+        filenames: &[],
+        interpreters: &[],
+        comment_prefix: None,
+        indent_width: 2,
+        expand_tabs: true,
+    },
+];
+
+/// Looks up a filetype by name.
+pub fn find(name: &str) -> Option<&'static Filetype> {
+    FILETYPES.iter().find(|filetype| filetype.name == name)
+}
+
+/// Detects the filetype of a document. A modeline takes priority, followed by the interpreter
+/// named in a shebang line, then the file name and extension. Filetypes set by a modeline don't
+/// need to be known to the editor, so settings can still be configured for them.
+///
+/// # Arguments
+///
+/// * 'path' - The path of the document, if it has one.
+/// * 'lines' - The lines at the start of the document, followed by the lines at the end of the
+///   document, which are checked for a modeline and shebang.
+pub fn detect(path: Option<&Path>, lines: &[String]) -> Option<String> {
+    if let Some(name) = lines.iter().find_map(|line| modeline(line)) {
+        return Some(name);
+    }
+
+    let from_shebang = lines
+        .first()
+        .and_then(|line| shebang(line))
+        .and_then(|interpreter| {
+            FILETYPES
+                .iter()
+                .find(|filetype| filetype.interpreters.contains(&interpreter.as_str()))
+        });
+    let from_path = path.and_then(|path| {
+        let filename = path.file_name()?.to_str()?;
+        let extension = path.extension().and_then(|e| e.to_str());
+        FILETYPES.iter().find(|filetype| {
+            filetype.filenames.contains(&filename)
+                || extension.is_some_and(|e| filetype.extensions.contains(&e))
+        })
+    });
+
+    from_shebang
+        .or(from_path)
+        .map(|filetype| filetype.name.to_string())
+}
+
+/// Parses the filetype from a vim style modeline, such as `// vim: set ft=rust:` or
+/// `# vi: filetype=python`.
+fn modeline(line: &str) -> Option<String> {
+    let start = ["vim:", "vi:", "ex:"].iter().find_map(|marker| {
+        line.match_indices(marker)
+            .find(|(i, _)| *i == 0 || line[..*i].ends_with(char::is_whitespace))
+            .map(|(i, marker)| i + marker.len())
+    })?;
+
+    line[start..]
+        .split(|c: char| c == ':' || c.is_whitespace())
+        .find_map(|option| {
+            option
+                .strip_prefix("ft=")
+                .or_else(|| option.strip_prefix("filetype="))
+        })
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+}
+
+/// Parses the name of the interpreter from a shebang line, skipping over `env` and ignoring any
+/// version number, so `#!/usr/bin/env python3` gives `python`.
+fn shebang(line: &str) -> Option<String> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut program = Path::new(words.next()?).file_name()?.to_str()?;
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+
+    let name = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    Some(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use test_case::test_case;
+
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    #[test_case("main.rs", Some("rust");       "extension")]
+    #[test_case("Makefile", Some("make");      "filename")]
+    #[test_case("Cargo.lock", Some("toml");    "filename_with_extension")]
+    #[test_case("notes", None;                 "unknown")]
+    fn detect_by_path(path: &str, expected: Option<&str>) {
+        let path = PathBuf::from(path);
+        let actual = detect(Some(&path), &lines("Lorem ipsum"));
+        assert_eq!(expected.map(String::from), actual);
+    }
+
+    #[test_case("#!/bin/sh", Some("sh");                     "absolute_path")]
+    #[test_case("#!/usr/bin/env python3", Some("python");    "env")]
+    #[test_case("#!/usr/bin/env -S node --flag", Some("javascript"); "env_with_flags")]
+    #[test_case("#!/usr/bin/python3.11", Some("python");     "version")]
+    #[test_case("#!/usr/bin/unknown", None;                  "unknown")]
+    fn detect_by_shebang(line: &str, expected: Option<&str>) {
+        let actual = detect(None, &lines(line));
+        assert_eq!(expected.map(String::from), actual);
+    }
+
+    #[test]
+    fn shebang_takes_priority_over_extension() {
+        let path = PathBuf::from("script.sh");
+        let actual = detect(Some(&path), &lines("#!/usr/bin/env python"));
+        assert_eq!(Some(String::from("python")), actual);
+    }
+
+    #[test_case("// vim: set ft=rust:", Some("rust");        "set")]
+    #[test_case("# vi: filetype=python", Some("python");     "filetype")]
+    #[test_case("/* vim: ts=4 ft=c sw=4 */", Some("c");      "multiple_options")]
+    #[test_case("# vim:ft=custom", Some("custom");           "unknown_filetype")]
+    #[test_case("revim: ft=rust", None;                      "not_a_modeline")]
+    #[test_case("# vim: ts=4", None;                         "no_filetype")]
+    fn detect_by_modeline(line: &str, expected: Option<&str>) {
+        let actual = detect(None, &lines(&format!("Lorem ipsum\n{}", line)));
+        assert_eq!(expected.map(String::from), actual);
+    }
+
+    #[test]
+    fn modeline_takes_priority_over_extension() {
+        let path = PathBuf::from("main.sh");
+        let actual = detect(Some(&path), &lines("# vim: ft=python"));
+        assert_eq!(Some(String::from("python")), actual);
+    }
+
+    #[test]
+    fn find_by_name() {
+        assert_eq!(Some("//"), find("rust").and_then(|f| f.comment_prefix));
+        assert_eq!(None, find("custom"));
+    }
+}
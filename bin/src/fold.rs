@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// The text which opens a fold when folding by markers.
+pub const OPEN_MARKER: &str = "{{{";
+/// The text which closes a fold when folding by markers.
+pub const CLOSE_MARKER: &str = "}}}";
+
+/// How the lines a fold covers are found.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FoldMethod {
+    /// A line folds the lines after it which are indented further.
+    Indent,
+    /// Folds run from a line containing `{{{` to the matching line containing `}}}`.
+    Marker,
+}
+
+/// Finds the fold at a line by indentation: the lines indented further than the line after it,
+/// or if there are none, the lines indented further than the line's parent, starting from the
+/// parent. Blank lines within the fold are part of it, but blank lines at its end aren't.
+///
+/// # Arguments
+///
+/// * 'line' - The line to find the fold at.
+/// * 'last' - The last line of the document.
+/// * 'indent' - The indentation of a line, or None if it's blank.
+pub fn indent_range(
+    line: u32,
+    last: u32,
+    indent: impl Fn(u32) -> Option<u32>,
+) -> Option<RangeInclusive<u32>> {
+    let children = |start: u32, base: u32| {
+        let mut end = start;
+        for next in start + 1..=last {
+            match indent(next) {
+                Some(level) if level <= base => break,
+                Some(_) => end = next,
+                None => {}
+            }
+        }
+        (end > start).then_some(start..=end)
+    };
+
+    let base = indent(line)?;
+    children(line, base).or_else(|| {
+        let parent = (1..line)
+            .rev()
+            .find(|&l| indent(l).is_some_and(|i| i < base))?;
+        children(parent, indent(parent)?)
+    })
+}
+
+/// Finds the innermost fold around a line by markers, from a line containing `{{{` to the
+/// line containing its matching `}}}`.
+///
+/// # Arguments
+///
+/// * 'line' - The line to find the fold at.
+/// * 'lines' - The lines of the document.
+pub fn marker_range(line: u32, lines: &[String]) -> Option<RangeInclusive<u32>> {
+    let mut open: Vec<u32> = vec![];
+    let mut innermost: Option<RangeInclusive<u32>> = None;
+    for (number, text) in (1..).zip(lines) {
+        for _ in 0..text.matches(OPEN_MARKER).count() {
+            open.push(number);
+        }
+        for _ in 0..text.matches(CLOSE_MARKER).count() {
+            let Some(start) = open.pop() else {
+                continue;
+            };
+            let range = start..=number;
+            if range.contains(&line) && innermost.as_ref().is_none_or(|r| r.start() < &start) {
+                innermost = Some(range);
+            }
+        }
+    }
+    innermost.filter(|range| range.start() < range.end())
+}
+
+/// The closed folds in a document. Each fold hides all but its first line, which is shown with
+/// a summary. Folds can be nested, and opening a fold leaves the folds within it as they were.
+/// Like virtual text, folds move with edits to the lines above them.
+#[derive(Debug, Default)]
+pub struct Folds {
+    closed: Vec<RangeInclusive<u32>>,
+}
+
+impl Folds {
+    pub fn new() -> Folds {
+        Folds::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.closed.is_empty()
+    }
+
+    /// The closed folds, as they're saved between sessions.
+    pub fn ranges(&self) -> &[RangeInclusive<u32>] {
+        &self.closed
+    }
+
+    pub fn close(&mut self, range: RangeInclusive<u32>) {
+        if !self.closed.contains(&range) {
+            self.closed.push(range);
+        }
+    }
+
+    /// Opens the closed fold starting at a line, returning whether there was one.
+    pub fn open(&mut self, start: u32) -> bool {
+        let len = self.closed.len();
+        self.closed.retain(|range| *range.start() != start);
+        self.closed.len() != len
+    }
+
+    /// Opens every closed fold hiding a line.
+    pub fn open_around(&mut self, line: u32) {
+        self.closed.retain(|range| !hides(range, line));
+    }
+
+    pub fn open_all(&mut self) {
+        self.closed.clear();
+    }
+
+    /// The line shown in place of a line hidden by a closed fold, which is the first line of
+    /// the outermost fold hiding it. Lines which aren't hidden are shown as themselves.
+    pub fn visible_line(&self, line: u32) -> u32 {
+        self.closed
+            .iter()
+            .filter(|range| hides(range, line))
+            .map(|range| *range.start())
+            .min()
+            .unwrap_or(line)
+    }
+
+    /// The last line of the closed fold starting at a line, if the line is visible and a closed
+    /// fold starts there.
+    pub fn closed_at(&self, line: u32) -> Option<u32> {
+        if self.visible_line(line) != line {
+            return None;
+        }
+        self.closed
+            .iter()
+            .filter(|range| *range.start() == line)
+            .map(|range| *range.end())
+            .max()
+    }
+
+    /// The visible line after a line, skipping the lines hidden by a closed fold starting there.
+    pub fn next_line(&self, line: u32) -> u32 {
+        self.closed_at(line).unwrap_or(line) + 1
+    }
+
+    /// Moves up a number of visible lines from a line, stopping at the first line.
+    pub fn up(&self, line: u32, count: u32) -> u32 {
+        (0..count).fold(line, |line, _| match line {
+            1 => 1,
+            line => self.visible_line(line - 1),
+        })
+    }
+
+    /// Moves down a number of visible lines from a line, stopping at the last line.
+    pub fn down(&self, line: u32, count: u32, last: u32) -> u32 {
+        (0..count).fold(line, |line, _| match self.next_line(line) {
+            next if next > last => line,
+            next => next,
+        })
+    }
+
+    /// Moves the folds for an edit. Folds the edit was made in grow or shrink with it, and folds
+    /// whose first line was deleted are removed.
+    ///
+    /// # Arguments
+    ///
+    /// * 'line' - The line the edit was made on.
+    /// * 'removed' - The number of line breaks the edit removed.
+    /// * 'inserted' - The number of line breaks the edit inserted.
+    pub fn edit(&mut self, line: u32, removed: u32, inserted: u32) {
+        if removed == inserted {
+            return;
+        }
+        let shift = |l: u32| l + inserted - removed.min(l + inserted);
+        self.closed = std::mem::take(&mut self.closed)
+            .into_iter()
+            .filter(|range| !(line < *range.start() && *range.start() <= line + removed))
+            .map(|range| {
+                let (start, end) = (*range.start(), *range.end());
+                let start = if start > line { shift(start) } else { start };
+                let end = if end > line {
+                    shift(end).max(line)
+                } else {
+                    end
+                };
+                start..=end
+            })
+            .filter(|range| range.start() < range.end())
+            .collect();
+    }
+}
+
+/// Whether a fold hides a line, which it does for every line but its first.
+fn hides(range: &RangeInclusive<u32>, line: u32) -> bool {
+    *range.start() < line && line <= *range.end()
+}
+
+/// The closed folds of every file, kept in a file so folds are restored when a file is opened
+/// again.
+#[derive(Debug, Default)]
+pub struct FoldStore {
+    files: HashMap<String, Vec<(u32, u32)>>,
+    path: Option<PathBuf>,
+}
+
+impl FoldStore {
+    /// Constructs an empty store which isn't saved to disk.
+    pub fn new() -> FoldStore {
+        FoldStore::default()
+    }
+
+    /// Loads the store from a JSON file mapping each file's path to its closed folds. A missing
+    /// or unreadable file is treated as an empty store.
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - The file the store is read from and saved to.
+    pub fn load(path: PathBuf) -> FoldStore {
+        let files = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        FoldStore {
+            files,
+            path: Some(path),
+        }
+    }
+
+    /// The folds closed in a file when it was last saved to the store.
+    pub fn get(&self, file: &Path) -> Vec<RangeInclusive<u32>> {
+        self.files
+            .get(&file.display().to_string())
+            .map_or(vec![], |folds| {
+                folds.iter().map(|&(start, end)| start..=end).collect()
+            })
+    }
+
+    /// Records a file's closed folds, forgetting the file if it has none.
+    pub fn set(&mut self, file: &Path, folds: &Folds) {
+        let key = file.display().to_string();
+        match folds.is_empty() {
+            true => self.files.remove(&key),
+            false => self.files.insert(
+                key,
+                folds
+                    .ranges()
+                    .iter()
+                    .map(|range| (*range.start(), *range.end()))
+                    .collect(),
+            ),
+        };
+    }
+
+    /// Moves a file's folds to the path it was renamed to, or forgets them if it was deleted.
+    pub fn move_file(&mut self, from: &Path, to: Option<&Path>) {
+        let folds = self.files.remove(&from.display().to_string());
+        if let (Some(folds), Some(to)) = (folds, to) {
+            self.files.insert(to.display().to_string(), folds);
+        }
+    }
+
+    /// Writes the store to its file, creating the containing directory if needed.
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(&self.files)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    const CODE: &str = "\
+fn main() {
+    if x {
+        a();
+
+        b();
+    }
+
+    c();
+}
+d();";
+
+    fn indent(line: u32) -> Option<u32> {
+        let text = CODE.lines().nth(line as usize - 1)?;
+        let trimmed = text.trim_start();
+        (!trimmed.is_empty()).then_some((text.len() - trimmed.len()) as u32)
+    }
+
+    #[test_case(1, Some(1..=8); "header")]
+    #[test_case(2, Some(2..=5); "nested_header")]
+    #[test_case(3, Some(2..=5); "body_folds_parent")]
+    #[test_case(8, Some(1..=8); "closing_line")]
+    #[test_case(10, None;       "top_level_without_children")]
+    fn fold_by_indent(line: u32, expected: Option<RangeInclusive<u32>>) {
+        assert_eq!(expected, indent_range(line, 10, indent));
+    }
+
+    #[test_case(3, Some(2..=4); "inner")]
+    #[test_case(5, Some(1..=6); "outer")]
+    #[test_case(7, None;        "outside")]
+    fn fold_by_marker(line: u32, expected: Option<RangeInclusive<u32>>) {
+        let lines: Vec<String> = ["a {{{", "b {{{", "c", "d }}}", "e", "}}}", "f"]
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        assert_eq!(expected, marker_range(line, &lines));
+    }
+
+    #[test]
+    fn closed_folds_hide_lines() {
+        let mut folds = Folds::new();
+        folds.close(3..=5);
+        folds.close(2..=8);
+
+        assert_eq!(2, folds.visible_line(4));
+        assert_eq!(2, folds.visible_line(8));
+        assert_eq!(9, folds.visible_line(9));
+        assert_eq!(Some(8), folds.closed_at(2));
+        assert_eq!(None, folds.closed_at(3));
+        assert_eq!((9, 2), (folds.down(2, 1, 20), folds.up(9, 1)));
+
+        folds.open(2);
+        assert_eq!(Some(5), folds.closed_at(3));
+        assert_eq!(6, folds.down(3, 1, 20));
+    }
+
+    #[test]
+    fn open_folds_around_line() {
+        let mut folds = Folds::new();
+        folds.close(3..=5);
+        folds.close(2..=8);
+        folds.close(10..=12);
+        folds.open_around(4);
+        assert_eq!(vec![10..=12], folds.ranges());
+    }
+
+    #[test_case(1, 0, 2, vec![5..=7];  "lines_inserted_above")]
+    #[test_case(4, 0, 1, vec![3..=6];  "line_inserted_inside")]
+    #[test_case(4, 1, 0, vec![3..=4];  "line_deleted_inside")]
+    #[test_case(2, 1, 0, vec![]; "first_line_deleted")]
+    #[test_case(6, 2, 0, vec![3..=5];  "lines_deleted_below")]
+    fn edits_move_folds(
+        line: u32,
+        removed: u32,
+        inserted: u32,
+        expected: Vec<RangeInclusive<u32>>,
+    ) {
+        let mut folds = Folds::new();
+        folds.close(3..=5);
+        folds.edit(line, removed, inserted);
+        assert_eq!(expected, folds.ranges());
+    }
+
+    #[test]
+    fn store_round_trip() {
+        let path = std::env::temp_dir().join("texteditor_fold_store.json");
+        let _ = fs::remove_file(&path);
+        let file = Path::new("/code/main.rs");
+
+        let mut folds = Folds::new();
+        folds.close(2..=5);
+        let mut store = FoldStore::load(path.clone());
+        store.set(file, &folds);
+        store.save().unwrap();
+
+        assert_eq!(vec![2..=5], FoldStore::load(path.clone()).get(file));
+        assert!(FoldStore::new().get(file).is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn store_moves_files() {
+        let (from, to) = (Path::new("/code/a.rs"), Path::new("/code/b.rs"));
+        let mut folds = Folds::new();
+        folds.close(1..=3);
+        let mut store = FoldStore::new();
+        store.set(from, &folds);
+
+        store.move_file(from, Some(to));
+        assert!(store.get(from).is_empty());
+        assert_eq!(vec![1..=3], store.get(to));
+        store.move_file(to, None);
+        assert!(store.get(to).is_empty());
+    }
+}
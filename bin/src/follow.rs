@@ -0,0 +1,142 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use log::error;
+
+use crate::editor::Event;
+
+/// How often the file is checked for new content.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Watches a file on a background thread as it grows, like `tail -f`, posting the text added to
+/// it to the editor's event queue.
+pub struct Follower {
+    path: PathBuf,
+    stopped: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl Follower {
+    /// Starts following a file.
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - The file to follow.
+    /// * 'offset' - The number of bytes of the file which have already been read.
+    /// * 'sender' - The queue new text is posted to.
+    pub fn start(path: PathBuf, offset: u64, sender: Sender<Event>) -> Follower {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let (path, stopped) = (path.clone(), stopped.clone());
+            thread::spawn(move || watch(&path, offset, sender, stopped))
+        };
+        Follower {
+            path,
+            stopped,
+            handle,
+        }
+    }
+
+    /// The file being followed.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Stops following the file, waiting for the background thread to finish.
+    pub fn stop(self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        if self.handle.join().is_err() {
+            error!("Follow thread panicked");
+        }
+    }
+}
+
+fn watch(path: &Path, mut offset: u64, sender: Sender<Event>, stopped: Arc<AtomicBool>) {
+    // Bytes at the end of what's been read which don't form a complete UTF-8 character yet.
+    let mut pending = vec![];
+    while !stopped.load(Ordering::Relaxed) {
+        thread::sleep(POLL_INTERVAL);
+        let Ok(len) = fs::metadata(path).map(|m| m.len()) else {
+            continue;
+        };
+        // A file which shrank was truncated or replaced, so it's read again from the start.
+        let truncated = len < offset;
+        if truncated {
+            offset = 0;
+            pending.clear();
+        } else if len == offset {
+            continue;
+        }
+
+        match read_from(path, offset, len) {
+            Ok(bytes) => {
+                offset += bytes.len() as u64;
+                pending.extend(bytes);
+            }
+            Err(e) => {
+                error!("Unable to read {}: {}", path.display(), e);
+                continue;
+            }
+        }
+        let text = take_text(&mut pending);
+        if (truncated || !text.is_empty())
+            && sender.send(Event::Followed { text, truncated }).is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// Reads the bytes of a file between two offsets.
+fn read_from(path: &Path, start: u64, end: u64) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut bytes = vec![];
+    file.take(end.saturating_sub(start))
+        .read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Takes the text from the start of the bytes read, leaving behind a UTF-8 character which
+/// hasn't been completely written yet. Invalid bytes are replaced, so a corrupt line doesn't
+/// stop the file being followed.
+fn take_text(pending: &mut Vec<u8>) -> String {
+    let valid = match std::str::from_utf8(pending) {
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        _ => pending.len(),
+    };
+    let rest = pending.split_off(valid);
+    let text = String::from_utf8_lossy(pending).into_owned();
+    *pending = rest;
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(b"abc\n", "abc\n", b"";                  "complete")]
+    #[test_case(b"a\xe2\x82", "a", b"\xe2\x82";          "partial_character")]
+    #[test_case(b"a\xffb", "a\u{fffd}b", b"";            "invalid")]
+    fn take_texts(bytes: &[u8], expected: &str, rest: &[u8]) {
+        let mut pending = bytes.to_vec();
+        assert_eq!(expected, take_text(&mut pending));
+        assert_eq!(rest, pending);
+    }
+
+    #[test]
+    fn read_between_offsets() {
+        let path = std::env::temp_dir().join("texteditor_follow_read.log");
+        fs::write(&path, "one\ntwo\n").unwrap();
+        assert_eq!(b"two\n".to_vec(), read_from(&path, 4, 8).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,359 @@
+//! Golden-frame tests, which draw representative screens to a headless terminal and compare them
+//! with the frames saved in `src/golden`. Run the tests with `UPDATE_GOLDEN=1` set to save the
+//! current frames instead, after checking the differences are intended.
+
+use std::fs;
+use std::path::PathBuf;
+
+use crossterm::event::{Event as TerminalEvent, KeyCode, KeyEvent, KeyModifiers};
+
+use crate::chord;
+use crate::editor::Event;
+use crate::virtual_text::{Annotation, Placement, Source};
+use crate::{Config, Document, Editor, EditorError, Terminal};
+
+const WIDTH: u16 = 40;
+const HEIGHT: u16 = 8;
+
+/// Opens a document in an editor drawing to a headless terminal, which keeps its history and
+/// other state in memory rather than the user's state directory.
+fn editor(text: &str) -> Editor {
+    let mut editor =
+        Editor::with_terminal(Config::default(), Terminal::headless(WIDTH, HEIGHT), None);
+    editor.open(Document::scratch("golden.txt", text));
+    editor
+}
+
+fn screen(editor: &Editor) -> String {
+    editor.terminal().screen().unwrap().dump()
+}
+
+/// Runs a command as if it was typed into the command prompt.
+fn run(editor: &mut Editor, command: &str) {
+    editor.handle_event(Event::OpenPrompt).unwrap();
+    for c in command.chars() {
+        editor.handle_event(Event::PromptInsert(c)).unwrap();
+    }
+    editor.handle_event(Event::PromptSubmit).unwrap();
+}
+
+/// The text drawn on the first row of the screen.
+fn first_row(editor: &mut Editor) -> String {
+    editor.redraw().unwrap();
+    screen(editor)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Draws the whole screen and compares it with the saved frame.
+fn assert_frame(name: &str, editor: &mut Editor) {
+    editor.redraw().unwrap();
+    let actual = screen(editor);
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/golden")
+        .join(format!("{}.txt", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_default();
+    assert_eq!(expected, actual, "frame differs from {}", path.display());
+}
+
+#[test]
+fn long_lines_are_cut_off() {
+    let text = "A line which is much too long to fit on the screen at once\n\
+                short\n\
+                \tindented with a tab and also long enough to be cut off\n";
+    assert_frame("long_lines", &mut editor(text));
+}
+
+#[test]
+fn wide_characters() {
+    let text = "漢字を含む行です。全角文字は二つのセルを使います。\n\
+                emoji 🎉 and accents é à ü\n\
+                ｆｕｌｌｗｉｄｔｈ\n";
+    assert_frame("wide_characters", &mut editor(text));
+}
+
+#[test]
+fn selection() {
+    let mut editor = editor("first line\nsecond line\nthird line\n");
+    editor.goto(1, 6);
+    editor.handle_event(Event::SelectDown(1)).unwrap();
+    editor.handle_event(Event::SelectRight(3)).unwrap();
+    assert_frame("selection", &mut editor);
+}
+
+#[test]
+fn new_line_replaces_selection() {
+    let mut editor = editor("first line\nsecond line\nthird line\n");
+    editor.goto(1, 6);
+    editor.handle_event(Event::SelectDown(1)).unwrap();
+    editor.handle_event(Event::SelectRight(3)).unwrap();
+    editor.handle_event(Event::NewLine).unwrap();
+    assert_frame("new_line_replaces_selection", &mut editor);
+}
+
+#[test]
+fn surround_word_and_undo() {
+    let mut editor = editor("let x = value;\n");
+    editor.goto(1, 10);
+    run(&mut editor, "ys ( iw");
+    assert_frame("surround_word", &mut editor);
+    editor.handle_event(Event::Undo).unwrap();
+    assert_eq!("let x = value;", first_row(&mut editor));
+
+    editor.handle_event(Event::Redo).unwrap();
+    run(&mut editor, "ds (");
+    assert_eq!("let x = value;", first_row(&mut editor));
+    editor.handle_event(Event::Undo).unwrap();
+    assert_eq!("let x = (value);", first_row(&mut editor));
+}
+
+#[test]
+fn surround_tag_and_undo() {
+    let mut editor = editor("let x = value;\n");
+    editor.goto(1, 10);
+    run(&mut editor, "ys <b> iw");
+    assert_frame("surround_tag", &mut editor);
+    editor.handle_event(Event::Undo).unwrap();
+    assert_eq!("let x = value;", first_row(&mut editor));
+
+    editor.handle_event(Event::Redo).unwrap();
+    run(&mut editor, "ds t");
+    assert_eq!("let x = value;", first_row(&mut editor));
+    editor.handle_event(Event::Undo).unwrap();
+    assert_eq!("let x = <b>value</b>;", first_row(&mut editor));
+}
+
+#[test]
+fn scrolled_to_end() {
+    let text: String = (1..=50).map(|i| format!("line {}\n", i)).collect();
+    let mut editor = editor(&text);
+    editor.goto(50, 0);
+    assert_frame("scrolled_to_end", &mut editor);
+}
+
+#[test]
+fn virtual_text() {
+    let mut document = Document::scratch("golden.txt", "let x = 1;\nlet y = x +;\nlet z = 3;\n");
+    let mut annotate = |line, placement, text: &str| {
+        document.virtual_text_mut().place(
+            line,
+            Annotation {
+                source: Source::Errors,
+                placement,
+                text: text.to_string(),
+                color: console::Color::Red,
+            },
+        )
+    };
+    annotate(2, Placement::EndOfLine, "expected expression");
+    annotate(2, Placement::Below, "help: remove the `+`");
+    annotate(
+        1,
+        Placement::EndOfLine,
+        "a message much too long to fit after the line",
+    );
+
+    let mut editor =
+        Editor::with_terminal(Config::default(), Terminal::headless(WIDTH, HEIGHT), None);
+    editor.open(document);
+    editor.goto(3, 0);
+    assert_frame("virtual_text", &mut editor);
+}
+
+#[test]
+fn title_shows_name_and_modified() {
+    let mut editor = editor("text\n");
+    editor.redraw().unwrap();
+    let title = |editor: &Editor| {
+        editor
+            .terminal()
+            .screen()
+            .unwrap()
+            .title()
+            .map(String::from)
+    };
+    assert_eq!(Some("golden.txt - texteditor"), title(&editor).as_deref());
+
+    editor.handle_event(Event::KeyPress('x')).unwrap();
+    editor.render().unwrap();
+    assert_eq!(
+        Some("golden.txt [+] - texteditor"),
+        title(&editor).as_deref()
+    );
+}
+
+#[test]
+fn long_message_over_several_rows() {
+    let text: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+    let mut editor = editor(&text);
+    editor.report(EditorError::Command(String::from(
+        "Command failed: error: could not compile `texteditor` due to 2 previous errors",
+    )));
+    assert_frame("long_message", &mut editor);
+
+    // The key which dismisses the message isn't typed.
+    let key = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE);
+    editor
+        .handle_event(Event::Input(TerminalEvent::Key(key)))
+        .unwrap();
+    editor.redraw().unwrap();
+    assert!(screen(&editor).starts_with("line 1\n"));
+}
+
+#[test]
+fn partial_redraw_matches_full_redraw() {
+    let mut editor = editor("first line\nsecond line\nthird line\n");
+    editor.redraw().unwrap();
+
+    editor.goto(2, 0);
+    for c in "new ".chars() {
+        editor.handle_event(Event::KeyPress(c)).unwrap();
+    }
+    editor.render().unwrap();
+    let partial = screen(&editor);
+
+    editor.redraw().unwrap();
+    assert_eq!(screen(&editor), partial);
+}
+
+#[test]
+fn picker_over_text() {
+    let text: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+    let mut editor = editor(&text);
+    editor.handle_event(Event::KillLine).unwrap();
+    editor.handle_event(Event::PasteHistory).unwrap();
+    assert_frame("picker_over_text", &mut editor);
+}
+
+#[test]
+fn buffer_picker_with_preview() {
+    let mut editor = editor("first buffer\n");
+    editor.open(Document::scratch("other.txt", "other text\nsecond line\n"));
+    editor
+        .handle_event(Event::StartChord(&chord::CTRL_X))
+        .unwrap();
+    editor.handle_event(Event::ChordKey('l')).unwrap();
+    editor.handle_event(Event::PickerNext).unwrap();
+    assert_frame("buffer_picker", &mut editor);
+}
+
+#[test]
+fn minimap_kept_by_partial_redraws() {
+    // Matching brackets again after each edit would redraw the whole screen anyway.
+    let config = Config {
+        minimap: true,
+        rainbow_brackets: false,
+        bracket_scope: false,
+        ..Config::default()
+    };
+    let mut editor = Editor::with_terminal(config, Terminal::headless(WIDTH, HEIGHT), None);
+    let text: String = (1..=40).map(|i| format!("line {}\n", i)).collect();
+    editor.open(Document::scratch("golden.txt", &text));
+    editor.redraw().unwrap();
+
+    // An edit which doesn't change the minimap's shading only redraws its own line.
+    editor.handle_event(Event::KeyPress('a')).unwrap();
+    editor.render().unwrap();
+    let partial = screen(&editor);
+    editor.redraw().unwrap();
+    assert_eq!(screen(&editor), partial);
+}
+
+#[test]
+fn closed_fold() {
+    let text: String = (1..=30)
+        .map(|i| match i {
+            2..=20 => format!("  line {}\n", i),
+            _ => format!("line {}\n", i),
+        })
+        .collect();
+    let mut editor = editor(&text);
+    editor.goto(1, 0);
+    editor
+        .handle_event(Event::StartChord(&chord::CTRL_X))
+        .unwrap();
+    editor.handle_event(Event::ChordKey('f')).unwrap();
+    assert_frame("closed_fold", &mut editor);
+
+    // Lines edited below the fold are drawn on the rows the fold moved them to.
+    editor.goto(22, 0);
+    editor.handle_event(Event::KeyPress('x')).unwrap();
+    editor.render().unwrap();
+    let partial = screen(&editor);
+    editor.redraw().unwrap();
+    assert_eq!(screen(&editor), partial);
+}
+
+#[test]
+fn prompt_history_kept_in_state_dir() {
+    let dir = std::env::temp_dir().join("texteditor_prompt_history_kept_in_state_dir");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let mut editor = Editor::with_terminal(
+        Config::default(),
+        Terminal::headless(WIDTH, HEIGHT),
+        Some(dir.clone()),
+    );
+    editor.open(Document::scratch("golden.txt", "text\n"));
+    run(&mut editor, "goto 1");
+    let history = fs::read_to_string(dir.join("command_history")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(history.lines().collect::<Vec<_>>(), ["goto 1"]);
+}
+
+#[test]
+fn jump_back_after_closing_other_buffers() {
+    let lines = |name: &str| -> String { (1..=40).map(|i| format!("{} {}\n", name, i)).collect() };
+    let mut editor = editor(&lines("a"));
+    editor.open(Document::scratch("b.txt", &lines("b")));
+    editor.open(Document::scratch("c.txt", &lines("c")));
+    editor.handle_event(Event::KeyPress('x')).unwrap();
+    editor.goto(30, 0);
+    editor.handle_event(Event::PreviousBuffer).unwrap();
+    editor.handle_event(Event::PreviousBuffer).unwrap();
+
+    // The unsaved buffer is kept, and moves into the place of the one closed before it.
+    run(&mut editor, "only");
+    editor.handle_event(Event::JumpBack).unwrap();
+    editor.redraw().unwrap();
+    assert!(screen(&editor).contains("c 30"));
+}
+
+#[test]
+fn jump_back_after_deleting_file() {
+    let path = std::env::temp_dir().join("texteditor_jump_back_after_deleting_file");
+    fs::write(&path, "deleted\n").unwrap();
+    let lines = |name: &str| -> String { (1..=40).map(|i| format!("{} {}\n", name, i)).collect() };
+    let mut editor = editor(&lines("a"));
+    editor.open(Document::open(path.clone(), &Config::default()).unwrap());
+    editor.open(Document::scratch("c.txt", &lines("c")));
+    editor.open(Document::scratch("d.txt", &lines("d")));
+    editor.handle_event(Event::PreviousBuffer).unwrap();
+    editor.goto(30, 0);
+    editor.handle_event(Event::PreviousBuffer).unwrap();
+
+    // Deleting the file moves the buffers after it into its place.
+    run(&mut editor, "delete-file!");
+    assert!(!path.exists());
+    editor.goto(1, 0);
+    editor.handle_event(Event::JumpBack).unwrap();
+    editor.redraw().unwrap();
+    assert!(screen(&editor).contains("c 30"));
+}
+
+#[test]
+fn serve_only_on_loopback_addresses() {
+    let mut editor = editor("shared\n");
+    assert!(matches!(
+        editor.serve("0.0.0.0:0", false),
+        Err(EditorError::RemoteAddress(_))
+    ));
+    editor.serve("127.0.0.1:0", false).unwrap();
+}
@@ -0,0 +1,310 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers};
+
+use crate::case::Case;
+use crate::chord::{self, Chord};
+use crate::editor::Event;
+use crate::keymaps::KeyMaps;
+
+/// The name of the buffer the help is opened in.
+pub const TITLE: &str = "[Help]";
+
+/// The help text, with placeholders for the keys, which are listed from the keymaps.
+const TEXT: &str = include_str!("help.txt");
+
+/// The keys which are looked up in the keymaps. Letters are also looked up with Shift, as their
+/// uppercase character.
+const NAMED_KEYS: [KeyCode; 14] = [
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::Delete,
+    KeyCode::Insert,
+    KeyCode::Esc,
+];
+const SYMBOLS: [char; 7] = ['/', '.', '(', ')', '~', '7', ','];
+
+/// Generates the help text, listing the keys bound by the keymaps.
+pub fn text(keymaps: &KeyMaps) -> String {
+    TEXT.replace(
+        "{keys}",
+        &list_keys(|key| keymaps.map_key_press_to_event(key)),
+    )
+    .replace(
+        "{prompt_keys}",
+        &list_keys(|key| keymaps.map_prompt_key_press_to_event(key)),
+    )
+    .replace(
+        "{picker_keys}",
+        &list_keys(|key| keymaps.map_picker_key_press_to_event(key)),
+    )
+    .replace("{chords}", &list_chord(&chord::CTRL_X))
+}
+
+/// The line a topic's tag, written `*topic*`, is on. Commands can be found with or without
+/// their leading ':'. Line numbers start from 1.
+///
+/// # Arguments
+///
+/// * 'text' - The help text.
+/// * 'topic' - The topic to find.
+pub fn find_topic(text: &str, topic: &str) -> Option<u32> {
+    let topic = topic.trim();
+    let find = |tag: String| text.lines().position(|line| line.contains(&tag));
+    find(format!("*{}*", topic))
+        .or_else(|| find(format!("*:{}*", topic)))
+        .map(|index| index as u32 + 1)
+}
+
+/// The topic of the link, written `|topic|`, at a column of a line.
+///
+/// # Arguments
+///
+/// * 'line' - The line of help text.
+/// * 'column' - The column of the line, counted in characters from 0.
+pub fn link_at(line: &str, column: usize) -> Option<&str> {
+    let bars: Vec<(usize, usize)> = line
+        .char_indices()
+        .enumerate()
+        .filter(|(_, (_, c))| *c == '|')
+        .map(|(column, (byte, _))| (column, byte))
+        .collect();
+    bars.windows(2)
+        .filter(|pair| pair[0].0 <= column && column <= pair[1].0)
+        .map(|pair| &line[pair[0].1 + 1..pair[1].1])
+        .find(|topic| !topic.is_empty() && !topic.contains(char::is_whitespace))
+}
+
+/// Lists the keys which a keymap binds to a described event, one event per line along with all
+/// the keys bound to it.
+fn list_keys(map: impl Fn(KeyEvent) -> Option<Event>) -> String {
+    let describe_key = |code, modifiers| map(key(code, modifiers)).as_ref().and_then(describe);
+
+    let mut codes: Vec<KeyCode> = NAMED_KEYS.to_vec();
+    codes.extend((1..=12).map(KeyCode::F));
+    codes.extend(SYMBOLS.map(KeyCode::Char));
+    codes.extend(('a'..='z').map(KeyCode::Char));
+
+    let mut bindings: Vec<(&str, Vec<String>)> = Vec::new();
+    for code in codes {
+        let plain = describe_key(code, KeyModifiers::NONE);
+        for modifiers in modifier_combinations(code) {
+            let (code, modifiers) = match code {
+                KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                    (KeyCode::Char(c.to_ascii_uppercase()), modifiers)
+                }
+                _ => (code, modifiers),
+            };
+            let Some(description) = describe_key(code, modifiers) else {
+                continue;
+            };
+            // Keys like the arrows do the same thing whatever modifiers are held.
+            if modifiers != KeyModifiers::NONE && plain == Some(description) {
+                continue;
+            }
+
+            let name = key_name(code, modifiers);
+            match bindings.iter_mut().find(|(d, _)| *d == description) {
+                Some((_, keys)) => keys.push(name),
+                None => bindings.push((description, vec![name])),
+            }
+        }
+    }
+
+    let lines: Vec<(String, &str)> = bindings
+        .into_iter()
+        .map(|(description, keys)| (keys.join(", "), description))
+        .collect();
+    let width = lines.iter().map(|(keys, _)| keys.len()).max().unwrap_or(0);
+    lines
+        .iter()
+        .map(|(keys, description)| format!("  {:<width$}  {}", keys, description))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Lists the keys which can follow a chord's prefix, each with a link to its command.
+fn list_chord(chord: &Chord) -> String {
+    chord
+        .continuations
+        .iter()
+        .map(|(key, command)| {
+            let (name, argument) = command.split_once(' ').unwrap_or((command, ""));
+            let key = chord::key_name(*key);
+            format!("  {} {:<6}  |:{}| {}", chord.prefix, key, name, argument)
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// The modifiers each key is looked up with. Shift is only looked up with letters and named
+/// keys, since Shift changes which character a symbol key types.
+fn modifier_combinations(code: KeyCode) -> Vec<KeyModifiers> {
+    let mut combinations = vec![KeyModifiers::NONE, KeyModifiers::CONTROL, KeyModifiers::ALT];
+    match code {
+        KeyCode::Char(c) if c.is_ascii_alphabetic() => combinations.extend([
+            KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+            KeyModifiers::ALT | KeyModifiers::SHIFT,
+        ]),
+        KeyCode::Char(_) => combinations.push(KeyModifiers::CONTROL | KeyModifiers::SHIFT),
+        _ => combinations.push(KeyModifiers::SHIFT),
+    }
+    combinations
+}
+
+fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+    KeyEvent {
+        code,
+        modifiers,
+        kind: KeyEventKind::Press,
+        state: KeyEventState::NONE,
+    }
+}
+
+/// The name of a key as it's written in the help, such as `Ctrl+Shift+K`. Letters are written
+/// in uppercase whether or not Shift is held, as they're printed on the keyboard.
+fn key_name(code: KeyCode, modifiers: KeyModifiers) -> String {
+    let mut name = String::new();
+    for (modifier, prefix) in [
+        (KeyModifiers::CONTROL, "Ctrl+"),
+        (KeyModifiers::ALT, "Alt+"),
+        (KeyModifiers::SHIFT, "Shift+"),
+    ] {
+        if modifiers.contains(modifier) {
+            name += prefix;
+        }
+    }
+    match code {
+        KeyCode::Char(c) => name.push(c.to_ascii_uppercase()),
+        KeyCode::F(n) => name += &format!("F{}", n),
+        _ => name += &format!("{:?}", code),
+    }
+    name
+}
+
+/// What an event bound to a key does, or None for events which aren't listed, like typing a
+/// character.
+fn describe(event: &Event) -> Option<&'static str> {
+    Some(match event {
+        Event::RepeatEdit => "Repeat the last edit",
+        Event::Exit => "Quit",
+        Event::MoveCursorUp(_) => "Move up",
+        Event::MoveCursorDown(_) => "Move down",
+        Event::MoveCursorLeft(_) => "Move left",
+        Event::MoveCursorRight(_) => "Move right",
+        Event::MoveCursorHome => "Move to the start of the line",
+        Event::NextParagraph => "Move to the next paragraph",
+        Event::PreviousParagraph => "Move to the previous paragraph",
+        Event::NextSentence => "Move to the next sentence",
+        Event::NextWord => "Move to the next word, see |subword_motion|",
+        Event::PreviousWord => "Move to the previous word",
+        Event::DeleteWordBack => "Delete to the start of the word",
+        Event::DeleteWordForward => "Delete to the start of the next word",
+        Event::PreviousSentence => "Move to the previous sentence",
+        Event::SelectUp(_) => "Select up",
+        Event::SelectDown(_) => "Select down",
+        Event::SelectLeft(_) => "Select left",
+        Event::SelectRight(_) => "Select right",
+        Event::SelectAll => "Select the whole document",
+        Event::SelectLine => "Select the line",
+        Event::ExpandSelection => "Expand the selection",
+        Event::ChangeCase(Case::Upper) => "Uppercase the selection",
+        Event::ChangeCase(Case::Lower) => "Lowercase the selection",
+        Event::ChangeCase(Case::Title) => "Titlecase the selection",
+        Event::ChangeCase(Case::Toggle) => "Toggle the case of the selection",
+        Event::Reflow => "Re-wrap the paragraph",
+        Event::FindNext => "Find the next match of the last search",
+        Event::FindPrevious => "Find the previous match of the last search",
+        Event::NextError => "Jump to the next error",
+        Event::PreviousError => "Jump to the previous error",
+        Event::ScrollDown(_) => "Scroll down",
+        Event::ScrollUp(_) => "Scroll up",
+        Event::Recenter => "Center the cursor's line on screen",
+        Event::NewLine => "Break the line, indenting the new one, or follow a link in the help",
+        Event::KillLine => "Cut to the end of the line",
+        Event::DeleteLine => "Delete the line",
+        Event::Yank => "Paste the last cut text",
+        Event::PasteHistory => "Pick cut or pasted text to paste",
+        Event::Indent => "Indent",
+        Event::ToggleComment => "Comment or uncomment lines",
+        Event::ToggleOverwrite => "Switch between inserting and overwriting",
+        Event::Cancel => "Clear the selection",
+        Event::NextBuffer => "Switch to the next buffer",
+        Event::PreviousBuffer => "Switch to the previous buffer",
+        Event::JumpBack => "Jump back",
+        Event::JumpForward => "Jump forward",
+        Event::Save => "Save",
+        Event::Suspend => "Suspend to the shell",
+        Event::Undo => "Undo",
+        Event::Redo => "Redo",
+        Event::InsertLiteral => "Insert a character by its code or a digraph",
+        Event::StartChord(_) => "Run a command with the next key, see |chords|",
+        Event::OpenPrompt => "Open the command prompt",
+        Event::OpenGotoPrompt => "Go to a line, moving to it while it's typed",
+        Event::OpenSearchPrompt => "Find text, moving to the next match while it's typed",
+        Event::PickerBackspace | Event::PromptBackspace => "Delete the last character",
+        Event::PickerNext => "Highlight the next item",
+        Event::PickerPrevious => "Highlight the previous item",
+        Event::PickerSubmit => "Choose the highlighted item",
+        Event::PickerCancel | Event::PromptCancel => "Close",
+        Event::PromptHistoryPrevious => "Previous entry from the prompt's history",
+        Event::PromptHistoryNext => "Next entry from the prompt's history",
+        Event::PromptComplete => "Complete the file path",
+        Event::PromptToggleHidden => "Include or exclude hidden files when completing",
+        Event::PromptSubmit => "Run the command, go to the line or find the text",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn text_lists_bound_keys() {
+        let text = text(&KeyMaps {});
+        let line = |keys: &str| text.lines().find(|l| l.trim_start().starts_with(keys));
+
+        assert!(!text.contains("{keys}"));
+        assert!(line("Ctrl+S ").is_some_and(|l| l.ends_with("  Save")));
+        assert!(line("Ctrl+Shift+K ").is_some_and(|l| l.ends_with("  Delete the line")));
+        assert!(line("Ctrl+/, Ctrl+7 ").is_some());
+        assert!(line("Left ").is_some_and(|l| l.ends_with("  Move left")));
+        assert!(line("Ctrl+Up").is_none(), "same as Up");
+        assert!(line("Ctrl+Left, Alt+Left ").is_some_and(|l| l.ends_with("previous word")));
+    }
+
+    #[test_case("commands", Some("*commands*");    "section")]
+    #[test_case("sort", Some("*:sort*");           "command_without_colon")]
+    #[test_case(":sort", Some("*:sort*");          "command")]
+    #[test_case("tab_width", Some("*tab_width*");  "option")]
+    #[test_case("nothing", None;                   "missing")]
+    fn topic_line(topic: &str, expected: Option<&str>) {
+        let line = find_topic(TEXT, topic).and_then(|line| TEXT.lines().nth(line as usize - 1));
+        assert_eq!(
+            expected,
+            line.map(|line| line.split_whitespace().next().unwrap_or_default())
+        );
+    }
+
+    #[test_case(0, None;           "before_link")]
+    #[test_case(6, Some("keys");   "opening_bar")]
+    #[test_case(9, Some("keys");   "inside")]
+    #[test_case(11, Some("keys");  "closing_bar")]
+    #[test_case(13, None;          "between_links")]
+    #[test_case(19, Some("é-x");   "after_multibyte")]
+    fn link_at_column(column: usize, expected: Option<&str>) {
+        assert_eq!(expected, link_at("  see |keys| or |é-x|", column));
+    }
+}
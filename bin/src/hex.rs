@@ -0,0 +1,140 @@
+/// The number of bytes displayed on each row of the hex view.
+pub const BYTES_PER_ROW: usize = 16;
+
+/// The number of bytes sampled from the start of a file when checking if it's binary.
+const SAMPLE_SIZE: usize = 8192;
+
+/// The proportion of invalid UTF-8 bytes above which a file is treated as binary.
+const INVALID_RATIO: f64 = 0.1;
+
+/// Width of the offset column, including the separating spaces.
+const OFFSET_WIDTH: usize = 10;
+
+/// Checks whether the start of the contents looks like binary data rather than text. Contents
+/// are treated as binary if they contain a NUL byte, or if too many of the bytes aren't valid
+/// UTF-8.
+///
+/// # Arguments
+///
+/// * 'bytes' - The contents to check. Only the start of the contents is sampled.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(SAMPLE_SIZE)];
+    if sample.is_empty() {
+        return false;
+    }
+
+    if sample.contains(&0) {
+        return true;
+    }
+
+    let invalid: usize = sample
+        .utf8_chunks()
+        .map(|chunk| chunk.invalid().len())
+        .sum();
+    invalid as f64 / sample.len() as f64 > INVALID_RATIO
+}
+
+/// The number of rows needed to display the bytes. Empty contents still display a single row.
+pub fn row_count(len: usize) -> u32 {
+    len.div_ceil(BYTES_PER_ROW).max(1) as u32
+}
+
+/// Renders a single row of the hex view, made up of the offset, the hex value of each byte and
+/// the printable ASCII characters, in the same layout as `hexdump -C`.
+///
+/// # Arguments
+///
+/// * 'bytes' - The full contents being displayed.
+/// * 'row' - The row to render. Rows start from 0.
+pub fn render_row(bytes: &[u8], row: usize) -> String {
+    let start = (row * BYTES_PER_ROW).min(bytes.len());
+    let end = (start + BYTES_PER_ROW).min(bytes.len());
+    let chunk = &bytes[start..end];
+
+    let mut line = format!("{:08x}  ", start);
+    for i in 0..BYTES_PER_ROW {
+        match chunk.get(i) {
+            Some(byte) => line += &format!("{:02x} ", byte),
+            None => line += "   ",
+        }
+        if i == BYTES_PER_ROW / 2 - 1 {
+            line.push(' ');
+        }
+    }
+
+    line += " |";
+    line.extend(chunk.iter().map(|&byte| match byte {
+        0x20..=0x7e => byte as char,
+        _ => '.',
+    }));
+    line.push('|');
+    line
+}
+
+/// The screen column of the hex digit for a byte in a row.
+///
+/// # Arguments
+///
+/// * 'column' - The index of the byte within the row.
+/// * 'low' - Whether to return the column of the low nibble rather than the high nibble.
+pub fn screen_column(column: u32, low: bool) -> u16 {
+    let gap = if column as usize >= BYTES_PER_ROW / 2 {
+        1
+    } else {
+        0
+    };
+    (OFFSET_WIDTH as u32 + column * 3 + gap + low as u32) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(b"Lorem ipsum dolor sit amet", false; "text")]
+    #[test_case(b"", false;                          "empty")]
+    #[test_case(b"Lorem\0ipsum", true;               "nul_byte")]
+    #[test_case(b"L\xf6rem ipsum dolor sit amet", false; "few_invalid_bytes")]
+    #[test_case(b"\xff\xfe\xfd\xfc\xfb ipsum", true; "mostly_invalid_bytes")]
+    fn detect_binary(bytes: &[u8], expected: bool) {
+        assert_eq!(expected, is_binary(bytes));
+    }
+
+    #[test]
+    fn render_full_row() {
+        let bytes = b"Lorem ipsum\n\x00\x01\x02\x03";
+
+        let expected =
+            "00000000  4c 6f 72 65 6d 20 69 70  73 75 6d 0a 00 01 02 03  |Lorem ipsum.....|";
+        assert_eq!(expected, render_row(bytes, 0));
+    }
+
+    #[test]
+    fn render_partial_row() {
+        let bytes = b"Lorem ipsum dolor sit";
+
+        let expected = "00000010  72 20 73 69 74                                    |r sit|";
+        assert_eq!(expected, render_row(bytes, 1));
+    }
+
+    #[test_case(0, 0;  "first_byte")]
+    #[test_case(7, 0;  "before_gap")]
+    #[test_case(15, 0; "last_byte")]
+    #[test_case(21, 1; "partial_last_row")]
+    fn screen_column_matches_render(index: usize, row: usize) {
+        let bytes: Vec<u8> = (0..22).collect();
+        let line = render_row(&bytes, row);
+
+        let column = (index % BYTES_PER_ROW) as u32;
+        let pos = screen_column(column, false) as usize;
+        assert_eq!(format!("{:02x}", bytes[index]), line[pos..pos + 2]);
+    }
+
+    #[test]
+    fn row_count_rounds_up() {
+        assert_eq!(1, row_count(0));
+        assert_eq!(1, row_count(16));
+        assert_eq!(2, row_count(17));
+    }
+}
@@ -0,0 +1,233 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Previously entered prompt input, which can be recalled with the up and down keys while the
+/// prompt is open. The history is saved to a file so it persists across invocations.
+#[derive(Debug)]
+pub struct History {
+    entries: Vec<String>,
+    max: usize,
+    path: Option<PathBuf>,
+    /// The entry currently being recalled, or None if the user is editing new input.
+    index: Option<usize>,
+    /// The input being edited before recalling history, restored when moving past the newest
+    /// entry.
+    draft: String,
+}
+
+impl History {
+    /// Constructs an empty history which isn't saved to disk.
+    ///
+    /// # Arguments
+    ///
+    /// * 'max' - The maximum number of entries to keep. The oldest entries are dropped first.
+    pub fn new(max: usize) -> History {
+        History {
+            entries: vec![],
+            max,
+            path: None,
+            index: None,
+            draft: String::new(),
+        }
+    }
+
+    /// Loads the history from a file with one entry per line, oldest first. A missing file is
+    /// treated as an empty history.
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - The file the history is read from and saved to.
+    /// * 'max' - The maximum number of entries to keep. The oldest entries are dropped first.
+    pub fn load(path: PathBuf, max: usize) -> History {
+        let mut history = History::new(max);
+        if let Ok(contents) = fs::read_to_string(&path) {
+            for line in contents.lines() {
+                history.push(line);
+            }
+        }
+        history.path = Some(path);
+        history
+    }
+
+    /// Writes the history to its file, creating the containing directory if needed.
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut contents = String::new();
+        for entry in &self.entries {
+            contents += entry;
+            contents.push('\n');
+        }
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Adds an entry as the newest in the history. Blank entries are ignored, and an entry which
+    /// is already in the history is moved to the end rather than duplicated.
+    pub fn push(&mut self, entry: &str) {
+        self.index = None;
+        if entry.trim().is_empty() {
+            return;
+        }
+
+        self.entries.retain(|e| e != entry);
+        self.entries.push(entry.to_string());
+        if self.entries.len() > self.max {
+            let excess = self.entries.len() - self.max;
+            self.entries.drain(..excess);
+        }
+    }
+
+    /// Replaces an entry where it is in the history, or removes it if there's nothing to replace
+    /// it with.
+    pub fn replace(&mut self, entry: &str, with: Option<&str>) {
+        self.index = None;
+        match with {
+            Some(with) => {
+                self.entries.retain(|e| e != with);
+                for e in self.entries.iter_mut().filter(|e| *e == entry) {
+                    *e = with.to_string();
+                }
+            }
+            None => self.entries.retain(|e| e != entry),
+        }
+    }
+
+    /// The entries, newest first.
+    pub fn newest_first(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().rev().map(String::as_str)
+    }
+
+    /// Moves to the next oldest entry, returning it.
+    ///
+    /// # Arguments
+    ///
+    /// * 'input' - The current prompt input, which is restored by moving forward past the
+    ///   newest entry.
+    pub fn previous(&mut self, input: &str) -> Option<&str> {
+        let index = match self.index {
+            Some(0) => return None,
+            Some(index) => index - 1,
+            None if self.entries.is_empty() => return None,
+            None => {
+                self.draft = input.to_string();
+                self.entries.len() - 1
+            }
+        };
+        self.index = Some(index);
+        self.entries.get(index).map(String::as_str)
+    }
+
+    /// Moves to the next newest entry, returning it. Moving past the newest entry returns the
+    /// input that was being edited before the history was recalled.
+    pub fn next(&mut self) -> Option<&str> {
+        let index = self.index? + 1;
+        if index < self.entries.len() {
+            self.index = Some(index);
+            self.entries.get(index).map(String::as_str)
+        } else {
+            self.index = None;
+            Some(&self.draft)
+        }
+    }
+
+    /// Stops recalling history, so the next call to `previous` starts from the newest entry.
+    pub fn reset(&mut self) {
+        self.index = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(entries: &[&str]) -> History {
+        let mut history = History::new(10);
+        for entry in entries {
+            history.push(entry);
+        }
+        history
+    }
+
+    #[test]
+    fn previous_and_next() {
+        let mut history = history(&["w", "q"]);
+
+        assert_eq!(Some("q"), history.previous("draft"));
+        assert_eq!(Some("w"), history.previous("q"));
+        assert_eq!(None, history.previous("w"));
+        assert_eq!(Some("q"), history.next());
+        assert_eq!(Some("draft"), history.next());
+        assert_eq!(None, history.next());
+    }
+
+    #[test]
+    fn entries_newest_first() {
+        let history = history(&["w", "q", "w"]);
+        assert_eq!(vec!["w", "q"], history.newest_first().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn replace_entries() {
+        let mut history = history(&["a", "b", "c"]);
+        history.replace("b", Some("c"));
+        assert_eq!(vec!["c", "a"], history.newest_first().collect::<Vec<_>>());
+        history.replace("a", None);
+        assert_eq!(vec!["c"], history.newest_first().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn previous_when_empty() {
+        let mut history = History::new(10);
+        assert_eq!(None, history.previous(""));
+        assert_eq!(None, history.next());
+    }
+
+    #[test]
+    fn push_moves_duplicate_to_end() {
+        let mut history = history(&["w", "q", "w"]);
+
+        assert_eq!(Some("w"), history.previous(""));
+        assert_eq!(Some("q"), history.previous(""));
+        assert_eq!(None, history.previous(""));
+    }
+
+    #[test]
+    fn push_ignores_blank_entries() {
+        let mut history = history(&["w", "  "]);
+        assert_eq!(Some("w"), history.previous(""));
+    }
+
+    #[test]
+    fn push_drops_oldest_entries() {
+        let mut history = History::new(2);
+        history.push("1");
+        history.push("2");
+        history.push("3");
+
+        assert_eq!(Some("3"), history.previous(""));
+        assert_eq!(Some("2"), history.previous(""));
+        assert_eq!(None, history.previous(""));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join("texteditor_history_save_and_load");
+        let mut history = History::load(path.to_owned(), 10);
+        history.push("w");
+        history.push("q");
+        history.save().unwrap();
+
+        let mut loaded = History::load(path.to_owned(), 10);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(Some("q"), loaded.previous(""));
+        assert_eq!(Some("w"), loaded.previous(""));
+    }
+}
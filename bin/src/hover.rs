@@ -0,0 +1,119 @@
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use crate::quickfix::Entry;
+
+/// How long the mouse has to rest in one place before a tooltip is shown for what's under it.
+pub const DELAY: Duration = Duration::from_millis(500);
+
+/// Where the mouse is resting over the screen, and the tooltip for what's under it once it has
+/// rested there long enough.
+#[derive(Debug)]
+pub struct Hover {
+    pub column: u16,
+    pub row: u16,
+    started: Instant,
+    /// The lines of the tooltip, once they've been worked out. Empty if there's nothing to show.
+    pub tooltip: Option<Vec<String>>,
+}
+
+impl Hover {
+    /// Starts waiting for the mouse to rest at a column and row of the screen.
+    pub fn new(column: u16, row: u16) -> Hover {
+        Hover {
+            column,
+            row,
+            started: Instant::now(),
+            tooltip: None,
+        }
+    }
+
+    /// Whether the mouse has rested long enough for the tooltip to be worked out, and it
+    /// hasn't been yet.
+    pub fn is_due(&self) -> bool {
+        self.tooltip.is_none() && self.started.elapsed() >= DELAY
+    }
+
+    /// Whether a tooltip is shown.
+    pub fn is_shown(&self) -> bool {
+        self.tooltip.as_ref().is_some_and(|lines| !lines.is_empty())
+    }
+}
+
+/// The messages of the errors under the mouse on a line. An error with a column is under the
+/// word at that column, and one without is under the whole line. Past the end of the line's
+/// text, where the first error is drawn, every error on the line is.
+///
+/// # Arguments
+///
+/// * 'entries' - The errors on the line.
+/// * 'line' - The text of the line.
+/// * 'column' - The character under the mouse, or None if it's past the end of the text.
+pub fn diagnostics<'a>(
+    entries: impl IntoIterator<Item = &'a Entry>,
+    line: &str,
+    column: Option<u32>,
+) -> Vec<String> {
+    entries
+        .into_iter()
+        .filter(|entry| match (entry.column, column) {
+            (Some(start), Some(column)) => {
+                word_at(line, start.saturating_sub(1) as usize).contains(&(column as usize))
+            }
+            _ => true,
+        })
+        .flat_map(|entry| entry.message.lines().map(String::from))
+        .collect()
+}
+
+/// The columns of the word containing a column, or just the column if it isn't in a word.
+fn word_at(line: &str, column: usize) -> Range<usize> {
+    let chars: Vec<char> = line.chars().collect();
+    let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+    if !chars.get(column).is_some_and(is_word) {
+        return column..column + 1;
+    }
+    let start = chars[..column]
+        .iter()
+        .rposition(|c| !is_word(c))
+        .map_or(0, |i| i + 1);
+    let end = chars[column..]
+        .iter()
+        .position(|c| !is_word(c))
+        .map_or(chars.len(), |i| column + i);
+    start..end
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use test_case::test_case;
+
+    use super::*;
+
+    fn entry(column: Option<u32>, message: &str) -> Entry {
+        Entry {
+            path: PathBuf::from("main.rs"),
+            line: 1,
+            column,
+            message: message.to_string(),
+        }
+    }
+
+    #[test_case("let value = 1;", 6, 4..9;    "inside_a_word")]
+    #[test_case("let value = 1;", 4, 4..9;    "start_of_a_word")]
+    #[test_case("let value = 1;", 10, 10..11; "symbol")]
+    #[test_case("x", 3, 3..4;                 "past_the_end")]
+    fn words(line: &str, column: usize, expected: Range<usize>) {
+        assert_eq!(expected, word_at(line, column));
+    }
+
+    #[test_case(Some(7), vec!["unused", "help: x", "line"]; "over_the_word")]
+    #[test_case(Some(1), vec!["line"];                    "elsewhere_on_the_line")]
+    #[test_case(None, vec!["unused", "help: x", "line"];  "past_the_end")]
+    fn diagnostics_under_the_mouse(column: Option<u32>, expected: Vec<&str>) {
+        let entries = [entry(Some(5), "unused\nhelp: x"), entry(None, "line")];
+        assert_eq!(expected, diagnostics(&entries, "let value = 1;", column));
+    }
+}
@@ -0,0 +1,133 @@
+use std::time::{Duration, Instant};
+
+/// How long the event loop spends on idle work at a time before checking for input again.
+pub const BUDGET: Duration = Duration::from_millis(10);
+
+/// Work which is put off until the editor is waiting for input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Task {
+    /// Loads the next chunk of a document which is loading in the background.
+    Load,
+    /// Rebuilds the piece table of a document which has been split up by editing.
+    Compact,
+    /// Builds the next part of the search index of a large document.
+    Index,
+}
+
+/// Runs idle tasks in turn, a step at a time, in slices bounded by a time budget so input is
+/// never kept waiting for long.
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    tasks: Vec<Task>,
+    /// The task the next slice starts with.
+    next: usize,
+}
+
+impl Scheduler {
+    pub fn new(tasks: Vec<Task>) -> Scheduler {
+        Scheduler { tasks, next: 0 }
+    }
+
+    /// Runs steps of the tasks in turn until the budget is spent or none of them has anything
+    /// left to do, returning whether any work was done. Each slice picks up from the task after
+    /// the last one run, so a task with a lot of work can't starve the others.
+    ///
+    /// # Arguments
+    ///
+    /// * 'budget' - How long the slice can run for. A step which is started always finishes, so
+    ///   the slice can overrun by one step.
+    /// * 'step' - Runs one step of a task, returning whether the task had anything to do.
+    pub fn run_slice(&mut self, budget: Duration, mut step: impl FnMut(Task) -> bool) -> bool {
+        let started = Instant::now();
+        let mut worked = false;
+        // The number of tasks in a row which had nothing to do.
+        let mut idle = 0;
+        while idle < self.tasks.len() && started.elapsed() < budget {
+            let task = self.tasks[self.next];
+            self.next = (self.next + 1) % self.tasks.len();
+            if step(task) {
+                worked = true;
+                idle = 0;
+            } else {
+                idle += 1;
+            }
+        }
+        worked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tasks_take_turns_until_done() {
+        let mut scheduler = Scheduler::new(vec![Task::Load, Task::Compact]);
+        let mut loads = 3;
+        let mut compactions = 1;
+        let mut order = vec![];
+
+        let worked = scheduler.run_slice(Duration::MAX, |task| {
+            order.push(task);
+            let remaining = match task {
+                Task::Load => &mut loads,
+                Task::Compact => &mut compactions,
+                Task::Index => return false,
+            };
+            if *remaining == 0 {
+                return false;
+            }
+            *remaining -= 1;
+            true
+        });
+
+        assert!(worked);
+        assert_eq!((0, 0), (loads, compactions));
+        assert_eq!(
+            vec![
+                Task::Load,
+                Task::Compact,
+                Task::Load,
+                Task::Compact,
+                Task::Load,
+                Task::Compact,
+                Task::Load,
+            ],
+            order
+        );
+    }
+
+    #[test]
+    fn nothing_to_do() {
+        let mut scheduler = Scheduler::new(vec![Task::Load, Task::Compact]);
+        let mut steps = 0;
+        assert!(!scheduler.run_slice(Duration::MAX, |_| {
+            steps += 1;
+            false
+        }));
+        assert_eq!(2, steps);
+    }
+
+    #[test]
+    fn slice_stops_when_budget_spent() {
+        let mut scheduler = Scheduler::new(vec![Task::Load, Task::Compact]);
+        let mut order = vec![];
+        scheduler.run_slice(Duration::ZERO, |task| {
+            order.push(task);
+            true
+        });
+        assert!(order.is_empty());
+
+        scheduler.run_slice(Duration::from_millis(1), |task| {
+            order.push(task);
+            std::thread::sleep(Duration::from_millis(2));
+            true
+        });
+        scheduler.run_slice(Duration::from_millis(1), |task| {
+            order.push(task);
+            std::thread::sleep(Duration::from_millis(2));
+            true
+        });
+        assert_eq!(vec![Task::Load, Task::Compact], order);
+    }
+}
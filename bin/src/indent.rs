@@ -0,0 +1,360 @@
+use crate::config::Settings;
+
+/// How a filetype's lines change the indentation of the lines around them. Tokens made of
+/// letters only match whole words, so `done` doesn't match the end of `undone`.
+#[derive(Debug, PartialEq)]
+pub struct Rules {
+    /// Tokens which, ending a line, indent the lines after it one level more, such as `{`.
+    pub indent_after: &'static [&'static str],
+    /// Words which, starting a line, indent the lines after it one level more, such as `def`,
+    /// unless the line also ends with a word from `dedent`, such as `end`.
+    pub block_starts: &'static [&'static str],
+    /// Tokens which, starting a line, indent it one level less than the lines before it, such
+    /// as `}`.
+    pub dedent: &'static [&'static str],
+    /// Whether whole lines can be reindented by the rules, which they can't where indentation
+    /// is part of the syntax, as the end of a block can't be told from the lines themselves.
+    pub reindents: bool,
+}
+
+/// The rules for languages which use braces for blocks.
+const BRACES: Rules = Rules {
+    indent_after: &["{", "[", "("],
+    block_starts: &[],
+    dedent: &["}", "]", ")"],
+    reindents: true,
+};
+
+/// The indent rules of each filetype. Filetypes without rules keep the indentation of the line
+/// before.
+const RULES: &[(&str, Rules)] = &[
+    ("rust", BRACES),
+    ("c", BRACES),
+    ("cpp", BRACES),
+    ("go", BRACES),
+    ("javascript", BRACES),
+    ("typescript", BRACES),
+    ("json", BRACES),
+    ("perl", BRACES),
+    (
+        "python",
+        Rules {
+            indent_after: &[":", "[", "(", "{"],
+            block_starts: &[],
+            dedent: &["else", "elif", "except", "finally", "]", ")", "}"],
+            reindents: false,
+        },
+    ),
+    (
+        "sh",
+        Rules {
+            indent_after: &["then", "do", "else", "{", "("],
+            block_starts: &[],
+            dedent: &["fi", "done", "esac", "else", "elif", "}", ")"],
+            reindents: true,
+        },
+    ),
+    (
+        "ruby",
+        Rules {
+            indent_after: &["do", "{", "[", "(", "|"],
+            block_starts: &[
+                "def", "class", "module", "if", "unless", "while", "until", "case", "begin",
+                "else", "elsif", "when", "rescue", "ensure",
+            ],
+            dedent: &[
+                "end", "else", "elsif", "when", "rescue", "ensure", "}", "]", ")",
+            ],
+            reindents: true,
+        },
+    ),
+    (
+        "lua",
+        Rules {
+            indent_after: &["then", "do", "else", "{", "("],
+            block_starts: &["function", "local function", "repeat"],
+            dedent: &["end", "else", "elseif", "until", "}", ")"],
+            reindents: true,
+        },
+    ),
+    (
+        "yaml",
+        Rules {
+            indent_after: &[":"],
+            block_starts: &[],
+            dedent: &[],
+            reindents: false,
+        },
+    ),
+];
+
+/// Looks up the indent rules for a filetype.
+pub fn rules(filetype: &str) -> Option<&'static Rules> {
+    RULES
+        .iter()
+        .find(|(name, _)| *name == filetype)
+        .map(|(_, rules)| rules)
+}
+
+/// Whether a token is made of letters, so it only matches whole words.
+fn is_word(token: &str) -> bool {
+    token.chars().all(|c| c.is_alphabetic() || c == ' ')
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl Rules {
+    /// Whether the lines after a line are indented one level more than it.
+    pub fn opens(&self, line: &str) -> bool {
+        let line = line.trim();
+        self.indent_after.iter().any(|token| ends_with(line, token))
+            || self.block_starts.iter().any(|token| {
+                starts_with(line, token)
+                    && !self
+                        .dedent
+                        .iter()
+                        .any(|end| is_word(end) && ends_with(&line[token.len()..], end))
+            })
+    }
+
+    /// Whether a line is indented one level less than the lines before it.
+    pub fn closes(&self, line: &str) -> bool {
+        let line = line.trim();
+        self.dedent.iter().any(|token| starts_with(line, token))
+    }
+}
+
+fn ends_with(line: &str, token: &str) -> bool {
+    line.strip_suffix(token)
+        .is_some_and(|before| !is_word(token) || !before.ends_with(is_word_char))
+}
+
+fn starts_with(line: &str, token: &str) -> bool {
+    line.strip_prefix(token)
+        .is_some_and(|after| !is_word(token) || !after.starts_with(is_word_char))
+}
+
+/// The width of a line's indentation in columns, with tabs reaching the next tab stop.
+pub fn width(line: &str, tab_width: usize) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace() && *c != '\r')
+        .fold(0, |width, c| match c {
+            '\t' => (width / tab_width.max(1) + 1) * tab_width.max(1),
+            _ => width + 1,
+        })
+}
+
+/// Works out the indentation a line should have, in columns. The line keeps the indentation of
+/// the line before it, adjusted by the filetype's rules.
+///
+/// # Arguments
+///
+/// * 'rules' - The filetype's indent rules, if it has any.
+/// * 'previous' - The closest line before which isn't blank, if there is one.
+/// * 'line' - The line being indented.
+/// * 'settings' - The settings giving the width of each level and of tabs.
+pub fn columns(
+    rules: Option<&Rules>,
+    previous: Option<&str>,
+    line: &str,
+    settings: &Settings,
+) -> usize {
+    let Some(previous) = previous else {
+        return 0;
+    };
+    let mut columns = width(previous, settings.tab_width);
+    if let Some(rules) = rules {
+        if rules.opens(previous) {
+            columns += settings.indent_width;
+        }
+        if rules.closes(line) {
+            columns = columns.saturating_sub(settings.indent_width);
+        }
+    }
+    columns
+}
+
+/// A line with its indentation replaced by indentation of a width.
+pub fn reindent_line(line: &str, columns: usize, settings: &Settings) -> String {
+    whitespace(columns, settings) + line.trim_start_matches([' ', '\t'])
+}
+
+/// Works out the indentation of each of a run of lines in turn, from the line before it. Blank
+/// lines lose their indentation and are skipped over.
+///
+/// # Arguments
+///
+/// * 'rules' - The filetype's indent rules.
+/// * 'previous' - The closest line before the run which isn't blank, if there is one.
+/// * 'lines' - The lines to reindent.
+/// * 'settings' - The settings giving the width of each level and of tabs.
+pub fn reindent(
+    rules: &Rules,
+    previous: Option<&str>,
+    lines: &[String],
+    settings: &Settings,
+) -> Vec<String> {
+    let mut previous = previous.map(String::from);
+    lines
+        .iter()
+        .map(|line| {
+            if line.trim().is_empty() {
+                return reindent_line(line, 0, settings);
+            }
+            let columns = columns(Some(rules), previous.as_deref(), line, settings);
+            let reindented = reindent_line(line, columns, settings);
+            previous = Some(reindented.clone());
+            reindented
+        })
+        .collect()
+}
+
+/// Shifts the lines of multi-line pasted text to the indentation at the cursor, keeping their
+/// indentation relative to the first line. The first line is pasted at the cursor, so its own
+/// indentation is dropped when only indentation comes before the cursor. Blank lines are left
+/// as they are, and lines are never shifted left of the margin.
+///
+/// # Arguments
+///
+/// * 'text' - The pasted text, with `\n` line breaks.
+/// * 'before' - The text of the cursor's line before the cursor.
+/// * 'line' - The whole of the cursor's line.
+/// * 'settings' - The settings giving the width of tabs and whether to indent with them.
+pub fn adjust_paste(text: &str, before: &str, line: &str, settings: &Settings) -> String {
+    let Some((first, rest)) = text.split_once('\n') else {
+        return text.to_string();
+    };
+    let at_indent = before.trim_start_matches([' ', '\t']).is_empty();
+    // Text pasted into the indentation takes the cursor's column, otherwise the line's.
+    let target = match at_indent {
+        true => width(before, settings.tab_width),
+        false => width(line, settings.tab_width),
+    };
+    let shift = target as isize - width(first, settings.tab_width) as isize;
+
+    let first = match at_indent {
+        true => first.trim_start_matches([' ', '\t']),
+        false => first,
+    };
+    let rest: Vec<String> = rest
+        .split('\n')
+        .map(|line| match line.trim().is_empty() || shift == 0 {
+            true => line.to_string(),
+            false => {
+                let columns = width(line, settings.tab_width) as isize + shift;
+                reindent_line(line, columns.max(0) as usize, settings)
+            }
+        })
+        .collect();
+    format!("{}\n{}", first, rest.join("\n"))
+}
+
+/// The whitespace making up indentation of a width, as spaces or as tabs followed by any spaces
+/// left over, depending on the settings.
+pub fn whitespace(columns: usize, settings: &Settings) -> String {
+    match settings.expand_tabs {
+        true => " ".repeat(columns),
+        false => {
+            let tab_width = settings.tab_width.max(1);
+            "\t".repeat(columns / tab_width) + &" ".repeat(columns % tab_width)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::config::Config;
+
+    #[test_case("rust", "fn main() {", true;          "brace")]
+    #[test_case("rust", "let x = 1;", false;          "statement")]
+    #[test_case("python", "if x:  ", true;            "colon")]
+    #[test_case("sh", "for f in *; do", true;         "do")]
+    #[test_case("sh", "echo undo", false;             "word_inside_another")]
+    #[test_case("ruby", "def main", true;             "block_start")]
+    #[test_case("ruby", "def main; end", false;       "one_line_block")]
+    #[test_case("ruby", "else", true;                 "block_start_and_dedent")]
+    #[test_case("lua", "local function f()", true;    "multiple_words")]
+    fn opening_lines(filetype: &str, line: &str, expected: bool) {
+        assert_eq!(expected, rules(filetype).unwrap().opens(line));
+    }
+
+    #[test_case("rust", "    }", true;                "brace")]
+    #[test_case("rust", "    } else {", true;         "brace_and_more")]
+    #[test_case("sh", "fi", true;                     "fi")]
+    #[test_case("sh", "file", false;                  "word_starting_with_token")]
+    #[test_case("python", "else:", true;              "else")]
+    fn closing_lines(filetype: &str, line: &str, expected: bool) {
+        assert_eq!(expected, rules(filetype).unwrap().closes(line));
+    }
+
+    #[test_case("  x", 2;     "spaces")]
+    #[test_case("\t x", 5;    "tab_then_space")]
+    #[test_case(" \tx", 4;    "space_then_tab")]
+    #[test_case("\r", 0;      "carriage_return")]
+    fn widths(line: &str, expected: usize) {
+        assert_eq!(expected, width(line, 4));
+    }
+
+    #[test_case(Some("rust"), Some("    if x {"), "", 8;      "indent")]
+    #[test_case(Some("rust"), Some("        y();"), "}", 4;   "dedent")]
+    #[test_case(Some("rust"), Some("    if x {"), "}", 4;     "empty_block")]
+    #[test_case(Some("rust"), Some("}"), "}", 0;              "no_negative_indent")]
+    #[test_case(None, Some("  if x {"), "", 2;                "no_rules")]
+    #[test_case(Some("rust"), None, "x", 0;                   "first_line")]
+    fn indentation(filetype: Option<&str>, previous: Option<&str>, line: &str, expected: usize) {
+        let settings = Config::default().settings(None);
+        let rules = filetype.and_then(rules);
+        assert_eq!(expected, columns(rules, previous, line, &settings));
+    }
+
+    #[test]
+    fn reindent_lines() {
+        let settings = Config::default().settings(None);
+        let lines: Vec<String> = "fn main() {\n  if x {\ny();\n  \n\t}\r\n        }"
+            .split('\n')
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            vec![
+                "    fn main() {",
+                "        if x {",
+                "            y();",
+                "",
+                "        }\r",
+                "    }",
+            ],
+            reindent(
+                rules("rust").unwrap(),
+                Some("    mod a {}"),
+                &lines,
+                &settings
+            )
+        );
+    }
+
+    #[test_case("a\n  b", "    ", "    ", "a\n      b";             "deeper")]
+    #[test_case("    a\n      b\n    c", "", "", "a\n  b\nc";        "shallower")]
+    #[test_case("  a\nb", "    x = ", "    x = ", "  a\n  b";          "after_text")]
+    #[test_case("a\n\n  b\n", "\t", "\t", "a\n\n      b\n";          "blank_lines")]
+    #[test_case("    a\nb", "  ", "  ", "a\nb";                         "not_past_the_margin")]
+    #[test_case("  a", "    ", "    ", "  a";                           "one_line")]
+    fn adjust_pastes(text: &str, before: &str, line: &str, expected: &str) {
+        let settings = Config::default().settings(None);
+        assert_eq!(expected, adjust_paste(text, before, line, &settings));
+    }
+
+    #[test_case(true, 6, "      ";   "spaces")]
+    #[test_case(false, 6, "\t  ";    "tabs")]
+    fn whitespace_for_columns(expand_tabs: bool, columns: usize, expected: &str) {
+        let settings = Settings {
+            expand_tabs,
+            ..Config::default().settings(None)
+        };
+        assert_eq!(expected, whitespace(columns, &settings));
+    }
+}
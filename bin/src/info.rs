@@ -0,0 +1,195 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use console::style;
+
+use crate::document::{Document, LineEnding};
+use crate::undo;
+
+/// Facts about a document and its file, shown in a panel by `:info`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Info {
+    pub path: Option<PathBuf>,
+    /// The size of the file, if it exists.
+    pub disk_size: Option<u64>,
+    /// The size of the document's text as it would be saved, before any compression.
+    pub buffer_size: u64,
+    pub binary: bool,
+    pub compression: Option<&'static str>,
+    pub encrypted: bool,
+    pub line_ending: LineEnding,
+    pub lines: u32,
+    pub words: usize,
+    pub chars: usize,
+    pub modified: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+    pub pieces: usize,
+    /// The number of bytes in the piece table's add buffer, which holds every piece of text
+    /// inserted since the document was loaded or compacted.
+    pub add_buffer: usize,
+}
+
+impl Info {
+    /// Gathers the facts about a document, reading its file's metadata.
+    pub fn of(document: &Document) -> Info {
+        let metadata = document.path().and_then(|path| fs::metadata(path).ok());
+        let (words, chars) = match document.is_binary() {
+            true => (0, 0),
+            false => {
+                let text = document.text();
+                (text.split_whitespace().count(), text.chars().count())
+            }
+        };
+        Info {
+            path: document.path().map(PathBuf::from),
+            disk_size: metadata.as_ref().map(|m| m.len()),
+            buffer_size: document.len() as u64,
+            binary: document.is_binary(),
+            compression: document.compression().map(|c| c.name()),
+            encrypted: document.is_encrypted(),
+            line_ending: document.line_ending(),
+            lines: document.line_count(),
+            words,
+            chars,
+            modified: metadata.as_ref().and_then(|m| m.modified().ok()),
+            created: metadata.as_ref().and_then(|m| m.created().ok()),
+            pieces: document.piece_count(),
+            add_buffer: document.add_buffer_len(),
+        }
+    }
+
+    /// Lays out the facts as rows of labels and values.
+    ///
+    /// # Arguments
+    ///
+    /// * 'now' - The current time, which the file's times are shown relative to.
+    pub fn render(&self, now: SystemTime) -> Vec<String> {
+        let age = |time: Option<SystemTime>| match time {
+            Some(time) => undo::format_age(now.duration_since(time).unwrap_or_default()),
+            None => String::from("unknown"),
+        };
+        let path = match &self.path {
+            Some(path) => path.display().to_string(),
+            None => String::from("not saved yet"),
+        };
+        let disk_size = match self.disk_size {
+            Some(size) => format_size(size),
+            None => String::from("not on disk"),
+        };
+        let mut format = vec![match self.binary {
+            true => "binary",
+            false => "UTF-8",
+        }];
+        if !self.binary {
+            format.push(match self.line_ending {
+                LineEnding::Lf => "LF",
+                LineEnding::Crlf => "CRLF",
+            });
+        }
+        format.extend(self.compression);
+        if self.encrypted {
+            format.push("encrypted");
+        }
+        let counts = match self.binary {
+            true => format!("{} rows", self.lines),
+            false => format!(
+                "{} lines, {} words, {} characters",
+                self.lines, self.words, self.chars
+            ),
+        };
+
+        [
+            ("Path", path),
+            (
+                "Size",
+                format!(
+                    "{} on disk, {} in the buffer",
+                    disk_size,
+                    format_size(self.buffer_size)
+                ),
+            ),
+            ("Format", format.join(", ")),
+            ("Counts", counts),
+            ("Modified", age(self.modified)),
+            ("Created", age(self.created)),
+            (
+                "Pieces",
+                format!(
+                    "{} in the table, {} in the add buffer",
+                    self.pieces,
+                    format_size(self.add_buffer as u64)
+                ),
+            ),
+        ]
+        .into_iter()
+        .map(|(label, value)| {
+            let label = format!("{:<10}", label);
+            format!("  {}{}", style(label).bold().force_styling(true), value)
+        })
+        .collect()
+    }
+}
+
+/// Formats a number of bytes, in KiB or MiB once it's large enough.
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(12, "12 B";                "bytes")]
+    #[test_case(1536, "1.5 KiB";           "kibibytes")]
+    #[test_case(3 * 1_048_576, "3.0 MiB";  "mebibytes")]
+    fn sizes(bytes: u64, expected: &str) {
+        assert_eq!(expected, format_size(bytes));
+    }
+
+    #[test]
+    fn render_rows() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(10_000);
+        let info = Info {
+            path: Some(PathBuf::from("/code/notes.txt.gz")),
+            disk_size: Some(40),
+            buffer_size: 2048,
+            binary: false,
+            compression: Some("gzip"),
+            encrypted: false,
+            line_ending: LineEnding::Crlf,
+            lines: 3,
+            words: 7,
+            chars: 30,
+            modified: Some(now - Duration::from_secs(120)),
+            created: None,
+            pieces: 4,
+            add_buffer: 10,
+        };
+        let rows: Vec<String> = info
+            .render(now)
+            .iter()
+            .map(|row| console::strip_ansi_codes(row).into_owned())
+            .collect();
+        assert_eq!(
+            vec![
+                "  Path      /code/notes.txt.gz",
+                "  Size      40 B on disk, 2.0 KiB in the buffer",
+                "  Format    UTF-8, CRLF, gzip",
+                "  Counts    3 lines, 7 words, 30 characters",
+                "  Modified  2m ago",
+                "  Created   unknown",
+                "  Pieces    4 in the table, 10 B in the add buffer",
+            ],
+            rows
+        );
+    }
+}
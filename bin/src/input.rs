@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crossterm::event;
+use log::error;
+
+use crate::editor::Event;
+use crate::terminal;
+
+/// How long the poller waits for input before checking whether it has been stopped.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Reads terminal input on a background thread, posting it to the editor's event queue so the
+/// event loop never blocks waiting for a key press.
+pub struct InputPoller {
+    stopped: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl InputPoller {
+    /// Starts reading terminal input.
+    ///
+    /// # Arguments
+    ///
+    /// * 'sender' - The queue terminal input is posted to.
+    pub fn start(sender: Sender<Event>) -> InputPoller {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let stopped = stopped.clone();
+            thread::spawn(move || poll(sender, stopped))
+        };
+        InputPoller { stopped, handle }
+    }
+
+    /// Stops reading terminal input, waiting for the background thread to finish so nothing else
+    /// is read from the terminal once this returns.
+    pub fn stop(self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        if self.handle.join().is_err() {
+            error!("Input thread panicked");
+        }
+    }
+}
+
+fn poll(sender: Sender<Event>, stopped: Arc<AtomicBool>) {
+    while !stopped.load(Ordering::Relaxed) {
+        let input = match event::poll(POLL_INTERVAL) {
+            Ok(true) => event::read(),
+            Ok(false) => continue,
+            Err(e) => Err(e),
+        };
+
+        // Key releases are dropped here, so the Windows console's release for every key press
+        // doesn't wake the event loop.
+        let input = match input {
+            Ok(event::Event::Key(key)) => match terminal::normalize_key(key) {
+                Some(key) => Ok(event::Event::Key(key)),
+                None => continue,
+            },
+            input => input,
+        };
+
+        match input {
+            Ok(input) => {
+                if sender.send(Event::Input(input)).is_err() {
+                    return;
+                }
+            }
+            Err(e) => {
+                error!("Unable to read terminal input: {}", e);
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use unicode_width::UnicodeWidthChar;
+
+use crate::layout;
+
+/// The zero width joiner, which joins the characters either side of it into one grapheme, as in
+/// many emoji.
+const ZWJ: char = '\u{200d}';
+
+/// Returns the grapheme starting at a column of a line: the character there along with any
+/// combining marks, variation selectors or joined characters following it. Returns None past
+/// the end of the line.
+///
+/// # Arguments
+///
+/// * 'line' - The line containing the grapheme.
+/// * 'column' - The index of the grapheme's first character. Columns start from 0.
+pub fn grapheme_at(line: &str, column: u32) -> Option<&str> {
+    let mut chars = line.char_indices().skip(column as usize).peekable();
+    let (start, first) = chars.next()?;
+    let mut end = start + first.len_utf8();
+    let mut joined = false;
+
+    for (i, c) in chars {
+        let extends = joined || c == ZWJ || c.width() == Some(0);
+        if !extends {
+            break;
+        }
+        joined = c == ZWJ;
+        end = i + c.len_utf8();
+    }
+    Some(&line[start..end])
+}
+
+/// Describes a grapheme: the codepoint of each character, its UTF-8 encoding and the number of
+/// terminal cells it takes up.
+pub fn describe(grapheme: &str) -> String {
+    let codepoints: Vec<String> = grapheme
+        .chars()
+        .map(|c| format!("U+{:04X}", c as u32))
+        .collect();
+    let bytes: Vec<String> = grapheme.bytes().map(|b| format!("{:02x}", b)).collect();
+    let shown = match grapheme.chars().any(char::is_control) {
+        true => grapheme.escape_default().to_string(),
+        false => grapheme.to_string(),
+    };
+
+    format!(
+        "'{}' {}  UTF-8 {}  width {}",
+        shown,
+        codepoints.join(" "),
+        bytes.join(" "),
+        layout::display_width(grapheme)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("abc", 1, Some("b");                          "ascii")]
+    #[test_case("ae\u{301}b", 1, Some("e\u{301}");             "combining_mark")]
+    #[test_case("a👩\u{200d}💻b", 1, Some("👩\u{200d}💻");      "joined_emoji")]
+    #[test_case("abc", 3, None;                               "end_of_line")]
+    fn grapheme(line: &str, column: u32, expected: Option<&str>) {
+        assert_eq!(expected, grapheme_at(line, column));
+    }
+
+    #[test_case("é", "'é' U+00E9  UTF-8 c3 a9  width 1";                   "accented")]
+    #[test_case("e\u{301}", "'e\u{301}' U+0065 U+0301  UTF-8 65 cc 81  width 1";  "combined")]
+    #[test_case("漢", "'漢' U+6F22  UTF-8 e6 bc a2  width 2";                "wide")]
+    #[test_case("\t", "'\\t' U+0009  UTF-8 09  width 1";                    "control")]
+    fn describe_grapheme(grapheme: &str, expected: &str) {
+        assert_eq!(expected, describe(grapheme));
+    }
+}
@@ -0,0 +1,199 @@
+/// The maximum number of jumps remembered. The oldest jumps are dropped once the limit is
+/// reached.
+const MAX_JUMPS: usize = 100;
+
+/// A cursor position recorded in the jump list.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Jump {
+    /// The index of the buffer the jump was made in.
+    pub buffer: usize,
+    /// The line of the cursor. Line numbers start from 1.
+    pub row: u32,
+    /// The column of the cursor. Columns start from 0.
+    pub column: u32,
+}
+
+/// The history of cursor positions before significant jumps, which can be moved backwards and
+/// forwards through.
+#[derive(Debug, Default)]
+pub struct JumpList {
+    jumps: Vec<Jump>,
+    index: usize,
+}
+
+impl JumpList {
+    pub fn new() -> JumpList {
+        JumpList::default()
+    }
+
+    /// Records the position being jumped away from. Any jumps ahead of the current position in
+    /// the list are discarded.
+    ///
+    /// # Arguments
+    ///
+    /// * 'jump' - The cursor position before the jump.
+    pub fn push(&mut self, jump: Jump) {
+        self.jumps.truncate(self.index);
+        if self.jumps.last() != Some(&jump) {
+            self.jumps.push(jump);
+        }
+        if self.jumps.len() > MAX_JUMPS {
+            self.jumps.remove(0);
+        }
+        self.index = self.jumps.len();
+    }
+
+    /// Moves back to the previous jump, returning the position to move the cursor to.
+    ///
+    /// # Arguments
+    ///
+    /// * 'current' - The current cursor position, which is recorded so it can be returned to by
+    ///   moving forward again.
+    pub fn back(&mut self, current: Jump) -> Option<Jump> {
+        if self.index == self.jumps.len() {
+            if self.jumps.last() != Some(&current) {
+                self.jumps.push(current);
+            }
+            self.index = self.jumps.len() - 1;
+        }
+
+        if self.index == 0 {
+            return None;
+        }
+        self.index -= 1;
+        self.jumps.get(self.index).copied()
+    }
+
+    /// Moves forward to the next jump, returning the position to move the cursor to.
+    pub fn forward(&mut self) -> Option<Jump> {
+        if self.index + 1 >= self.jumps.len() {
+            return None;
+        }
+        self.index += 1;
+        self.jumps.get(self.index).copied()
+    }
+
+    /// Drops the jumps made in buffers which were closed, and renumbers the jumps made in the
+    /// buffers after them to match their new indices.
+    ///
+    /// # Arguments
+    ///
+    /// * 'closed' - The indices of the closed buffers, in ascending order.
+    pub fn remove_buffers(&mut self, closed: &[usize]) {
+        let mut position = 0;
+        let mut dropped = 0;
+        self.jumps.retain_mut(|jump| {
+            let keep = closed.binary_search(&jump.buffer).is_err();
+            match keep {
+                true => jump.buffer -= closed.partition_point(|&index| index < jump.buffer),
+                false if position < self.index => dropped += 1,
+                false => {}
+            }
+            position += 1;
+            keep
+        });
+        self.index -= dropped;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jump(buffer: usize, row: u32) -> Jump {
+        Jump {
+            buffer,
+            row,
+            column: 0,
+        }
+    }
+
+    #[test]
+    fn back_and_forward() {
+        let mut jumps = JumpList::new();
+        jumps.push(jump(0, 1));
+        jumps.push(jump(0, 50));
+
+        assert_eq!(Some(jump(0, 50)), jumps.back(jump(1, 10)));
+        assert_eq!(Some(jump(0, 1)), jumps.back(jump(0, 50)));
+        assert_eq!(None, jumps.back(jump(0, 1)));
+        assert_eq!(Some(jump(0, 50)), jumps.forward());
+        assert_eq!(Some(jump(1, 10)), jumps.forward());
+        assert_eq!(None, jumps.forward());
+    }
+
+    #[test]
+    fn back_when_empty() {
+        let mut jumps = JumpList::new();
+        assert_eq!(None, jumps.back(jump(0, 1)));
+        assert_eq!(None, jumps.forward());
+    }
+
+    #[test]
+    fn back_from_last_jump() {
+        let mut jumps = JumpList::new();
+        jumps.push(jump(0, 1));
+        jumps.push(jump(0, 50));
+
+        assert_eq!(Some(jump(0, 1)), jumps.back(jump(0, 50)));
+    }
+
+    #[test]
+    fn push_discards_forward_jumps() {
+        let mut jumps = JumpList::new();
+        jumps.push(jump(0, 1));
+        jumps.push(jump(0, 50));
+        jumps.back(jump(0, 80));
+        jumps.back(jump(0, 50));
+
+        jumps.push(jump(0, 1));
+        assert_eq!(None, jumps.forward());
+        assert_eq!(Some(jump(0, 1)), jumps.back(jump(0, 20)));
+        assert_eq!(None, jumps.back(jump(0, 1)));
+    }
+
+    #[test]
+    fn push_drops_oldest_jumps() {
+        let mut jumps = JumpList::new();
+        for row in 1..=MAX_JUMPS as u32 + 10 {
+            jumps.push(jump(0, row));
+        }
+
+        let mut last = None;
+        while let Some(previous) = jumps.back(jump(0, 0)) {
+            last = Some(previous);
+        }
+        assert_eq!(Some(jump(0, 11)), last);
+    }
+
+    #[test]
+    fn remove_buffers_renumbers_jumps() {
+        let mut jumps = JumpList::new();
+        jumps.push(jump(0, 1));
+        jumps.push(jump(1, 2));
+        jumps.push(jump(2, 3));
+        jumps.push(jump(3, 4));
+
+        jumps.remove_buffers(&[1, 2]);
+        assert_eq!(Some(jump(1, 4)), jumps.back(jump(0, 5)));
+        assert_eq!(Some(jump(0, 1)), jumps.back(jump(1, 4)));
+        assert_eq!(None, jumps.back(jump(0, 1)));
+    }
+
+    #[test]
+    fn remove_buffers_while_moving_back() {
+        let mut jumps = JumpList::new();
+        jumps.push(jump(0, 1));
+        jumps.push(jump(1, 2));
+        jumps.push(jump(0, 3));
+        jumps.back(jump(2, 4));
+        jumps.back(jump(0, 3));
+
+        // The jump being moved back to was dropped, so moving back goes to the one before it.
+        jumps.remove_buffers(&[1]);
+        assert_eq!(Some(jump(0, 1)), jumps.back(jump(0, 3)));
+        assert_eq!(Some(jump(0, 3)), jumps.forward());
+        assert_eq!(Some(jump(1, 4)), jumps.forward());
+        assert_eq!(None, jumps.forward());
+    }
+}
@@ -1,5 +1,9 @@
-use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::event::{
+    KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
 
+use crate::case::Case;
+use crate::chord;
 use crate::editor::Event;
 
 pub struct KeyMaps {}
@@ -7,48 +11,560 @@ pub struct KeyMaps {}
 impl KeyMaps {
     pub fn map_key_press_to_event(&self, event: KeyEvent) -> Option<Event> {
         match event {
+            KeyEvent {
+                code: KeyCode::Up,
+                modifiers: KeyModifiers::ALT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::PreviousParagraph),
+            KeyEvent {
+                code: KeyCode::Down,
+                modifiers: KeyModifiers::ALT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::NextParagraph),
+            // Terminals differ in whether Shift is reported along with the bracket.
+            KeyEvent {
+                code: KeyCode::Char('('),
+                modifiers,
+                kind: KeyEventKind::Press,
+                state: _,
+            } if modifiers.contains(KeyModifiers::ALT) => Some(Event::PreviousSentence),
+            KeyEvent {
+                code: KeyCode::Char(')'),
+                modifiers,
+                kind: KeyEventKind::Press,
+                state: _,
+            } if modifiers.contains(KeyModifiers::ALT) => Some(Event::NextSentence),
+            KeyEvent {
+                code: KeyCode::Left,
+                modifiers: KeyModifiers::CONTROL | KeyModifiers::ALT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::PreviousWord),
+            KeyEvent {
+                code: KeyCode::Right,
+                modifiers: KeyModifiers::CONTROL | KeyModifiers::ALT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::NextWord),
+            KeyEvent {
+                code: KeyCode::Backspace,
+                modifiers: KeyModifiers::CONTROL | KeyModifiers::ALT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::DeleteWordBack),
+            KeyEvent {
+                code: KeyCode::Delete,
+                modifiers: KeyModifiers::CONTROL | KeyModifiers::ALT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::DeleteWordForward),
+            KeyEvent {
+                code: KeyCode::Left,
+                modifiers: KeyModifiers::SHIFT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::SelectLeft(1)),
             KeyEvent {
                 code: KeyCode::Left,
                 modifiers: _,
                 kind: KeyEventKind::Press,
                 state: _,
             } => Some(Event::MoveCursorLeft(1)),
+            KeyEvent {
+                code: KeyCode::Right,
+                modifiers: KeyModifiers::SHIFT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::SelectRight(1)),
             KeyEvent {
                 code: KeyCode::Right,
                 modifiers: _,
                 kind: KeyEventKind::Press,
                 state: _,
             } => Some(Event::MoveCursorRight(1)),
+            KeyEvent {
+                code: KeyCode::Up,
+                modifiers: KeyModifiers::SHIFT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::SelectUp(1)),
             KeyEvent {
                 code: KeyCode::Up,
                 modifiers: _,
                 kind: KeyEventKind::Press,
                 state: _,
             } => Some(Event::MoveCursorUp(1)),
+            KeyEvent {
+                code: KeyCode::Down,
+                modifiers: KeyModifiers::SHIFT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::SelectDown(1)),
             KeyEvent {
                 code: KeyCode::Down,
                 modifiers: _,
                 kind: KeyEventKind::Press,
                 state: _,
             } => Some(Event::MoveCursorDown(1)),
+            KeyEvent {
+                code: KeyCode::Home,
+                modifiers: _,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::MoveCursorHome),
             KeyEvent {
                 code: KeyCode::Enter,
                 modifiers: _,
                 kind: KeyEventKind::Press,
                 state: _,
             } => Some(Event::NewLine),
+            KeyEvent {
+                code: KeyCode::Insert,
+                modifiers: KeyModifiers::NONE,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::ToggleOverwrite),
+            KeyEvent {
+                code: KeyCode::Esc,
+                modifiers: _,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::Cancel),
+            KeyEvent {
+                code: KeyCode::PageDown,
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::NextBuffer),
+            KeyEvent {
+                code: KeyCode::PageUp,
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::PreviousBuffer),
+            KeyEvent {
+                code: KeyCode::Char('k'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::KillLine),
+            // Only terminals which report Shift along with Ctrl can tell this apart from Ctrl+K.
+            KeyEvent {
+                code: KeyCode::Char('k') | KeyCode::Char('K'),
+                modifiers,
+                kind: KeyEventKind::Press,
+                state: _,
+            } if modifiers == KeyModifiers::CONTROL | KeyModifiers::SHIFT => {
+                Some(Event::DeleteLine)
+            }
+            KeyEvent {
+                code: KeyCode::Char('y'),
+                modifiers: KeyModifiers::ALT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::Yank),
+            // Alt+Shift+Y is reported with or without Shift depending on the terminal.
+            KeyEvent {
+                code: KeyCode::Char('Y'),
+                modifiers,
+                kind: KeyEventKind::Press,
+                state: _,
+            } if modifiers.contains(KeyModifiers::ALT) => Some(Event::PasteHistory),
+            KeyEvent {
+                code: KeyCode::Char('u'),
+                modifiers: KeyModifiers::ALT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::ChangeCase(Case::Upper)),
+            // Alt+Shift+U is reported as Alt+U, with or without Shift depending on the terminal.
+            KeyEvent {
+                code: KeyCode::Char('U'),
+                modifiers,
+                kind: KeyEventKind::Press,
+                state: _,
+            } if modifiers.contains(KeyModifiers::ALT) => Some(Event::ChangeCase(Case::Lower)),
+            KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::ALT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::ChangeCase(Case::Title)),
+            KeyEvent {
+                code: KeyCode::Char('~'),
+                modifiers,
+                kind: KeyEventKind::Press,
+                state: _,
+            } if modifiers.contains(KeyModifiers::ALT) => Some(Event::ChangeCase(Case::Toggle)),
+            KeyEvent {
+                code: KeyCode::F(3),
+                modifiers: KeyModifiers::NONE,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::FindNext),
+            KeyEvent {
+                code: KeyCode::F(3),
+                modifiers: KeyModifiers::SHIFT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::FindPrevious),
+            KeyEvent {
+                code: KeyCode::F(8),
+                modifiers: KeyModifiers::NONE,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::NextError),
+            KeyEvent {
+                code: KeyCode::F(8),
+                modifiers: KeyModifiers::SHIFT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::PreviousError),
+            KeyEvent {
+                code: KeyCode::Char('q'),
+                modifiers: KeyModifiers::ALT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::Reflow),
+            KeyEvent {
+                code: KeyCode::Char('a'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::SelectAll),
+            KeyEvent {
+                code: KeyCode::Char('l'),
+                modifiers: KeyModifiers::ALT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::SelectLine),
+            KeyEvent {
+                code: KeyCode::Char('w'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::ExpandSelection),
+            KeyEvent {
+                code: KeyCode::Char('e'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::ScrollDown(1)),
+            KeyEvent {
+                code: KeyCode::Char('y'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::ScrollUp(1)),
+            KeyEvent {
+                code: KeyCode::Char('l'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::Recenter),
+            KeyEvent {
+                code: KeyCode::Char('o'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::JumpBack),
+            KeyEvent {
+                code: KeyCode::Tab,
+                modifiers: KeyModifiers::NONE,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::Indent),
+            // Most terminals send Ctrl+I as a tab, so this only works in terminals which report
+            // the two separately.
+            KeyEvent {
+                code: KeyCode::Char('i'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::JumpForward),
+            KeyEvent {
+                code: KeyCode::Char('s'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::Save),
+            KeyEvent {
+                code: KeyCode::Char('p'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::OpenPrompt),
+            KeyEvent {
+                code: KeyCode::Char('g'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::OpenGotoPrompt),
+            KeyEvent {
+                code: KeyCode::Char('f'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::OpenSearchPrompt),
+            KeyEvent {
+                code: KeyCode::Char('v'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::InsertLiteral),
+            KeyEvent {
+                code: KeyCode::Char('z'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::Suspend),
+            KeyEvent {
+                code: KeyCode::Char('z'),
+                modifiers: KeyModifiers::ALT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::Undo),
+            // Alt+Shift+Z is reported with or without Shift depending on the terminal.
+            KeyEvent {
+                code: KeyCode::Char('Z'),
+                modifiers,
+                kind: KeyEventKind::Press,
+                state: _,
+            } if modifiers.contains(KeyModifiers::ALT) => Some(Event::Redo),
+            // Terminals without extended key reporting send Ctrl+/ as Ctrl+7.
+            KeyEvent {
+                code: KeyCode::Char('/') | KeyCode::Char('7'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::ToggleComment),
+            // Shift+. is reported as '>' unless the terminal reports the unshifted key.
+            KeyEvent {
+                code: KeyCode::Char('.') | KeyCode::Char('>'),
+                modifiers,
+                kind: KeyEventKind::Press,
+                state: _,
+            } if modifiers == KeyModifiers::CONTROL | KeyModifiers::SHIFT => {
+                Some(Event::RepeatEdit)
+            }
+            KeyEvent {
+                code: KeyCode::Char('q'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::Exit),
+            KeyEvent {
+                code: KeyCode::Char('x'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::StartChord(&chord::CTRL_X)),
             KeyEvent {
                 code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::KeyPress(c)),
+            _ => None,
+        }
+    }
+
+    pub fn map_mouse_to_event(&self, event: MouseEvent) -> Option<Event> {
+        match event.kind {
+            MouseEventKind::ScrollDown => Some(Event::MouseScrollDown),
+            MouseEventKind::ScrollUp => Some(Event::MouseScrollUp),
+            MouseEventKind::Down(MouseButton::Left) => {
+                Some(Event::MouseClick(event.column, event.row))
+            }
+            MouseEventKind::Moved => Some(Event::MouseMove(event.column, event.row)),
+            _ => None,
+        }
+    }
+
+    /// Maps keys while a picker is open, where typing narrows down the items.
+    pub fn map_picker_key_press_to_event(&self, event: KeyEvent) -> Option<Event> {
+        match event {
+            KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: _,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::PickerSubmit),
+            KeyEvent {
+                code: KeyCode::Esc,
+                modifiers: _,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::PickerCancel),
+            KeyEvent {
+                code: KeyCode::Backspace,
+                modifiers: _,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::PickerBackspace),
+            KeyEvent {
+                code: KeyCode::Up,
+                modifiers: _,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::PickerPrevious),
+            KeyEvent {
+                code: KeyCode::Down,
+                modifiers: _,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::PickerNext),
+            KeyEvent {
+                code: KeyCode::Char('p'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::PickerPrevious),
+            KeyEvent {
+                code: KeyCode::Char('n'),
                 modifiers: KeyModifiers::CONTROL,
                 kind: KeyEventKind::Press,
                 state: _,
-            } if c == 'q' => Some(Event::Exit),
+            } => Some(Event::PickerNext),
+            KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::PickerInsert(c)),
+            _ => None,
+        }
+    }
+
+    /// Maps the key pressed after a chord's prefix. Any key other than a character cancels the
+    /// chord.
+    pub fn map_chord_key_press_to_event(&self, event: KeyEvent) -> Option<Event> {
+        match event {
+            KeyEvent {
+                kind: KeyEventKind::Release,
+                ..
+            } => None,
+            KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                kind: _,
+                state: _,
+            } => Some(Event::ChordKey(c)),
+            // Letters pressed with Ctrl are told apart from the letters alone by their control
+            // characters.
+            KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::CONTROL,
+                kind: _,
+                state: _,
+            } if c.is_ascii_alphabetic() => Some(Event::ChordKey(
+                (c.to_ascii_uppercase() as u8 - b'@') as char,
+            )),
+            _ => Some(Event::ChordCancel),
+        }
+    }
+
+    /// Maps the keys typed after Ctrl+V, which are collected rather than inserted. Any key other
+    /// than a character or Enter cancels the literal.
+    pub fn map_literal_key_press_to_event(&self, event: KeyEvent) -> Option<Event> {
+        match event {
+            KeyEvent {
+                kind: KeyEventKind::Release,
+                ..
+            } => None,
+            KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                kind: _,
+                state: _,
+            } => Some(Event::LiteralKey(c)),
+            KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: _,
+                kind: _,
+                state: _,
+            } => Some(Event::LiteralSubmit),
+            _ => Some(Event::LiteralCancel),
+        }
+    }
+
+    /// Maps the keys pressed while the document info panel is shown, any of which closes it.
+    pub fn map_info_key_press_to_event(&self, event: KeyEvent) -> Option<Event> {
+        match event.kind {
+            KeyEventKind::Release => None,
+            _ => Some(Event::CloseInfo),
+        }
+    }
+
+    /// Maps the keys pressed while a substitution asks whether to replace a match. Any key other
+    /// than a character stops substituting.
+    pub fn map_confirm_key_press_to_event(&self, event: KeyEvent) -> Option<Event> {
+        match event {
+            KeyEvent {
+                kind: KeyEventKind::Release,
+                ..
+            } => None,
             KeyEvent {
                 code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                kind: _,
+                state: _,
+            } => Some(Event::ConfirmKey(c)),
+            _ => Some(Event::ConfirmCancel),
+        }
+    }
+
+    pub fn map_prompt_key_press_to_event(&self, event: KeyEvent) -> Option<Event> {
+        match event {
+            KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: _,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::PromptSubmit),
+            KeyEvent {
+                code: KeyCode::Esc,
+                modifiers: _,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::PromptCancel),
+            KeyEvent {
+                code: KeyCode::Backspace,
+                modifiers: _,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::PromptBackspace),
+            KeyEvent {
+                code: KeyCode::Up,
+                modifiers: _,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::PromptHistoryPrevious),
+            KeyEvent {
+                code: KeyCode::Down,
+                modifiers: _,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::PromptHistoryNext),
+            KeyEvent {
+                code: KeyCode::Tab,
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            } => Some(Event::KeyPress(c)),
+            } => Some(Event::PromptComplete),
+            KeyEvent {
+                code: KeyCode::Char('.'),
+                modifiers: KeyModifiers::ALT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::PromptToggleHidden),
+            KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Some(Event::PromptInsert(c)),
             _ => None,
         }
     }
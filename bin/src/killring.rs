@@ -0,0 +1,91 @@
+use std::collections::VecDeque;
+
+/// The maximum number of kills remembered.
+const MAX_KILLS: usize = 100;
+
+/// Remembers text removed by kill commands so it can be yanked back, with the most recent kill
+/// first.
+#[derive(Debug, Default)]
+pub struct KillRing {
+    entries: VecDeque<String>,
+}
+
+impl KillRing {
+    pub fn new() -> KillRing {
+        KillRing::default()
+    }
+
+    /// Adds killed text as a new entry, forgetting the oldest entry once the ring is full.
+    pub fn push(&mut self, text: String) {
+        self.entries.push_front(text);
+        self.entries.truncate(MAX_KILLS);
+    }
+
+    /// Adds killed text to the end of the most recent entry, so consecutive kills can be yanked
+    /// back together.
+    pub fn append(&mut self, text: &str) {
+        match self.entries.front_mut() {
+            Some(entry) => entry.push_str(text),
+            None => self.push(text.to_string()),
+        }
+    }
+
+    /// The most recently killed text.
+    pub fn latest(&self) -> Option<&str> {
+        self.entries.front().map(|entry| entry.as_str())
+    }
+
+    /// Every entry, from the most recent kill to the oldest.
+    pub fn entries(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.as_str())
+    }
+
+    /// The entry at a position in the ring, where 0 is the most recent kill.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(|entry| entry.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latest_is_last_pushed() {
+        let mut ring = KillRing::new();
+        ring.push(String::from("Lorem"));
+        ring.push(String::from("ipsum"));
+
+        assert_eq!(Some("ipsum"), ring.latest());
+    }
+
+    #[test]
+    fn append_extends_latest() {
+        let mut ring = KillRing::new();
+        ring.append("Lorem");
+        ring.append(" ipsum");
+
+        assert_eq!(Some("Lorem ipsum"), ring.latest());
+    }
+
+    #[test]
+    fn entries_are_most_recent_first() {
+        let mut ring = KillRing::new();
+        ring.push(String::from("Lorem"));
+        ring.push(String::from("ipsum"));
+
+        assert_eq!(vec!["ipsum", "Lorem"], ring.entries().collect::<Vec<_>>());
+        assert_eq!(Some("Lorem"), ring.get(1));
+    }
+
+    #[test]
+    fn oldest_entries_are_dropped() {
+        let mut ring = KillRing::new();
+        for i in 0..=MAX_KILLS {
+            ring.push(i.to_string());
+        }
+
+        assert_eq!(MAX_KILLS, ring.entries.len());
+        assert_eq!(Some("1"), ring.entries.back().map(|e| e.as_str()));
+    }
+}
@@ -0,0 +1,392 @@
+use std::ops::Range;
+
+use console::{style, Color, Style};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// The number of terminal cells needed to display the text.
+pub fn display_width(text: &str) -> usize {
+    text.width()
+}
+
+/// The terminal cell a character in a line is displayed at, accounting for wide characters such
+/// as CJK and emoji which take up two cells, and tabs which extend to the next tab stop.
+///
+/// # Arguments
+///
+/// * 'line' - The line containing the character.
+/// * 'column' - The index of the character in the line. Columns start from 0.
+/// * 'tab_width' - The number of cells between tab stops.
+pub fn display_column(line: &str, column: u32, tab_width: usize) -> usize {
+    line.chars()
+        .take(column as usize)
+        .fold(0, |cell, c| cell + cell_width(c, cell, tab_width))
+}
+
+/// The index of the character displayed at a terminal cell, or the length of the line if the
+/// cell is past its end. A cell in the middle of a wide character or tab gives that character.
+///
+/// # Arguments
+///
+/// * 'line' - The line containing the character.
+/// * 'cell' - The terminal cell. Cells start from 0.
+/// * 'tab_width' - The number of cells between tab stops.
+pub fn column_at(line: &str, cell: usize, tab_width: usize) -> u32 {
+    let mut used = 0;
+    for (i, c) in line.chars().enumerate() {
+        used += cell_width(c, used, tab_width);
+        if used > cell {
+            return i as u32;
+        }
+    }
+    line.chars().count() as u32
+}
+
+/// Lays out a line for display, expanding tabs to spaces up to the next tab stop and truncating
+/// the line so it fits within the given number of cells.
+///
+/// # Arguments
+///
+/// * 'line' - The line to lay out.
+/// * 'width' - The number of cells available to display the line.
+/// * 'tab_width' - The number of cells between tab stops.
+pub fn render_line(line: &str, width: usize, tab_width: usize) -> String {
+    render_highlighted_line(line, width, tab_width, 0..0)
+}
+
+/// Lays out a line for display in the same way as `render_line`, drawing the characters in the
+/// highlighted columns in reverse video. If the highlight extends past the end of the line, the
+/// line break is drawn as a highlighted space.
+///
+/// # Arguments
+///
+/// * 'line' - The line to lay out.
+/// * 'width' - The number of cells available to display the line.
+/// * 'tab_width' - The number of cells between tab stops.
+/// * 'highlight' - The columns to highlight.
+pub fn render_highlighted_line(
+    line: &str,
+    width: usize,
+    tab_width: usize,
+    highlight: Range<u32>,
+) -> String {
+    let mut rendered = String::new();
+    let mut segment = String::new();
+    let mut highlighted = false;
+    let mut used = 0;
+    for (i, c) in line.chars().enumerate() {
+        if highlight.contains(&(i as u32)) != highlighted {
+            push_segment(&mut rendered, &mut segment, highlighted);
+            highlighted = !highlighted;
+        }
+
+        let cells = cell_width(c, used, tab_width);
+        if c == '\t' {
+            segment.extend(std::iter::repeat_n(' ', cells.min(width - used)));
+        } else if used + cells <= width {
+            segment.push(c);
+        }
+
+        used += cells;
+        if used >= width {
+            break;
+        }
+    }
+
+    let len = line.chars().count() as u32;
+    if highlight.contains(&len) && used < width {
+        if !highlighted {
+            push_segment(&mut rendered, &mut segment, highlighted);
+            highlighted = true;
+        }
+        segment.push(' ');
+    }
+    push_segment(&mut rendered, &mut segment, highlighted);
+    rendered
+}
+
+/// Lays out a line for display in the same way as `render_line`, drawing the cursors of other
+/// participants in a shared editing session as blocks of colour. A cursor at the end of the
+/// line is drawn as a coloured space.
+///
+/// # Arguments
+///
+/// * 'line' - The line to lay out.
+/// * 'width' - The number of cells available to display the line.
+/// * 'tab_width' - The number of cells between tab stops.
+/// * 'cursors' - The column of each cursor on the line, and its colour.
+pub fn render_line_with_cursors(
+    line: &str,
+    width: usize,
+    tab_width: usize,
+    cursors: &[(u32, Color)],
+) -> String {
+    let cursor_at = |column: usize| {
+        cursors
+            .iter()
+            .find(|(c, _)| *c as usize == column)
+            .map(|(_, color)| *color)
+    };
+    let mut rendered = String::new();
+    let mut used = 0;
+    for (i, c) in line.chars().enumerate() {
+        let cells = cell_width(c, used, tab_width);
+        let text = match c {
+            '\t' => " ".repeat(cells.min(width - used)),
+            c if used + cells <= width => c.to_string(),
+            _ => String::new(),
+        };
+        match cursor_at(i) {
+            Some(color) if !text.is_empty() => {
+                rendered += &style(text).bg(color).force_styling(true).to_string()
+            }
+            _ => rendered += &text,
+        }
+
+        used += cells;
+        if used >= width {
+            return rendered;
+        }
+    }
+
+    if let Some(color) = cursor_at(line.chars().count()) {
+        rendered += &style(" ").bg(color).force_styling(true).to_string();
+    }
+    rendered
+}
+
+/// Lays out a line for display in the same way as `render_line`, drawing each character in its
+/// own style, and optionally drawing a guide line in the leading whitespace at the start of each
+/// level of indentation.
+///
+/// # Arguments
+///
+/// * 'line' - The line to lay out.
+/// * 'width' - The number of cells available to display the line.
+/// * 'tab_width' - The number of cells between tab stops.
+/// * 'guides' - The number of cells in each level of indentation and the colour guides are drawn
+///   in, or None to leave them out.
+/// * 'style' - The style of the character in a column.
+pub fn render_decorated_line(
+    line: &str,
+    width: usize,
+    tab_width: usize,
+    guides: Option<(usize, Color)>,
+    style: impl Fn(u32) -> Style,
+) -> String {
+    let mut spans: Vec<(String, Style)> = vec![];
+    let mut push = |c: char, style: Style| match spans.last_mut() {
+        Some((text, last)) if *last == style => text.push(c),
+        _ => spans.push((c.to_string(), style)),
+    };
+
+    let mut leading = 0;
+    if let Some((indent_width, color)) = guides {
+        leading = line.chars().take_while(|c| *c == ' ' || *c == '\t').count();
+        let guide = Style::new().fg(color).force_styling(true);
+        for cell in 0..display_column(line, leading as u32, tab_width) {
+            match indent_width > 0 && cell % indent_width == 0 {
+                true => push('│', guide.clone()),
+                false => push(' ', Style::new()),
+            }
+        }
+    }
+    for (column, c) in (0..).zip(line.chars()).skip(leading) {
+        push(c, style(column));
+    }
+    render_styled_line(&spans, width, tab_width)
+}
+
+/// Lays out styled runs of text for display in the same way as `render_line`, drawing each run
+/// in its style.
+///
+/// # Arguments
+///
+/// * 'spans' - The runs of text making up the line, and their styles.
+/// * 'width' - The number of cells available to display the line.
+/// * 'tab_width' - The number of cells between tab stops.
+pub fn render_styled_line(spans: &[(String, Style)], width: usize, tab_width: usize) -> String {
+    let mut rendered = String::new();
+    let mut used = 0;
+    for (text, style) in spans {
+        let mut segment = String::new();
+        for c in text.chars() {
+            let cells = cell_width(c, used, tab_width);
+            if c == '\t' {
+                segment.extend(std::iter::repeat_n(' ', cells.min(width - used)));
+            } else if used + cells <= width {
+                segment.push(c);
+            }
+            used += cells;
+            if used >= width {
+                break;
+            }
+        }
+        if !segment.is_empty() {
+            rendered += &style.apply_to(segment).to_string();
+        }
+        if used >= width {
+            break;
+        }
+    }
+    rendered
+}
+
+fn push_segment(rendered: &mut String, segment: &mut String, highlighted: bool) {
+    if highlighted && !segment.is_empty() {
+        rendered.push_str(&style(&segment).reverse().force_styling(true).to_string());
+    } else {
+        rendered.push_str(segment);
+    }
+    segment.clear();
+}
+
+/// The escape sequence which moves the cursor to a column of the current row, so text can be
+/// drawn at a fixed position after a line of unknown width.
+pub fn move_to_column(column: usize) -> String {
+    format!("\x1b[{}G", column + 1)
+}
+
+/// Truncates a line so it fits within the given number of cells. A wide character which would
+/// only partially fit is left out.
+///
+/// # Arguments
+///
+/// * 'line' - The line to truncate.
+/// * 'width' - The number of cells available to display the line.
+pub fn truncate(line: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (i, c) in line.char_indices() {
+        used += char_width(c);
+        if used > width {
+            return &line[..i];
+        }
+    }
+    line
+}
+
+fn char_width(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+/// The number of cells a character takes up when displayed at the given cell.
+fn cell_width(c: char, cell: usize, tab_width: usize) -> usize {
+    match c {
+        '\t' => {
+            let tab_width = tab_width.max(1);
+            tab_width - cell % tab_width
+        }
+        c => char_width(c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("Lorem", 5;    "ascii")]
+    #[test_case("日本語", 6;   "cjk")]
+    #[test_case("a😀b", 4;     "emoji")]
+    #[test_case("e\u{301}", 1; "combining_mark")]
+    fn width_of_text(text: &str, expected: usize) {
+        assert_eq!(expected, display_width(text));
+    }
+
+    #[test_case("Lorem", 3, 3;    "ascii")]
+    #[test_case("日本語", 2, 4;   "cjk")]
+    #[test_case("a😀b", 2, 3;     "after_emoji")]
+    #[test_case("abc", 10, 3;     "past_end")]
+    #[test_case("\tabc", 1, 4;    "after_tab")]
+    #[test_case("ab\tc", 3, 4;    "tab_to_next_stop")]
+    #[test_case("abcd\te", 5, 8;  "tab_at_stop")]
+    fn column_of_character(line: &str, column: u32, expected: usize) {
+        assert_eq!(expected, display_column(line, column, 4));
+    }
+
+    #[test_case("Lorem", 3, 3;     "ascii")]
+    #[test_case("日本語", 3, 1;    "middle_of_cjk")]
+    #[test_case("\tabc", 2, 0;     "inside_tab")]
+    #[test_case("\tabc", 5, 2;     "after_tab")]
+    #[test_case("abc", 10, 3;      "past_end")]
+    fn column_at_cell(line: &str, cell: usize, expected: u32) {
+        assert_eq!(expected, column_at(line, cell, 4));
+    }
+
+    #[test_case("Lorem ipsum", 5, "Lorem";  "ascii")]
+    #[test_case("\tabc", 10, "    abc";    "leading_tab")]
+    #[test_case("ab\tc", 10, "ab  c";      "tab_to_next_stop")]
+    #[test_case("ab\tc", 3, "ab ";         "tab_split")]
+    #[test_case("日本語", 5, "日本";        "cjk_split")]
+    fn render_line_for_display(line: &str, width: usize, expected: &str) {
+        assert_eq!(expected, render_line(line, width, 4));
+    }
+
+    #[test_case("Lorem", 1..3, "L\x1b[7mor\x1b[0mem";         "middle")]
+    #[test_case("Lorem", 3..u32::MAX, "Lor\x1b[7mem \x1b[0m"; "line_break")]
+    #[test_case("Lorem", 0..0, "Lorem";                       "empty")]
+    fn render_line_with_highlight(line: &str, highlight: Range<u32>, expected: &str) {
+        assert_eq!(expected, render_highlighted_line(line, 10, 4, highlight));
+    }
+
+    #[test_case("Lorem", &[(1, Color::Cyan)], "L\x1b[46mo\x1b[0mrem";       "middle")]
+    #[test_case("Lorem", &[(5, Color::Red)], "Lorem\x1b[41m \x1b[0m";        "end_of_line")]
+    #[test_case("Lorem", &[(0, Color::Red), (7, Color::Cyan)], "\x1b[41mL\x1b[0morem"; "past_end")]
+    #[test_case("Lorem ipsum", &[(10, Color::Red)], "Lorem ipsu"; "off_screen")]
+    fn render_line_with_remote_cursors(line: &str, cursors: &[(u32, Color)], expected: &str) {
+        assert_eq!(expected, render_line_with_cursors(line, 10, 4, cursors));
+    }
+
+    #[test]
+    fn render_line_with_styles() {
+        let red = Style::new().red().force_styling(true);
+        let spans = vec![
+            (String::from("ab\t"), red.clone()),
+            (String::from("Lorem"), Style::new()),
+        ];
+        assert_eq!(
+            "\x1b[31mab  \x1b[0mLorem",
+            render_styled_line(&spans, 10, 4)
+        );
+    }
+
+    #[test_case("Lorem", "Lorem";                                                   "no_indent")]
+    #[test_case("  Lorem", "\x1b[38;5;240m│\x1b[0m Lorem";                          "one_level")]
+    #[test_case("    Lorem", "\x1b[38;5;240m│\x1b[0m \x1b[38;5;240m│\x1b[0m Lorem"; "two_levels")]
+    #[test_case("\tLorem", "\x1b[38;5;240m│\x1b[0m \x1b[38;5;240m│\x1b[0m Lorem";   "tab")]
+    #[test_case("   ", "\x1b[38;5;240m│\x1b[0m \x1b[38;5;240m│\x1b[0m";             "blank")]
+    fn render_line_with_indent_guides(line: &str, expected: &str) {
+        assert_eq!(
+            expected,
+            render_decorated_line(
+                line,
+                20,
+                4,
+                Some((2, Color::Color256(240))),
+                |_| Style::new()
+            )
+        );
+    }
+
+    #[test]
+    fn render_line_with_styled_characters() {
+        let red = Style::new().red().force_styling(true);
+        let style = |column| match column {
+            1 | 2 => red.clone(),
+            _ => Style::new(),
+        };
+        assert_eq!(
+            "a\x1b[31m()\x1b[0mb",
+            render_decorated_line("a()b", 10, 4, None, style)
+        );
+    }
+
+    #[test_case("Lorem ipsum", 5, "Lorem"; "ascii")]
+    #[test_case("Lorem", 10, "Lorem";      "shorter_than_width")]
+    #[test_case("日本語", 4, "日本";       "cjk_at_boundary")]
+    #[test_case("日本語", 5, "日本";       "cjk_split")]
+    #[test_case("a😀b", 2, "a";            "emoji_split")]
+    fn truncate_line(line: &str, width: usize, expected: &str) {
+        assert_eq!(expected, truncate(line, width));
+    }
+}
@@ -0,0 +1,77 @@
+//! A terminal text editor. The editor is run by the `texteditor` binary, and is a library so
+//! it can be benchmarked.
+
+mod align;
+mod ansi;
+mod brackets;
+mod case;
+mod chord;
+mod collab;
+mod colors;
+mod command;
+mod completion;
+mod compress;
+mod config;
+mod conflict;
+mod dirty;
+mod document;
+mod drafts;
+mod dropped;
+mod edit;
+mod editor;
+mod error;
+pub mod export;
+mod filetype;
+mod fold;
+mod follow;
+#[cfg(test)]
+mod golden;
+mod help;
+mod hex;
+mod history;
+mod hover;
+mod idle;
+mod indent;
+mod info;
+mod input;
+mod inspect;
+mod jumplist;
+mod keymaps;
+mod killring;
+mod layout;
+mod literal;
+pub mod location;
+mod lock;
+pub mod logging;
+mod macros;
+mod marks;
+mod minimap;
+mod overlay;
+mod perf;
+mod picker;
+mod project;
+mod prompt;
+mod quickfix;
+mod range;
+mod reflow;
+mod registers;
+mod scrollbar;
+mod search;
+mod selection;
+mod shell;
+mod signs;
+mod sort;
+mod status;
+mod substitute;
+mod surround;
+mod terminal;
+mod textobject;
+mod undo;
+mod virtual_text;
+mod welcome;
+
+pub use self::config::Config;
+pub use self::document::Document;
+pub use self::editor::Editor;
+pub use self::error::EditorError;
+pub use self::terminal::{Screen, Terminal};
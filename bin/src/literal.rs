@@ -0,0 +1,255 @@
+/// Digraphs, pairs of keys which are typed to insert a character that's missing from the
+/// keyboard, following RFC 1345. Accented letters are typed as the letter followed by the
+/// accent: `'` acute, `!` grave, `>` circumflex, `:` diaeresis and `?` tilde.
+const DIGRAPHS: &[(&str, char)] = &[
+    ("a'", 'á'),
+    ("a!", 'à'),
+    ("a>", 'â'),
+    ("a:", 'ä'),
+    ("a?", 'ã'),
+    ("aa", 'å'),
+    ("ae", 'æ'),
+    ("A'", 'Á'),
+    ("A!", 'À'),
+    ("A>", 'Â'),
+    ("A:", 'Ä'),
+    ("A?", 'Ã'),
+    ("AA", 'Å'),
+    ("AE", 'Æ'),
+    ("c,", 'ç'),
+    ("C,", 'Ç'),
+    ("e'", 'é'),
+    ("e!", 'è'),
+    ("e>", 'ê'),
+    ("e:", 'ë'),
+    ("E'", 'É'),
+    ("E!", 'È'),
+    ("E>", 'Ê'),
+    ("E:", 'Ë'),
+    ("i'", 'í'),
+    ("i!", 'ì'),
+    ("i>", 'î'),
+    ("i:", 'ï'),
+    ("I'", 'Í'),
+    ("I!", 'Ì'),
+    ("I>", 'Î'),
+    ("I:", 'Ï'),
+    ("n?", 'ñ'),
+    ("N?", 'Ñ'),
+    ("o'", 'ó'),
+    ("o!", 'ò'),
+    ("o>", 'ô'),
+    ("o:", 'ö'),
+    ("o?", 'õ'),
+    ("o/", 'ø'),
+    ("O'", 'Ó'),
+    ("O!", 'Ò'),
+    ("O>", 'Ô'),
+    ("O:", 'Ö'),
+    ("O?", 'Õ'),
+    ("O/", 'Ø'),
+    ("ss", 'ß'),
+    ("u'", 'ú'),
+    ("u!", 'ù'),
+    ("u>", 'û'),
+    ("u:", 'ü'),
+    ("U'", 'Ú'),
+    ("U!", 'Ù'),
+    ("U>", 'Û'),
+    ("U:", 'Ü'),
+    ("y'", 'ý'),
+    ("y:", 'ÿ'),
+    ("Y'", 'Ý'),
+    ("!I", '¡'),
+    ("?I", '¿'),
+    ("<<", '«'),
+    (">>", '»'),
+    ("Ct", '¢'),
+    ("Pd", '£'),
+    ("Eu", '€'),
+    ("Ye", '¥'),
+    ("SE", '§'),
+    ("Co", '©'),
+    ("Rg", '®'),
+    ("DG", '°'),
+    ("+-", '±'),
+    ("My", 'µ'),
+    ("PI", '¶'),
+    (".M", '·'),
+    ("*X", '×'),
+    ("-:", '÷'),
+    ("a*", 'α'),
+    ("b*", 'β'),
+    ("g*", 'γ'),
+    ("d*", 'δ'),
+    ("e*", 'ε'),
+    ("l*", 'λ'),
+    ("m*", 'μ'),
+    ("p*", 'π'),
+    ("s*", 'σ'),
+    ("D*", 'Δ'),
+    ("S*", 'Σ'),
+    ("W*", 'Ω'),
+    ("-N", '–'),
+    ("-M", '—'),
+    ("'6", '‘'),
+    ("'9", '’'),
+    ("\"6", '“'),
+    ("\"9", '”'),
+    (",.", '…'),
+    ("<-", '←'),
+    ("-!", '↑'),
+    ("->", '→'),
+    ("-v", '↓'),
+    ("!=", '≠'),
+    ("=<", '≤'),
+    (">=", '≥'),
+    ("?2", '≈'),
+    ("00", '∞'),
+    ("OK", '✓'),
+];
+
+/// The keys typed after Ctrl+V to insert a character by its codepoint or a digraph. Codepoints
+/// are typed in hex after `x` (2 digits), `u` (4 digits) or `U` (8 digits), and can be ended
+/// early by pressing Enter or typing a key which isn't a hex digit. Any other pair of keys is
+/// looked up as a digraph.
+#[derive(Debug, Default)]
+pub struct Literal {
+    keys: String,
+}
+
+/// The result of typing a key into a literal.
+#[derive(Debug, PartialEq)]
+pub enum Step {
+    /// More keys are needed.
+    Pending,
+    /// The literal is complete and the text should be inserted.
+    Insert(String),
+    /// The keys don't make up a valid character.
+    Invalid(String),
+}
+
+impl Literal {
+    pub fn new() -> Literal {
+        Literal::default()
+    }
+
+    /// The keys typed so far.
+    pub fn keys(&self) -> &str {
+        &self.keys
+    }
+
+    /// Adds a key to the literal.
+    pub fn push(&mut self, c: char) -> Step {
+        let Some(digits) = self.max_digits() else {
+            self.keys.push(c);
+            return match self.keys.chars().count() {
+                1 => Step::Pending,
+                _ => self.digraph(),
+            };
+        };
+
+        let typed = self.keys.len() - 1;
+        if c.is_ascii_hexdigit() {
+            self.keys.push(c);
+            return match typed + 1 == digits {
+                true => self.codepoint(),
+                false => Step::Pending,
+            };
+        }
+        // A digraph starting with `u` or `U`, such as `u:` for ü.
+        if typed == 0 {
+            self.keys.push(c);
+            return self.digraph();
+        }
+        match self.codepoint() {
+            Step::Insert(mut text) => {
+                text.push(c);
+                Step::Insert(text)
+            }
+            step => step,
+        }
+    }
+
+    /// Ends the literal early, as happens when Enter is pressed.
+    pub fn finish(&self) -> Step {
+        match self.max_digits() {
+            Some(_) if self.keys.len() > 1 => self.codepoint(),
+            _ => Step::Invalid(String::from("Literal cancelled")),
+        }
+    }
+
+    /// The number of hex digits in the codepoint being typed, or None if a digraph is being
+    /// typed instead.
+    fn max_digits(&self) -> Option<usize> {
+        match self.keys.chars().next() {
+            Some('x') => Some(2),
+            Some('u') => Some(4),
+            Some('U') => Some(8),
+            _ => None,
+        }
+    }
+
+    fn codepoint(&self) -> Step {
+        let digits = &self.keys[1..];
+        match u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+        {
+            Some(c) => Step::Insert(c.to_string()),
+            None => Step::Invalid(format!("Invalid codepoint: U+{}", digits.to_uppercase())),
+        }
+    }
+
+    fn digraph(&self) -> Step {
+        match DIGRAPHS.iter().find(|(keys, _)| *keys == self.keys) {
+            Some((_, c)) => Step::Insert(c.to_string()),
+            None => Step::Invalid(format!("Unknown digraph: {}", self.keys)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn type_keys(keys: &str) -> Step {
+        let mut literal = Literal::new();
+        let mut step = Step::Pending;
+        for c in keys.chars() {
+            step = literal.push(c);
+        }
+        step
+    }
+
+    #[test_case("u00e9", Step::Insert(String::from("é"));                       "four_digits")]
+    #[test_case("U0001F600", Step::Insert(String::from("😀"));                  "eight_digits")]
+    #[test_case("x41", Step::Insert(String::from("A"));                         "two_digits")]
+    #[test_case("ue9 ", Step::Insert(String::from("é "));                       "ended_by_other_key")]
+    #[test_case("ud800", Step::Invalid(String::from("Invalid codepoint: U+D800")); "surrogate")]
+    #[test_case("u00", Step::Pending;                                            "pending")]
+    #[test_case("e'", Step::Insert(String::from("é"));                          "digraph")]
+    #[test_case("u:", Step::Insert(String::from("ü"));                          "digraph_starting_with_u")]
+    #[test_case("qq", Step::Invalid(String::from("Unknown digraph: qq"));        "unknown_digraph")]
+    fn push(keys: &str, expected: Step) {
+        assert_eq!(expected, type_keys(keys));
+    }
+
+    #[test]
+    fn finish_early() {
+        let mut literal = Literal::new();
+        literal.push('u');
+        literal.push('4');
+        literal.push('1');
+        assert_eq!(Step::Insert(String::from("A")), literal.finish());
+    }
+
+    #[test]
+    fn finish_without_digits() {
+        let mut literal = Literal::new();
+        literal.push('u');
+        assert!(matches!(literal.finish(), Step::Invalid(_)));
+    }
+}
@@ -0,0 +1,141 @@
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+
+/// A document passed on the command line, along with where the cursor should be placed once it
+/// has been loaded. Line and column numbers start from 1.
+#[derive(Debug, PartialEq)]
+pub struct Location {
+    pub path: PathBuf,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+impl Location {
+    /// Parses a document argument in the form `file`, `file:line` or `file:line:column`. If a
+    /// file exists with the literal name of the argument, it's used as-is so paths containing
+    /// colons can still be opened.
+    ///
+    /// # Arguments
+    ///
+    /// * 'arg' - The command line argument to parse.
+    pub fn parse(arg: &OsStr) -> Location {
+        let plain = Location {
+            path: PathBuf::from(arg),
+            line: None,
+            column: None,
+        };
+
+        if plain.path.exists() {
+            return plain;
+        }
+
+        let Some((rest, last)) = arg.to_str().and_then(split_number) else {
+            return plain;
+        };
+
+        match split_number(rest) {
+            Some((file, line)) => Location {
+                path: PathBuf::from(file),
+                line: Some(line),
+                column: Some(last),
+            },
+            None => Location {
+                path: PathBuf::from(rest),
+                line: Some(last),
+                column: None,
+            },
+        }
+    }
+}
+
+/// Parses the document arguments passed on the command line. A `+line` argument sets the line
+/// for the document which follows it, and a lone `+` opens it at the last line.
+///
+/// # Arguments
+///
+/// * 'args' - The document arguments, in the order they were passed.
+pub fn parse_locations(args: &[OsString]) -> Vec<Location> {
+    let mut locations = vec![];
+    let mut line = None;
+
+    for arg in args {
+        if let Some(number) = arg.to_str().and_then(|a| a.strip_prefix('+')) {
+            if number.is_empty() {
+                line = Some(u32::MAX);
+                continue;
+            }
+            if let Ok(number) = number.parse() {
+                line = Some(number);
+                continue;
+            }
+        }
+
+        let mut location = Location::parse(arg);
+        if let Some(line) = line.take() {
+            location.line = Some(line);
+            location.column = None;
+        }
+        locations.push(location);
+    }
+
+    locations
+}
+
+/// Splits a trailing `:number` from the text, returning the remaining text and the number.
+fn split_number(text: &str) -> Option<(&str, u32)> {
+    let (rest, number) = text.rsplit_once(':')?;
+    if rest.is_empty() {
+        return None;
+    }
+
+    number.parse().ok().map(|number| (rest, number))
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn location(path: &str, line: Option<u32>, column: Option<u32>) -> Location {
+        Location {
+            path: PathBuf::from(path),
+            line,
+            column,
+        }
+    }
+
+    #[test_case("file.txt", None, None;           "file_only")]
+    #[test_case("file.txt:42", Some(42), None;    "file_and_line")]
+    #[test_case("file.txt:42:7", Some(42), Some(7); "file_line_and_column")]
+    fn parse_location(arg: &str, line: Option<u32>, column: Option<u32>) {
+        let expected = location("file.txt", line, column);
+        let actual = Location::parse(OsStr::new(arg));
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_location_with_non_numeric_suffix() {
+        let expected = location("dir:name/file.txt", None, None);
+        let actual = Location::parse(OsStr::new("dir:name/file.txt"));
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_locations_with_line_argument() {
+        let args = vec![OsString::from("+42"), OsString::from("file.txt")];
+
+        let expected = vec![location("file.txt", Some(42), None)];
+        let actual = parse_locations(&args);
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_locations_with_last_line_argument() {
+        let args = vec![OsString::from("+"), OsString::from("file.txt")];
+
+        let expected = vec![location("file.txt", Some(u32::MAX), None)];
+        let actual = parse_locations(&args);
+        assert_eq!(expected, actual);
+    }
+}
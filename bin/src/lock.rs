@@ -0,0 +1,207 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// What's written to a lock file, identifying the instance of the editor holding it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Owner {
+    pid: u32,
+    file: PathBuf,
+}
+
+/// An advisory lock on a file being edited, which tells other instances of the editor that the
+/// file is open. The lock file is removed when the lock is dropped, so a lock file left behind
+/// by an instance which didn't exit cleanly is stale and can be taken over.
+#[derive(Debug)]
+pub struct Lock {
+    path: PathBuf,
+}
+
+/// The result of trying to lock a file.
+#[derive(Debug)]
+pub enum Acquired {
+    Locked(Lock),
+    /// Another running instance holds the lock, with this process ID if it's been written yet.
+    HeldBy(Option<u32>),
+}
+
+impl Lock {
+    /// Locks a file, taking over a stale lock.
+    ///
+    /// # Arguments
+    ///
+    /// * 'dir' - The directory lock files are kept in.
+    /// * 'file' - The file being edited.
+    pub fn acquire(dir: &Path, file: &Path) -> io::Result<Acquired> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(lock_name(file));
+        let owner = Owner {
+            pid: std::process::id(),
+            file: file.to_path_buf(),
+        };
+        // The lock is written to a file of its own and then linked into place, which fails if
+        // the lock file exists, so the lock file is never seen without the owner in it.
+        let written = dir.join(format!("{}.{}", lock_name(file), owner.pid));
+        fs::write(&written, serde_json::to_string(&owner)?)?;
+        let acquired = Lock::link(&written, path, owner.pid);
+        let _ = fs::remove_file(&written);
+        acquired
+    }
+
+    /// Links a written lock file into place, taking over a stale lock.
+    fn link(written: &Path, path: PathBuf, pid: u32) -> io::Result<Acquired> {
+        loop {
+            match fs::hard_link(written, &path) {
+                Ok(()) => return Ok(Acquired::Locked(Lock { path })),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    let contents = match fs::read_to_string(&path) {
+                        Ok(contents) => contents,
+                        // The lock was released since, so it can be taken.
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                        Err(e) => return Err(e),
+                    };
+                    // An empty lock file may have been created by an instance which hasn't
+                    // written to it yet, so it isn't taken over.
+                    if contents.is_empty() {
+                        return Ok(Acquired::HeldBy(None));
+                    }
+                    if let Some(holder) = holder(&contents).filter(|holder| *holder != pid) {
+                        return Ok(Acquired::HeldBy(Some(holder)));
+                    }
+                    fs::remove_file(&path)?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// The process ID of the running instance holding a lock file, or None if the lock is stale
+/// or can't be read.
+///
+/// # Arguments
+///
+/// * 'contents' - The contents of the lock file.
+fn holder(contents: &str) -> Option<u32> {
+    let owner: Owner = serde_json::from_str(contents).ok()?;
+    is_running(owner.pid).then_some(owner.pid)
+}
+
+/// Whether a process is still running.
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: sending signal 0 only checks whether the process exists.
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    // The process exists but belongs to another user.
+    io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Whether a process is still running, which can't be told here, so every lock is assumed to
+/// be held.
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+    true
+}
+
+/// The name of a file's lock file, made from its path with the separators replaced so every
+/// file's lock can sit in one directory.
+fn lock_name(file: &Path) -> String {
+    let name: String = file
+        .to_string_lossy()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' => '%',
+            c => c,
+        })
+        .collect();
+    format!("{}.lock", name)
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("/code/src/main.rs", "%code%src%main.rs.lock"; "unix")]
+    #[test_case("C:\\code\\main.rs", "C%%code%main.rs.lock";    "windows")]
+    fn lock_names(file: &str, expected: &str) {
+        assert_eq!(expected, lock_name(Path::new(file)));
+    }
+
+    fn setup(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        let file = PathBuf::from("/code/main.rs");
+        (dir.clone(), dir.join(lock_name(&file)))
+    }
+
+    #[test]
+    fn lock_is_removed_when_dropped() {
+        let (dir, lock_file) = setup("texteditor_lock_dropped");
+        let lock = Lock::acquire(&dir, Path::new("/code/main.rs")).unwrap();
+        assert!(matches!(lock, Acquired::Locked(_)));
+        assert!(lock_file.exists());
+        drop(lock);
+        assert!(!lock_file.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn lock_held_by_running_process() {
+        let (dir, lock_file) = setup("texteditor_lock_held");
+        fs::create_dir_all(&dir).unwrap();
+        // The parent process is running for as long as the tests are.
+        let pid = std::os::unix::process::parent_id();
+        let owner = format!("{{\"pid\":{},\"file\":\"/code/main.rs\"}}", pid);
+        fs::write(&lock_file, owner).unwrap();
+
+        let lock = Lock::acquire(&dir, Path::new("/code/main.rs")).unwrap();
+        assert!(matches!(lock, Acquired::HeldBy(Some(p)) if p == pid));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn empty_lock_is_held() {
+        let (dir, lock_file) = setup("texteditor_lock_empty");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&lock_file, "").unwrap();
+
+        let lock = Lock::acquire(&dir, Path::new("/code/main.rs")).unwrap();
+        assert!(matches!(lock, Acquired::HeldBy(None)));
+        assert_eq!(1, fs::read_dir(&dir).unwrap().count());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test_case("{\"pid\":4294967295,\"file\":\"/code/main.rs\"}"; "exited_process")]
+    #[test_case("not json";                                        "unreadable")]
+    fn stale_lock_is_taken_over(contents: &str) {
+        let name = format!("texteditor_lock_stale_{}", contents.len());
+        let (dir, lock_file) = setup(&name);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&lock_file, contents).unwrap();
+
+        let lock = Lock::acquire(&dir, Path::new("/code/main.rs")).unwrap();
+        assert!(matches!(lock, Acquired::Locked(_)));
+        assert!(fs::read_to_string(&lock_file)
+            .unwrap()
+            .contains(&std::process::id().to_string()));
+        drop(lock);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,182 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::config;
+
+/// The size the log can grow to before it's rotated, in bytes.
+pub const MAX_SIZE: u64 = 1024 * 1024;
+/// The number of log lines shown by `:messages`.
+pub const MESSAGE_LINES: usize = 500;
+
+/// The log file used when none is given on the command line, in the state directory.
+pub fn default_path() -> Option<PathBuf> {
+    config::state_dir().map(|dir| dir.join("texteditor.log"))
+}
+
+/// The path a log is moved to when it's rotated, which is the log's path with `.1` added.
+pub fn rotated_path(path: &Path) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(".1");
+    PathBuf::from(rotated)
+}
+
+/// A log file which is moved aside once it grows past a size, replacing the log moved aside
+/// before it, so at most two logs are kept.
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    /// Opens a log file to append to, creating it and its directory if they don't exist.
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - The log file.
+    /// * 'max_size' - The size in bytes the log can grow to before it's rotated.
+    pub fn open(path: PathBuf, max_size: u64) -> io::Result<RotatingFile> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = File::options().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile {
+            path,
+            max_size,
+            file,
+            size,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        fs::rename(&self.path, rotated_path(&self.path))?;
+        self.file = File::options().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Reads the last records from a log, formatted for reading.
+///
+/// # Arguments
+///
+/// * 'path' - The log file.
+/// * 'count' - The number of records to read.
+pub fn recent_messages(path: &Path, count: usize) -> io::Result<String> {
+    let log = fs::read_to_string(path)?;
+    let lines: Vec<&str> = log.lines().collect();
+    let recent = &lines[lines.len().saturating_sub(count)..];
+    Ok(recent
+        .iter()
+        .map(|line| format_record(line))
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+/// Formats a JSON log record as `time LEVEL target: message`, followed by any other fields. Lines
+/// which aren't JSON records are left as they are.
+fn format_record(line: &str) -> String {
+    let Ok(Value::Object(mut fields)) = serde_json::from_str::<Value>(line) else {
+        return line.to_string();
+    };
+    let mut take = |key: &str| match fields.remove(key) {
+        Some(Value::String(value)) => value,
+        Some(value) => value.to_string(),
+        None => String::new(),
+    };
+
+    let time = take("timestamp")
+        .parse::<u64>()
+        .map_or(String::new(), format_time);
+    let mut record = format!(
+        "{} {:<5} {}: {}",
+        time,
+        take("level"),
+        take("target"),
+        take("message")
+    );
+    for (key, value) in fields {
+        record += &format!(" {}={}", key, value);
+    }
+    record
+}
+
+/// Formats a time as the time of day in UTC, `HH:MM:SS.mmm`.
+///
+/// # Arguments
+///
+/// * 'millis' - The number of milliseconds since the Unix epoch.
+fn format_time(millis: u64) -> String {
+    let seconds = millis / 1000 % 86400;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        millis % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(
+        r#"{"level":"INFO","message":"Opened","target":"texteditor::editor","timestamp":1792060966101}"#,
+        "10:42:46.101 INFO  texteditor::editor: Opened";
+        "record"
+    )]
+    #[test_case(
+        r#"{"level":"INFO","message":"frame","target":"perf","timestamp":0,"total_us":253}"#,
+        "00:00:00.000 INFO  perf: frame total_us=253";
+        "extra_fields"
+    )]
+    #[test_case("not json", "not json"; "plain_line")]
+    fn format_log_record(line: &str, expected: &str) {
+        assert_eq!(expected, format_record(line));
+    }
+
+    #[test]
+    fn rotate_when_full() {
+        let path = std::env::temp_dir().join("texteditor_rotate_when_full.log");
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(rotated_path(&path));
+
+        let mut file = RotatingFile::open(path.clone(), 10).unwrap();
+        file.write_all(b"0123456\n").unwrap();
+        file.write_all(b"abcdef\n").unwrap();
+        file.flush().unwrap();
+
+        assert_eq!(
+            "0123456\n",
+            fs::read_to_string(rotated_path(&path)).unwrap()
+        );
+        assert_eq!("abcdef\n", fs::read_to_string(&path).unwrap());
+        assert_eq!("abcdef", recent_messages(&path, 5).unwrap());
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(rotated_path(&path)).unwrap();
+    }
+}
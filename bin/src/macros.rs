@@ -0,0 +1,267 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+/// How many macros can be playing at once, as macros can play other macros, or themselves.
+pub const MAX_DEPTH: u32 = 16;
+
+/// The keys with names, written between angle brackets in a macro's text.
+const NAMED_KEYS: &[(KeyCode, &str)] = &[
+    (KeyCode::Enter, "Enter"),
+    (KeyCode::Tab, "Tab"),
+    (KeyCode::BackTab, "BackTab"),
+    (KeyCode::Backspace, "BS"),
+    (KeyCode::Delete, "Del"),
+    (KeyCode::Insert, "Insert"),
+    (KeyCode::Esc, "Esc"),
+    (KeyCode::Up, "Up"),
+    (KeyCode::Down, "Down"),
+    (KeyCode::Left, "Left"),
+    (KeyCode::Right, "Right"),
+    (KeyCode::Home, "Home"),
+    (KeyCode::End, "End"),
+    (KeyCode::PageUp, "PageUp"),
+    (KeyCode::PageDown, "PageDown"),
+    (KeyCode::Char(' '), "Space"),
+    (KeyCode::Char('<'), "lt"),
+];
+
+/// The modifiers written before a key's name, such as `<C-p>` for Ctrl+P.
+const MODIFIERS: &[(KeyModifiers, &str)] = &[
+    (KeyModifiers::CONTROL, "C-"),
+    (KeyModifiers::ALT, "A-"),
+    (KeyModifiers::SHIFT, "S-"),
+];
+
+/// Records keyboard macros: sequences of keys which can be played back as if they were typed
+/// again. Recorded macros are stored as text in the registers, shared with yanks and deletes.
+#[derive(Debug, Default)]
+pub struct Macros {
+    recording: Option<Recording>,
+    /// The number of macros being played, counting macros played by other macros.
+    pub depth: u32,
+}
+
+/// A macro being recorded.
+#[derive(Debug)]
+struct Recording {
+    register: char,
+    keys: Vec<KeyEvent>,
+    /// The number of keys recorded before the key which opened the current prompt or chord, so
+    /// the keys which stop the recording can be left out of it.
+    mark: usize,
+}
+
+impl Macros {
+    pub fn new() -> Macros {
+        Macros::default()
+    }
+
+    /// Starts recording keys for a register.
+    pub fn start(&mut self, register: char) {
+        self.recording = Some(Recording {
+            register,
+            keys: vec![],
+            mark: 0,
+        });
+    }
+
+    /// Stops recording, returning the register and the keys recorded for it, or None if nothing
+    /// was being recorded. The keys which ran the command to stop are left out.
+    pub fn stop(&mut self) -> Option<(char, Vec<KeyEvent>)> {
+        let mut recording = self.recording.take()?;
+        recording.keys.truncate(recording.mark);
+        Some((recording.register, recording.keys))
+    }
+
+    /// The register being recorded into, if a macro is being recorded.
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|r| r.register)
+    }
+
+    /// Records a key pressed while recording. Keys sent by a macro being played aren't recorded.
+    ///
+    /// # Arguments
+    ///
+    /// * 'key' - The key pressed.
+    /// * 'in_document' - Whether the key was pressed in the document, rather than in a prompt,
+    ///   picker or chord, so it could be starting the command which stops the recording.
+    pub fn record(&mut self, key: KeyEvent, in_document: bool) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+        if self.depth > 0 || key.kind == KeyEventKind::Release {
+            return;
+        }
+        if in_document {
+            recording.mark = recording.keys.len();
+        }
+        recording.keys.push(key);
+    }
+}
+
+/// Checks that a register is named by a single letter or digit.
+pub fn parse_register(name: &str) -> Result<char, String> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_lowercase() || c.is_ascii_digit() => Ok(c),
+        (None, _) => Err(String::from("No register entered")),
+        _ => Err(format!("Invalid register: {}", name)),
+    }
+}
+
+/// Writes keys as text, with printable characters as themselves and other keys named between
+/// angle brackets, such as `<Enter>` or `<C-p>`.
+pub fn to_text(keys: &[KeyEvent]) -> String {
+    keys.iter().map(key_to_text).collect()
+}
+
+fn key_to_text(key: &KeyEvent) -> String {
+    // Shift is part of the character typed, so it's only written for other keys.
+    let mut modifiers = key.modifiers;
+    if matches!(key.code, KeyCode::Char(_)) {
+        modifiers.remove(KeyModifiers::SHIFT);
+    }
+    let name = match NAMED_KEYS.iter().find(|(code, _)| *code == key.code) {
+        Some((_, name)) => name.to_string(),
+        None => match key.code {
+            KeyCode::Char(c) if modifiers.is_empty() => return c.to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::F(n) => format!("F{}", n),
+            code => format!("{:?}", code),
+        },
+    };
+    let prefix: String = MODIFIERS
+        .iter()
+        .filter(|(modifier, _)| modifiers.contains(*modifier))
+        .map(|(_, prefix)| *prefix)
+        .collect();
+    format!("<{}{}>", prefix, name)
+}
+
+/// Reads keys back from text written by `to_text`. Line breaks are ignored, so a long macro can
+/// be split across lines.
+pub fn parse(text: &str) -> Result<Vec<KeyEvent>, String> {
+    let mut keys = vec![];
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        match c {
+            '\n' | '\r' => {}
+            '<' => {
+                let (name, after) = rest
+                    .split_once('>')
+                    .ok_or_else(|| String::from("Missing > after <"))?;
+                keys.push(parse_key(name)?);
+                rest = after;
+            }
+            c => keys.push(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)),
+        }
+    }
+    Ok(keys)
+}
+
+fn parse_key(name: &str) -> Result<KeyEvent, String> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = name;
+    while let Some((modifier, after)) = MODIFIERS
+        .iter()
+        .find_map(|(modifier, prefix)| Some((modifier, rest.strip_prefix(prefix)?)))
+        .filter(|(_, after)| !after.is_empty())
+    {
+        modifiers |= *modifier;
+        rest = after;
+    }
+
+    let code = match NAMED_KEYS.iter().find(|(_, n)| *n == rest) {
+        Some((code, _)) => *code,
+        None => {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => match rest.strip_prefix('F').and_then(|n| n.parse().ok()) {
+                    Some(n) => KeyCode::F(n),
+                    None => return Err(format!("Unknown key: <{}>", name)),
+                },
+            }
+        }
+    };
+    Ok(KeyEvent::new(code, modifiers))
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test_case(key(KeyCode::Char('a'), KeyModifiers::NONE), "a";             "character")]
+    #[test_case(key(KeyCode::Char('A'), KeyModifiers::SHIFT), "A";            "shifted_character")]
+    #[test_case(key(KeyCode::Char('p'), KeyModifiers::CONTROL), "<C-p>";      "control")]
+    #[test_case(key(KeyCode::Enter, KeyModifiers::NONE), "<Enter>";           "named")]
+    #[test_case(key(KeyCode::Left, KeyModifiers::SHIFT), "<S-Left>";          "shifted_named")]
+    #[test_case(key(KeyCode::Char('<'), KeyModifiers::NONE), "<lt>";          "less_than")]
+    #[test_case(key(KeyCode::F(3), KeyModifiers::SHIFT), "<S-F3>";            "function_key")]
+    #[test_case(key(KeyCode::Right, KeyModifiers::CONTROL | KeyModifiers::ALT), "<C-A-Right>"; "several_modifiers")]
+    fn key_round_trip(key: KeyEvent, text: &str) {
+        assert_eq!(text, to_text(&[key]));
+        let mut expected = key;
+        if let KeyCode::Char(_) = key.code {
+            expected.modifiers.remove(KeyModifiers::SHIFT);
+        }
+        assert_eq!(Ok(vec![expected]), parse(text));
+    }
+
+    #[test]
+    fn parse_ignores_line_breaks() {
+        let keys = parse("a<Enter>\nb").unwrap();
+        assert_eq!("a<Enter>b", to_text(&keys));
+    }
+
+    #[test_case("<Enter", "Missing > after <"; "unclosed")]
+    #[test_case("<Nope>", "Unknown key: <Nope>"; "unknown")]
+    fn parse_invalid(text: &str, expected: &str) {
+        assert_eq!(Err(expected.to_string()), parse(text));
+    }
+
+    #[test_case("a", Ok('a');                                   "letter")]
+    #[test_case("1", Ok('1');                                   "digit")]
+    #[test_case("", Err(String::from("No register entered"));    "empty")]
+    #[test_case("ab", Err(String::from("Invalid register: ab")); "too_long")]
+    #[test_case("A", Err(String::from("Invalid register: A"));   "uppercase")]
+    fn register_names(name: &str, expected: Result<char, String>) {
+        assert_eq!(expected, parse_register(name));
+    }
+
+    #[test]
+    fn recording_leaves_out_stop_command() {
+        let mut macros = Macros::new();
+        macros.start('a');
+        assert_eq!(Some('a'), macros.recording());
+        macros.record(key(KeyCode::Char('x'), KeyModifiers::NONE), true);
+        macros.record(key(KeyCode::Down, KeyModifiers::NONE), true);
+        // Ctrl+P opens the prompt, where the command to stop recording is typed.
+        macros.record(key(KeyCode::Char('p'), KeyModifiers::CONTROL), true);
+        for c in "record".chars() {
+            macros.record(key(KeyCode::Char(c), KeyModifiers::NONE), false);
+        }
+
+        let (register, keys) = macros.stop().unwrap();
+        assert_eq!('a', register);
+        assert_eq!("x<Down>", to_text(&keys));
+        assert_eq!(None, macros.stop());
+    }
+
+    #[test]
+    fn keys_played_back_are_not_recorded() {
+        let mut macros = Macros::new();
+        macros.start('b');
+        macros.depth = 1;
+        macros.record(key(KeyCode::Char('x'), KeyModifiers::NONE), true);
+        macros.depth = 0;
+        macros.record(key(KeyCode::Char('p'), KeyModifiers::CONTROL), true);
+        assert_eq!(Some(('b', vec![])), macros.stop());
+    }
+}
@@ -1,41 +1,91 @@
 use std::ffi::OsString;
-use std::fs::File;
-use std::path::Path;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
 use clap::Parser;
+use log::{error, LevelFilter};
 use structured_logger::json::new_writer;
 use structured_logger::Builder;
 
-use self::editor::Editor;
-
-mod document;
-mod editor;
-mod keymaps;
-mod terminal;
+use texteditor::export::{self, Format};
+use texteditor::location::parse_locations;
+use texteditor::logging::{self, RotatingFile};
+use texteditor::{Config, Document, Editor, EditorError};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
+    /// The documents to open. Use `file:line:column` or a preceding `+line` to open a document
+    /// at a specific position
     #[arg(name = "Document")]
-    doc: Option<OsString>,
+    doc: Vec<OsString>,
+
+    /// Open the documents in read-only mode
+    #[arg(long)]
+    readonly: bool,
+
+    /// Follow the first document as its file grows, like `tail -f`, opening it read-only
+    #[arg(long)]
+    follow: bool,
+
+    /// Open documents which don't exist yet as empty buffers, creating them on save
+    #[arg(long)]
+    new_file: bool,
+
+    /// Write the document to stdout and exit without starting the editor
+    #[arg(long)]
+    print: bool,
+
+    /// Write the document to stdout as html or ansi, with line numbers, and exit without
+    /// starting the editor
+    #[arg(long, value_name = "FORMAT", value_parser = Format::parse, conflicts_with = "print")]
+    export: Option<Format>,
+
+    /// Share the first document with other editors, listening on an address such as
+    /// 127.0.0.1:7878
+    #[arg(long, value_name = "ADDRESS", conflicts_with = "connect")]
+    serve: Option<String>,
+
+    /// Let --serve listen on addresses other machines can reach. Connections aren't
+    /// authenticated, so anyone who can connect can read and edit the document
+    #[arg(long, requires = "serve")]
+    allow_remote: bool,
+
+    /// Join a document shared by another editor with --serve
+    #[arg(long, value_name = "ADDRESS")]
+    connect: Option<String>,
+
+    /// Log how long each stage of every frame takes, to measure the editor's responsiveness
+    #[arg(long)]
+    profile_events: bool,
+
+    /// Write the log to this file, rather than texteditor.log in the state directory
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// The most detailed messages to log: off, error, warn, info, debug or trace
+    #[arg(long, value_name = "LEVEL", default_value = "info")]
+    log_level: LevelFilter,
 }
 
 fn main() {
     let args = Args::parse();
-    let file = args.doc.and_then(|file| {
-        std::env::current_dir().map_or(None, |dir| Some(Path::new(&dir).join(file)))
-    });
-
-    // Initialize the logger.
-    let log_file = File::options()
-        .create(true)
-        .append(true)
-        .open("app.log")
-        .unwrap();
+    let mut locations = parse_locations(&args.doc);
+    if let Ok(dir) = std::env::current_dir() {
+        for location in locations.iter_mut() {
+            location.path = Path::new(&dir).join(&location.path);
+        }
+    }
 
-    Builder::new()
-        .with_target_writer("*", new_writer(log_file))
-        .init();
+    let log_file = args.log_file.clone().or_else(logging::default_path);
+    if let Some(path) = &log_file {
+        match RotatingFile::open(path.clone(), logging::MAX_SIZE) {
+            Ok(file) => Builder::with_level(args.log_level.as_str())
+                .with_target_writer("*", new_writer(file))
+                .init(),
+            Err(e) => eprintln!("Unable to open log file {}: {}", path.display(), e),
+        }
+    }
 
     /*panic::set_hook(Box::new(|e| {
         if Terminal::exit().is_ok() {
@@ -47,7 +97,144 @@ fn main() {
     //let mut stdout = io::stdout();
     //run(&mut stdout)
 
-    let mut editor = Editor::new();
-    editor.load(file);
-    editor.run();
+    let (config, config_error) = match Config::load() {
+        Ok(config) => (config, None),
+        Err(e) => (Config::default(), Some(e)),
+    };
+
+    if args.print || args.export.is_some() {
+        if let Some(e) = &config_error {
+            eprintln!("{}", e);
+        }
+    }
+
+    if args.print {
+        let files = locations.into_iter().map(|location| location.path);
+        if let Err(e) = print(files, &config) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(format) = args.export {
+        let files = locations.into_iter().map(|location| location.path);
+        if let Err(e) = export(files, format, &config) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let mut editor = Editor::new(config);
+    if let Some(e) = config_error {
+        editor.report(e);
+    }
+    editor.set_profile_events(args.profile_events);
+    editor.set_log_file(log_file);
+    for location in locations {
+        let document = if args.new_file && !location.path.exists() {
+            Ok(Document::create(location.path.clone(), editor.config()))
+        } else {
+            Document::open(location.path.clone(), editor.config())
+        };
+
+        match document {
+            Ok(mut document) => {
+                document.set_readonly(args.readonly);
+                editor.open(document);
+
+                let line = location.line.unwrap_or(1);
+                let column = location.column.unwrap_or(1).saturating_sub(1);
+                editor.goto(line, column);
+            }
+            Err(e) => editor.report(EditorError::Open {
+                path: location.path,
+                source: Box::new(e),
+            }),
+        }
+    }
+    editor.switch_buffer(0);
+
+    if args.follow {
+        if let Err(e) = editor.follow() {
+            eprintln!("Unable to follow: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let session = match (&args.serve, &args.connect) {
+        (Some(address), _) => editor.serve(address, args.allow_remote),
+        (None, Some(address)) => editor.connect(address),
+        (None, None) => Ok(()),
+    };
+    if let Err(e) = session {
+        eprintln!("Unable to start shared editing: {}", e);
+        std::process::exit(1);
+    }
+
+    if let Err(e) = editor.run() {
+        error!("{}", e);
+        eprintln!("{}", e);
+    }
+}
+
+/// Loads each document and writes its contents to stdout, bypassing the terminal setup used by
+/// the editor. Encrypted documents are decrypted first.
+fn print(files: impl Iterator<Item = PathBuf>, config: &Config) -> Result<(), EditorError> {
+    let mut stdout = io::stdout().lock();
+    let mut printed = false;
+
+    for path in files {
+        let document = load(path, config)?;
+        if document.is_binary() {
+            for line in document.get_lines(1..document.line_count() + 1) {
+                writeln!(stdout, "{}", line)?;
+            }
+        } else {
+            stdout.write_all(document.text().as_bytes())?;
+        }
+        printed = true;
+    }
+
+    if !printed {
+        return Err(EditorError::NoDocument);
+    }
+
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Loads each document and writes it to stdout in an export format.
+fn export(
+    files: impl Iterator<Item = PathBuf>,
+    format: Format,
+    config: &Config,
+) -> Result<(), EditorError> {
+    let mut stdout = io::stdout().lock();
+    let mut exported = false;
+
+    for path in files {
+        let document = load(path, config)?;
+        let tab_width = config.settings(document.filetype()).tab_width;
+        let lines = document.get_lines(1..document.line_count() + 1);
+        let text = export::export(&lines, 1, &document.name(), format, tab_width);
+        stdout.write_all(text.as_bytes())?;
+        exported = true;
+    }
+
+    if !exported {
+        return Err(EditorError::NoDocument);
+    }
+
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Loads the whole of a document, decrypting it if it's encrypted.
+fn load(path: PathBuf, config: &Config) -> Result<Document, EditorError> {
+    match config.encryption(&path) {
+        Some(encryption) => Document::load_encrypted(path, encryption.clone()),
+        None => Document::load(path),
+    }
 }
@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// A position in a file, marked so it can be jumped back to from any file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Mark {
+    pub path: PathBuf,
+    pub row: u32,
+    pub column: u32,
+}
+
+/// The global marks, named `A` to `Z`, kept in a file so they survive restarts.
+#[derive(Debug, Default)]
+pub struct MarkStore {
+    marks: BTreeMap<char, Mark>,
+    path: Option<PathBuf>,
+}
+
+impl MarkStore {
+    /// Constructs an empty store which isn't saved to disk.
+    pub fn new() -> MarkStore {
+        MarkStore::default()
+    }
+
+    /// Loads the store from a JSON file mapping each mark's name to its position. A missing or
+    /// unreadable file is treated as an empty store.
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - The file the store is read from and saved to.
+    pub fn load(path: PathBuf) -> MarkStore {
+        let marks = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        MarkStore {
+            marks,
+            path: Some(path),
+        }
+    }
+
+    pub fn get(&self, name: char) -> Option<&Mark> {
+        self.marks.get(&name)
+    }
+
+    /// The line of a mark, if it's set in a file.
+    pub fn line_in(&self, name: char, file: &Path) -> Option<u32> {
+        self.get(name)
+            .filter(|mark| mark.path == file)
+            .map(|mark| mark.row)
+    }
+
+    /// Sets a mark, moving it if it's already set.
+    pub fn set(&mut self, name: char, mark: Mark) {
+        self.marks.insert(name, mark);
+    }
+
+    /// Removes a mark, returning whether it was set.
+    pub fn remove(&mut self, name: char) -> bool {
+        self.marks.remove(&name).is_some()
+    }
+
+    /// Moves the marks set in a file to the path it was renamed to, or removes them if it was
+    /// deleted.
+    pub fn move_file(&mut self, from: &Path, to: Option<&Path>) {
+        match to {
+            Some(to) => self
+                .marks
+                .values_mut()
+                .filter(|mark| mark.path == from)
+                .for_each(|mark| mark.path = to.to_path_buf()),
+            None => self.marks.retain(|_, mark| mark.path != from),
+        }
+    }
+
+    /// The marks, in order of their names.
+    pub fn iter(&self) -> impl Iterator<Item = (char, &Mark)> {
+        self.marks.iter().map(|(name, mark)| (*name, mark))
+    }
+
+    /// Writes the store to its file, creating the containing directory if needed.
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(&self.marks)?)
+    }
+}
+
+/// Checks that a global mark is named by a single uppercase letter.
+pub fn parse_name(name: &str) -> Result<char, String> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_uppercase() => Ok(c),
+        (None, _) => Err(String::from("No mark entered")),
+        _ => Err(format!("Invalid mark: {} (marks are named A to Z)", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("A", Ok('A');                                                    "letter")]
+    #[test_case("", Err(String::from("No mark entered"));                         "empty")]
+    #[test_case("a", Err(String::from("Invalid mark: a (marks are named A to Z)")); "lowercase")]
+    #[test_case("AB", Err(String::from("Invalid mark: AB (marks are named A to Z)")); "too_long")]
+    fn mark_names(name: &str, expected: Result<char, String>) {
+        assert_eq!(expected, parse_name(name));
+    }
+
+    #[test]
+    fn store_round_trip() {
+        let path = std::env::temp_dir().join("texteditor_mark_store.json");
+        let _ = fs::remove_file(&path);
+        let file = PathBuf::from("/code/main.rs");
+        let mark = Mark {
+            path: file.clone(),
+            row: 12,
+            column: 4,
+        };
+
+        let mut store = MarkStore::load(path.clone());
+        store.set('B', mark.clone());
+        store.set('A', mark.clone());
+        assert!(store.remove('A'));
+        assert!(!store.remove('C'));
+        store.save().unwrap();
+
+        let store = MarkStore::load(path.clone());
+        assert_eq!(Some(&mark), store.get('B'));
+        assert_eq!(Some(12), store.line_in('B', &file));
+        assert_eq!(None, store.line_in('B', Path::new("/code/lib.rs")));
+        assert_eq!(
+            vec!['B'],
+            store.iter().map(|(name, _)| name).collect::<Vec<_>>()
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn store_moves_files() {
+        let mark = |path: &str| Mark {
+            path: PathBuf::from(path),
+            row: 1,
+            column: 0,
+        };
+        let mut store = MarkStore::new();
+        store.set('A', mark("/code/a.rs"));
+        store.set('B', mark("/code/b.rs"));
+
+        store.move_file(Path::new("/code/a.rs"), Some(Path::new("/code/c.rs")));
+        assert_eq!(Some(&mark("/code/c.rs")), store.get('A'));
+        store.move_file(Path::new("/code/b.rs"), None);
+        assert_eq!(None, store.get('B'));
+    }
+}
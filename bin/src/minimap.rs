@@ -0,0 +1,138 @@
+use std::ops::Range;
+
+use console::{style, Color};
+
+use crate::layout;
+
+/// The number of cells taken up by the minimap, including the space separating it from the text.
+pub const WIDTH: usize = 11;
+/// The number of document columns shown by each cell of the minimap.
+const COLUMNS_PER_CELL: usize = 8;
+/// The characters drawn for increasingly dense parts of the document.
+const SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+
+/// What a document's minimap was drawn from, which tells when it needs to be drawn again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    /// The number of edits made to the document.
+    pub edits: u64,
+    /// The first line in the viewport.
+    pub top: u32,
+    pub height: u32,
+    pub tab_width: usize,
+}
+
+/// The number of document lines shown by each row of the minimap, so the whole document fits.
+///
+/// # Arguments
+///
+/// * 'line_count' - The number of lines in the document.
+/// * 'height' - The number of rows available to draw the minimap in.
+pub fn lines_per_row(line_count: u32, height: usize) -> u32 {
+    line_count.div_ceil(height.max(1) as u32).max(1)
+}
+
+/// The first document line shown by a row of the minimap.
+///
+/// # Arguments
+///
+/// * 'row' - The row of the minimap. Rows start from 0.
+/// * 'line_count' - The number of lines in the document.
+/// * 'height' - The number of rows available to draw the minimap in.
+pub fn line_at(row: usize, line_count: u32, height: usize) -> u32 {
+    let line = row as u32 * lines_per_row(line_count, height) + 1;
+    line.min(line_count.max(1))
+}
+
+/// Draws a compressed overview of a document, one row per group of lines, shading each cell by
+/// how much of that part of the document is text. The rows covering the viewport are
+/// highlighted.
+///
+/// # Arguments
+///
+/// * 'lines' - Every line of the document.
+/// * 'height' - The number of rows available to draw the minimap in.
+/// * 'viewport' - The lines visible in the viewport. Line numbers start from 1.
+/// * 'tab_width' - The number of cells between tab stops.
+pub fn render(
+    lines: &[String],
+    height: usize,
+    viewport: Range<u32>,
+    tab_width: usize,
+) -> Vec<String> {
+    let per_row = lines_per_row(lines.len() as u32, height) as usize;
+    let cells = WIDTH - 1;
+
+    lines
+        .chunks(per_row)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let mut filled = vec![0; cells];
+            for line in chunk {
+                let line = layout::render_line(line, cells * COLUMNS_PER_CELL, tab_width);
+                for (i, c) in line.chars().enumerate() {
+                    if !c.is_whitespace() {
+                        filled[i / COLUMNS_PER_CELL] += 1;
+                    }
+                }
+            }
+
+            let capacity = chunk.len() * COLUMNS_PER_CELL;
+            let shades: String = filled
+                .iter()
+                .map(|&count| match count {
+                    0 => SHADES[0],
+                    _ => SHADES[(1 + count * 3 / capacity).min(SHADES.len() - 1)],
+                })
+                .collect();
+
+            let first = (row * per_row) as u32 + 1;
+            let shown = first < viewport.end && viewport.start < first + chunk.len() as u32;
+            let shades = match shown {
+                true => style(shades).fg(Color::White).bg(Color::Color256(238)),
+                false => style(shades).fg(Color::Color256(244)),
+            };
+            format!(" {}", shades.force_styling(true))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(10, 20, 1;   "fits")]
+    #[test_case(100, 20, 5;  "exact")]
+    #[test_case(101, 20, 6;  "rounds_up")]
+    #[test_case(0, 20, 1;    "empty")]
+    fn lines_shown_per_row(line_count: u32, height: usize, expected: u32) {
+        assert_eq!(expected, lines_per_row(line_count, height));
+    }
+
+    #[test_case(0, 100, 1;   "first_row")]
+    #[test_case(3, 100, 16;  "later_row")]
+    #[test_case(30, 100, 100; "past_end")]
+    fn first_line_of_row(row: usize, line_count: u32, expected: u32) {
+        assert_eq!(expected, line_at(row, line_count, 20));
+    }
+
+    #[test]
+    fn render_shades_and_viewport() {
+        let lines = vec![
+            "x".repeat(16),
+            String::from("        ab"),
+            String::new(),
+            String::new(),
+        ];
+        let rows = render(&lines, 2, 3..5, 4);
+
+        assert_eq!(2, rows.len());
+        assert_eq!(
+            " \x1b[38;5;244m▒▒        \x1b[0m", rows[0],
+            "two lines per row, outside the viewport"
+        );
+        assert_eq!(" \x1b[37m\x1b[48;5;238m          \x1b[0m", rows[1]);
+    }
+}
@@ -0,0 +1,291 @@
+use console::{measure_text_width, pad_str, style, truncate_str, Alignment, AnsiCodeIterator};
+use unicode_width::UnicodeWidthChar;
+
+/// Where an overlay is placed over the text area.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Placement {
+    /// Across the whole width of the bottom of the text area.
+    Bottom,
+    /// In the middle of the text area, as wide as its contents.
+    Center,
+    /// Just below a cell of the text area, such as the cursor's, or just above it if there's
+    /// more room there. Rows and columns start from 0.
+    Below { row: usize, column: usize },
+}
+
+/// A popup drawn in a box over the text area, such as a picker or a question. Boxes which
+/// float over the text cast a shadow.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overlay {
+    /// The title drawn in the top of the border, if it isn't empty.
+    pub title: String,
+    /// The rows inside the box, which may be styled.
+    pub lines: Vec<String>,
+    pub placement: Placement,
+}
+
+/// Text drawn over a row of the text area, starting from a column.
+#[derive(Debug, PartialEq)]
+struct Segment {
+    row: usize,
+    column: usize,
+    text: String,
+}
+
+impl Overlay {
+    pub fn new(title: &str, lines: Vec<String>, placement: Placement) -> Overlay {
+        Overlay {
+            title: title.to_string(),
+            lines,
+            placement,
+        }
+    }
+
+    /// The number of rows inside the box which fit in the text area, and the row the box
+    /// starts on.
+    fn rows(&self, height: usize, shadow: usize) -> (usize, usize) {
+        let wanted = self.lines.len();
+        match self.placement {
+            Placement::Bottom => {
+                let rows = wanted.min(height.saturating_sub(2));
+                (rows, height.saturating_sub(rows + 2))
+            }
+            Placement::Center => {
+                let rows = wanted.min(height.saturating_sub(2 + shadow));
+                (rows, height.saturating_sub(rows + 2 + shadow) / 2)
+            }
+            Placement::Below { row, .. } => {
+                let below = height.saturating_sub(row + 1 + shadow);
+                let above = row;
+                match below >= wanted + 2 || below >= above {
+                    true => (wanted.min(below.saturating_sub(2)), row + 1),
+                    false => {
+                        let rows = wanted.min(above.saturating_sub(2));
+                        (rows, row - (rows + 2))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lays out the rows of the box, with its border and any shadow, to draw over a text area.
+    ///
+    /// # Arguments
+    ///
+    /// * 'width' - The number of cells across the text area.
+    /// * 'height' - The number of rows in the text area.
+    fn segments(&self, width: usize, height: usize) -> Vec<Segment> {
+        let shadow = match self.placement {
+            Placement::Bottom => 0,
+            Placement::Center | Placement::Below { .. } => 1,
+        };
+        if width < 4 + shadow || height < 3 + shadow {
+            return vec![];
+        }
+        let widest = self
+            .lines
+            .iter()
+            .map(|line| measure_text_width(line))
+            .chain(std::iter::once(measure_text_width(&self.title) + 4))
+            .max()
+            .unwrap_or(0);
+        let inner = match self.placement {
+            Placement::Bottom => width - 2,
+            Placement::Center | Placement::Below { .. } => widest.min(width - 2 - shadow),
+        };
+        let left = match self.placement {
+            Placement::Bottom => 0,
+            Placement::Center => (width - inner - 2 - shadow) / 2,
+            Placement::Below { column, .. } => column.min(width - inner - 2 - shadow),
+        };
+        let (rows, top) = self.rows(height, shadow);
+
+        let title = match self.title.is_empty() {
+            true => String::new(),
+            false => format!(
+                "─ {} ",
+                truncate_str(&self.title, inner.saturating_sub(3), "")
+            ),
+        };
+        let border = |start: &str, text: &str, end: &str| {
+            let fill = "─".repeat(inner.saturating_sub(measure_text_width(text)));
+            format!("{}{}{}{}", start, text, fill, end)
+        };
+        let mut lines = vec![border("┌", &title, "┐")];
+        lines.extend(self.lines[..rows].iter().map(|line| {
+            format!(
+                "│{}\x1b[0m│",
+                pad_str(line, inner, Alignment::Left, Some(""))
+            )
+        }));
+        lines.push(border("└", "", "┘"));
+
+        let mut segments: Vec<Segment> = (top..)
+            .zip(lines)
+            .map(|(row, text)| Segment {
+                row,
+                column: left,
+                text,
+            })
+            .collect();
+        if shadow > 0 {
+            let shade = |cells: usize| {
+                style(" ".repeat(cells))
+                    .on_color256(236)
+                    .force_styling(true)
+                    .to_string()
+            };
+            segments.extend((top + 1..top + rows + 2).map(|row| Segment {
+                row,
+                column: left + inner + 2,
+                text: shade(1),
+            }));
+            segments.push(Segment {
+                row: top + rows + 2,
+                column: left + 1,
+                text: shade(inner + 2),
+            });
+        }
+        segments
+    }
+}
+
+/// Draws overlays over the rows of the text area, each on top of the ones before it.
+///
+/// # Arguments
+///
+/// * 'rows' - The rows of the text area, which may be styled.
+/// * 'overlays' - The overlays, bottom layer first.
+/// * 'width' - The number of cells across the text area.
+pub fn compose(rows: &mut [String], overlays: &[Overlay], width: usize) {
+    let height = rows.len();
+    for overlay in overlays {
+        for segment in overlay.segments(width, height) {
+            if let Some(row) = rows.get_mut(segment.row) {
+                *row = splice(row, segment.column, &segment.text);
+            }
+        }
+    }
+}
+
+/// Replaces the cells of a styled row from a column with some text, keeping the row's text on
+/// either side along with its styles. A wide character cut by the text is replaced by spaces.
+fn splice(row: &str, column: usize, text: &str) -> String {
+    let end = column + measure_text_width(text);
+    let mut left = String::new();
+    let mut right = String::new();
+    // The styles set before the right side, which are set again after the text.
+    let mut codes = String::new();
+    let mut cell = 0;
+
+    for (piece, is_code) in AnsiCodeIterator::new(row) {
+        if is_code {
+            match cell {
+                cell if cell < column => left += piece,
+                cell if cell >= end => right += piece,
+                _ => {}
+            }
+            if cell < end {
+                codes += piece;
+            }
+            continue;
+        }
+        for c in piece.chars() {
+            let width = c.width().unwrap_or(0);
+            if cell + width <= column {
+                left.push(c);
+            } else if cell < column {
+                left += &" ".repeat(column - cell);
+            } else if cell >= end {
+                right.push(c);
+            } else if cell + width > end {
+                right += &" ".repeat(cell + width - end);
+            }
+            cell += width;
+        }
+    }
+    if cell < column {
+        left += &" ".repeat(column - cell);
+    }
+    match right.is_empty() {
+        true => format!("{}\x1b[0m{}\x1b[0m", left, text),
+        false => format!("{}\x1b[0m{}\x1b[0m{}{}", left, text, codes, right),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("abcdef", 2, "XY", "ab\x1b[0mXY\x1b[0mef";        "middle")]
+    #[test_case("ab", 4, "XY", "ab  \x1b[0mXY\x1b[0m";            "past_the_end")]
+    #[test_case("a漢b", 2, "X", "a \x1b[0mX\x1b[0mb";             "cutting_a_wide_character")]
+    #[test_case("a\x1b[1mbcd\x1b[0m", 2, "X", "a\x1b[1mb\x1b[0mX\x1b[0m\x1b[1md\x1b[0m"; "keeping_styles")]
+    fn splices(row: &str, column: usize, text: &str, expected: &str) {
+        assert_eq!(expected, splice(row, column, text));
+    }
+
+    fn plain(rows: &[String]) -> Vec<String> {
+        rows.iter()
+            .map(|row| console::strip_ansi_codes(row).trim_end().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn bottom_overlay() {
+        let mut rows = vec![String::from("text"); 5];
+        let overlay = Overlay::new("Pick", vec![String::from("one")], Placement::Bottom);
+        compose(&mut rows, &[overlay], 12);
+        assert_eq!(
+            vec![
+                "text",
+                "text",
+                "┌─ Pick ───┐",
+                "│one       │",
+                "└──────────┘"
+            ],
+            plain(&rows)
+        );
+    }
+
+    #[test]
+    fn centered_overlay_with_shadow() {
+        let mut rows = vec![String::from("..........."); 6];
+        let overlay = Overlay::new("", vec![String::from("Sure?")], Placement::Center);
+        compose(&mut rows, &[overlay], 11);
+        assert_eq!(
+            vec![
+                "...........",
+                ".┌─────┐...",
+                ".│Sure?│ ..",
+                ".└─────┘ ..",
+                "..       ..",
+                "...........",
+            ],
+            plain(&rows)
+        );
+    }
+
+    #[test_case(1, 2;   "below")]
+    #[test_case(4, 0;   "above")]
+    fn overlay_by_a_cell(row: usize, top: usize) {
+        let overlay = Overlay::new(
+            "",
+            vec![String::from("a"), String::from("b")],
+            Placement::Below { row, column: 3 },
+        );
+        let segments = overlay.segments(20, 7);
+        assert_eq!((top, 3), (segments[0].row, segments[0].column));
+    }
+
+    #[test]
+    fn later_overlays_are_on_top() {
+        let mut rows = vec![String::new(); 5];
+        let under = Overlay::new("", vec![String::from("under")], Placement::Bottom);
+        let over = Overlay::new("", vec![String::from("x")], Placement::Bottom);
+        compose(&mut rows, &[under, over], 9);
+        assert_eq!("│x      │", plain(&rows)[3]);
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use log::info;
+
+/// The number of recent frames kept to average over.
+const FRAMES: usize = 60;
+
+/// How long each stage of one iteration of the event loop took.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Frame {
+    /// The number of events handled.
+    pub event_count: usize,
+    /// Handling the events, including any edits they made.
+    pub events: Duration,
+    /// Loading documents, in the background and the lines about to be drawn.
+    pub buffer: Duration,
+    /// Laying out the screen.
+    pub render: Duration,
+    /// Writing the screen to the terminal.
+    pub flush: Duration,
+}
+
+impl Frame {
+    pub fn total(&self) -> Duration {
+        self.events + self.buffer + self.render + self.flush
+    }
+
+    /// Writes the frame's timings to the log as a structured record, in microseconds.
+    pub fn log(&self) {
+        info!(
+            target: "perf",
+            event_count = self.event_count,
+            events_us = self.events.as_micros() as u64,
+            buffer_us = self.buffer.as_micros() as u64,
+            render_us = self.render.as_micros() as u64,
+            flush_us = self.flush.as_micros() as u64,
+            total_us = self.total().as_micros() as u64;
+            "frame"
+        );
+    }
+}
+
+/// The timings of recent frames.
+#[derive(Debug, Default)]
+pub struct Perf {
+    frames: VecDeque<Frame>,
+}
+
+impl Perf {
+    pub fn new() -> Perf {
+        Perf::default()
+    }
+
+    /// Records a frame's timings, dropping the oldest frame once enough are kept.
+    pub fn record(&mut self, frame: Frame) {
+        if self.frames.len() == FRAMES {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    /// Describes the last frame's timings, along with the average and slowest of the recent
+    /// frames.
+    pub fn summary(&self) -> String {
+        let Some(last) = self.frames.back() else {
+            return String::from("No frames");
+        };
+        let totals = self.frames.iter().map(Frame::total);
+        let average = totals.clone().sum::<Duration>() / self.frames.len() as u32;
+        let slowest = totals.max().unwrap_or_default();
+
+        format!(
+            "events {} buffer {} render {} flush {} | avg {} max {}",
+            millis(last.events),
+            millis(last.buffer),
+            millis(last.render),
+            millis(last.flush),
+            millis(average),
+            millis(slowest)
+        )
+    }
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(micros: u64) -> Frame {
+        Frame {
+            event_count: 1,
+            events: Duration::from_micros(micros),
+            buffer: Duration::ZERO,
+            render: Duration::from_micros(micros * 2),
+            flush: Duration::from_micros(micros),
+        }
+    }
+
+    #[test]
+    fn summary_of_recent_frames() {
+        let mut perf = Perf::new();
+        assert_eq!("No frames", perf.summary());
+
+        perf.record(frame(500));
+        perf.record(frame(100));
+        assert_eq!(
+            "events 0.10ms buffer 0.00ms render 0.20ms flush 0.10ms | avg 1.20ms max 2.00ms",
+            perf.summary()
+        );
+    }
+
+    #[test]
+    fn oldest_frames_dropped() {
+        let mut perf = Perf::new();
+        perf.record(frame(1000));
+        for _ in 0..FRAMES {
+            perf.record(frame(10));
+        }
+        assert!(perf.summary().ends_with("avg 0.04ms max 0.04ms"));
+    }
+}
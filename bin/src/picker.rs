@@ -0,0 +1,160 @@
+/// A list of items to choose from, narrowed down by typing a query. An item matches when it
+/// contains every word of the query, ignoring case.
+#[derive(Debug)]
+pub struct Picker {
+    title: String,
+    items: Vec<String>,
+    query: String,
+    /// The indices of the items which match the query, in their original order.
+    matches: Vec<usize>,
+    /// The position of the highlighted item within the matches.
+    selected: usize,
+}
+
+impl Picker {
+    /// # Arguments
+    ///
+    /// * 'title' - Shown before the query, describing what's being picked.
+    /// * 'items' - The text shown for each item.
+    pub fn new(title: &str, items: Vec<String>) -> Picker {
+        Picker {
+            title: title.to_string(),
+            matches: (0..items.len()).collect(),
+            items,
+            query: String::new(),
+            selected: 0,
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.filter();
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.filter();
+    }
+
+    /// Highlights the next matching item, wrapping around to the first.
+    pub fn select_next(&mut self) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected + 1) % self.matches.len();
+        }
+    }
+
+    /// Highlights the previous matching item, wrapping around to the last.
+    pub fn select_previous(&mut self) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected + self.matches.len() - 1) % self.matches.len();
+        }
+    }
+
+    /// Highlights an item, if it matches the query.
+    ///
+    /// # Arguments
+    ///
+    /// * 'item' - The index of the item in the original list.
+    pub fn select(&mut self, item: usize) {
+        if let Some(i) = self.matches.iter().position(|m| *m == item) {
+            self.selected = i;
+        }
+    }
+
+    /// The index of the highlighted item in the original list, or None if nothing matches.
+    pub fn selected(&self) -> Option<usize> {
+        self.matches.get(self.selected).copied()
+    }
+
+    /// The matching items which fit in a number of rows, scrolled so the highlighted item is
+    /// visible, along with whether each one is highlighted.
+    pub fn visible(&self, rows: usize) -> Vec<(&str, bool)> {
+        let start = (self.selected + 1).saturating_sub(rows);
+        self.matches
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(rows)
+            .map(|(i, item)| (self.items[*item].as_str(), i == self.selected))
+            .collect()
+    }
+
+    fn filter(&mut self) {
+        let words: Vec<String> = self
+            .query
+            .split_whitespace()
+            .map(|word| word.to_lowercase())
+            .collect();
+        self.matches = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| {
+                let item = item.to_lowercase();
+                words.iter().all(|word| item.contains(word))
+            })
+            .map(|(i, _)| i)
+            .collect();
+        self.selected = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn picker() -> Picker {
+        let items = ["Lorem ipsum", "dolor sit", "amet lorem", "consectetur"];
+        Picker::new("Test", items.iter().map(|i| i.to_string()).collect())
+    }
+
+    #[test_case("", Some(0);         "empty_query")]
+    #[test_case("LOREM", Some(0);    "ignores_case")]
+    #[test_case("lorem amet", Some(2); "all_words")]
+    #[test_case("nothing", None;     "no_match")]
+    fn query_selects_first_match(query: &str, expected: Option<usize>) {
+        let mut picker = picker();
+        query.chars().for_each(|c| picker.push(c));
+        assert_eq!(expected, picker.selected());
+    }
+
+    #[test]
+    fn selection_wraps_around_matches() {
+        let mut picker = picker();
+        "lorem".chars().for_each(|c| picker.push(c));
+        picker.select_next();
+        assert_eq!(Some(2), picker.selected());
+        picker.select_next();
+        assert_eq!(Some(0), picker.selected());
+        picker.select_previous();
+        assert_eq!(Some(2), picker.selected());
+    }
+
+    #[test]
+    fn select_item() {
+        let mut picker = picker();
+        picker.select(3);
+        assert_eq!(Some(3), picker.selected());
+        "lorem".chars().for_each(|c| picker.push(c));
+        picker.select(1);
+        assert_eq!(Some(0), picker.selected());
+    }
+
+    #[test]
+    fn visible_items_scroll_to_selection() {
+        let mut picker = picker();
+        picker.select_previous();
+        let expected = vec![("amet lorem", false), ("consectetur", true)];
+        assert_eq!(expected, picker.visible(2));
+    }
+}
@@ -0,0 +1,51 @@
+use std::path::{Path, PathBuf};
+
+/// Finds the root directory of the project a file is in, which is the closest directory above
+/// the file holding one of the markers. Markers are tried in order, so a marker earlier in the
+/// list wins even if a later one is closer, such as a repository's `.git` over a crate's
+/// `Cargo.toml` inside it.
+///
+/// # Arguments
+///
+/// * 'path' - The path of a file in the project, which doesn't need to exist.
+/// * 'markers' - The names of files or directories which mark a project's root.
+pub fn root(path: &Path, markers: &[String]) -> Option<PathBuf> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let dir = dir.canonicalize().ok()?;
+    markers.iter().find_map(|marker| {
+        dir.ancestors()
+            .find(|ancestor| ancestor.join(marker).exists())
+            .map(Path::to_path_buf)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn markers(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn root_prefers_earlier_markers() {
+        let dir = std::env::temp_dir().join("texteditor_project");
+        let member = dir.join("member");
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::create_dir_all(member.join("src")).unwrap();
+        fs::write(member.join("Cargo.toml"), "").unwrap();
+        let (dir, member) = (dir.canonicalize().unwrap(), member.canonicalize().unwrap());
+        let file = member.join("src").join("main.rs");
+
+        let repository = markers(&[".git", "Cargo.toml"]);
+        assert_eq!(Some(dir), root(&file, &repository));
+        let krate = markers(&["Cargo.toml", ".git"]);
+        assert_eq!(Some(member), root(&file, &krate));
+        assert_eq!(None, root(&file, &markers(&["texteditor_missing_marker"])));
+    }
+}
@@ -0,0 +1,209 @@
+use crate::command::Command;
+use crate::completion::expand_home;
+
+/// What a prompt asks for, which decides how its input is checked, previewed and run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    /// A command, entered after `:`.
+    Command,
+    /// A line to go to, which is jumped to while it's typed.
+    Goto,
+    /// Text to find, whose next match is jumped to while it's typed.
+    Search,
+    /// The path to save the document to, overwriting read-only files if forced.
+    SaveAs { force: bool },
+}
+
+/// Text being entered on the status line, such as a command or a line to go to.
+#[derive(Debug)]
+pub struct Prompt {
+    pub kind: Kind,
+    input: String,
+    /// The text used if the input is left empty, shown in the prompt's label.
+    default: Option<String>,
+}
+
+impl Prompt {
+    pub fn new(kind: Kind) -> Prompt {
+        Prompt::with_input(kind, "")
+    }
+
+    /// Constructs a prompt with some input already entered.
+    pub fn with_input(kind: Kind, input: &str) -> Prompt {
+        Prompt {
+            kind,
+            input: input.to_string(),
+            default: None,
+        }
+    }
+
+    /// Sets the text used if the input is left empty. An empty default is ignored.
+    pub fn with_default(mut self, default: Option<String>) -> Prompt {
+        self.default = default.filter(|d| !d.is_empty());
+        self
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn set_input(&mut self, input: &str) {
+        self.input = input.to_string();
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.input.push(c);
+    }
+
+    pub fn push_str(&mut self, text: &str) {
+        self.input.push_str(text);
+    }
+
+    pub fn pop(&mut self) {
+        self.input.pop();
+    }
+
+    /// The text before the input, naming what's being asked for along with any default.
+    pub fn label(&self) -> String {
+        let name = match self.kind {
+            Kind::Command => return String::from(":"),
+            Kind::Goto => "Go to line",
+            Kind::Search => "Find",
+            Kind::SaveAs { .. } => "Save as",
+        };
+        match &self.default {
+            Some(default) => format!("{} [{}]: ", name, default),
+            None => format!("{}: ", name),
+        }
+    }
+
+    /// The input, or the default if no input has been entered.
+    pub fn value(&self) -> &str {
+        match (&self.default, self.input.is_empty()) {
+            (Some(default), true) => default,
+            _ => &self.input,
+        }
+    }
+
+    /// Checks the input can be submitted, returning why not if it can't. Commands are only
+    /// checked when they're run.
+    pub fn validate(&self) -> Result<(), String> {
+        let value = self.value();
+        match self.kind {
+            Kind::Command => Ok(()),
+            Kind::Goto if value.is_empty() => Err(String::from("No line entered")),
+            Kind::Goto => match value.parse::<u32>() {
+                Ok(line) if line > 0 => Ok(()),
+                _ => Err(format!("Not a line number: {}", value)),
+            },
+            Kind::Search if value.is_empty() => Err(String::from("No search text entered")),
+            Kind::SaveAs { .. } if value.trim().is_empty() => {
+                Err(String::from("No file name entered"))
+            }
+            Kind::Search | Kind::SaveAs { .. } => Ok(()),
+        }
+    }
+
+    /// The command run when the prompt is submitted.
+    pub fn command(&self) -> Result<Command, String> {
+        self.validate()?;
+        let value = self.value();
+        match self.kind {
+            Kind::Command => Command::parse(value),
+            Kind::Goto => value
+                .parse()
+                .map(Command::Goto)
+                .map_err(|_| format!("Not a line number: {}", value)),
+            Kind::Search => Ok(Command::Find {
+                pattern: value.to_string(),
+                forward: true,
+            }),
+            Kind::SaveAs { force } => Ok(Command::Write {
+                path: Some(expand_home(value.trim())),
+                force,
+            }),
+        }
+    }
+
+    /// Where the file path being entered starts in the input, for prompts which take one:
+    /// commands which open or save a file, and Save As.
+    pub fn path_start(&self) -> Option<usize> {
+        match self.kind {
+            Kind::Command => {
+                let (name, argument) = self.input.split_once(' ')?;
+                let command = name.trim_start_matches(':').trim_end_matches('!');
+                matches!(command, "e" | "edit" | "w" | "write")
+                    .then(|| self.input.len() - argument.trim_start().len())
+            }
+            Kind::SaveAs { .. } => Some(0),
+            Kind::Goto | Kind::Search => None,
+        }
+    }
+
+    /// The file path being entered, for prompts which take one.
+    pub fn path(&self) -> Option<&str> {
+        self.path_start().map(|start| &self.input[start..])
+    }
+
+    /// Replaces the file path being entered, such as with a completion of it.
+    pub fn set_path(&mut self, path: &str) {
+        if let Some(start) = self.path_start() {
+            self.input.replace_range(start.., path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(Kind::Command, None, ":";                       "command")]
+    #[test_case(Kind::Goto, None, "Go to line: ";               "goto")]
+    #[test_case(Kind::Search, Some("fn"), "Find [fn]: ";        "search_with_default")]
+    #[test_case(Kind::SaveAs { force: false }, Some(""), "Save as: "; "empty_default")]
+    fn labels(kind: Kind, default: Option<&str>, expected: &str) {
+        let prompt = Prompt::new(kind).with_default(default.map(String::from));
+        assert_eq!(expected, prompt.label());
+    }
+
+    #[test_case(Kind::Goto, "12", Ok(());                                        "line")]
+    #[test_case(Kind::Goto, "12a", Err(String::from("Not a line number: 12a"));  "not_a_number")]
+    #[test_case(Kind::Goto, "0", Err(String::from("Not a line number: 0"));      "line_zero")]
+    #[test_case(Kind::Goto, "", Err(String::from("No line entered"));            "empty")]
+    #[test_case(Kind::Search, "", Err(String::from("No search text entered"));   "empty_search")]
+    #[test_case(Kind::SaveAs { force: false }, " ", Err(String::from("No file name entered")); "blank_path")]
+    #[test_case(Kind::Command, "nothing", Ok(());                                "command")]
+    fn validation(kind: Kind, input: &str, expected: Result<(), String>) {
+        assert_eq!(expected, Prompt::with_input(kind, input).validate());
+    }
+
+    #[test_case(Kind::Goto, "", Some("7"), Ok(Command::Goto(7));                  "goto_default")]
+    #[test_case(Kind::Goto, "12", Some("7"), Ok(Command::Goto(12));               "goto_input")]
+    #[test_case(Kind::Search, "a b", None, Ok(Command::Find { pattern: String::from("a b"), forward: true }); "search")]
+    #[test_case(Kind::SaveAs { force: true }, "x.rs", None, Ok(Command::Write { path: Some(PathBuf::from("x.rs")), force: true }); "save_as")]
+    #[test_case(Kind::Command, "new", None, Ok(Command::New);                     "command")]
+    fn commands(kind: Kind, input: &str, default: Option<&str>, expected: Result<Command, String>) {
+        let prompt = Prompt::with_input(kind, input).with_default(default.map(String::from));
+        assert_eq!(expected, prompt.command());
+    }
+
+    #[test_case(Kind::Command, "e  src/ma", Some("src/ma");   "edit")]
+    #[test_case(Kind::Command, "w! out", Some("out");         "forced_write")]
+    #[test_case(Kind::Command, "sort n", None;                "other_command")]
+    #[test_case(Kind::SaveAs { force: false }, "a/b", Some("a/b"); "save_as")]
+    #[test_case(Kind::Search, "a/b", None;                    "search")]
+    fn paths(kind: Kind, input: &str, expected: Option<&str>) {
+        assert_eq!(expected, Prompt::with_input(kind, input).path());
+    }
+
+    #[test]
+    fn set_path() {
+        let mut prompt = Prompt::with_input(Kind::Command, "e  src/ma");
+        prompt.set_path("src/main.rs");
+        assert_eq!("e  src/main.rs", prompt.input());
+    }
+}
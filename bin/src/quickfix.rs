@@ -0,0 +1,243 @@
+use std::path::{Path, PathBuf};
+
+/// A location reported by a compiler or linter. Lines and columns start from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub line: u32,
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+/// The locations found in the output of the last build, along with the one last jumped to.
+#[derive(Debug, Default)]
+pub struct QuickFix {
+    entries: Vec<Entry>,
+    current: Option<usize>,
+    /// The directory the build was run from, which relative paths are resolved against.
+    dir: PathBuf,
+}
+
+impl QuickFix {
+    pub fn new(entries: Vec<Entry>, dir: PathBuf) -> QuickFix {
+        QuickFix {
+            entries,
+            current: None,
+            dir,
+        }
+    }
+
+    /// The path of an entry's file, resolved against the directory the build was run from.
+    pub fn path(&self, entry: &Entry) -> PathBuf {
+        self.dir.join(&entry.path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The position of the current entry in the list, starting from 0.
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Moves on to the next entry, staying on the last entry once it's reached.
+    pub fn next(&mut self) -> Option<&Entry> {
+        let next = self.current.map_or(0, |i| i + 1);
+        if next >= self.entries.len() {
+            return None;
+        }
+        self.current = Some(next);
+        self.entries.get(next)
+    }
+
+    /// Moves back to the previous entry, staying on the first entry once it's reached.
+    pub fn previous(&mut self) -> Option<&Entry> {
+        let previous = self.current?.checked_sub(1)?;
+        self.current = Some(previous);
+        self.entries.get(previous)
+    }
+
+    /// The lines with entries in a document. Entries are usually reported with paths relative
+    /// to where the build was run, so they're resolved against it to match the document's path.
+    pub fn lines_in(&self, path: &Path) -> Vec<u32> {
+        self.entries_in(path).map(|entry| entry.line).collect()
+    }
+
+    /// The entries in a document, matched in the same way as `lines_in`.
+    pub fn entries_in<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = &'a Entry> {
+        self.entries
+            .iter()
+            .filter(move |entry| self.path(entry) == path)
+    }
+
+    /// The entries as text, one per line in `file:line:column: message` form.
+    pub fn list(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| {
+                let column = entry.column.map_or(String::new(), |c| format!(":{}", c));
+                format!(
+                    "{}:{}{}: {}\n",
+                    entry.path.display(),
+                    entry.line,
+                    column,
+                    entry.message
+                )
+            })
+            .collect()
+    }
+}
+
+/// Finds the locations in a build command's output. Lines in the form `file:line: message` or
+/// `file:line:column: message` are used, along with rustc's `--> file:line:column` lines, which
+/// take their message from the preceding `error:` or `warning:` line.
+pub fn parse(output: &str) -> Vec<Entry> {
+    let mut entries = vec![];
+    let mut heading = None;
+
+    for line in output.lines() {
+        if line.starts_with("error") || line.starts_with("warning") {
+            if let Some((_, message)) = line.split_once(": ") {
+                heading = Some(message.trim().to_string());
+            }
+        }
+
+        let Some(mut entry) = parse_line(line) else {
+            continue;
+        };
+        if entry.message.is_empty() {
+            entry.message = heading.take().unwrap_or_default();
+        }
+        entries.push(entry);
+    }
+
+    entries
+}
+
+fn parse_line(line: &str) -> Option<Entry> {
+    let trimmed = line.trim_start();
+    let line = trimmed.strip_prefix("--> ").unwrap_or(trimmed);
+
+    for (i, _) in line.match_indices(':') {
+        let path = &line[..i];
+        if path.is_empty() || path.contains(char::is_whitespace) {
+            continue;
+        }
+        let Some((number, rest)) = split_number(&line[i + 1..]) else {
+            continue;
+        };
+        let (column, rest) = match rest.strip_prefix(':').and_then(split_number) {
+            Some((column, rest)) => (Some(column), rest),
+            None => (None, rest),
+        };
+        let message = match rest.strip_prefix(':') {
+            Some(message) => message.trim(),
+            None if rest.trim().is_empty() => "",
+            None => continue,
+        };
+
+        return Some(Entry {
+            path: PathBuf::from(path),
+            line: number,
+            column,
+            message: message.to_string(),
+        });
+    }
+    None
+}
+
+/// Splits the number from the start of some text.
+fn split_number(text: &str) -> Option<(u32, &str)> {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let number = text[..end].parse().ok()?;
+    Some((number, &text[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn entry(path: &str, line: u32, column: Option<u32>, message: &str) -> Entry {
+        Entry {
+            path: PathBuf::from(path),
+            line,
+            column,
+            message: message.to_string(),
+        }
+    }
+
+    #[test_case("src/main.c:12:5: error: expected ';'", Some(entry("src/main.c", 12, Some(5), "error: expected ';'")); "with_column")]
+    #[test_case("lib.py:3: E302 expected 2 blank lines", Some(entry("lib.py", 3, None, "E302 expected 2 blank lines")); "without_column")]
+    #[test_case("make: *** [Makefile:4: all] Error 1", None;                  "path_with_spaces")]
+    #[test_case("note: see https://example.com:8080", None;                  "url")]
+    #[test_case("Compiling texteditor v0.1.0", None;                         "no_location")]
+    fn parse_single_line(line: &str, expected: Option<Entry>) {
+        assert_eq!(expected, parse(line).pop());
+    }
+
+    #[test]
+    fn parse_rustc_output() {
+        let output = "error[E0425]: cannot find value `x` in this scope\n \
+                      --> src/main.rs:2:5\n  |\n2 |     x\n  |     ^ not found\n";
+        let expected = vec![entry(
+            "src/main.rs",
+            2,
+            Some(5),
+            "cannot find value `x` in this scope",
+        )];
+        assert_eq!(expected, parse(output));
+    }
+
+    #[test]
+    fn next_and_previous_stop_at_ends() {
+        let entries = vec![entry("a", 1, None, ""), entry("b", 2, None, "")];
+        let mut quickfix = QuickFix::new(entries, PathBuf::new());
+        assert_eq!(None, quickfix.previous());
+        assert_eq!(Some(1), quickfix.next().map(|e| e.line));
+        assert_eq!(Some(2), quickfix.next().map(|e| e.line));
+        assert_eq!(None, quickfix.next());
+        assert_eq!(Some(1), quickfix.previous().map(|e| e.line));
+        assert_eq!(Some(0), quickfix.current());
+    }
+
+    #[test]
+    fn lines_in_document() {
+        let quickfix = QuickFix::new(
+            vec![
+                entry("src/main.rs", 3, None, ""),
+                entry("src/lib.rs", 4, None, ""),
+                entry("src/main.rs", 9, Some(1), ""),
+            ],
+            PathBuf::from("/code"),
+        );
+        assert_eq!(
+            vec![3, 9],
+            quickfix.lines_in(Path::new("/code/src/main.rs"))
+        );
+        assert!(quickfix.lines_in(Path::new("/code/main.rs")).is_empty());
+        // A file with the same relative path in another directory isn't the one reported.
+        assert!(quickfix
+            .lines_in(Path::new("/other/src/main.rs"))
+            .is_empty());
+    }
+
+    #[test]
+    fn list_entries() {
+        let quickfix = QuickFix::new(vec![entry("a.rs", 1, Some(2), "oops")], PathBuf::new());
+        assert_eq!("a.rs:1:2: oops\n", quickfix.list());
+    }
+
+    #[test]
+    fn paths_resolve_against_build_directory() {
+        let quickfix = QuickFix::new(vec![], PathBuf::from("/code"));
+        let relative = entry("src/main.rs", 1, None, "");
+        assert_eq!(PathBuf::from("/code/src/main.rs"), quickfix.path(&relative));
+        let absolute = entry("/tmp/a.rs", 1, None, "");
+        assert_eq!(PathBuf::from("/tmp/a.rs"), quickfix.path(&absolute));
+    }
+}
@@ -0,0 +1,211 @@
+use std::ops::RangeInclusive;
+
+/// A line given in a command's range, counted from a starting point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Address {
+    /// A line number, such as `10`.
+    Line(u32),
+    /// The cursor's line, `.`, which is also used when a range starts with an offset.
+    Current,
+    /// The last line of the document, `$`.
+    Last,
+    /// The line of a mark, such as `'<` for the start of the selection.
+    Mark(char),
+}
+
+/// A line in a range: an address followed by any number of offsets, such as `.+2` or `$-1`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineSpec {
+    pub address: Address,
+    pub offset: i64,
+}
+
+impl LineSpec {
+    pub fn new(address: Address) -> LineSpec {
+        LineSpec { address, offset: 0 }
+    }
+
+    /// Works out the line number.
+    ///
+    /// # Arguments
+    ///
+    /// * 'current' - The cursor's line.
+    /// * 'last' - The last line of the document.
+    /// * 'mark' - The line of a mark, if it's set.
+    fn resolve(
+        &self,
+        current: u32,
+        last: u32,
+        mark: &impl Fn(char) -> Option<u32>,
+    ) -> Result<u32, String> {
+        let line = match self.address {
+            Address::Line(line) => line,
+            Address::Current => current,
+            Address::Last => last,
+            Address::Mark(name) => mark(name).ok_or_else(|| format!("Mark not set: {}", name))?,
+        };
+        match line as i64 + self.offset {
+            line if (1..=last as i64).contains(&line) => Ok(line as u32),
+            line => Err(format!("Invalid range: line {} doesn't exist", line)),
+        }
+    }
+}
+
+/// The lines a command acts on, written before its name, such as `10,20` or `%`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineRange {
+    /// Every line in the document, `%`.
+    All,
+    /// The lines from one line to another. A single line is both the start and the end.
+    Lines(LineSpec, LineSpec),
+}
+
+impl LineRange {
+    /// Works out the first and last lines of the range. A range given backwards is turned
+    /// around.
+    ///
+    /// # Arguments
+    ///
+    /// * 'current' - The cursor's line.
+    /// * 'last' - The last line of the document.
+    /// * 'mark' - The line of a mark, if it's set.
+    pub fn resolve(
+        &self,
+        current: u32,
+        last: u32,
+        mark: impl Fn(char) -> Option<u32>,
+    ) -> Result<RangeInclusive<u32>, String> {
+        match self {
+            LineRange::All => Ok(1..=last.max(1)),
+            LineRange::Lines(start, end) => {
+                let start = start.resolve(current, last, &mark)?;
+                let end = end.resolve(current, last, &mark)?;
+                Ok(start.min(end)..=start.max(end))
+            }
+        }
+    }
+}
+
+/// Parses the range at the start of a command, returning it along with the rest of the command.
+/// The range is None if the command doesn't start with one.
+///
+/// # Arguments
+///
+/// * 'input' - The command entered at the prompt, without the leading ':'.
+pub fn parse(input: &str) -> Result<(Option<LineRange>, &str), String> {
+    if let Some(rest) = input.strip_prefix('%') {
+        return Ok((Some(LineRange::All), rest));
+    }
+    let Some((start, rest)) = parse_spec(input)? else {
+        return Ok((None, input));
+    };
+    let Some(rest) = rest.strip_prefix(',') else {
+        return Ok((Some(LineRange::Lines(start, start)), rest));
+    };
+    match parse_spec(rest)? {
+        Some((end, rest)) => Ok((Some(LineRange::Lines(start, end)), rest)),
+        None => Err(String::from("Invalid range: missing the end")),
+    }
+}
+
+/// Parses a line at the start of some text, returning it along with the rest of the text, or
+/// None if the text doesn't start with one.
+fn parse_spec(input: &str) -> Result<Option<(LineSpec, &str)>, String> {
+    let (address, mut rest) = match input.chars().next() {
+        Some('.') => (Address::Current, &input[1..]),
+        Some('$') => (Address::Last, &input[1..]),
+        Some('\'') => {
+            let mut chars = input[1..].chars();
+            let name = chars
+                .next()
+                .ok_or_else(|| String::from("Invalid range: missing the mark"))?;
+            (Address::Mark(name), chars.as_str())
+        }
+        Some(c) if c.is_ascii_digit() => {
+            let (number, rest) = split_number(input);
+            let line = number
+                .parse()
+                .map_err(|_| format!("Invalid line number: {}", number))?;
+            (Address::Line(line), rest)
+        }
+        Some('+' | '-') => (Address::Current, input),
+        _ => return Ok(None),
+    };
+
+    let mut spec = LineSpec::new(address);
+    while let Some(sign) = rest.chars().next().filter(|c| *c == '+' || *c == '-') {
+        let (number, after) = split_number(&rest[1..]);
+        // An offset without a number moves by one line.
+        let count: i64 = match number {
+            "" => 1,
+            number => number
+                .parse()
+                .map_err(|_| format!("Invalid offset: {}", number))?,
+        };
+        spec.offset += if sign == '+' { count } else { -count };
+        rest = after;
+    }
+    Ok(Some((spec, rest)))
+}
+
+fn split_number(input: &str) -> (&str, &str) {
+    let end = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    input.split_at(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn line(line: u32) -> LineSpec {
+        LineSpec::new(Address::Line(line))
+    }
+
+    fn offset(address: Address, offset: i64) -> LineSpec {
+        LineSpec { address, offset }
+    }
+
+    #[test_case("10,20d", Some(LineRange::Lines(line(10), line(20))), "d"; "absolute")]
+    #[test_case("%s/a/b/", Some(LineRange::All), "s/a/b/"; "whole_document")]
+    #[test_case("'<,'>sort", Some(LineRange::Lines(LineSpec::new(Address::Mark('<')), LineSpec::new(Address::Mark('>')))), "sort"; "selection")]
+    #[test_case(".,$d", Some(LineRange::Lines(LineSpec::new(Address::Current), LineSpec::new(Address::Last))), "d"; "current_to_last")]
+    #[test_case("-2,+3d", Some(LineRange::Lines(offset(Address::Current, -2), offset(Address::Current, 3))), "d"; "relative")]
+    #[test_case("$-1", Some(LineRange::Lines(offset(Address::Last, -1), offset(Address::Last, -1))), ""; "single_line")]
+    #[test_case(".++", Some(LineRange::Lines(offset(Address::Current, 2), offset(Address::Current, 2))), ""; "repeated_offsets")]
+    #[test_case("sort", None, "sort"; "no_range")]
+    fn parse_range(input: &str, expected: Option<LineRange>, rest: &str) {
+        assert_eq!(Ok((expected, rest)), parse(input));
+    }
+
+    #[test_case("5,", "Invalid range: missing the end"; "missing_end")]
+    #[test_case("'", "Invalid range: missing the mark"; "missing_mark")]
+    fn parse_invalid_range(input: &str, expected: &str) {
+        assert_eq!(Err(expected.to_string()), parse(input));
+    }
+
+    fn resolve(input: &str) -> Result<RangeInclusive<u32>, String> {
+        let (range, _) = parse(input)?;
+        let mark = |name| match name {
+            '<' => Some(3),
+            '>' => Some(6),
+            _ => None,
+        };
+        range.unwrap().resolve(5, 10, mark)
+    }
+
+    #[test_case("2,4", Ok(2..=4);                                              "absolute")]
+    #[test_case("4,2", Ok(2..=4);                                              "backwards")]
+    #[test_case("%", Ok(1..=10);                                               "whole_document")]
+    #[test_case(".,$", Ok(5..=10);                                             "current_to_last")]
+    #[test_case("-1,+1", Ok(4..=6);                                            "relative")]
+    #[test_case("'<,'>", Ok(3..=6);                                            "selection")]
+    #[test_case("'a", Err(String::from("Mark not set: a"));                    "unset_mark")]
+    #[test_case("$+1", Err(String::from("Invalid range: line 11 doesn't exist")); "past_end")]
+    fn resolve_range(input: &str, expected: Result<RangeInclusive<u32>, String>) {
+        assert_eq!(expected, resolve(input));
+    }
+}
@@ -0,0 +1,114 @@
+use crate::layout;
+
+/// Re-wraps paragraphs so each line fits within a width, moving words between lines. Paragraphs
+/// are separated by blank lines or by a change in prefix, and are wrapped separately. Each line
+/// keeps the prefix of the paragraph's first line: its indentation and comment prefix. Words
+/// wider than the width are left on a line of their own.
+///
+/// # Arguments
+///
+/// * 'lines' - The lines to wrap.
+/// * 'width' - The number of terminal cells each line should fit in.
+/// * 'comment_prefix' - The prefix used for line comments, if the filetype has them.
+/// * 'tab_width' - The number of cells between tab stops.
+pub fn reflow(
+    lines: &[String],
+    width: usize,
+    comment_prefix: Option<&str>,
+    tab_width: usize,
+) -> Vec<String> {
+    let mut reflowed = vec![];
+    let mut paragraph: Option<(&str, Vec<&str>)> = None;
+
+    for line in lines {
+        let (prefix, text) = split_prefix(line, comment_prefix);
+        if text.is_empty() || paragraph.as_ref().is_some_and(|(p, _)| *p != prefix) {
+            if let Some((prefix, words)) = paragraph.take() {
+                reflowed.extend(wrap(prefix, &words, width, tab_width));
+            }
+        }
+        if text.is_empty() {
+            reflowed.push(line.clone());
+            continue;
+        }
+
+        paragraph
+            .get_or_insert_with(|| (prefix, vec![]))
+            .1
+            .extend(text.split_whitespace());
+    }
+    if let Some((prefix, words)) = paragraph {
+        reflowed.extend(wrap(prefix, &words, width, tab_width));
+    }
+
+    reflowed
+}
+
+/// Splits a line into its prefix, made up of indentation and any comment prefix with the
+/// whitespace after it, and the rest of the line.
+fn split_prefix<'a>(line: &'a str, comment_prefix: Option<&str>) -> (&'a str, &'a str) {
+    let indent = line.len() - line.trim_start().len();
+    let mut end = indent;
+    if let Some(rest) = comment_prefix
+        .filter(|p| !p.is_empty())
+        .and_then(|p| line[indent..].strip_prefix(p))
+    {
+        end = line.len() - rest.trim_start().len();
+    }
+    (&line[..end], line[end..].trim_end())
+}
+
+/// Greedily fills lines with words, starting each line with the prefix.
+fn wrap(prefix: &str, words: &[&str], width: usize, tab_width: usize) -> Vec<String> {
+    let prefix_width = layout::display_column(prefix, prefix.chars().count() as u32, tab_width);
+    let mut lines = vec![];
+    let mut line = String::from(prefix);
+    let mut used = prefix_width;
+
+    for word in words {
+        let word_width = layout::display_width(word);
+        if used > prefix_width && used + 1 + word_width > width {
+            lines.push(std::mem::replace(&mut line, String::from(prefix)));
+            used = prefix_width;
+        }
+        if used > prefix_width {
+            line.push(' ');
+            used += 1;
+        }
+        line.push_str(word);
+        used += word_width;
+    }
+    lines.push(line);
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split('\n').map(String::from).collect()
+    }
+
+    #[test_case("aaa bbb\nccc ddd eee", 11, "aaa bbb ccc\nddd eee";               "joins_and_splits")]
+    #[test_case("  aaa bbb ccc", 9, "  aaa bbb\n  ccc";                           "keeps_indentation")]
+    #[test_case("// aaa bbb\n// ccc", 12, "// aaa bbb\n// ccc";                    "fits_exactly")]
+    #[test_case("// aaa\n// bbb ccc", 20, "// aaa bbb ccc";                       "comment_prefix")]
+    #[test_case("aaa\n\nbbb\nccc", 20, "aaa\n\nbbb ccc";                           "blank_lines_separate")]
+    #[test_case("// aaa\n//\n// bbb", 20, "// aaa\n//\n// bbb";                    "blank_comment_lines")]
+    #[test_case("aaa\n  bbb\n  ccc", 20, "aaa\n  bbb ccc";                         "prefix_change_separates")]
+    #[test_case("abcdefghij k", 5, "abcdefghij\nk";                                "long_word")]
+    fn reflow_lines(text: &str, width: usize, expected: &str) {
+        let actual = reflow(&lines(text), width, Some("//"), 4);
+        assert_eq!(lines(expected), actual);
+    }
+
+    #[test]
+    fn tab_indentation_uses_tab_width() {
+        let actual = reflow(&lines("\taaa bbb"), 10, None, 4);
+        assert_eq!(lines("\taaa\n\tbbb"), actual);
+    }
+}
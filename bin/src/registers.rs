@@ -0,0 +1,276 @@
+use std::collections::BTreeMap;
+
+/// The number of numbered registers holding deleted text, `1` to `9`.
+const DELETED: usize = 9;
+
+/// Text stored in a register.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Register {
+    pub text: String,
+    /// Whether the text is whole lines, each ending with a line break, which are put on the
+    /// lines below the cursor rather than at it.
+    pub linewise: bool,
+}
+
+impl Register {
+    pub fn new(text: String, linewise: bool) -> Register {
+        Register { text, linewise }
+    }
+}
+
+/// The registers text is yanked and deleted into and put from, and macros are recorded into.
+/// Yanks and deletes always write to the unnamed register, `"`, and also to a named register,
+/// `a` to `z`, if one is given. Without one, yanks write to `0` and deletes shift through `1` to
+/// `9`. Naming a register in uppercase appends to it instead of replacing it.
+#[derive(Debug, Default)]
+pub struct Registers {
+    unnamed: Option<Register>,
+    yanked: Option<Register>,
+    /// Deleted text, most recent first.
+    deleted: [Option<Register>; DELETED],
+    named: BTreeMap<char, Register>,
+}
+
+impl Registers {
+    pub fn new() -> Registers {
+        Registers::default()
+    }
+
+    /// Stores yanked text.
+    ///
+    /// # Arguments
+    ///
+    /// * 'name' - The named register to write to, if one was given.
+    /// * 'register' - The text yanked.
+    pub fn yank(&mut self, name: Option<char>, register: Register) {
+        match name.filter(|&name| name != '"') {
+            Some(name) => self.write_named(name, register),
+            None => {
+                self.yanked = Some(register.clone());
+                self.unnamed = Some(register);
+            }
+        }
+    }
+
+    /// Stores deleted text.
+    ///
+    /// # Arguments
+    ///
+    /// * 'name' - The named register to write to, if one was given.
+    /// * 'register' - The text deleted.
+    pub fn delete(&mut self, name: Option<char>, register: Register) {
+        match name.filter(|&name| name != '"') {
+            Some(name) => self.write_named(name, register),
+            None => {
+                self.deleted.rotate_right(1);
+                self.deleted[0] = Some(register.clone());
+                self.unnamed = Some(register);
+            }
+        }
+    }
+
+    /// Adds text to the end of the most recent deletion, for deletions which continue the last
+    /// one, such as consecutive kills.
+    pub fn append_delete(&mut self, text: &str) {
+        match &mut self.deleted[0] {
+            Some(register) => {
+                register.text.push_str(text);
+                self.unnamed = Some(register.clone());
+            }
+            None => self.delete(None, Register::new(text.to_string(), false)),
+        }
+    }
+
+    fn write_named(&mut self, name: char, register: Register) {
+        let register = match (
+            name.is_ascii_uppercase(),
+            self.named.get(&name.to_ascii_lowercase()),
+        ) {
+            (true, Some(existing)) => Register::new(
+                existing.text.clone() + &register.text,
+                existing.linewise || register.linewise,
+            ),
+            _ => register,
+        };
+        self.unnamed = Some(register.clone());
+        self.named.insert(name.to_ascii_lowercase(), register);
+    }
+
+    /// Replaces the text in any register, leaving the others alone, as when a macro is recorded
+    /// into it.
+    pub fn set(&mut self, name: char, register: Register) {
+        match name {
+            '"' => self.unnamed = Some(register),
+            '0' => self.yanked = Some(register),
+            '1'..='9' => self.deleted[name as usize - '1' as usize] = Some(register),
+            name => {
+                self.named.insert(name.to_ascii_lowercase(), register);
+            }
+        }
+    }
+
+    /// The text in a register, if anything has been stored in it.
+    pub fn get(&self, name: char) -> Option<&Register> {
+        match name {
+            '"' => self.unnamed.as_ref(),
+            '0' => self.yanked.as_ref(),
+            '1'..='9' => self.deleted[name as usize - '1' as usize].as_ref(),
+            name => self.named.get(&name.to_ascii_lowercase()),
+        }
+    }
+
+    /// The registers with text in them: the unnamed register, then the numbered and named
+    /// registers in order.
+    pub fn list(&self) -> Vec<(char, &Register)> {
+        std::iter::once('"')
+            .chain('0'..='9')
+            .chain('a'..='z')
+            .filter_map(|name| Some((name, self.get(name)?)))
+            .collect()
+    }
+}
+
+/// Sums up text in a single line for a list: its first line, with the number of lines after it.
+pub fn summary(text: &str) -> String {
+    let mut lines = text.lines();
+    let first = lines.next().unwrap_or_default().replace('\t', " ");
+    match lines.count() {
+        0 => first,
+        more => format!("{} (+{} lines)", first, more),
+    }
+}
+
+/// Checks that a register is named by a single letter, digit or `"`.
+pub fn parse_name(name: &str) -> Result<char, String> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphanumeric() || c == '"' => Ok(c),
+        (None, _) => Err(String::from("No register entered")),
+        _ => Err(format!("Invalid register: {}", name)),
+    }
+}
+
+/// Checks that text can be yanked or deleted into a register, which is a letter, or `"` for
+/// the registers written when none is given. The numbered registers are only written by yanks
+/// and deletes without a register.
+pub fn parse_target(name: &str) -> Result<Option<char>, String> {
+    match parse_name(name)? {
+        '"' => Ok(None),
+        c if c.is_ascii_digit() => Err(format!("Register {} can't be written to", c)),
+        c => Ok(Some(c)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn text(text: &str) -> Register {
+        Register::new(text.to_string(), false)
+    }
+
+    fn texts(registers: &Registers) -> Vec<(char, &str)> {
+        registers
+            .list()
+            .into_iter()
+            .map(|(name, register)| (name, register.text.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn yanks_and_deletes() {
+        let mut registers = Registers::new();
+        registers.yank(None, text("one"));
+        registers.delete(None, text("two"));
+        registers.delete(None, text("three"));
+        assert_eq!(
+            vec![('"', "three"), ('0', "one"), ('1', "three"), ('2', "two")],
+            texts(&registers)
+        );
+    }
+
+    #[test]
+    fn named_registers() {
+        let mut registers = Registers::new();
+        registers.yank(Some('a'), text("one"));
+        registers.delete(Some('A'), Register::new(String::from("two\n"), true));
+        let expected = Register::new(String::from("onetwo\n"), true);
+        assert_eq!(Some(&expected), registers.get('a'));
+        assert_eq!(Some(&expected), registers.get('"'));
+        // Named registers leave the numbered ones alone.
+        assert_eq!(None, registers.get('0'));
+        assert_eq!(None, registers.get('1'));
+    }
+
+    #[test]
+    fn deletes_shift_through_numbered_registers() {
+        let mut registers = Registers::new();
+        for i in 0..12 {
+            registers.delete(None, text(&i.to_string()));
+        }
+        assert_eq!("11", registers.get('1').unwrap().text);
+        assert_eq!("3", registers.get('9').unwrap().text);
+    }
+
+    #[test]
+    fn unnamed_register_writes_default_registers() {
+        let mut registers = Registers::new();
+        registers.yank(Some('"'), text("one"));
+        registers.delete(Some('"'), text("two"));
+        assert_eq!(
+            vec![('"', "two"), ('0', "one"), ('1', "two")],
+            texts(&registers)
+        );
+    }
+
+    #[test]
+    fn set_replaces_any_register() {
+        let mut registers = Registers::new();
+        registers.delete(None, text("one"));
+        registers.set('3', text("x<Down>"));
+        registers.set('0', text("yanked"));
+        assert_eq!(
+            vec![
+                ('"', "one"),
+                ('0', "yanked"),
+                ('1', "one"),
+                ('3', "x<Down>")
+            ],
+            texts(&registers)
+        );
+    }
+
+    #[test]
+    fn append_to_last_delete() {
+        let mut registers = Registers::new();
+        registers.append_delete("Lorem");
+        registers.append_delete(" ipsum");
+        assert_eq!("Lorem ipsum", registers.get('1').unwrap().text);
+        assert_eq!("Lorem ipsum", registers.get('"').unwrap().text);
+    }
+
+    #[test_case("one", "one";                  "single_line")]
+    #[test_case("one\ttwo\nthree\nfour\n", "one two (+2 lines)"; "several_lines")]
+    fn summarize(text: &str, expected: &str) {
+        assert_eq!(expected, summary(text));
+    }
+
+    #[test_case("a", Ok('a');                                    "letter")]
+    #[test_case("B", Ok('B');                                    "uppercase")]
+    #[test_case("\"", Ok('"');                                   "unnamed")]
+    #[test_case("", Err(String::from("No register entered"));     "empty")]
+    #[test_case("ab", Err(String::from("Invalid register: ab"));  "too_long")]
+    fn register_names(name: &str, expected: Result<char, String>) {
+        assert_eq!(expected, parse_name(name));
+    }
+
+    #[test_case("a", Ok(Some('a'));                                      "letter")]
+    #[test_case("\"", Ok(None);                                          "unnamed")]
+    #[test_case("0", Err(String::from("Register 0 can't be written to")); "yanked")]
+    #[test_case("3", Err(String::from("Register 3 can't be written to")); "deleted")]
+    fn target_names(name: &str, expected: Result<Option<char>, String>) {
+        assert_eq!(expected, parse_target(name));
+    }
+}
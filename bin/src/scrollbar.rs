@@ -0,0 +1,82 @@
+use std::ops::Range;
+
+use console::{style, Color};
+
+/// The number of cells taken up by the scrollbar.
+pub const WIDTH: usize = 1;
+
+/// The lines of the document covered by a row of the scrollbar's track.
+///
+/// # Arguments
+///
+/// * 'row' - The row of the track. Rows start from 0.
+/// * 'line_count' - The number of lines in the document.
+/// * 'height' - The number of rows in the track.
+pub fn lines_at(row: usize, line_count: u32, height: usize) -> Range<u32> {
+    let height = height.max(1) as u64;
+    let line_count = line_count.max(1) as u64;
+    let start = row as u64 * line_count / height;
+    let end = ((row as u64 + 1) * line_count / height).max(start + 1);
+    start as u32 + 1..end as u32 + 1
+}
+
+/// Draws a scrollbar, one cell per row, with a thumb showing where the viewport is and how much
+/// of the document it shows. Rows covering a marked line show the mark instead of the track.
+///
+/// # Arguments
+///
+/// * 'height' - The number of rows in the track.
+/// * 'line_count' - The number of lines in the document.
+/// * 'viewport' - The lines visible in the viewport. Line numbers start from 1.
+/// * 'marks' - Lines to mark, such as error locations, and the colour to mark them with.
+pub fn render(
+    height: usize,
+    line_count: u32,
+    viewport: Range<u32>,
+    marks: &[(u32, Color)],
+) -> Vec<String> {
+    (0..height)
+        .map(|row| {
+            let lines = lines_at(row, line_count, height);
+            let thumb = lines.start < viewport.end && viewport.start < lines.end;
+            let mark = marks.iter().find(|(line, _)| lines.contains(line));
+
+            let cell = match mark {
+                Some((_, color)) => style('•').fg(*color),
+                None if thumb => style('┃').fg(Color::White),
+                None => style('│').fg(Color::Color256(240)),
+            };
+            let cell = match thumb {
+                true => cell.bg(Color::Color256(238)),
+                false => cell,
+            };
+            cell.force_styling(true).to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(0, 100, 1..11;   "first_row")]
+    #[test_case(9, 100, 91..101; "last_row")]
+    #[test_case(3, 5, 2..3;      "short_document")]
+    #[test_case(9, 5, 5..6;      "past_end")]
+    fn lines_covered_by_row(row: usize, line_count: u32, expected: Range<u32>) {
+        assert_eq!(expected, lines_at(row, line_count, 10));
+    }
+
+    #[test]
+    fn render_thumb_and_marks() {
+        let rows = render(4, 40, 11..21, &[(35, Color::Red)]);
+
+        assert_eq!(4, rows.len());
+        assert_eq!("\x1b[38;5;240m│\x1b[0m", rows[0]);
+        assert_eq!("\x1b[37m\x1b[48;5;238m┃\x1b[0m", rows[1]);
+        assert_eq!("\x1b[38;5;240m│\x1b[0m", rows[2]);
+        assert_eq!("\x1b[31m•\x1b[0m", rows[3]);
+    }
+}
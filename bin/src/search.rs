@@ -0,0 +1,498 @@
+use std::ops::Range;
+
+use crate::selection::Position;
+
+/// The number of lines in each block of a search index, and the number of lines read at a time
+/// while searching.
+const BLOCK_LINES: u32 = 1024;
+/// The number of bits in each block's trigram filter.
+const FILTER_BITS: usize = 1 << 16;
+/// The most matches counted, so counting the matches in a huge document doesn't take long.
+pub const MAX_COUNT: usize = 9999;
+
+/// Where a search found the text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Found {
+    pub position: Position,
+    /// Whether the search wrapped around past the end of the document, or past the start when
+    /// searching backwards.
+    pub wrapped: bool,
+}
+
+/// The number of matches of a search in a document, and which of them a match is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Count {
+    /// The match's place among the matches, counting from one, or None if it's past the most
+    /// matches counted.
+    pub index: Option<usize>,
+    /// The number of matches, up to `MAX_COUNT`.
+    pub total: usize,
+    /// Whether there are more matches than were counted.
+    pub more: bool,
+}
+
+impl Count {
+    /// Describes the match's place among the matches, such as "match 3 of 17".
+    pub fn describe(&self) -> String {
+        match (self.index, self.more) {
+            (Some(index), false) => format!("match {} of {}", index, self.total),
+            (Some(index), true) => format!("match {} of over {}", index, self.total),
+            (None, _) => format!("over {} matches", self.total),
+        }
+    }
+}
+
+/// A run of consecutive lines in a search index.
+#[derive(Debug)]
+struct Block {
+    lines: u32,
+    /// The hashes of the trigrams in the block's lines, or None if the block hasn't been built
+    /// since it was last edited.
+    filter: Option<Vec<u64>>,
+}
+
+impl Block {
+    fn stale(lines: u32) -> Block {
+        Block {
+            lines,
+            filter: None,
+        }
+    }
+
+    /// Whether the block might contain all of the trigrams. Blocks which haven't been built might
+    /// contain anything.
+    fn may_contain(&self, trigrams: &[usize]) -> bool {
+        match &self.filter {
+            Some(filter) => trigrams
+                .iter()
+                .all(|&hash| filter[hash / 64] & 1 << (hash % 64) != 0),
+            None => true,
+        }
+    }
+}
+
+/// An index of the text in a document, which rules out the lines a search can't match so that
+/// searching a large document only reads a small part of it. The lines are split into blocks,
+/// each with a filter of the three-byte sequences, or trigrams, in its text, folded to lowercase.
+///
+/// Blocks are built a few at a time while the editor is idle. Edits mark the blocks they touch
+/// as stale, and stale blocks are searched in full until they're built again.
+#[derive(Debug)]
+pub struct SearchIndex {
+    blocks: Vec<Block>,
+}
+
+impl SearchIndex {
+    /// Constructs an index with nothing built yet.
+    ///
+    /// # Arguments
+    ///
+    /// * 'line_count' - The number of lines in the document.
+    pub fn new(line_count: u32) -> SearchIndex {
+        SearchIndex {
+            blocks: vec![Block::stale(line_count)],
+        }
+    }
+
+    /// Whether every block of the index has been built.
+    pub fn is_complete(&self) -> bool {
+        self.blocks.iter().all(|block| block.filter.is_some())
+    }
+
+    /// Builds the first stale block, returning whether there was one. Stale blocks spanning more
+    /// than `BLOCK_LINES` lines are split up first, so each build takes about as long.
+    ///
+    /// # Arguments
+    ///
+    /// * 'text' - Reads the text of a range of lines.
+    pub fn build_next(&mut self, text: impl FnOnce(Range<u32>) -> String) -> bool {
+        let mut start = 1;
+        for i in 0..self.blocks.len() {
+            let block = &mut self.blocks[i];
+            if block.filter.is_some() {
+                start += block.lines;
+                continue;
+            }
+
+            if block.lines > BLOCK_LINES {
+                let rest = block.lines - BLOCK_LINES;
+                block.lines = BLOCK_LINES;
+                self.blocks.insert(i + 1, Block::stale(rest));
+            }
+            let block = &mut self.blocks[i];
+            let mut filter = vec![0u64; FILTER_BITS / 64];
+            for hash in trigrams(&text(start..start + block.lines)) {
+                filter[hash / 64] |= 1 << (hash % 64);
+            }
+            block.filter = Some(filter);
+            return true;
+        }
+        false
+    }
+
+    /// Updates the index for an edit, merging the blocks it touched into one stale block.
+    ///
+    /// # Arguments
+    ///
+    /// * 'line' - The line the edit was made on.
+    /// * 'removed' - The number of line breaks the edit removed.
+    /// * 'inserted' - The number of line breaks the edit inserted.
+    pub fn edit(&mut self, line: u32, removed: u32, inserted: u32) {
+        let mut first = None;
+        let mut last = self.blocks.len() - 1;
+        let mut start = 1;
+        for (i, block) in self.blocks.iter().enumerate() {
+            let end = start + block.lines;
+            if first.is_none() && line < end {
+                first = Some(i);
+            }
+            if line + removed < end {
+                last = i;
+                break;
+            }
+            start = end;
+        }
+
+        let first = first.unwrap_or(last);
+        let lines: u32 = self.blocks[first..=last].iter().map(|b| b.lines).sum();
+        let lines = (lines + inserted).saturating_sub(removed).max(1);
+        self.blocks.splice(first..=last, [Block::stale(lines)]);
+    }
+
+    /// The ranges of lines which might contain some text, in order. Text shorter than a trigram
+    /// can't be ruled out anywhere.
+    ///
+    /// # Arguments
+    ///
+    /// * 'pattern' - The text being searched for.
+    pub fn candidates(&self, pattern: &str) -> Vec<Range<u32>> {
+        let trigrams: Vec<usize> = trigrams(pattern).collect();
+        let mut ranges: Vec<Range<u32>> = vec![];
+        let mut start = 1;
+        for block in &self.blocks {
+            let end = start + block.lines;
+            if block.may_contain(&trigrams) {
+                match ranges.last_mut() {
+                    Some(range) if range.end == start => range.end = end,
+                    _ => ranges.push(start..end),
+                }
+            }
+            start = end;
+        }
+        ranges
+    }
+}
+
+/// The hashes of the trigrams in some text, folded to lowercase. Trigrams spanning a line break
+/// are left out, as searches don't match across lines.
+fn trigrams(text: &str) -> impl Iterator<Item = usize> + '_ {
+    text.as_bytes()
+        .windows(3)
+        .filter(|window| !window.contains(&b'\n'))
+        .map(|window| {
+            let value = window
+                .iter()
+                .fold(0u32, |value, b| value << 8 | b.to_ascii_lowercase() as u32);
+            (value.wrapping_mul(0x9E37_79B1) >> 16) as usize % FILTER_BITS
+        })
+}
+
+/// Finds the next occurrence of some text after a position, or the previous one before it,
+/// optionally wrapping around the document if there's none in that direction.
+///
+/// # Arguments
+///
+/// * 'ranges' - The ranges of lines which might contain the text, in order, from the document's
+///   search index or covering the whole document.
+/// * 'read' - Reads the lines in a range.
+/// * 'pattern' - The text to find.
+/// * 'from' - The position to search from, which is only matched once the search has wrapped.
+/// * 'forward' - Whether to search towards the end of the document.
+/// * 'wrap' - Whether to carry on from the other end of the document.
+pub fn find(
+    ranges: &[Range<u32>],
+    read: impl Fn(Range<u32>) -> Vec<String>,
+    pattern: &str,
+    from: Position,
+    forward: bool,
+    wrap: bool,
+) -> Option<Found> {
+    if pattern.is_empty() {
+        return None;
+    }
+
+    let row = from.row;
+    let after: Vec<Range<u32>> = ranges
+        .iter()
+        .map(|r| r.start.max(row)..r.end)
+        .filter(|r| !r.is_empty())
+        .collect();
+    let before: Vec<Range<u32>> = ranges
+        .iter()
+        .map(|r| r.start..r.end.min(row + 1))
+        .filter(|r| !r.is_empty())
+        .collect();
+    // The lines in the direction of the search are searched first, then those behind it once
+    // the search has wrapped around. The cursor's line is in both.
+    let passes = match forward {
+        true => [(after, false), (before, true)],
+        false => [(before, false), (after, true)],
+    };
+
+    for (ranges, wrapped) in passes {
+        if wrapped && !wrap {
+            break;
+        }
+        let mut chunks: Vec<Range<u32>> = ranges
+            .into_iter()
+            .flat_map(|r| {
+                r.clone()
+                    .step_by(BLOCK_LINES as usize)
+                    .map(move |start| start..(start + BLOCK_LINES).min(r.end))
+            })
+            .collect();
+        if !forward {
+            chunks.reverse();
+        }
+
+        for chunk in chunks {
+            let mut lines: Vec<(u32, String)> = chunk.clone().zip(read(chunk)).collect();
+            if !forward {
+                lines.reverse();
+            }
+            for (line, text) in lines {
+                // On the cursor's line, only matches past the cursor count before wrapping.
+                let accept = |column: u32| {
+                    line != row
+                        || match (forward, wrapped) {
+                            (true, false) => column > from.column,
+                            (true, true) => column <= from.column,
+                            (false, false) => column < from.column,
+                            (false, true) => column >= from.column,
+                        }
+                };
+                if let Some(column) = find_in_line(&text, pattern, forward, accept) {
+                    let position = Position { row: line, column };
+                    return Some(Found { position, wrapped });
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Counts the occurrences of some text in a document, and finds which of them is at a position.
+/// Counting stops after `MAX_COUNT` occurrences.
+///
+/// # Arguments
+///
+/// * 'ranges' - The ranges of lines which might contain the text, in order.
+/// * 'read' - Reads the lines in a range.
+/// * 'pattern' - The text to count.
+/// * 'at' - The position of the occurrence to find the place of.
+pub fn count(
+    ranges: &[Range<u32>],
+    read: impl Fn(Range<u32>) -> Vec<String>,
+    pattern: &str,
+    at: Position,
+) -> Count {
+    let mut count = Count {
+        index: None,
+        total: 0,
+        more: false,
+    };
+    if pattern.is_empty() {
+        return count;
+    }
+
+    let chunks = ranges.iter().flat_map(|r| {
+        r.clone()
+            .step_by(BLOCK_LINES as usize)
+            .map(move |start| start..(start + BLOCK_LINES).min(r.end))
+    });
+    for chunk in chunks {
+        for (line, text) in chunk.clone().zip(read(chunk)) {
+            for column in columns_in_line(&text, pattern) {
+                if count.total == MAX_COUNT {
+                    count.more = true;
+                    return count;
+                }
+                count.total += 1;
+                if (Position { row: line, column }) == at {
+                    count.index = Some(count.total);
+                }
+            }
+        }
+    }
+    count
+}
+
+/// The columns where some text occurs in a line, including occurrences which overlap.
+fn columns_in_line<'a>(text: &'a str, pattern: &'a str) -> impl Iterator<Item = u32> + 'a {
+    let chars = text
+        .contains(pattern)
+        .then(|| text.char_indices().enumerate());
+    chars
+        .into_iter()
+        .flatten()
+        .filter(move |(_, (offset, _))| text[*offset..].starts_with(pattern))
+        .map(|(column, _)| column as u32)
+}
+
+/// The column of the first occurrence of some text in a line, or the last one when searching
+/// backwards, out of the ones accepted.
+fn find_in_line(
+    text: &str,
+    pattern: &str,
+    forward: bool,
+    accept: impl Fn(u32) -> bool,
+) -> Option<u32> {
+    let mut columns = columns_in_line(text, pattern).filter(|&column| accept(column));
+    match forward {
+        true => columns.next(),
+        false => columns.last(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    const TEXT: [&str; 5] = ["one two", "three", "two two", "four", "Two"];
+    /// The ranges searched when nothing is ruled out.
+    const WHOLE: &[Range<u32>] = &[Range { start: 1, end: 6 }];
+
+    fn read(lines: Range<u32>) -> Vec<String> {
+        lines
+            .map(|line| TEXT[line as usize - 1].to_string())
+            .collect()
+    }
+
+    fn position(row: u32, column: u32) -> Position {
+        Position { row, column }
+    }
+
+    #[test_case((1, 0), true,  Some((1, 4)); "forward_on_line")]
+    #[test_case((1, 4), true,  Some((3, 0)); "forward_next_line")]
+    #[test_case((3, 0), true,  Some((3, 4)); "forward_same_line")]
+    #[test_case((3, 4), true,  Some((1, 4)); "forward_wraps")]
+    #[test_case((3, 4), false, Some((3, 0)); "backward_on_line")]
+    #[test_case((3, 0), false, Some((1, 4)); "backward_previous_line")]
+    #[test_case((1, 4), false, Some((3, 4)); "backward_wraps")]
+    fn find_two(from: (u32, u32), forward: bool, expected: Option<(u32, u32)>) {
+        let actual = find(WHOLE, read, "two", position(from.0, from.1), forward, true);
+        let actual = actual.map(|found| found.position);
+        assert_eq!(expected.map(|(row, column)| position(row, column)), actual);
+    }
+
+    #[test]
+    fn find_only_match_wraps_to_itself() {
+        let actual = find(WHOLE, read, "four", position(4, 0), true, true);
+        let expected = Found {
+            position: position(4, 0),
+            wrapped: true,
+        };
+        assert_eq!(Some(expected), actual);
+    }
+
+    #[test_case(true,  Some(true); "wrapping")]
+    #[test_case(false, None;       "not_wrapping")]
+    fn find_past_end(wrap: bool, expected: Option<bool>) {
+        let actual = find(WHOLE, read, "one", position(3, 0), true, wrap);
+        assert_eq!(expected, actual.map(|found| found.wrapped));
+    }
+
+    #[test]
+    fn find_nothing() {
+        assert_eq!(None, find(WHOLE, read, "five", position(1, 0), true, true));
+        assert_eq!(None, find(WHOLE, read, "", position(1, 0), true, true));
+    }
+
+    #[test]
+    fn find_skips_lines_ruled_out() {
+        let actual = find(&[2..3, 4..5], read, "o", position(1, 0), true, true);
+        assert_eq!(Some(position(4, 1)), actual.map(|found| found.position));
+    }
+
+    #[test_case((1, 4), Some(1); "first")]
+    #[test_case((3, 4), Some(3); "last")]
+    #[test_case((2, 0), None;    "not_a_match")]
+    fn count_two(at: (u32, u32), index: Option<usize>) {
+        let count = count(WHOLE, read, "two", position(at.0, at.1));
+        let expected = Count {
+            index,
+            total: 3,
+            more: false,
+        };
+        assert_eq!(expected, count);
+    }
+
+    #[test_case(Some(3), false, "match 3 of 17";      "counted")]
+    #[test_case(Some(3), true,  "match 3 of over 17"; "more")]
+    #[test_case(None,    true,  "over 17 matches";    "past_most")]
+    fn describe_count(index: Option<usize>, more: bool, expected: &str) {
+        let count = Count {
+            index,
+            total: 17,
+            more,
+        };
+        assert_eq!(expected, count.describe());
+    }
+
+    #[test]
+    fn count_stops_at_most() {
+        let lines = vec!["aa".repeat(MAX_COUNT); 2];
+        let read =
+            |range: Range<u32>| lines[range.start as usize - 1..range.end as usize - 1].to_vec();
+        let count = count(&[1..2, 2..3], read, "a", position(2, 0));
+        assert_eq!(
+            (None, MAX_COUNT, true),
+            (count.index, count.total, count.more)
+        );
+    }
+
+    fn build(index: &mut SearchIndex, lines: &[String]) {
+        while index
+            .build_next(|range| lines[range.start as usize - 1..range.end as usize - 1].join("\n"))
+        {
+        }
+    }
+
+    fn numbered_lines(count: u32) -> Vec<String> {
+        (1..=count).map(|i| format!("line {}", i)).collect()
+    }
+
+    #[test]
+    fn candidates_rule_out_blocks() {
+        let mut lines = numbered_lines(3000);
+        lines[2500] = String::from("needle");
+        let mut index = SearchIndex::new(3000);
+        assert_eq!(vec![1..3001], index.candidates("needle"));
+
+        build(&mut index, &lines);
+        assert!(index.is_complete());
+        assert_eq!(vec![2049..3001], index.candidates("NEEDLE"));
+        assert_eq!(vec![1..3001], index.candidates("li"));
+    }
+
+    #[test]
+    fn edits_mark_blocks_stale() {
+        let mut lines = numbered_lines(3000);
+        let mut index = SearchIndex::new(3000);
+        build(&mut index, &lines);
+        assert!(index.candidates("needle").is_empty());
+
+        // Join lines 1024 and 1025, in the first and second blocks, and insert two new lines.
+        lines.splice(1023..1025, ["needle".to_string(), "a".into(), "b".into()]);
+        index.edit(1024, 1, 2);
+        assert!(!index.is_complete());
+        assert_eq!(vec![1..3002], index.candidates("line"));
+        assert_eq!(vec![1..2050], index.candidates("needle"));
+
+        // The merged block is split up again as it's built.
+        build(&mut index, &lines);
+        assert_eq!(vec![1..1025], index.candidates("needle"));
+    }
+}
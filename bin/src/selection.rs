@@ -0,0 +1,94 @@
+use std::ops::Range;
+
+/// A position in a document. Rows start from 1 and columns start from 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub row: u32,
+    pub column: u32,
+}
+
+/// The text between the anchor, where the selection was started, and the cursor. The cursor
+/// can be before or after the anchor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Selection {
+    pub anchor: Position,
+    pub cursor: Position,
+}
+
+impl Selection {
+    pub fn new(anchor: Position, cursor: Position) -> Selection {
+        Selection { anchor, cursor }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.cursor
+    }
+
+    /// The first selected position.
+    pub fn start(&self) -> Position {
+        self.anchor.min(self.cursor)
+    }
+
+    /// The position just after the last selected character.
+    pub fn end(&self) -> Position {
+        self.anchor.max(self.cursor)
+    }
+
+    /// The columns of a row which are selected, or None if the row isn't part of the selection.
+    /// When the line break at the end of the row is selected the range extends to `u32::MAX`.
+    ///
+    /// # Arguments
+    ///
+    /// * 'row' - The row to check. Rows start from 1.
+    pub fn columns(&self, row: u32) -> Option<Range<u32>> {
+        let (start, end) = (self.start(), self.end());
+        if self.is_empty() || row < start.row || row > end.row {
+            return None;
+        }
+
+        let first = match row == start.row {
+            true => start.column,
+            false => 0,
+        };
+        let last = match row == end.row {
+            true => end.column,
+            false => u32::MAX,
+        };
+        Some(first..last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn position(row: u32, column: u32) -> Position {
+        Position { row, column }
+    }
+
+    #[test]
+    fn start_and_end_are_ordered() {
+        let selection = Selection::new(position(3, 1), position(2, 5));
+        assert_eq!(position(2, 5), selection.start());
+        assert_eq!(position(3, 1), selection.end());
+    }
+
+    #[test_case(1, None;                "before")]
+    #[test_case(2, Some(5..u32::MAX);   "first_row")]
+    #[test_case(3, Some(0..u32::MAX);   "middle_row")]
+    #[test_case(4, Some(0..2);          "last_row")]
+    #[test_case(5, None;                "after")]
+    fn columns_on_row(row: u32, expected: Option<Range<u32>>) {
+        let selection = Selection::new(position(4, 2), position(2, 5));
+        assert_eq!(expected, selection.columns(row));
+    }
+
+    #[test]
+    fn empty_selection_has_no_columns() {
+        let selection = Selection::new(position(1, 3), position(1, 3));
+        assert!(selection.is_empty());
+        assert_eq!(None, selection.columns(1));
+    }
+}
@@ -0,0 +1,170 @@
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+
+use crate::error::{EditorError, Result};
+
+/// What a shell command printed, and how it exited.
+#[derive(Debug)]
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: ExitStatus,
+}
+
+impl Output {
+    /// A message describing a failed command, using the first line it printed to stderr.
+    pub fn error(&self) -> String {
+        match self.stderr.lines().find(|line| !line.trim().is_empty()) {
+            Some(line) => format!("Command failed: {}", line.trim()),
+            None => format!("Command exited with {}", self.status),
+        }
+    }
+}
+
+/// Runs a command with the user's shell, waiting for it to finish. The command's input is
+/// written from a separate thread, so commands which print output before reading all of their
+/// input don't block.
+///
+/// # Arguments
+///
+/// * 'command' - The command line to run.
+/// * 'input' - The text to pass to the command on stdin. Without input, stdin is closed.
+pub fn run(command: &str, input: Option<String>) -> Result<Output> {
+    run_in(command, input, Path::new("."))
+}
+
+/// Runs a command like `run`, from a different directory.
+///
+/// # Arguments
+///
+/// * 'command' - The command line to run.
+/// * 'input' - The text to pass to the command on stdin. Without input, stdin is closed.
+/// * 'dir' - The directory to run the command from.
+pub fn run_in(command: &str, input: Option<String>, dir: &Path) -> Result<Output> {
+    let (stdout, stderr, status) = run_bytes(command, input.map(String::into_bytes), dir)?;
+    Ok(Output {
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        stderr,
+        status,
+    })
+}
+
+/// Pipes data through a command, returning what it printed to stdout. Unlike `run`, the input
+/// and output don't need to be text, and the command failing is an error.
+///
+/// # Arguments
+///
+/// * 'command' - The command line to run.
+/// * 'input' - The data to pass to the command on stdin.
+pub fn pipe(command: &str, input: Vec<u8>) -> Result<Vec<u8>> {
+    let (stdout, stderr, status) = run_bytes(command, Some(input), Path::new("."))?;
+    if !status.success() {
+        let output = Output {
+            stdout: String::new(),
+            stderr,
+            status,
+        };
+        return Err(EditorError::Command(output.error()));
+    }
+    Ok(stdout)
+}
+
+fn run_bytes(
+    command: &str,
+    input: Option<Vec<u8>>,
+    dir: &Path,
+) -> Result<(Vec<u8>, String, ExitStatus)> {
+    let mut child = shell()
+        .arg(command)
+        .current_dir(dir)
+        .stdin(match input {
+            Some(_) => Stdio::piped(),
+            None => Stdio::null(),
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let writer = match (child.stdin.take(), input) {
+        (Some(mut stdin), Some(input)) => Some(std::thread::spawn(move || {
+            // The command may exit without reading all of its input, which isn't an error.
+            let _ = stdin.write_all(&input);
+        })),
+        _ => None,
+    };
+
+    let mut stderr = String::new();
+    let stderr_reader = child.stderr.take().map(|mut pipe| {
+        std::thread::spawn(move || {
+            let _ = pipe.read_to_string(&mut stderr);
+            stderr
+        })
+    });
+    let mut stdout = vec![];
+    if let Some(mut pipe) = child.stdout.take() {
+        pipe.read_to_end(&mut stdout)?;
+    }
+
+    let status = child.wait()?;
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    let stderr = stderr_reader
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+
+    Ok((stdout, stderr, status))
+}
+
+#[cfg(unix)]
+fn shell() -> Command {
+    let shell = std::env::var_os("SHELL").unwrap_or_else(|| "sh".into());
+    let mut command = Command::new(shell);
+    command.arg("-c");
+    command
+}
+
+#[cfg(not(unix))]
+fn shell() -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C");
+    command
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_without_input() {
+        let output = run("echo Lorem", None).unwrap();
+        assert!(output.status.success());
+        assert_eq!("Lorem\n", output.stdout);
+    }
+
+    #[test]
+    fn run_with_input() {
+        let output = run("sort", Some(String::from("b\na\n"))).unwrap();
+        assert_eq!("a\nb\n", output.stdout);
+    }
+
+    #[test]
+    fn pipe_binary_data() {
+        let input = vec![0, 159, 146, 150, 255];
+        assert_eq!(input, pipe("cat", input.clone()).unwrap());
+    }
+
+    #[test]
+    fn pipe_fails_with_command() {
+        let error = pipe("echo denied >&2; exit 2", vec![]).unwrap_err();
+        assert_eq!("Command failed: denied", error.to_string());
+    }
+
+    #[test]
+    fn failed_command_reports_stderr() {
+        let output = run("echo oops >&2; exit 3", None).unwrap();
+        assert!(!output.status.success());
+        assert_eq!("Command failed: oops", output.error());
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+
+use console::{style, Color};
+
+/// The number of cells taken up by the gutter, a sign followed by a space.
+pub const GUTTER_WIDTH: usize = 2;
+
+/// What placed a sign. Each group's signs are managed separately, so toggling a bookmark leaves
+/// other signs on the line alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Group {
+    Bookmark,
+    Todo,
+}
+
+/// A marker drawn in the gutter next to a line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sign {
+    pub group: Group,
+    /// The character drawn in the gutter.
+    pub text: char,
+    pub color: Color,
+    /// When several signs share a line, the one with the highest priority is drawn.
+    pub priority: u32,
+}
+
+impl Sign {
+    pub fn bookmark() -> Sign {
+        Sign {
+            group: Group::Bookmark,
+            text: '>',
+            color: Color::Cyan,
+            priority: 20,
+        }
+    }
+
+    pub fn todo() -> Sign {
+        Sign {
+            group: Group::Todo,
+            text: '!',
+            color: Color::Yellow,
+            priority: 10,
+        }
+    }
+}
+
+/// The signs placed on a document's lines, with at most one sign from each group on a line.
+#[derive(Debug, Default)]
+pub struct Signs {
+    lines: BTreeMap<u32, Vec<Sign>>,
+}
+
+impl Signs {
+    pub fn new() -> Signs {
+        Signs::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Places a sign on a line, replacing any sign from the same group.
+    ///
+    /// # Arguments
+    ///
+    /// * 'line' - The line to place the sign on. Line numbers start from 1.
+    /// * 'sign' - The sign to place.
+    pub fn place(&mut self, line: u32, sign: Sign) {
+        let signs = self.lines.entry(line).or_default();
+        signs.retain(|s| s.group != sign.group);
+        signs.push(sign);
+    }
+
+    /// Removes a group's sign from a line, returning whether there was one.
+    pub fn remove(&mut self, line: u32, group: Group) -> bool {
+        let Some(signs) = self.lines.get_mut(&line) else {
+            return false;
+        };
+        let len = signs.len();
+        signs.retain(|s| s.group != group);
+        let removed = signs.len() != len;
+        if signs.is_empty() {
+            self.lines.remove(&line);
+        }
+        removed
+    }
+
+    /// Whether a line has a sign from a group.
+    pub fn contains(&self, line: u32, group: Group) -> bool {
+        self.lines
+            .get(&line)
+            .is_some_and(|signs| signs.iter().any(|s| s.group == group))
+    }
+
+    /// The sign drawn next to a line, which is the sign with the highest priority. Of signs with
+    /// the same priority, the one placed last is drawn.
+    pub fn top(&self, line: u32) -> Option<&Sign> {
+        self.lines
+            .get(&line)?
+            .iter()
+            .rev()
+            .max_by_key(|sign| sign.priority)
+    }
+
+    /// Each line with a sign, in order, along with the sign drawn next to it.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &Sign)> {
+        self.lines
+            .keys()
+            .filter_map(|&line| self.top(line).map(|sign| (line, sign)))
+    }
+
+    /// The first line after the given line which has a sign, wrapping around to the first line
+    /// with a sign.
+    pub fn next(&self, line: u32) -> Option<u32> {
+        self.lines
+            .range(line + 1..)
+            .chain(self.lines.range(..=line))
+            .map(|(line, _)| *line)
+            .next()
+    }
+
+    /// The last line before the given line which has a sign, wrapping around to the last line
+    /// with a sign.
+    pub fn previous(&self, line: u32) -> Option<u32> {
+        self.lines
+            .range(..line)
+            .rev()
+            .chain(self.lines.range(line..).rev())
+            .map(|(line, _)| *line)
+            .next()
+    }
+
+    /// Moves the signs for an edit. Signs below the edit move up or down with it, and signs on
+    /// lines joined onto the edited line are removed.
+    ///
+    /// # Arguments
+    ///
+    /// * 'line' - The line the edit was made on.
+    /// * 'removed' - The number of line breaks the edit removed.
+    /// * 'inserted' - The number of line breaks the edit inserted.
+    pub fn edit(&mut self, line: u32, removed: u32, inserted: u32) {
+        if removed == inserted {
+            return;
+        }
+        self.lines = std::mem::take(&mut self.lines)
+            .into_iter()
+            .filter(|(l, _)| *l <= line || *l > line + removed)
+            .map(|(l, signs)| match l > line {
+                true => (l + inserted - removed, signs),
+                false => (l, signs),
+            })
+            .collect();
+    }
+
+    /// Draws the gutter for a line: its sign in colour, or blank cells if it has none.
+    pub fn render(&self, line: u32) -> String {
+        match self.top(line) {
+            Some(sign) => format!(
+                "{} ",
+                style(sign.text).fg(sign.color).bold().force_styling(true)
+            ),
+            None => " ".repeat(GUTTER_WIDTH),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn place_replaces_sign_from_same_group() {
+        let mut signs = Signs::new();
+        signs.place(3, Sign::todo());
+        signs.place(
+            3,
+            Sign {
+                text: '?',
+                ..Sign::todo()
+            },
+        );
+
+        assert_eq!(Some('?'), signs.top(3).map(|s| s.text));
+        assert!(signs.remove(3, Group::Todo));
+        assert!(signs.is_empty());
+    }
+
+    #[test]
+    fn top_sign_has_highest_priority() {
+        let mut signs = Signs::new();
+        signs.place(1, Sign::bookmark());
+        signs.place(1, Sign::todo());
+
+        assert_eq!(Some(Group::Bookmark), signs.top(1).map(|s| s.group));
+        signs.remove(1, Group::Bookmark);
+        assert_eq!(Some(Group::Todo), signs.top(1).map(|s| s.group));
+        assert!(!signs.remove(1, Group::Bookmark));
+    }
+
+    #[test]
+    fn iterate_top_signs() {
+        let mut signs = Signs::new();
+        signs.place(7, Sign::todo());
+        signs.place(2, Sign::todo());
+        signs.place(2, Sign::bookmark());
+
+        let groups: Vec<(u32, Group)> = signs.iter().map(|(line, s)| (line, s.group)).collect();
+        assert_eq!(vec![(2, Group::Bookmark), (7, Group::Todo)], groups);
+    }
+
+    #[test_case(1, Some(4);  "before_first")]
+    #[test_case(4, Some(9);  "between")]
+    #[test_case(9, Some(4);  "wraps")]
+    fn next_line_with_sign(line: u32, expected: Option<u32>) {
+        let mut signs = Signs::new();
+        signs.place(4, Sign::bookmark());
+        signs.place(9, Sign::todo());
+        assert_eq!(expected, signs.next(line));
+    }
+
+    #[test_case(10, Some(9); "after_last")]
+    #[test_case(9, Some(4);  "between")]
+    #[test_case(4, Some(9);  "wraps")]
+    fn previous_line_with_sign(line: u32, expected: Option<u32>) {
+        let mut signs = Signs::new();
+        signs.place(4, Sign::bookmark());
+        signs.place(9, Sign::todo());
+        assert_eq!(expected, signs.previous(line));
+    }
+
+    #[test_case(2, 0, 3, vec![1, 8];  "inserted_above")]
+    #[test_case(5, 0, 3, vec![1, 5];  "inserted_on_line")]
+    #[test_case(2, 2, 0, vec![1, 3];  "removed_above")]
+    #[test_case(3, 2, 0, vec![1];     "joined")]
+    fn edit_moves_signs(line: u32, removed: u32, inserted: u32, expected: Vec<u32>) {
+        let mut signs = Signs::new();
+        signs.place(1, Sign::bookmark());
+        signs.place(5, Sign::todo());
+        signs.edit(line, removed, inserted);
+
+        let lines: Vec<u32> = signs.iter().map(|(line, _)| line).collect();
+        assert_eq!(expected, lines);
+    }
+
+    #[test]
+    fn render_gutter() {
+        let mut signs = Signs::new();
+        signs.place(2, Sign::todo());
+        assert_eq!("\x1b[33m\x1b[1m!\x1b[0m ", signs.render(2));
+        assert_eq!("  ", signs.render(1));
+    }
+}
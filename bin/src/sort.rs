@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+
+/// How lines are compared when sorting.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SortOptions {
+    /// Compare lines without regard to case.
+    pub ignore_case: bool,
+    /// Compare lines by the first number in each line. Lines without a number sort first, in
+    /// their original order.
+    pub numeric: bool,
+    /// Sort from largest to smallest.
+    pub descending: bool,
+}
+
+impl SortOptions {
+    /// Parses the flags given to the sort command: `i` to ignore case and `n` for a numeric
+    /// sort. Flags can be combined and separated by whitespace.
+    pub fn parse(flags: &str, descending: bool) -> Result<SortOptions, String> {
+        let mut options = SortOptions {
+            descending,
+            ..SortOptions::default()
+        };
+        for flag in flags.chars().filter(|c| !c.is_whitespace()) {
+            match flag {
+                'i' => options.ignore_case = true,
+                'n' => options.numeric = true,
+                _ => return Err(format!("Invalid sort option: {}", flag)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Sorts lines. The sort is stable, so lines which compare equal keep their order.
+pub fn sort(lines: &mut [String], options: SortOptions) {
+    lines.sort_by(|a, b| {
+        let ordering = match (options.numeric, options.ignore_case) {
+            (true, _) => first_number(a).cmp(&first_number(b)),
+            (false, true) => a.to_lowercase().cmp(&b.to_lowercase()),
+            (false, false) => a.cmp(b),
+        };
+        match options.descending {
+            true => ordering.reverse(),
+            false => ordering,
+        }
+    });
+}
+
+/// Removes repeated lines, keeping the first occurrence of each line.
+pub fn unique(lines: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    lines
+        .into_iter()
+        .filter(|line| seen.insert(line.clone()))
+        .collect()
+}
+
+/// The first integer in a line, including a leading minus sign.
+fn first_number(line: &str) -> Option<i128> {
+    let start = line.find(|c: char| c.is_ascii_digit())?;
+    let digits = &line[start..];
+    let end = digits
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+    let number: i128 = digits[..end].parse().unwrap_or(i128::MAX);
+    match line[..start].ends_with('-') {
+        true => Some(-number),
+        false => Some(number),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split(' ').map(String::from).collect()
+    }
+
+    #[test_case("b a C", "", false, "C a b";              "default")]
+    #[test_case("b a C", "i", false, "a b C";             "ignore_case")]
+    #[test_case("x10 x9 -3 none", "n", false, "none -3 x9 x10"; "numeric")]
+    #[test_case("b a c", "", true, "c b a";               "descending")]
+    fn sort_lines(text: &str, flags: &str, descending: bool, expected: &str) {
+        let mut actual = lines(text);
+        sort(&mut actual, SortOptions::parse(flags, descending).unwrap());
+        assert_eq!(lines(expected), actual);
+    }
+
+    #[test]
+    fn unique_keeps_first_occurrence() {
+        assert_eq!(lines("b a c"), unique(lines("b a b c a")));
+    }
+
+    #[test]
+    fn parse_invalid_option() {
+        assert!(SortOptions::parse("x", false).is_err());
+    }
+}
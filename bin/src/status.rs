@@ -0,0 +1,73 @@
+use std::collections::BTreeMap;
+
+/// A subsystem which reports what it's doing in the status line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Subsystem {
+    /// Keys being recorded into a macro.
+    Macro,
+    /// A shared editing session being hosted or joined.
+    Session,
+    /// A file being followed as it grows.
+    Follow,
+}
+
+/// The segments added to the status line by subsystems which work in the background. Each
+/// subsystem sets or clears its own segment by posting an event, so the status line doesn't
+/// need to ask each of them what they're doing.
+#[derive(Debug, Default)]
+pub struct Segments {
+    segments: BTreeMap<Subsystem, String>,
+}
+
+impl Segments {
+    pub fn new() -> Segments {
+        Segments::default()
+    }
+
+    /// Sets the text of a subsystem's segment, or removes the segment.
+    ///
+    /// # Arguments
+    ///
+    /// * 'subsystem' - The subsystem the segment belongs to.
+    /// * 'text' - The segment's text, or `None` to remove it. Empty text removes it too.
+    pub fn set(&mut self, subsystem: Subsystem, text: Option<String>) {
+        match text.filter(|text| !text.is_empty()) {
+            Some(text) => self.segments.insert(subsystem, text),
+            None => self.segments.remove(&subsystem),
+        };
+    }
+
+    /// The segments in a fixed order, each in brackets after a space.
+    pub fn render(&self) -> String {
+        self.segments
+            .values()
+            .map(|text| format!(" [{}]", text))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_are_rendered_in_order() {
+        let mut segments = Segments::new();
+        assert_eq!("", segments.render());
+        segments.set(Subsystem::Follow, Some(String::from("Following")));
+        segments.set(Subsystem::Macro, Some(String::from("Recording @q")));
+        assert_eq!(" [Recording @q] [Following]", segments.render());
+    }
+
+    #[test]
+    fn segments_are_replaced_and_removed() {
+        let mut segments = Segments::new();
+        segments.set(Subsystem::Session, Some(String::from("Sharing")));
+        segments.set(Subsystem::Session, Some(String::from("Connected")));
+        assert_eq!(" [Connected]", segments.render());
+        segments.set(Subsystem::Session, Some(String::new()));
+        segments.set(Subsystem::Macro, Some(String::from("Recording @a")));
+        segments.set(Subsystem::Macro, None);
+        assert_eq!("", segments.render());
+    }
+}
@@ -0,0 +1,398 @@
+use std::ops::{Range, RangeInclusive};
+
+use regex::{Regex, RegexBuilder};
+
+/// A substitution, written `s/pattern/replacement/flags`. The pattern is a regular expression,
+/// and the replacement can refer to what it matched with `&` or `\0`, and to its capture groups
+/// with `\1` to `\9`.
+#[derive(Debug, Clone)]
+pub struct Substitution {
+    regex: Regex,
+    /// The replacement, rewritten for the regex crate, which refers to groups as `${1}`.
+    replacement: String,
+    /// Whether every match on a line is replaced, rather than only the first.
+    pub global: bool,
+    /// Whether each replacement is confirmed before it's made.
+    pub confirm: bool,
+}
+
+impl PartialEq for Substitution {
+    fn eq(&self, other: &Substitution) -> bool {
+        self.regex.as_str() == other.regex.as_str()
+            && self.replacement == other.replacement
+            && (self.global, self.confirm) == (other.global, other.confirm)
+    }
+}
+
+impl Substitution {
+    /// Parses a substitution from the text after the `s`. The first character is the delimiter,
+    /// which can be written in the pattern or replacement by escaping it. The replacement and
+    /// flags can be left out, along with the delimiters before them.
+    ///
+    /// # Arguments
+    ///
+    /// * 'input' - The delimited pattern, replacement and flags, such as `/a(b)/\1/g`.
+    pub fn parse(input: &str) -> Result<Substitution, String> {
+        let Some(delimiter) = input.chars().next() else {
+            return Err(String::from("No pattern entered"));
+        };
+        let mut fields = split_fields(&input[delimiter.len_utf8()..], delimiter).into_iter();
+        let pattern = fields.next().unwrap_or_default();
+        let replacement = fields.next().unwrap_or_default();
+        let flags = fields.next().unwrap_or_default();
+        if pattern.is_empty() {
+            return Err(String::from("No pattern entered"));
+        }
+
+        let mut builder = RegexBuilder::new(&unescape_pattern(&pattern, delimiter));
+        let (mut global, mut confirm) = (false, false);
+        for flag in flags.chars() {
+            match flag {
+                'g' => global = true,
+                'c' => confirm = true,
+                'i' => {
+                    builder.case_insensitive(true);
+                }
+                flag => return Err(format!("Unknown flag: {}", flag)),
+            }
+        }
+        let regex = builder.build().map_err(|e| {
+            // Syntax errors show the pattern over several lines, ending with the problem.
+            let message = e.to_string();
+            let problem = message.lines().last().unwrap_or_default();
+            format!("Invalid pattern: {}", problem.trim_start_matches("error: "))
+        })?;
+
+        Ok(Substitution {
+            regex,
+            replacement: convert_replacement(&replacement),
+            global,
+            confirm,
+        })
+    }
+
+    /// The pattern, as it's shown in messages.
+    pub fn pattern(&self) -> &str {
+        self.regex.as_str()
+    }
+
+    /// Finds the first match in a line starting at or after a byte offset.
+    pub fn find(&self, line: &str, from: usize) -> Option<Range<usize>> {
+        self.regex.find_at(line, from).map(|m| m.range())
+    }
+
+    /// The text a match is replaced with, with its references filled in.
+    ///
+    /// # Arguments
+    ///
+    /// * 'line' - The line the match was found in.
+    /// * 'range' - The bytes of the line matched, as returned by `find`.
+    pub fn expand(&self, line: &str, range: Range<usize>) -> String {
+        let mut expanded = String::new();
+        if let Some(captures) = self.regex.captures_at(line, range.start) {
+            captures.expand(&self.replacement, &mut expanded);
+        }
+        expanded
+    }
+
+    /// Replaces the first match in a line, or every match for a global substitution. Returns
+    /// the new line along with the columns of the replacements in it, or None if nothing
+    /// matched. The new line may contain line breaks, if the replacement does.
+    pub fn replace(&self, line: &str) -> Option<(String, Vec<Range<u32>>)> {
+        let limit = if self.global { usize::MAX } else { 1 };
+        let mut replaced = String::new();
+        let mut columns = vec![];
+        let mut last = 0;
+        for captures in self.regex.captures_iter(line).take(limit) {
+            let range = captures.get(0).map_or(0..0, |m| m.range());
+            replaced += &line[last..range.start];
+            let start = replaced.chars().count() as u32;
+            captures.expand(&self.replacement, &mut replaced);
+            columns.push(start..replaced.chars().count() as u32);
+            last = range.end;
+        }
+        if columns.is_empty() {
+            return None;
+        }
+        replaced += &line[last..];
+        Some((replaced, columns))
+    }
+}
+
+/// A substitution made a match at a time, asking before each replacement. Matches are found
+/// from the start of the lines onwards, and the search carries on after each replacement, so
+/// text a replacement inserts is never matched.
+#[derive(Debug)]
+pub struct Confirmation {
+    pub substitution: Substitution,
+    /// The line the search for the next match starts on, and the byte it starts from.
+    row: u32,
+    offset: usize,
+    /// The last line to search, which moves as replacements add line breaks.
+    last: u32,
+    /// The match being confirmed, as its line and the bytes of the line it covers.
+    current: Option<(u32, Range<usize>)>,
+    /// The number of replacements made so far.
+    pub count: usize,
+}
+
+impl Confirmation {
+    /// Constructs a confirmation which hasn't found its first match yet.
+    ///
+    /// # Arguments
+    ///
+    /// * 'substitution' - The substitution to make.
+    /// * 'lines' - The first and last lines to make it on.
+    pub fn new(substitution: Substitution, lines: RangeInclusive<u32>) -> Confirmation {
+        Confirmation {
+            substitution,
+            row: *lines.start(),
+            offset: 0,
+            last: *lines.end(),
+            current: None,
+            count: 0,
+        }
+    }
+
+    /// The match being confirmed, as its line and the bytes of the line it covers.
+    pub fn current(&self) -> Option<(u32, Range<usize>)> {
+        self.current.clone()
+    }
+
+    /// Finds the next match to confirm, which becomes the current match. Returns None once
+    /// there are no more.
+    ///
+    /// # Arguments
+    ///
+    /// * 'line' - Reads a line of the document.
+    pub fn next(&mut self, line: impl Fn(u32) -> String) -> Option<(u32, Range<usize>)> {
+        self.current = None;
+        while self.row <= self.last {
+            let text = line(self.row);
+            let found = (self.offset <= text.len())
+                .then(|| self.substitution.find(&text, self.offset))
+                .flatten();
+            if let Some(range) = found {
+                self.current = Some((self.row, range));
+                break;
+            }
+            self.row += 1;
+            self.offset = 0;
+        }
+        self.current()
+    }
+
+    /// Replaces the current match, returning its line and the lines to put in its place, which
+    /// are more than one if the replacement has line breaks.
+    ///
+    /// # Arguments
+    ///
+    /// * 'line' - The text of the current match's line.
+    pub fn accept(&mut self, line: &str) -> Option<(u32, Vec<String>)> {
+        let (row, range) = self.current.take()?;
+        let before =
+            line[..range.start].to_string() + &self.substitution.expand(line, range.clone());
+        let lines: Vec<String> = (before.clone() + &line[range.end..])
+            .split('\n')
+            .map(String::from)
+            .collect();
+        let breaks = lines.len() as u32 - 1;
+        self.last += breaks;
+        self.count += 1;
+
+        let end = before.len() - before.rfind('\n').map_or(0, |i| i + 1);
+        self.carry_on(row + breaks, &lines[lines.len() - 1], end, range.is_empty());
+        Some((row, lines))
+    }
+
+    /// Leaves the current match as it is.
+    ///
+    /// # Arguments
+    ///
+    /// * 'line' - The text of the current match's line.
+    pub fn skip(&mut self, line: &str) {
+        if let Some((row, range)) = self.current.take() {
+            self.carry_on(row, line, range.end, range.is_empty());
+        }
+    }
+
+    /// Moves the search on past a match, to the next line unless every match on a line is
+    /// replaced. An empty match moves it on a character further, so it isn't found again.
+    fn carry_on(&mut self, row: u32, line: &str, end: usize, empty: bool) {
+        let next = match empty {
+            true => line[end..].chars().next().map(|c| end + c.len_utf8()),
+            false => Some(end),
+        };
+        match next.filter(|_| self.substitution.global) {
+            Some(offset) if offset <= line.len() => (self.row, self.offset) = (row, offset),
+            _ => (self.row, self.offset) = (row + 1, 0),
+        }
+    }
+
+    /// The current match's line as it would be after replacing the match, along with the
+    /// columns of the replacement, or None for other lines.
+    pub fn preview(&self, row: u32, line: &str) -> Option<(String, Vec<Range<u32>>)> {
+        let (_, range) = self.current.clone().filter(|(r, _)| *r == row)?;
+        let mut preview = line[..range.start].to_string();
+        let start = preview.chars().count() as u32;
+        preview += &self.substitution.expand(line, range.clone());
+        let end = preview.chars().count() as u32;
+        preview += &line[range.end..];
+        Some((preview, std::iter::once(start..end).collect()))
+    }
+}
+
+/// Splits text on a delimiter, leaving delimiters escaped with a backslash, along with their
+/// backslashes, in the fields.
+fn split_fields(input: &str, delimiter: char) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().expect("there's always a field");
+        match c {
+            '\\' => {
+                field.push(c);
+                field.extend(chars.next());
+            }
+            c if c == delimiter => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+    fields
+}
+
+/// Turns escaped delimiters in a pattern back into the delimiter, which is matched literally.
+fn unescape_pattern(pattern: &str, delimiter: char) -> String {
+    pattern.replace(
+        &format!("\\{}", delimiter),
+        &regex::escape(&delimiter.to_string()),
+    )
+}
+
+/// Rewrites a replacement for the regex crate. `&` and `\0` become the whole match, `\1` to `\9`
+/// become capture groups and `\n` and `\t` become a line break and a tab. Any other escaped
+/// character, such as the delimiter, and `$` stand for themselves.
+fn convert_replacement(replacement: &str) -> String {
+    let mut converted = String::new();
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => converted += "${0}",
+            '$' => converted += "$$",
+            '\\' => match chars.next() {
+                Some(digit @ '0'..='9') => converted += &format!("${{{}}}", digit),
+                Some('n') => converted.push('\n'),
+                Some('t') => converted.push('\t'),
+                Some('$') => converted += "$$",
+                Some(c) => converted.push(c),
+                None => converted.push('\\'),
+            },
+            c => converted.push(c),
+        }
+    }
+    converted
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn replace(input: &str, line: &str) -> Option<String> {
+        let substitution = Substitution::parse(input).unwrap();
+        substitution.replace(line).map(|(replaced, _)| replaced)
+    }
+
+    #[test_case("/o/0/", "foo boo", Some("f0o boo");                 "first_match")]
+    #[test_case("/o/0/g", "foo boo", Some("f00 b00");                "global")]
+    #[test_case("/O/0/gi", "foo", Some("f00");                       "ignore_case")]
+    #[test_case(r"/(\w+) (\w+)/\2 \1/", "hello world", Some("world hello"); "capture_groups")]
+    #[test_case("/o+/[&]/", "foo", Some("f[oo]");                    "whole_match")]
+    #[test_case(r"/o/\&$1/", "foo", Some("f&$1o");                   "literals")]
+    #[test_case(r"#/#\##", "a/b", Some("a#b");                       "other_delimiter")]
+    #[test_case(r"/\//-/g", "a/b/c", Some("a-b-c");                  "escaped_delimiter")]
+    #[test_case(r"/, /\n/", "a, b", Some("a\nb");                    "line_break")]
+    #[test_case("/x", "axa", Some("aa");                             "no_replacement")]
+    #[test_case("/z/y/", "abc", None;                                "no_match")]
+    fn substitute(input: &str, line: &str, expected: Option<&str>) {
+        assert_eq!(expected.map(String::from), replace(input, line));
+    }
+
+    #[test]
+    fn replacement_columns() {
+        let substitution = Substitution::parse("/é/ee/g").unwrap();
+        let (_, columns) = substitution.replace("éaé").unwrap();
+        assert_eq!(vec![0..2, 3..5], columns);
+    }
+
+    #[test]
+    fn flags() {
+        let substitution = Substitution::parse("/a/b/cg").unwrap();
+        assert!(substitution.confirm && substitution.global);
+    }
+
+    #[test_case("", "No pattern entered";           "empty")]
+    #[test_case("//b/", "No pattern entered";       "empty_pattern")]
+    #[test_case("/a/b/x", "Unknown flag: x";        "unknown_flag")]
+    #[test_case("/(/b/", "Invalid pattern: unclosed group"; "invalid_pattern")]
+    fn parse_invalid(input: &str, expected: &str) {
+        assert_eq!(Err(expected.to_string()), Substitution::parse(input));
+    }
+
+    fn confirm(input: &str, lines: &mut Vec<String>, answers: &str) -> usize {
+        let substitution = Substitution::parse(input).unwrap();
+        let last = lines.len() as u32;
+        let mut confirmation = Confirmation::new(substitution, 1..=last);
+        let mut answers = answers.chars();
+        while let Some((row, _)) = confirmation.next(|row| lines[row as usize - 1].clone()) {
+            let line = lines[row as usize - 1].clone();
+            match answers.next() {
+                Some('y') => {
+                    let (row, replaced) = confirmation.accept(&line).unwrap();
+                    let index = row as usize - 1;
+                    lines.splice(index..index + 1, replaced);
+                }
+                _ => confirmation.skip(&line),
+            }
+        }
+        confirmation.count
+    }
+
+    #[test_case("/a/b/g", "yny", "bab bb";   "global")]
+    #[test_case("/a/b/", "yy", "bab bb";       "first_on_each_line")]
+    #[test_case("/a/aa/g", "yyy", "aaaab aab"; "replacement_not_matched")]
+    #[test_case("/x*/-/g", "yyyyyyy", "-a-a-b- -a-b-"; "empty_matches")]
+    fn confirm_matches(input: &str, answers: &str, expected: &str) {
+        let mut lines = vec![String::from("aab"), String::from("ab")];
+        confirm(input, &mut lines, answers);
+        assert_eq!(expected, lines.join(" "));
+    }
+
+    #[test]
+    fn confirm_line_breaks() {
+        let mut lines = vec![String::from("a,b,c"), String::from("d,e")];
+        assert_eq!(2, confirm(r"/,/\n/g", &mut lines, "yyn"));
+        assert_eq!(vec!["a", "b", "c", "d,e"], lines);
+    }
+
+    #[test]
+    fn confirm_preview() {
+        let substitution = Substitution::parse("/b+/[&]/").unwrap();
+        let mut confirmation = Confirmation::new(substitution, 1..=1);
+        assert_eq!(Some((1, 1..3)), confirmation.next(|_| String::from("abbc")));
+        let (preview, columns) = confirmation.preview(1, "abbc").unwrap();
+        assert_eq!("a[bb]c", preview);
+        assert_eq!((1, Some(&(1..5))), (columns.len(), columns.first()));
+        assert_eq!(None, confirmation.preview(2, "abbc"));
+    }
+
+    #[test]
+    fn expand_match() {
+        let substitution = Substitution::parse(r"/(\d+)/<\1>/").unwrap();
+        let line = "ab 12 cd";
+        let range = substitution.find(line, 0).unwrap();
+        assert_eq!(3..5, range);
+        assert_eq!("<12>", substitution.expand(line, range));
+    }
+}
@@ -0,0 +1,117 @@
+use crate::textobject::{Kind, TextObject};
+
+/// The text added before and after a piece of text to surround it, such as a pair of brackets
+/// or an opening and closing tag.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pair {
+    pub open: String,
+    pub close: String,
+}
+
+impl Pair {
+    /// Parses a pair from either of its brackets, `b` for `()` or `B` for `{}`, a tag such as
+    /// `<a href="/">`, or any other symbol, which is used on both sides.
+    pub fn parse(name: &str) -> Result<Pair, String> {
+        let pair = |open: &str, close: &str| {
+            Ok(Pair {
+                open: open.to_string(),
+                close: close.to_string(),
+            })
+        };
+        if name.len() > 2 && name.starts_with('<') && name.ends_with('>') {
+            let tag: String = name[1..]
+                .chars()
+                .take_while(|c| !c.is_whitespace() && *c != '>' && *c != '/')
+                .collect();
+            return pair(name, &format!("</{}>", tag));
+        }
+
+        let mut chars = name.chars();
+        match (chars.next(), chars.next()) {
+            (Some('(' | ')' | 'b'), None) => pair("(", ")"),
+            (Some('[' | ']'), None) => pair("[", "]"),
+            (Some('{' | '}' | 'B'), None) => pair("{", "}"),
+            (Some('<' | '>'), None) => pair("<", ">"),
+            (Some(c), None) if !c.is_alphanumeric() && !c.is_whitespace() => {
+                pair(&c.to_string(), &c.to_string())
+            }
+            (None, _) => Err(String::from("No pair entered")),
+            _ => Err(format!("Unknown pair: {}", name)),
+        }
+    }
+}
+
+/// Parses the pair surrounding the cursor to change or delete, as a text object taking in the
+/// pair. Brackets are named by either of them, `b` or `B`, `t` is the closest tag, and any other
+/// symbol is looked for on both sides on the cursor's line.
+pub fn target(name: &str) -> Result<TextObject, String> {
+    let object = |kind| Ok(TextObject { around: true, kind });
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some('t'), None) => object(Kind::Tag),
+        (Some('b' | 'B'), None) => TextObject::parse(&format!("a{}", name)),
+        (Some(c), None) if c.is_alphanumeric() || c.is_whitespace() => {
+            Err(format!("Unknown pair: {}", name))
+        }
+        (Some(c), None) => {
+            TextObject::parse(&format!("a{}", c)).or_else(|_| object(Kind::Quote(c)))
+        }
+        (None, _) => Err(String::from("No pair entered")),
+        _ => Err(format!("Unknown pair: {}", name)),
+    }
+}
+
+/// Splits a pair from the text after it, where a tag can contain spaces.
+pub fn split_pair(argument: &str) -> (&str, &str) {
+    let end = match argument.starts_with('<') {
+        true => argument.find('>').map(|i| i + 1),
+        false => argument.find(char::is_whitespace),
+    };
+    match end {
+        Some(end) => (&argument[..end], argument[end..].trim_start()),
+        None => (argument, ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(")", "(", ")";                              "closing_bracket")]
+    #[test_case("B", "{", "}";                              "braces")]
+    #[test_case("\"", "\"", "\"";                           "quotes")]
+    #[test_case("*", "*", "*";                              "symbol")]
+    #[test_case("<a href=\"/\">", "<a href=\"/\">", "</a>"; "tag")]
+    fn parse_pairs(name: &str, open: &str, close: &str) {
+        let expected = Pair {
+            open: open.to_string(),
+            close: close.to_string(),
+        };
+        assert_eq!(Ok(expected), Pair::parse(name));
+    }
+
+    #[test_case("", "No pair entered";     "empty")]
+    #[test_case("x", "Unknown pair: x";    "letter")]
+    #[test_case("((", "Unknown pair: ((";  "too_long")]
+    fn parse_pair_errors(name: &str, expected: &str) {
+        assert_eq!(Err(String::from(expected)), Pair::parse(name));
+    }
+
+    #[test_case("b", Ok(Kind::Bracket('(', ')'));            "block")]
+    #[test_case("]", Ok(Kind::Bracket('[', ']'));            "closing_bracket")]
+    #[test_case("t", Ok(Kind::Tag);                          "tag")]
+    #[test_case("*", Ok(Kind::Quote('*'));                   "symbol")]
+    #[test_case("w", Err(String::from("Unknown pair: w"));   "word")]
+    fn targets(name: &str, expected: Result<Kind, String>) {
+        assert_eq!(expected, target(name).map(|object| object.kind));
+    }
+
+    #[test_case("( iw", ("(", "iw");                      "pair_and_object")]
+    #[test_case("<a href=\"/\"> ip", ("<a href=\"/\">", "ip"); "tag_with_spaces")]
+    #[test_case("\"", ("\"", "");                         "pair_alone")]
+    fn split_pairs(argument: &str, expected: (&str, &str)) {
+        assert_eq!(expected, split_pair(argument));
+    }
+}
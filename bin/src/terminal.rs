@@ -1,78 +1,430 @@
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
 use std::io::stdout;
 use std::io::Write;
 
 use crossterm::cursor;
+use crossterm::event::{self, KeyEvent, KeyEventKind};
 use crossterm::execute;
+use crossterm::queue;
 use crossterm::terminal;
 
-pub struct Terminal {}
+use crate::colors::ColorSupport;
+
+pub struct Terminal {
+    /// The screen frames are drawn to instead of the terminal, when running headless.
+    headless: Option<RefCell<Screen>>,
+    capabilities: Capabilities,
+    colors: ColorSupport,
+    /// The size the terminal last reported, used when its size can't be read.
+    last_size: Cell<(u16, u16)>,
+    /// The error from the last time the terminal's size couldn't be read, until it's reported.
+    size_error: RefCell<Option<std::io::Error>>,
+}
+
+impl Default for Terminal {
+    fn default() -> Terminal {
+        Terminal::new()
+    }
+}
 
 impl Terminal {
     pub fn new() -> Terminal {
-        Terminal {}
+        Terminal {
+            headless: None,
+            capabilities: Capabilities::detect(),
+            colors: ColorSupport::detect(),
+            last_size: Cell::new((80, 24)),
+            size_error: RefCell::new(None),
+        }
+    }
+
+    /// Constructs a terminal which draws frames to a screen in memory, for tests and benchmarks.
+    ///
+    /// # Arguments
+    ///
+    /// * 'width' - The number of columns on the screen.
+    /// * 'height' - The number of rows on the screen.
+    pub fn headless(width: u16, height: u16) -> Terminal {
+        Terminal {
+            headless: Some(RefCell::new(Screen::new(width, height))),
+            capabilities: Capabilities::for_console(Console::Unix),
+            colors: ColorSupport::TrueColor,
+            last_size: Cell::new((width, height)),
+            size_error: RefCell::new(None),
+        }
+    }
+
+    /// Sets the colours the terminal supports, overriding the ones detected from the environment.
+    pub fn set_colors(&mut self, colors: ColorSupport) {
+        self.colors = colors;
+    }
+
+    /// Prepares text to be written to the terminal, mapping its colours to ones the terminal
+    /// supports, or removing the escape sequences which style it if they aren't interpreted.
+    fn styled<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self.capabilities.ansi {
+            true => self.colors.downgrade(text),
+            false => console::strip_ansi_codes(text),
+        }
+    }
+
+    /// The frames drawn so far, if the terminal is headless.
+    pub fn screen(&self) -> Option<Screen> {
+        self.headless.as_ref().map(|screen| screen.borrow().clone())
     }
 
     pub fn startup(&self) -> std::io::Result<()> {
-        execute!(stdout(), terminal::EnterAlternateScreen)?;
+        if self.headless.is_some() {
+            return Ok(());
+        }
+        execute!(
+            stdout(),
+            terminal::EnterAlternateScreen,
+            event::EnableMouseCapture,
+            event::EnableFocusChange
+        )?;
+        // Save the window's title on the terminal's title stack, so it can be restored on exit.
+        if self.capabilities.ansi {
+            write!(stdout(), "\x1b[22;0t")?;
+        }
+        if self.capabilities.bracketed_paste {
+            execute!(stdout(), event::EnableBracketedPaste)?;
+        }
         terminal::enable_raw_mode()
     }
 
     pub fn shutdown(&self) -> std::io::Result<()> {
+        if self.headless.is_some() {
+            return Ok(());
+        }
+        execute!(
+            stdout(),
+            event::DisableMouseCapture,
+            event::DisableFocusChange
+        )?;
+        if self.capabilities.bracketed_paste {
+            execute!(stdout(), event::DisableBracketedPaste)?;
+        }
+        if self.capabilities.cursor_shape {
+            execute!(stdout(), cursor::SetCursorStyle::DefaultUserShape)?;
+        }
+        if self.capabilities.ansi {
+            write!(stdout(), "\x1b[23;0t")?;
+        }
         stdout().flush()?;
+        terminal::disable_raw_mode()?;
         execute!(stdout(), terminal::LeaveAlternateScreen)
     }
 
+    /// Restores the terminal and stops the process, as if Ctrl+Z had been pressed in the shell.
+    /// Once the process is resumed the terminal is set up for the editor again.
+    #[cfg(unix)]
+    pub fn suspend(&self) -> std::io::Result<()> {
+        if self.headless.is_some() {
+            return Ok(());
+        }
+        self.shutdown()?;
+        // SAFETY: raise has no preconditions, and only returns once the process is continued.
+        if unsafe { libc::raise(libc::SIGTSTP) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        self.startup()
+    }
+
     pub fn size(&self) -> TerminalSize {
-        let (width, height) = terminal::size().expect("");
+        if let Some(screen) = &self.headless {
+            let screen = screen.borrow();
+            return TerminalSize {
+                width: screen.width,
+                height: screen.height,
+            };
+        }
+        let (width, height) = match terminal::size() {
+            Ok(size) => {
+                self.last_size.set(size);
+                size
+            }
+            Err(e) => {
+                self.size_error.replace(Some(e));
+                self.last_size.get()
+            }
+        };
         TerminalSize { width, height }
     }
 
-    pub fn cursor_pos(&self) -> CursorPosition {
-        let (x, y) = cursor::position().expect("");
-        CursorPosition { x, y }
+    /// Takes the error from the last time the terminal's size couldn't be read, so it can be
+    /// reported. The last known size is used in the meantime.
+    pub fn take_error(&self) -> Option<std::io::Error> {
+        self.size_error.take()
+    }
+
+    /// Sets the title of the terminal's window. The original title is restored on shutdown.
+    pub fn set_title(&self, title: &str) -> std::io::Result<()> {
+        if let Some(screen) = &self.headless {
+            screen.borrow_mut().title = Some(title.to_string());
+            return Ok(());
+        }
+        execute!(stdout(), terminal::SetTitle(title))
+    }
+
+    /// Draws a frame to the terminal, then places the cursor at the given position. The cursor is
+    /// left hidden if there's no position to place it at.
+    pub fn render(&self, frame: String, cursor: Option<CursorPosition>) -> std::io::Result<()> {
+        if let Some(screen) = &self.headless {
+            let mut screen = screen.borrow_mut();
+            screen.draw(&frame);
+            screen.cursor = cursor.map(|c| (c.x, c.y));
+            return Ok(());
+        }
+
+        // Clear the terminal
+        execute!(stdout(), cursor::Hide)?;
+        execute!(stdout(), cursor::MoveTo(0, 0))?;
+        execute!(stdout(), terminal::Clear(terminal::ClearType::All))?;
+
+        print!("{}", self.styled(&frame));
+        stdout().flush()?;
+
+        if let Some(cursor) = cursor {
+            execute!(stdout(), cursor::MoveTo(cursor.x, cursor.y))?;
+            if self.capabilities.cursor_shape {
+                execute!(stdout(), cursor.shape.style())?;
+            }
+            execute!(stdout(), cursor::Show)?;
+        }
+
+        Ok(())
+    }
+
+    /// Redraws individual rows of the terminal, leaving the rest of the screen untouched, then
+    /// places the cursor at the given position.
+    ///
+    /// # Arguments
+    ///
+    /// * 'rows' - The screen row to draw each line on, along with its contents.
+    /// * 'cursor' - The position to place the cursor at once the rows have been drawn, or None to
+    ///   leave it hidden.
+    pub fn render_rows(
+        &self,
+        rows: Vec<(u16, String)>,
+        cursor: Option<CursorPosition>,
+    ) -> std::io::Result<()> {
+        if let Some(screen) = &self.headless {
+            let mut screen = screen.borrow_mut();
+            for (y, row) in rows {
+                screen.draw_row(y, row);
+            }
+            screen.cursor = cursor.map(|c| (c.x, c.y));
+            return Ok(());
+        }
+
+        let mut stdout = stdout();
+        queue!(stdout, cursor::Hide)?;
+        for (y, row) in rows {
+            queue!(
+                stdout,
+                cursor::MoveTo(0, y),
+                terminal::Clear(terminal::ClearType::CurrentLine)
+            )?;
+            write!(stdout, "{}", self.styled(&row))?;
+        }
+        if let Some(cursor) = cursor {
+            queue!(stdout, cursor::MoveTo(cursor.x, cursor.y))?;
+            if self.capabilities.cursor_shape {
+                queue!(stdout, cursor.shape.style())?;
+            }
+            queue!(stdout, cursor::Show)?;
+        }
+        stdout.flush()
+    }
+}
+
+/// The kind of terminal the editor is running in, which decides what it supports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Console {
+    /// A terminal emulator on a Unix-like system.
+    Unix,
+    /// Windows Terminal, which supports the same escape sequences as Unix terminals.
+    WindowsTerminal,
+    /// The console built into Windows, which only understands escape sequences once virtual
+    /// terminal processing has been enabled, and never supports bracketed paste.
+    Classic { ansi: bool },
+}
+
+impl Console {
+    /// Detects the terminal the editor is running in. Windows Terminal sets `WT_SESSION` for the
+    /// processes it starts, so any other Windows terminal is treated as the classic console.
+    pub fn detect() -> Console {
+        if cfg!(windows) {
+            match std::env::var_os("WT_SESSION") {
+                Some(_) => Console::WindowsTerminal,
+                None => Console::Classic {
+                    ansi: supports_ansi(),
+                },
+            }
+        } else {
+            Console::Unix
+        }
     }
+}
 
-    pub fn move_cursor_to(&self, pos: CursorPosition) {
-        execute!(stdout(), cursor::MoveTo(pos.x, pos.y)).expect("");
+#[cfg(windows)]
+fn supports_ansi() -> bool {
+    crossterm::ansi_support::supports_ansi()
+}
+
+#[cfg(not(windows))]
+fn supports_ansi() -> bool {
+    true
+}
+
+/// What the terminal supports beyond moving the cursor and writing text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capabilities {
+    /// Whether escape sequences written as part of the text are interpreted. Styles are left out
+    /// of the frames drawn to terminals which don't interpret them.
+    pub ansi: bool,
+    /// Whether pasted text is reported as a paste, rather than as key presses.
+    pub bracketed_paste: bool,
+    /// Whether the cursor's shape can be changed.
+    pub cursor_shape: bool,
+}
+
+impl Capabilities {
+    pub fn detect() -> Capabilities {
+        Capabilities::for_console(Console::detect())
     }
 
-    pub fn move_cursor_left(&self, u: u16) -> std::io::Result<()> {
-        execute!(stdout(), cursor::MoveLeft(u))
+    /// The capabilities of a kind of terminal, which doesn't depend on the platform the editor
+    /// was built for.
+    ///
+    /// # Arguments
+    ///
+    /// * 'console' - The kind of terminal.
+    pub fn for_console(console: Console) -> Capabilities {
+        match console {
+            Console::Unix => Capabilities {
+                ansi: true,
+                bracketed_paste: true,
+                cursor_shape: true,
+            },
+            Console::WindowsTerminal => Capabilities {
+                ansi: true,
+                bracketed_paste: true,
+                cursor_shape: true,
+            },
+            Console::Classic { ansi } => Capabilities {
+                ansi,
+                bracketed_paste: false,
+                cursor_shape: ansi,
+            },
+        }
     }
+}
 
-    pub fn move_cursor_right(&self, u: u16) -> std::io::Result<()> {
-        execute!(stdout(), cursor::MoveRight(u))
+/// Prepares a key event to be mapped to an editor event. Key releases, which the Windows console
+/// reports for every key, are dropped, and repeats of a held key are treated as presses.
+///
+/// # Arguments
+///
+/// * 'event' - The key event reported by the terminal.
+pub fn normalize_key(event: KeyEvent) -> Option<KeyEvent> {
+    match event.kind {
+        KeyEventKind::Press => Some(event),
+        KeyEventKind::Repeat => Some(KeyEvent {
+            kind: KeyEventKind::Press,
+            ..event
+        }),
+        KeyEventKind::Release => None,
     }
+}
+
+/// The rows drawn to a headless terminal, as they'd be shown on screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Screen {
+    width: u16,
+    height: u16,
+    rows: Vec<String>,
+    /// Where the cursor was placed, if it's shown.
+    cursor: Option<(u16, u16)>,
+    title: Option<String>,
+}
 
-    pub fn move_cursor_up(&self, u: u16) -> std::io::Result<()> {
-        execute!(stdout(), cursor::MoveUp(u))
+impl Screen {
+    fn new(width: u16, height: u16) -> Screen {
+        Screen {
+            width,
+            height,
+            rows: vec![String::new(); height as usize],
+            cursor: None,
+            title: None,
+        }
     }
 
-    pub fn move_cursor_down(&self, u: u16) -> std::io::Result<()> {
-        execute!(stdout(), cursor::MoveDown(u))
+    /// Replaces every row with a frame's rows, which are separated by line breaks.
+    fn draw(&mut self, frame: &str) {
+        self.rows = frame.split("\r\n").map(String::from).collect();
+        self.rows.resize(self.height as usize, String::new());
     }
 
-    pub fn render(&self, frame: String) -> std::io::Result<()> {
-        let (x, y) = crossterm::cursor::position()?;
+    fn draw_row(&mut self, y: u16, row: String) {
+        if let Some(line) = self.rows.get_mut(y as usize) {
+            *line = row;
+        }
+    }
 
-        // Clear the terminal
-        execute!(stdout(), crossterm::cursor::Hide)?;
-        execute!(stdout(), crossterm::cursor::MoveTo(0, 0))?;
-        execute!(stdout(), terminal::Clear(terminal::ClearType::All))?;
+    /// The rows of the screen, including the escape sequences which style them.
+    pub fn rows(&self) -> &[String] {
+        &self.rows
+    }
 
-        print!("{}", frame);
-        stdout().flush()?;
+    /// The title the terminal's window was given, if it's been set.
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
 
-        execute!(stdout(), crossterm::cursor::MoveTo(x, y))?;
-        execute!(stdout(), crossterm::cursor::Show)?;
+    /// The column and row the cursor was placed at, if it's shown.
+    pub fn cursor(&self) -> Option<(u16, u16)> {
+        self.cursor
+    }
 
-        Ok(())
+    /// Writes out the screen as text, one line per row, with escape characters written as `\e`
+    /// so styles can be compared as well. The cursor's position is written on the last line.
+    pub fn dump(&self) -> String {
+        let mut dump = String::new();
+        for row in &self.rows {
+            dump += row.replace('\x1b', "\\e").trim_end();
+            dump += "\n";
+        }
+        dump += &match self.cursor {
+            Some((x, y)) => format!("cursor {},{}\n", x, y),
+            None => String::from("cursor hidden\n"),
+        };
+        dump
     }
 }
 
 pub struct CursorPosition {
     pub x: u16,
     pub y: u16,
+    pub shape: CursorShape,
+}
+
+/// The shape the cursor is drawn with.
+pub enum CursorShape {
+    /// The shape configured in the user's terminal.
+    Default,
+    Underline,
+}
+
+impl CursorShape {
+    fn style(&self) -> cursor::SetCursorStyle {
+        match self {
+            CursorShape::Default => cursor::SetCursorStyle::DefaultUserShape,
+            CursorShape::Underline => cursor::SetCursorStyle::SteadyUnderScore,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -80,3 +432,48 @@ pub struct TerminalSize {
     pub width: u16,
     pub height: u16,
 }
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyModifiers};
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(Console::Unix,                           true,  true;  "unix")]
+    #[test_case(Console::WindowsTerminal,                true,  true;  "windows_terminal")]
+    #[test_case(Console::Classic { ansi: true },  true,  false; "classic_console")]
+    #[test_case(Console::Classic { ansi: false }, false, false; "legacy_console")]
+    fn console_capabilities(console: Console, ansi: bool, paste: bool) {
+        let capabilities = Capabilities::for_console(console);
+        assert_eq!(ansi, capabilities.ansi);
+        assert_eq!(ansi, capabilities.cursor_shape);
+        assert_eq!(paste, capabilities.bracketed_paste);
+    }
+
+    #[test_case(true,  ColorSupport::TrueColor, "\x1b[1;38;5;160mbold\x1b[0m text"; "styles_kept")]
+    #[test_case(true,  ColorSupport::Basic,     "\x1b[1;31mbold\x1b[0m text";       "colours_mapped")]
+    #[test_case(false, ColorSupport::TrueColor, "bold text";                        "styles_removed")]
+    fn styled_text(ansi: bool, colors: ColorSupport, expected: &str) {
+        let terminal = Terminal {
+            capabilities: Capabilities {
+                ansi,
+                ..Capabilities::for_console(Console::Unix)
+            },
+            colors,
+            ..Terminal::headless(1, 1)
+        };
+        assert_eq!(
+            expected,
+            terminal.styled("\x1b[1;38;5;160mbold\x1b[0m text")
+        );
+    }
+
+    #[test_case(KeyEventKind::Press,   Some(KeyEventKind::Press); "press")]
+    #[test_case(KeyEventKind::Repeat,  Some(KeyEventKind::Press); "repeat")]
+    #[test_case(KeyEventKind::Release, None;                      "release")]
+    fn normalize_key_kind(kind: KeyEventKind, expected: Option<KeyEventKind>) {
+        let event = KeyEvent::new_with_kind(KeyCode::Char('a'), KeyModifiers::NONE, kind);
+        assert_eq!(expected, normalize_key(event).map(|e| e.kind));
+    }
+}
@@ -0,0 +1,377 @@
+use std::ops::Range;
+
+use crate::document::CharKind;
+use crate::selection::Position;
+
+/// The number of lines above and below the cursor searched for the brackets and paragraph
+/// around it.
+pub const SCAN_LINES: u32 = 1000;
+
+/// A piece of text around the cursor which can be selected, named like `iw` or `a(`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextObject {
+    /// Whether the object includes what surrounds it, such as the brackets around a block or
+    /// the whitespace after a word.
+    pub around: bool,
+    pub kind: Kind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    /// A run of word characters, of other characters which aren't whitespace, or of whitespace.
+    Word,
+    /// Text between a pair of quotes on the cursor's line.
+    Quote(char),
+    /// Text between a pair of brackets, which can span lines.
+    Bracket(char, char),
+    /// Text between an opening and closing tag, such as `<p>` and `</p>`.
+    Tag,
+    /// Lines up to the blank lines around them.
+    Paragraph,
+}
+
+impl TextObject {
+    pub fn parse(name: &str) -> Result<TextObject, String> {
+        let mut chars = name.chars();
+        let around = match chars.next() {
+            Some('i') => false,
+            Some('a') => true,
+            None => return Err(String::from("No text object entered")),
+            Some(_) => return Err(format!("Unknown text object: {}", name)),
+        };
+        let kind = match (chars.next(), chars.next()) {
+            (Some('w'), None) => Kind::Word,
+            (Some('p'), None) => Kind::Paragraph,
+            (Some(c @ ('"' | '\'' | '`')), None) => Kind::Quote(c),
+            (Some('b' | '(' | ')'), None) => Kind::Bracket('(', ')'),
+            (Some('B' | '{' | '}'), None) => Kind::Bracket('{', '}'),
+            (Some('[' | ']'), None) => Kind::Bracket('[', ']'),
+            (Some('<' | '>'), None) => Kind::Bracket('<', '>'),
+            (Some('t'), None) => Kind::Tag,
+            _ => return Err(format!("Unknown text object: {}", name)),
+        };
+        Ok(TextObject { around, kind })
+    }
+
+    /// Finds the object at the cursor, returning the start of it and the position just after
+    /// its end.
+    ///
+    /// # Arguments
+    ///
+    /// * 'lines' - The lines around the cursor.
+    /// * 'first' - The line number of the first of the lines.
+    /// * 'cursor' - The cursor's position.
+    pub fn find(
+        &self,
+        lines: &[String],
+        first: u32,
+        cursor: Position,
+    ) -> Option<(Position, Position)> {
+        let chars: Vec<Vec<char>> = lines
+            .iter()
+            .map(|line| line.trim_end_matches('\r').chars().collect())
+            .collect();
+        let index = cursor.row.checked_sub(first)? as usize;
+        let line = chars.get(index)?;
+        let at = |index: usize, column: usize| Position {
+            row: first + index as u32,
+            column: column as u32,
+        };
+
+        match self.kind {
+            Kind::Word => {
+                let (start, end) = word(line, cursor.column as usize, self.around)?;
+                Some((at(index, start), at(index, end)))
+            }
+            Kind::Quote(quote) => {
+                let (start, end) = quoted(line, cursor.column as usize, quote)?;
+                match self.around {
+                    true => Some((at(index, start), at(index, end + 1))),
+                    false => Some((at(index, start + 1), at(index, end))),
+                }
+            }
+            Kind::Bracket(open, close) => {
+                let ((open_line, open_column), (close_line, close_column)) =
+                    brackets(&chars, (index, cursor.column as usize), open, close)?;
+                match self.around {
+                    true => Some((at(open_line, open_column), at(close_line, close_column + 1))),
+                    false => Some((at(open_line, open_column + 1), at(close_line, close_column))),
+                }
+            }
+            Kind::Tag => {
+                let text = chars.join(&'\n');
+                let starts: Vec<usize> = std::iter::once(0)
+                    .chain(chars.iter().scan(0, |start, line| {
+                        *start += line.len() + 1;
+                        Some(*start)
+                    }))
+                    .take(chars.len())
+                    .collect();
+                let position = |offset: usize| {
+                    let index = starts.partition_point(|start| *start <= offset) - 1;
+                    at(index, offset - starts[index])
+                };
+                let offset = starts[index] + (cursor.column as usize).min(line.len());
+                let (open, close) = tag_pair(&text, offset)?;
+                match self.around {
+                    true => Some((position(open.start), position(close.end))),
+                    false => Some((position(open.end), position(close.start))),
+                }
+            }
+            Kind::Paragraph => {
+                let blank = |i: usize| chars[i].iter().all(|c| c.is_whitespace());
+                let run = |i: usize| {
+                    let start = (0..i).rev().take_while(|j| blank(*j) == blank(i)).last();
+                    let end = (i + 1..chars.len())
+                        .take_while(|j| blank(*j) == blank(i))
+                        .last();
+                    (start.unwrap_or(i), end.unwrap_or(i))
+                };
+                let (start, mut end) = run(index);
+                // Around a paragraph takes in the blank lines after it too.
+                if self.around && end + 1 < chars.len() {
+                    end = run(end + 1).1;
+                }
+                Some((at(start, 0), at(end, chars[end].len())))
+            }
+        }
+    }
+}
+
+/// The columns of the run of characters of the same kind at a column. Around a word takes in
+/// the whitespace after it, or before it if there's none after.
+fn word(line: &[char], column: usize, around: bool) -> Option<(usize, usize)> {
+    let column = column.min(line.len().checked_sub(1)?);
+    let run = |column: usize| {
+        let kind = CharKind::of(line[column]);
+        let start = (0..column)
+            .rev()
+            .take_while(|i| CharKind::of(line[*i]) == kind)
+            .last()
+            .unwrap_or(column);
+        let end = (column..line.len())
+            .find(|i| CharKind::of(line[*i]) != kind)
+            .unwrap_or(line.len());
+        (start, end)
+    };
+    let (start, end) = run(column);
+    if !around || line[column].is_whitespace() {
+        return Some((start, end));
+    }
+    match line.get(end).is_some_and(|c| c.is_whitespace()) {
+        true => Some((start, run(end).1)),
+        false if start > 0 && line[start - 1].is_whitespace() => Some((run(start - 1).0, end)),
+        false => Some((start, end)),
+    }
+}
+
+/// The columns of a pair of quotes on a line around a column, or of the first pair after it.
+/// Quotes escaped with a backslash are skipped.
+fn quoted(line: &[char], column: usize, quote: char) -> Option<(usize, usize)> {
+    let quotes: Vec<usize> = (0..line.len())
+        .filter(|i| line[*i] == quote && (*i == 0 || line[i - 1] != '\\'))
+        .collect();
+    quotes
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .find(|(_, end)| *end >= column)
+}
+
+/// The line indices and columns of the innermost pair of brackets around a position. A bracket
+/// under the cursor counts as being around it.
+///
+/// # Arguments
+///
+/// * 'lines' - The characters of each line.
+/// * 'line' - The index of the cursor's line.
+/// * 'column' - The cursor's column.
+/// * 'open' - The opening bracket.
+/// * 'close' - The closing bracket.
+fn brackets(
+    lines: &[Vec<char>],
+    (line, column): (usize, usize),
+    open: char,
+    close: char,
+) -> Option<((usize, usize), (usize, usize))> {
+    let at = |(line, column): (usize, usize)| lines[line].get(column).copied();
+
+    let mut depth = 0;
+    let mut start = None;
+    let mut position = (line, column.min(lines[line].len()));
+    loop {
+        match at(position) {
+            Some(c) if c == open && depth == 0 => {
+                start = Some(position);
+                break;
+            }
+            Some(c) if c == open => depth -= 1,
+            Some(c) if c == close && position != (line, column) => depth += 1,
+            _ => {}
+        }
+        position = match position {
+            (0, 0) => break,
+            (line, 0) => (line - 1, lines[line - 1].len()),
+            (line, column) => (line, column - 1),
+        };
+    }
+    let start = start?;
+
+    let mut depth = 0;
+    let mut position = start;
+    loop {
+        position = match position {
+            (line, column) if column + 1 < lines[line].len() => (line, column + 1),
+            (line, _) if line + 1 < lines.len() => (line + 1, 0),
+            _ => return None,
+        };
+        match at(position) {
+            Some(c) if c == open => depth += 1,
+            Some(c) if c == close && depth == 0 => return Some((start, position)),
+            Some(c) if c == close => depth -= 1,
+            _ => {}
+        }
+    }
+}
+
+/// An opening or closing tag found in text.
+struct Tag {
+    /// The offsets of the tag's characters, from `<` to `>`.
+    range: Range<usize>,
+    name: String,
+    closing: bool,
+}
+
+/// Finds the opening and closing tags in text. Self-closing tags, comments and declarations
+/// are skipped, since they don't enclose anything.
+fn tags(text: &[char]) -> Vec<Tag> {
+    let mut tags = vec![];
+    let mut i = 0;
+    while let Some(start) = (i..text.len()).find(|i| text[*i] == '<') {
+        let Some(end) = (start..text.len()).find(|i| text[*i] == '>') else {
+            break;
+        };
+        let inside = &text[start + 1..end];
+        let closing = inside.first() == Some(&'/');
+        let name: String = inside[closing as usize..]
+            .iter()
+            .take_while(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ':' | '.'))
+            .collect();
+        if !name.is_empty() && !inside.ends_with(&['/']) {
+            tags.push(Tag {
+                range: start..end + 1,
+                name,
+                closing,
+            });
+        }
+        i = end + 1;
+    }
+    tags
+}
+
+/// The innermost pair of opening and closing tags around an offset in text, including the
+/// tags themselves. Opening tags which are never closed, like `<br>`, are ignored.
+fn tag_pair(text: &[char], offset: usize) -> Option<(Range<usize>, Range<usize>)> {
+    let mut open: Vec<Tag> = vec![];
+    let mut pairs = vec![];
+    for tag in tags(text) {
+        if !tag.closing {
+            open.push(tag);
+            continue;
+        }
+        if let Some(index) = open.iter().rposition(|o| o.name == tag.name) {
+            let opening = open.swap_remove(index);
+            open.truncate(index);
+            pairs.push((opening.range, tag.range));
+        }
+    }
+    pairs
+        .into_iter()
+        .filter(|(open, close)| open.start <= offset && offset < close.end)
+        .min_by_key(|(open, close)| close.end - open.start)
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("iw", TextObject { around: false, kind: Kind::Word };               "inner_word")]
+    #[test_case("a\"", TextObject { around: true, kind: Kind::Quote('"') };         "around_quotes")]
+    #[test_case("ab", TextObject { around: true, kind: Kind::Bracket('(', ')') };   "around_block")]
+    #[test_case("i}", TextObject { around: false, kind: Kind::Bracket('{', '}') };  "inner_braces")]
+    #[test_case("ip", TextObject { around: false, kind: Kind::Paragraph };          "paragraph")]
+    fn parse(name: &str, expected: TextObject) {
+        assert_eq!(Ok(expected), TextObject::parse(name));
+    }
+
+    #[test_case("", "No text object entered";     "empty")]
+    #[test_case("xw", "Unknown text object: xw";  "unknown_scope")]
+    #[test_case("iq", "Unknown text object: iq";  "unknown_kind")]
+    fn parse_errors(name: &str, expected: &str) {
+        assert_eq!(Err(String::from(expected)), TextObject::parse(name));
+    }
+
+    const TEXT: &str = "\
+fn main() {
+    let s = \"Lorem \\\"ipsum\\\"\";
+    call(a, (b), c);
+
+}
+
+next";
+
+    fn find(name: &str, cursor: (u32, u32)) -> Option<((u32, u32), (u32, u32))> {
+        let lines: Vec<String> = TEXT.lines().map(String::from).collect();
+        let cursor = Position {
+            row: cursor.0,
+            column: cursor.1,
+        };
+        TextObject::parse(name)
+            .unwrap()
+            .find(&lines, 1, cursor)
+            .map(|(start, end)| ((start.row, start.column), (end.row, end.column)))
+    }
+
+    #[test_case("iw", (1, 4), ((1, 3), (1, 7));     "inner_word")]
+    #[test_case("aw", (1, 4), ((1, 2), (1, 7));     "around_word_taking_whitespace_before")]
+    #[test_case("aw", (1, 0), ((1, 0), (1, 3));     "around_word")]
+    #[test_case("aw", (3, 17), ((3, 16), (3, 18));  "around_word_before_punctuation")]
+    #[test_case("iw", (2, 1), ((2, 0), (2, 4));     "inner_whitespace")]
+    #[test_case("i\"", (2, 12), ((2, 13), (2, 28)); "inner_quotes_with_escapes")]
+    #[test_case("a\"", (2, 4), ((2, 12), (2, 29));  "first_quotes_after_cursor")]
+    #[test_case("ib", (3, 13), ((3, 13), (3, 14));  "inner_nested_brackets")]
+    #[test_case("ab", (3, 16), ((3, 8), (3, 19));   "around_skipping_nested")]
+    #[test_case("i(", (3, 8), ((3, 9), (3, 18));    "on_opening_bracket")]
+    #[test_case("a(", (3, 18), ((3, 8), (3, 19));   "on_closing_bracket")]
+    #[test_case("iB", (3, 4), ((1, 11), (5, 0));    "inner_block_over_lines")]
+    #[test_case("ip", (2, 0), ((1, 0), (3, 20));    "inner_paragraph")]
+    #[test_case("ap", (2, 0), ((1, 0), (4, 0));     "around_paragraph")]
+    fn find_objects(name: &str, cursor: (u32, u32), expected: ((u32, u32), (u32, u32))) {
+        assert_eq!(Some(expected), find(name, cursor));
+    }
+
+    #[test_case("it", (2, 9), ((2, 8), (2, 13));     "inner_tag")]
+    #[test_case("it", (2, 18), ((2, 5), (2, 28));    "unclosed_tag")]
+    #[test_case("at", (1, 2), ((1, 0), (3, 6));      "around_tag_over_lines")]
+    #[test_case("it", (1, 7), ((1, 5), (3, 0));      "inner_tag_over_lines")]
+    fn find_tags(name: &str, cursor: (u32, u32), expected: ((u32, u32), (u32, u32))) {
+        let lines: Vec<String> = "<div>\n  <p><b>Lorem</b> <br> ipsum</p>\n</div>"
+            .lines()
+            .map(String::from)
+            .collect();
+        let cursor = Position {
+            row: cursor.0,
+            column: cursor.1,
+        };
+        let found = TextObject::parse(name).unwrap().find(&lines, 1, cursor);
+        let position = |(row, column)| Position { row, column };
+        assert_eq!(Some((position(expected.0), position(expected.1))), found);
+    }
+
+    #[test_case("i[", (3, 4);   "no_brackets")]
+    #[test_case("i'", (2, 4);   "no_quotes")]
+    fn missing_objects(name: &str, cursor: (u32, u32)) {
+        assert_eq!(None, find(name, cursor));
+    }
+}
@@ -0,0 +1,430 @@
+use std::time::{Duration, SystemTime};
+
+/// A change to the text of a document, where `deleted` was replaced with `inserted` at a
+/// position in the document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub pos: usize,
+    pub deleted: String,
+    pub inserted: String,
+}
+
+/// Changes to apply to a document to move through its history.
+#[derive(Debug, PartialEq)]
+pub enum Step {
+    /// Reverts the changes, which should be applied in reverse order.
+    Undo(Vec<Change>),
+    /// Makes the changes again, in order.
+    Redo(Vec<Change>),
+}
+
+/// How far to move through a document's history, in the order the revisions were made rather
+/// than along the branch of the tree being edited.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distance {
+    Revisions(usize),
+    Time(Duration),
+}
+
+impl Distance {
+    /// Parses a number of revisions, such as `3`, or an amount of time with a unit of `s`,
+    /// `m`, `h` or `d`, such as `30s`. Without any text, the distance is one revision.
+    pub fn parse(text: &str) -> Result<Distance, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(Distance::Revisions(1));
+        }
+
+        let invalid = || format!("Invalid time: {}", text);
+        let split = text
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len());
+        let (number, unit) = text.split_at(split);
+        let number: u64 = number.parse().map_err(|_| invalid())?;
+        let seconds = match unit {
+            "" => return Ok(Distance::Revisions(number as usize)),
+            "s" => 1,
+            "m" => 60,
+            "h" => 3600,
+            "d" => 86400,
+            _ => return Err(invalid()),
+        };
+        let seconds = number.checked_mul(seconds).ok_or_else(invalid)?;
+        Ok(Distance::Time(Duration::from_secs(seconds)))
+    }
+}
+
+/// A state of the document, reached by making some changes to its parent state.
+#[derive(Debug)]
+struct Revision {
+    parent: usize,
+    children: Vec<usize>,
+    /// The child which redo moves to, which is the one most recently created or undone.
+    redo: Option<usize>,
+    changes: Vec<Change>,
+    time: SystemTime,
+}
+
+/// The history of a document as a tree of revisions. Undoing moves to the parent revision, and
+/// making changes after an undo starts a new branch rather than discarding the revisions which
+/// were undone, so every state the document has been in can be returned to.
+#[derive(Debug)]
+pub struct UndoTree {
+    /// The revisions in the order they were made. The first is the document as it was opened.
+    revisions: Vec<Revision>,
+    current: usize,
+    /// Changes which have been made but not yet committed as a revision.
+    pending: Vec<Change>,
+    /// When the last pending change was made, which becomes the time of the revision.
+    changed: SystemTime,
+}
+
+impl Default for UndoTree {
+    fn default() -> UndoTree {
+        UndoTree::new()
+    }
+}
+
+impl UndoTree {
+    pub fn new() -> UndoTree {
+        UndoTree {
+            revisions: vec![Revision {
+                parent: 0,
+                children: vec![],
+                redo: None,
+                changes: vec![],
+                time: SystemTime::now(),
+            }],
+            current: 0,
+            pending: vec![],
+            changed: SystemTime::now(),
+        }
+    }
+
+    /// The revision the document is currently at.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Adds a change to the revision being built up. Text typed straight after the previous
+    /// change is merged into it.
+    pub fn record(&mut self, change: Change) {
+        self.changed = SystemTime::now();
+        if let Some(last) = self.pending.last_mut() {
+            if change.deleted.is_empty() && last.pos + last.inserted.len() == change.pos {
+                last.inserted += &change.inserted;
+                return;
+            }
+        }
+        self.pending.push(change);
+    }
+
+    /// Commits the changes recorded since the last commit as a new revision, returning whether
+    /// there were any.
+    pub fn commit(&mut self) -> bool {
+        if self.pending.is_empty() {
+            return false;
+        }
+
+        let index = self.revisions.len();
+        self.revisions.push(Revision {
+            parent: self.current,
+            children: vec![],
+            redo: None,
+            changes: std::mem::take(&mut self.pending),
+            time: self.changed,
+        });
+        let parent = &mut self.revisions[self.current];
+        parent.children.push(index);
+        parent.redo = Some(index);
+        self.current = index;
+        true
+    }
+
+    /// Moves to the parent revision, returning the changes to revert.
+    pub fn undo(&mut self) -> Option<Step> {
+        self.commit();
+        if self.current == 0 {
+            return None;
+        }
+
+        let revision = self.current;
+        self.current = self.revisions[revision].parent;
+        self.revisions[self.current].redo = Some(revision);
+        Some(Step::Undo(self.revisions[revision].changes.clone()))
+    }
+
+    /// Moves to the child revision which was most recently undone, returning the changes to
+    /// make again.
+    pub fn redo(&mut self) -> Option<Step> {
+        self.commit();
+        let revision = self.revisions[self.current].redo?;
+        self.current = revision;
+        Some(Step::Redo(self.revisions[revision].changes.clone()))
+    }
+
+    /// Moves to any revision, returning the steps needed to get there: undoing back to the
+    /// revision both share, then redoing down the other branch.
+    pub fn jump(&mut self, target: usize) -> Vec<Step> {
+        self.commit();
+        if target >= self.revisions.len() {
+            return vec![];
+        }
+
+        let ancestors = self.ancestors(target);
+        let mut steps = vec![];
+        while !ancestors.contains(&self.current) {
+            steps.extend(self.undo());
+        }
+        let common = ancestors
+            .iter()
+            .position(|r| *r == self.current)
+            .unwrap_or(0);
+        for revision in ancestors[..common].iter().rev() {
+            let parent = self.revisions[*revision].parent;
+            self.revisions[parent].redo = Some(*revision);
+            steps.extend(self.redo());
+        }
+        steps
+    }
+
+    /// Moves back to the revision made the given distance before the current one, or to the
+    /// document as it was opened if there isn't one that old.
+    pub fn earlier(&mut self, distance: Distance) -> Vec<Step> {
+        self.commit();
+        let target = match distance {
+            Distance::Revisions(count) => self.current.saturating_sub(count),
+            Distance::Time(duration) => self.revisions[self.current]
+                .time
+                .checked_sub(duration)
+                .and_then(|time| self.revisions.iter().rposition(|r| r.time <= time))
+                .unwrap_or(0),
+        };
+        self.jump(target)
+    }
+
+    /// Moves forward to the latest revision made within the given distance after the current
+    /// one.
+    pub fn later(&mut self, distance: Distance) -> Vec<Step> {
+        self.commit();
+        let target = match distance {
+            Distance::Revisions(count) => self.current.saturating_add(count),
+            Distance::Time(duration) => {
+                match self.revisions[self.current].time.checked_add(duration) {
+                    Some(time) => self
+                        .revisions
+                        .iter()
+                        .rposition(|r| r.time <= time)
+                        .unwrap_or(self.current),
+                    None => self.revisions.len() - 1,
+                }
+            }
+        };
+        self.jump(target.clamp(self.current, self.revisions.len() - 1))
+    }
+
+    /// The revision and its ancestors, ending with the first revision.
+    fn ancestors(&self, revision: usize) -> Vec<usize> {
+        let mut ancestors = vec![revision];
+        let mut revision = revision;
+        while revision != 0 {
+            revision = self.revisions[revision].parent;
+            ancestors.push(revision);
+        }
+        ancestors
+    }
+
+    /// Describes each revision for display, as the tree is walked depth first. Later branches
+    /// are indented below the revision they branched from, and each revision shows how long
+    /// ago it was made and how much it changed. The current revision is marked with a `*`.
+    pub fn describe(&self, now: SystemTime) -> Vec<(usize, String)> {
+        let mut lines = vec![];
+        let mut stack = vec![(0, 0)];
+        while let Some((revision, depth)) = stack.pop() {
+            let entry = &self.revisions[revision];
+            // Push the later branches first so the first child is visited next, continuing at
+            // the same depth so a linear history isn't indented.
+            for (i, child) in entry.children.iter().enumerate().rev() {
+                stack.push((*child, depth + usize::from(i > 0)));
+            }
+
+            let marker = if revision == self.current { '*' } else { 'o' };
+            let summary = match revision {
+                0 => String::from("original"),
+                _ => {
+                    let inserted: usize = entry.changes.iter().map(|c| c.inserted.len()).sum();
+                    let deleted: usize = entry.changes.iter().map(|c| c.deleted.len()).sum();
+                    format!("+{} -{}", inserted, deleted)
+                }
+            };
+            let age = now.duration_since(entry.time).unwrap_or_default();
+            lines.push((
+                revision,
+                format!(
+                    "{}{} {:>3}  {:>8}  {}",
+                    "  ".repeat(depth),
+                    marker,
+                    revision,
+                    format_age(age),
+                    summary
+                ),
+            ));
+        }
+        lines
+    }
+}
+
+/// Formats a duration as a short age, such as `5s ago` or `3h ago`.
+pub fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    match seconds {
+        0..60 => format!("{}s ago", seconds),
+        60..3600 => format!("{}m ago", seconds / 60),
+        3600..86400 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn insert(pos: usize, text: &str) -> Change {
+        Change {
+            pos,
+            deleted: String::new(),
+            inserted: text.to_string(),
+        }
+    }
+
+    /// Builds a tree with revisions 1 and 2 on one branch, and revision 3 branching from 1.
+    fn branched() -> UndoTree {
+        let mut tree = UndoTree::new();
+        tree.record(insert(0, "a"));
+        tree.commit();
+        tree.record(insert(1, "b"));
+        tree.commit();
+        tree.undo();
+        tree.record(insert(1, "c"));
+        tree.commit();
+        tree
+    }
+
+    #[test]
+    fn typing_is_merged() {
+        let mut tree = UndoTree::new();
+        tree.record(insert(0, "Lo"));
+        tree.record(insert(2, "rem"));
+        tree.record(insert(0, ">"));
+
+        assert_eq!(
+            Some(Step::Undo(vec![insert(0, "Lorem"), insert(0, ">")])),
+            tree.undo()
+        );
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut tree = UndoTree::new();
+        tree.record(insert(0, "a"));
+        assert_eq!(Some(Step::Undo(vec![insert(0, "a")])), tree.undo());
+        assert_eq!(None, tree.undo());
+        assert_eq!(Some(Step::Redo(vec![insert(0, "a")])), tree.redo());
+        assert_eq!(None, tree.redo());
+    }
+
+    #[test]
+    fn changes_after_undo_create_branch() {
+        let mut tree = branched();
+        assert_eq!(3, tree.current());
+        tree.undo();
+        // Redo follows the most recent branch.
+        assert_eq!(Some(Step::Redo(vec![insert(1, "c")])), tree.redo());
+    }
+
+    #[test]
+    fn jump_across_branches() {
+        let mut tree = branched();
+        let steps = tree.jump(2);
+
+        let expected = vec![
+            Step::Undo(vec![insert(1, "c")]),
+            Step::Redo(vec![insert(1, "b")]),
+        ];
+        assert_eq!(expected, steps);
+        assert_eq!(2, tree.current());
+    }
+
+    #[test]
+    fn describe_indents_branches() {
+        let tree = branched();
+        let now = SystemTime::now();
+        let lines: Vec<String> = tree
+            .describe(now)
+            .into_iter()
+            .map(|(_, line)| {
+                line.split_whitespace()
+                    .take(2)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+
+        assert_eq!(vec!["o 0", "o 1", "o 2", "* 3"], lines);
+        assert!(tree.describe(now)[3].1.starts_with("  * "));
+    }
+
+    /// Builds the branched tree with each revision made a minute after the last.
+    fn timed() -> UndoTree {
+        let mut tree = branched();
+        let start = SystemTime::UNIX_EPOCH;
+        for (i, revision) in tree.revisions.iter_mut().enumerate() {
+            revision.time = start + Duration::from_secs(60 * i as u64);
+        }
+        tree
+    }
+
+    #[test_case(Distance::Revisions(1), 2;                  "one_revision")]
+    #[test_case(Distance::Revisions(5), 0;                  "past_original")]
+    #[test_case(Distance::Time(Duration::from_secs(90)), 1; "time")]
+    #[test_case(Distance::Time(Duration::from_secs(60)), 2; "exact_time")]
+    fn earlier(distance: Distance, expected: usize) {
+        let mut tree = timed();
+        tree.earlier(distance);
+        assert_eq!(expected, tree.current());
+    }
+
+    #[test_case(Distance::Revisions(2), 2;                  "revisions")]
+    #[test_case(Distance::Revisions(9), 3;                  "past_latest")]
+    #[test_case(Distance::Time(Duration::from_secs(30)), 0; "too_short")]
+    #[test_case(Distance::Time(Duration::from_secs(60)), 1; "exact_time")]
+    #[test_case(Distance::Time(Duration::MAX), 3;           "past_end_of_time")]
+    fn later(distance: Distance, expected: usize) {
+        let mut tree = timed();
+        tree.jump(0);
+        tree.later(distance);
+        assert_eq!(expected, tree.current());
+    }
+
+    #[test_case("", Ok(Distance::Revisions(1));                     "empty")]
+    #[test_case("3", Ok(Distance::Revisions(3));                    "revisions")]
+    #[test_case("30s", Ok(Distance::Time(Duration::from_secs(30))); "seconds")]
+    #[test_case("2m", Ok(Distance::Time(Duration::from_secs(120))); "minutes")]
+    #[test_case("1h", Ok(Distance::Time(Duration::from_secs(3600))); "hours")]
+    #[test_case("2x", Err(String::from("Invalid time: 2x"));         "invalid_unit")]
+    #[test_case("m", Err(String::from("Invalid time: m"));           "missing_number")]
+    #[test_case("999999999999999999d", Err(String::from("Invalid time: 999999999999999999d")); "overflow")]
+    fn parse_distance(text: &str, expected: Result<Distance, String>) {
+        assert_eq!(expected, Distance::parse(text));
+    }
+
+    #[test_case(5, "5s ago";       "seconds")]
+    #[test_case(125, "2m ago";     "minutes")]
+    #[test_case(7200, "2h ago";    "hours")]
+    #[test_case(172800, "2d ago";  "days")]
+    fn age(seconds: u64, expected: &str) {
+        assert_eq!(expected, format_age(Duration::from_secs(seconds)));
+    }
+}
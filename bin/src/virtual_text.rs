@@ -0,0 +1,234 @@
+use std::collections::BTreeMap;
+
+use console::{style, Color};
+
+use crate::fold::Folds;
+use crate::layout;
+
+/// The number of cells left between the end of a line and the annotation drawn after it.
+pub const GAP: usize = 2;
+
+/// What placed a piece of virtual text. Each source's text is replaced separately, so updating
+/// one leaves the others alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// The errors found by the last build.
+    Errors,
+}
+
+/// Where virtual text is drawn relative to the line it's anchored to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    /// After the end of the line's text, if there's room.
+    EndOfLine,
+    /// On a row of its own above the line.
+    Above,
+    /// On a row of its own below the line.
+    Below,
+}
+
+/// Text drawn among a document's lines which isn't part of the document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub source: Source,
+    pub placement: Placement,
+    pub text: String,
+    pub color: Color,
+}
+
+impl Annotation {
+    /// Draws the annotation in its colour, cut off to fit in a number of cells.
+    pub fn render(&self, width: usize) -> String {
+        style(layout::truncate(&self.text, width))
+            .fg(self.color)
+            .force_styling(true)
+            .to_string()
+    }
+}
+
+/// A row of the text area, which shows either a line of the document or an annotation placed
+/// between lines.
+#[derive(Debug, PartialEq)]
+pub enum Row<'a> {
+    Line(u32),
+    Virtual(&'a Annotation),
+}
+
+/// The annotations shown in a document, anchored to lines. The anchors move with edits, so an
+/// annotation stays with its line as lines are added or removed above it, and annotations on
+/// deleted lines move to the line the deletion joined them to.
+#[derive(Debug, Default)]
+pub struct VirtualText {
+    lines: BTreeMap<u32, Vec<Annotation>>,
+}
+
+impl VirtualText {
+    pub fn new() -> VirtualText {
+        VirtualText::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Anchors an annotation to a line, after any already there.
+    ///
+    /// # Arguments
+    ///
+    /// * 'line' - The line to anchor the annotation to. Line numbers start from 1.
+    /// * 'annotation' - The annotation to place.
+    pub fn place(&mut self, line: u32, annotation: Annotation) {
+        self.lines.entry(line).or_default().push(annotation);
+    }
+
+    /// Removes every annotation placed by a source.
+    pub fn clear(&mut self, source: Source) {
+        self.lines.retain(|_, annotations| {
+            annotations.retain(|a| a.source != source);
+            !annotations.is_empty()
+        });
+    }
+
+    /// Moves the anchors for an edit.
+    ///
+    /// # Arguments
+    ///
+    /// * 'line' - The line the edit was made on.
+    /// * 'removed' - The number of line breaks the edit removed.
+    /// * 'inserted' - The number of line breaks the edit inserted.
+    pub fn edit(&mut self, line: u32, removed: u32, inserted: u32) {
+        if removed == inserted {
+            return;
+        }
+        for (anchor, annotations) in self.lines.split_off(&(line + 1)) {
+            let anchor = match anchor <= line + removed {
+                true => line,
+                false => anchor - removed + inserted,
+            };
+            self.lines.entry(anchor).or_default().extend(annotations);
+        }
+    }
+
+    /// The annotation drawn after the end of a line, if it has one. Only the first is drawn.
+    pub fn end_of_line(&self, line: u32) -> Option<&Annotation> {
+        self.placed(line, Placement::EndOfLine).next()
+    }
+
+    /// Whether any annotation between the lines in a range is drawn on a row of its own.
+    pub fn has_rows(&self, lines: std::ops::Range<u32>) -> bool {
+        self.lines
+            .range(lines)
+            .flat_map(|(_, annotations)| annotations)
+            .any(|a| a.placement != Placement::EndOfLine)
+    }
+
+    /// Lays out the rows of the text area, starting from a line, with the annotations placed
+    /// above and below each line on rows of their own. Lines hidden by closed folds are left
+    /// out, along with their annotations.
+    ///
+    /// # Arguments
+    ///
+    /// * 'folds' - The document's closed folds.
+    /// * 'top' - The line at the top of the text area.
+    /// * 'last' - The last line to lay out, usually the last line of the document.
+    /// * 'height' - The number of rows in the text area.
+    pub fn rows(&self, folds: &Folds, top: u32, last: u32, height: u32) -> Vec<Row<'_>> {
+        let mut rows = vec![];
+        let mut line = folds.visible_line(top);
+        while rows.len() < height as usize && line <= last {
+            rows.extend(self.placed(line, Placement::Above).map(Row::Virtual));
+            rows.push(Row::Line(line));
+            rows.extend(self.placed(line, Placement::Below).map(Row::Virtual));
+            line = folds.next_line(line);
+        }
+        rows.truncate(height as usize);
+        rows
+    }
+
+    fn placed(&self, line: u32, placement: Placement) -> impl Iterator<Item = &Annotation> {
+        self.lines
+            .get(&line)
+            .into_iter()
+            .flatten()
+            .filter(move |a| a.placement == placement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn annotation(text: &str, placement: Placement) -> Annotation {
+        Annotation {
+            source: Source::Errors,
+            placement,
+            text: text.to_string(),
+            color: Color::Red,
+        }
+    }
+
+    fn anchors(virtual_text: &VirtualText) -> Vec<u32> {
+        virtual_text.lines.keys().copied().collect()
+    }
+
+    #[test_case(4, 0, 2, vec![2, 7, 11]; "lines_inserted_above")]
+    #[test_case(2, 0, 1, vec![2, 6, 10]; "line_inserted_on_anchor")]
+    #[test_case(3, 3, 0, vec![2, 3, 6];  "lines_deleted_above")]
+    #[test_case(4, 2, 0, vec![2, 4, 7];  "anchor_deleted")]
+    #[test_case(9, 1, 0, vec![2, 5, 9];  "lines_deleted_below")]
+    fn edits_move_anchors(line: u32, removed: u32, inserted: u32, expected: Vec<u32>) {
+        let mut virtual_text = VirtualText::new();
+        for anchor in [2, 5, 9] {
+            virtual_text.place(anchor, annotation("error", Placement::EndOfLine));
+        }
+        virtual_text.edit(line, removed, inserted);
+        assert_eq!(expected, anchors(&virtual_text));
+    }
+
+    #[test]
+    fn clear_source() {
+        let mut virtual_text = VirtualText::new();
+        virtual_text.place(1, annotation("error", Placement::EndOfLine));
+        assert!(!virtual_text.is_empty());
+        virtual_text.clear(Source::Errors);
+        assert!(virtual_text.is_empty());
+    }
+
+    #[test]
+    fn rows_between_lines() {
+        let mut virtual_text = VirtualText::new();
+        let above = annotation("above", Placement::Above);
+        let below = annotation("below", Placement::Below);
+        virtual_text.place(2, annotation("end", Placement::EndOfLine));
+        virtual_text.place(2, above.clone());
+        virtual_text.place(3, below.clone());
+
+        assert_eq!(
+            vec![
+                Row::Line(1),
+                Row::Virtual(&above),
+                Row::Line(2),
+                Row::Line(3),
+                Row::Virtual(&below),
+                Row::Line(4),
+            ],
+            virtual_text.rows(&Folds::new(), 1, 10, 6)
+        );
+        assert_eq!(
+            vec![Row::Line(3), Row::Virtual(&below), Row::Line(4)],
+            virtual_text.rows(&Folds::new(), 3, 4, 6)
+        );
+
+        let mut folds = Folds::new();
+        folds.close(1..=2);
+        assert_eq!(
+            vec![Row::Line(1), Row::Line(3), Row::Virtual(&below)],
+            virtual_text.rows(&folds, 2, 3, 6)
+        );
+        assert!(virtual_text.has_rows(3..4));
+        assert!(!virtual_text.has_rows(1..2));
+        assert_eq!("end", virtual_text.end_of_line(2).unwrap().text);
+    }
+}
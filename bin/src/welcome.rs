@@ -0,0 +1,87 @@
+use console::style;
+
+use crate::layout;
+
+/// The most recent files listed on the start screen.
+const MAX_RECENT: usize = 8;
+
+/// Keys worth knowing when starting out, and what they do.
+const HINTS: [(&str, &str); 6] = [
+    ("Ctrl+P", "Open the command prompt"),
+    (":e path", "Open a file"),
+    (":recent", "Pick a recently opened file"),
+    ("Ctrl+S", "Save"),
+    ("Alt+Z", "Undo"),
+    ("Ctrl+Q", "Quit"),
+];
+
+/// Draws the start screen shown when the editor is launched without a document, listing the
+/// most recently opened files and some key hints, centered on the screen.
+///
+/// # Arguments
+///
+/// * 'recent' - The paths of recently opened files, newest first.
+/// * 'width' - The number of cells available to draw the screen in.
+/// * 'height' - The number of rows available to draw the screen in.
+pub fn render(recent: &[String], width: usize, height: usize) -> Vec<String> {
+    let mut lines = vec![
+        format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+        String::new(),
+    ];
+    if !recent.is_empty() {
+        lines.push(String::from("Recent files"));
+        lines.extend(
+            recent
+                .iter()
+                .take(MAX_RECENT)
+                .map(|path| format!("  {}", path)),
+        );
+        lines.push(String::new());
+    }
+    lines.extend(
+        HINTS
+            .iter()
+            .map(|(keys, action)| format!("{:<10}{}", keys, action)),
+    );
+
+    let block_width = lines.iter().map(|l| layout::display_width(l)).max();
+    let left = width.saturating_sub(block_width.unwrap_or_default()) / 2;
+    let top = height.saturating_sub(lines.len()) / 2;
+
+    let mut rows = vec![String::new(); top];
+    for (i, line) in lines.iter().enumerate() {
+        let line = layout::truncate(line, width.saturating_sub(left));
+        let line = match i {
+            0 => style(line).bold().force_styling(true).to_string(),
+            _ => line.to_string(),
+        };
+        rows.push(" ".repeat(left) + &line);
+    }
+    rows.truncate(height);
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_centers_block() {
+        let recent = vec![String::from("/tmp/notes.txt")];
+        let rows = render(&recent, 60, 20);
+
+        let version = format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        assert_eq!(4, rows.iter().take_while(|r| r.is_empty()).count());
+        assert!(rows[4].ends_with(&style(version).bold().force_styling(true).to_string()));
+        assert_eq!(" ".repeat(11) + "Recent files", rows[6]);
+        assert_eq!(" ".repeat(11) + "  /tmp/notes.txt", rows[7]);
+        assert!(rows.last().unwrap().trim().starts_with("Ctrl+Q"));
+    }
+
+    #[test]
+    fn render_without_recent_files() {
+        let rows = render(&[], 40, 3);
+        assert_eq!(3, rows.len());
+        assert!(!rows.iter().any(|r| r.contains("Recent")));
+    }
+}
@@ -1,4 +1,4 @@
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
 use text_buffer::TextBuffer;
 
 fn insert_to_empty_document(c: &mut Criterion) {
@@ -17,6 +17,19 @@ fn insert_to_existing_document(c: &mut Criterion) {
     });
 }
 
+fn paste_into_existing_document(c: &mut Criterion) {
+    let text =
+        "Lorem ipsum dolor sit amet, consectetur adipiscing elit.\n".repeat(1024 * 1024 / 57);
+
+    c.bench_function("paste 1MB in the middle of an existing document", |b| {
+        b.iter_batched(
+            setup_existing_doc,
+            |mut buffer| buffer.insert(buffer.doc_len() / 2, &text),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
 fn delete_from_document(c: &mut Criterion) {
     let mut buffer = setup_existing_doc();
 
@@ -55,6 +68,7 @@ criterion_group!(
     benches,
     insert_to_empty_document,
     insert_to_existing_document,
+    paste_into_existing_document,
     delete_from_document,
     get_line_content_near_start_of_document,
     get_line_content_near_end_of_document
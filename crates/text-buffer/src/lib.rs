@@ -69,10 +69,12 @@ impl TextBuffer {
     /// # Examples
     ///
     /// ```
-    /// let buffer = TextBuffer::new(Stromg(String::from("Lorem ipsum dolor sit amet")));
+    /// use text_buffer::TextBuffer;
+    ///
+    /// let buffer = TextBuffer::new(Some(String::from("Lorem ipsum dolor sit amet")));
     /// ```
     pub fn new(text: Option<String>) -> TextBuffer {
-        if let Some(txt) = text {
+        if let Some(txt) = text.filter(|t| !t.is_empty()) {
             let mut buffer = TextBuffer {
                 original: txt,
                 add: String::new(),
@@ -82,13 +84,13 @@ impl TextBuffer {
             buffer
                 .table
                 .push(buffer.create_span(BufferType::Original, 0, buffer.original.len()));
-            return buffer;
+            buffer
         } else {
-            return TextBuffer {
+            TextBuffer {
                 original: String::new(),
                 add: String::new(),
                 table: Vec::with_capacity(500),
-            };
+            }
         }
     }
 
@@ -148,14 +150,20 @@ impl TextBuffer {
                 piece.index
             );
             let pos_in_add_buffer = self.add_to_buffer(text);
+            let piece2 = self.create_span(BufferType::Add, pos_in_add_buffer, text.len());
+
+            // position is at the boundary between two pieces
+            if pos == piece.doc.end {
+                self.table.insert(piece.index + 1, piece2);
+                return;
+            }
 
             let piece1 =
-                self.create_span(piece.span.buffer, piece.span.start, pos - piece.doc.start); //pos_in_document + pos);
-            let piece2 = self.create_span(BufferType::Add, pos_in_add_buffer, text.len());
+                self.create_span(piece.span.buffer, piece.span.start, pos - piece.doc.start);
             let piece3 = self.create_span(
                 piece.span.buffer,
                 piece1.start + piece1.len,
-                piece.span.len - (piece1.start + piece1.len),
+                piece.span.len - piece1.len,
             );
 
             self.table[piece.index] = piece1;
@@ -194,102 +202,46 @@ impl TextBuffer {
         self.insert(pos, c.to_string().as_str());
     }
 
-    /// Deletes a section of text from the table. This function will perform the following
-    /// depending on whether or not the start and end position are in the same piece:
-    ///
-    /// start and end are in the same piece:
-    ///     1. split the piece into two new pieces.
-    /// start and end are in different pieces:
-    ///     1. set new length for start piece.
-    ///     2. set new start for end piece.
-    ///     3. remove any pieces between these two pieces.
+    /// Deletes a section of text from the table. Pieces entirely within the deleted section are
+    /// removed, while pieces which overlap the start or end of the section are shortened. A piece
+    /// which contains the whole section is split into two new pieces.
     ///
     /// # Arguments
     ///
     /// * 'start' - The position in the document where the text to be deleted starts
-    /// * 'end' - The position in the document where the text to tbe deleted ends
+    /// * 'end' - The position in the document where the text to tbe deleted ends. Positions past
+    ///   the end of the document are treated as the end of the document.
     pub fn delete(&mut self, start: usize, end: usize) {
-        let len = end - start;
-        let p1 = self.get_piece_at_position(start);
-        let p2 = self.get_piece_at_position(end);
-
-        match (p1, p2) {
-            (Some(p1), Some(p2)) if p1.index == p2.index => {
-                let start_relative = start - p1.doc.start;
-                let end_relative = start + len;
-                self.delete_split_piece(p1.index, start_relative, end_relative);
-            }
-            (Some(p1), Some(p2)) => {
-                self.delete_multiple(&p1, &p2, start, end);
-            }
-            (Some(p), None) => {}
-            _ => {
-                eprintln!("none");
-            }
-        };
-    }
-
-    /// Deletes a section of text when it only resides on in a single piece.
-    /// Will split the piece into two new pieces.
-    ///
-    /// # Arguments
-    ///
-    /// * 'index' - The index of the piece to split in the piece table
-    /// * 'start' - The position within the span that the text to be deleted starts, relative to
-    /// the start of the span.
-    /// * 'end' - The position with the span that the text to be deleted ends, relative to the
-    /// start of the span.
-    fn delete_split_piece(&mut self, index: usize, start: usize, end: usize) {
-        // buffer   start length
-        // original 0     22
-        //
-        // delete 15-20
-        //
-        // buffer   start length func
-        // original 0     15     (ex.start) (start)
-        // original 20    22     (ex.start + end) (ex.length - end)
-        let ex = &self.table[index];
-        let p1 = self.create_span(ex.buffer, ex.start, start);
-        let p2 = self.create_span(ex.buffer, ex.start + end, ex.len - end);
-
-        self.table[index] = p1;
-        self.table.insert(index + 1, p2);
-    }
-
-    /// Deletes a section of text from the piece table when it resides over multiple pieces.
-    /// Will modify the start/end of the first/last piece and delete any pieces between them.
-    ///
-    /// # Arguments
-    ///
-    /// * 'p1' - The piece where the start of the text to be deleted is located
-    /// * 'p2' - The piece where the end of the text to be deleted is located
-    /// * 'start' - The position in the document where the text to be deleted starts
-    /// * 'end' - The position in the document where the text to be deleted ends
-    fn delete_multiple(
-        &mut self,
-        p1: &DocumentPiece,
-        p2: &DocumentPiece,
-        start: usize,
-        end: usize,
-    ) {
-        // update the first piece.
-        let p1_len_to_delete = p1.doc.end - start;
-        let p1_new_len = p1.span.len - p1_len_to_delete;
-
-        self.table[p1.index] = self.create_span(p1.span.buffer, p1.span.start, p1_new_len);
-
-        // update the final piece.
-        let p2_new_len = p2.doc.end - end;
-        let p2_new_start = p2.span.end - p2_new_len;
-
-        self.table[p2.index] = self.create_span(p2.span.buffer, p2_new_start, p2_new_len);
-
-        // remove and pieces between the two pieces.
-        if p2.index - p1.index > 1 {
-            for i in p1.index + 1..p2.index {
-                debug!("Removing index {} from piece table", i);
-                self.table.remove(i);
+        let end = end.min(self.doc_len());
+        if start >= end {
+            return;
+        }
+        info!("Deleting text between positions {} and {}", start, end);
+
+        let spans = std::mem::take(&mut self.table);
+        let mut doc_start = 0;
+        for span in spans {
+            let doc_end = doc_start + span.len;
+            if doc_end <= start || doc_start >= end {
+                self.table.push(span);
+            } else {
+                // keep the text before the deleted section
+                if doc_start < start {
+                    let len = start - doc_start;
+                    self.table
+                        .push(self.create_span(span.buffer, span.start, len));
+                }
+                // keep the text after the deleted section
+                if doc_end > end {
+                    let offset = end - doc_start;
+                    self.table.push(self.create_span(
+                        span.buffer,
+                        span.start + offset,
+                        span.len - offset,
+                    ));
+                }
             }
+            doc_start = doc_end;
         }
     }
 
@@ -305,6 +257,39 @@ impl TextBuffer {
         text
     }
 
+    /// Constructs the text between two positions in the document, without building the rest of
+    /// the document.
+    ///
+    /// # Arguments
+    ///
+    /// * 'start' - The position of the first character.
+    /// * 'end' - The position after the last character.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use text_buffer::TextBuffer;
+    ///
+    /// let buffer = TextBuffer::new(Some(String::from("Lorem ipsum dolor sit amet")));
+    /// assert_eq!("ipsum", buffer.text_range(6, 11));
+    /// ```
+    pub fn text_range(&self, start: usize, end: usize) -> String {
+        let mut text = String::new();
+        let mut doc_start = 0;
+
+        for span in &self.table {
+            let doc_end = doc_start + span.len;
+            if doc_end > start && doc_start < end {
+                let from = start.saturating_sub(doc_start);
+                let to = (end - doc_start).min(span.len);
+                text += &self.get_span_contents(span)[from..to];
+            }
+            doc_start = doc_end;
+        }
+
+        text
+    }
+
     /// Generates the text for a single span in the piece table.
     ///
     /// # Arguments
@@ -364,6 +349,8 @@ impl TextBuffer {
     /// # Examples
     ///
     /// ```
+    /// use text_buffer::TextBuffer;
+    ///
     /// let buffer = TextBuffer::new(Some(String::from("Lorem ipsum dolor sit amet, consectetur adipiscing elit.\nPraesent ultricies lacus ut molestie dapibus.")));
     /// let content = buffer.get_line_content(2);
     /// assert_eq!(Some(String::from("Praesent ultricies lacus ut molestie dapibus.")), content);
@@ -378,10 +365,10 @@ impl TextBuffer {
         // special case if accessing the first line number
         if line == 1 {
             for span in &self.table {
-                let text = self.get_span_contents(&span);
+                let text = self.get_span_contents(span);
 
                 // find the next new line character and return once it's found.
-                for pos in &span.lines {
+                if let Some(pos) = span.lines.first() {
                     result += &text[..*pos];
                     return Some(result);
                 }
@@ -394,19 +381,16 @@ impl TextBuffer {
             return Some(result);
         }
 
-        // main case where line number != 1
+        // main case where line number != 1. Pieces which end before the line are skipped whole,
+        // using the number of new lines cached for each one.
         let mut current_line = 1;
-        let mut index = 0;
 
-        for piece in &self.table {
-            for pos in &piece.lines {
-                current_line += 1;
-                if current_line == line {
-                    return Some(self.get_line_content_until_next_linebreak(index, *pos));
-                }
+        for (index, piece) in self.table.iter().enumerate() {
+            let skip = line.checked_sub(current_line + 1)? as usize;
+            if let Some(pos) = piece.lines.get(skip) {
+                return Some(self.get_line_content_until_next_linebreak(index, *pos));
             }
-
-            index += 1;
+            current_line += piece.lines.len() as u32;
         }
 
         None
@@ -419,9 +403,9 @@ impl TextBuffer {
         while i < self.table.len() {
             let span = &self.table[i];
             let text = if i == index {
-                self.get_span_contents_with_offset(&span, offset + 1)
+                self.get_span_contents_with_offset(span, offset + 1)
             } else {
-                self.get_span_contents(&span)
+                self.get_span_contents(span)
             };
 
             // find the next new line character and return once it's found.
@@ -446,39 +430,119 @@ impl TextBuffer {
         result
     }
 
+    /// Returns the number of lines in the document, using the new line positions cached for
+    /// each span.
     pub fn get_line_count(&self) -> u32 {
-        let mut count = 1;
+        1 + self
+            .table
+            .iter()
+            .map(|span| span.lines.len() as u32)
+            .sum::<u32>()
+    }
 
-        for span in &self.table {
-            let text = self.get_span_contents(&span);
-            for c in text.chars() {
-                if is_newline_char(c) {
-                    count += 1;
-                }
-            }
+    /// Converts a line number and an offset from the start of that line into a position in the
+    /// document. Line numbers start from 1, so requesting line 0 will always return a None result.
+    ///
+    /// # Arguments
+    ///
+    /// * 'line' - The line number containing the position.
+    /// * 'offset' - The offset from the start of the line.
+    pub fn get_doc_pos(&self, line: u32, offset: u32) -> Option<u32> {
+        if line == 0 {
+            return None;
         }
 
-        count
-    }
+        if line == 1 {
+            return Some(offset);
+        }
 
-    pub fn get_doc_pos(&self, line: u32, offset: u32) -> Option<u32> {
         let mut pos = 0;
         let mut current_line = 1;
 
         for piece in &self.table {
-            for line_pos in &piece.lines {
-                current_line += 1;
-                if current_line == line {
-                    let final_pos = pos + line_pos + offset as usize;
-                    return Some(final_pos as u32);
-                }
+            let skip = (line - current_line - 1) as usize;
+            if let Some(line_pos) = piece.lines.get(skip) {
+                let final_pos = pos + line_pos + 1 + offset as usize;
+                return Some(final_pos as u32);
             }
+            current_line += piece.lines.len() as u32;
             pos += piece.len;
         }
 
         None
     }
 
+    /// Converts a position in the document into a line number and the offset from the start of
+    /// that line, the reverse of `get_doc_pos`. Positions past the end of the document give the
+    /// end of the last line.
+    ///
+    /// # Arguments
+    ///
+    /// * 'pos' - The position in the document.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use text_buffer::TextBuffer;
+    ///
+    /// let buffer = TextBuffer::new(Some(String::from("Lorem ipsum\ndolor sit amet")));
+    /// assert_eq!((2, 6), buffer.get_line_pos(18));
+    /// ```
+    pub fn get_line_pos(&self, pos: usize) -> (u32, u32) {
+        let pos = pos.min(self.doc_len());
+        let mut line = 1;
+        let mut line_start = 0;
+        let mut doc_pos = 0;
+
+        for piece in &self.table {
+            if doc_pos >= pos {
+                break;
+            }
+            for line_pos in &piece.lines {
+                if doc_pos + line_pos >= pos {
+                    break;
+                }
+                line += 1;
+                line_start = doc_pos + line_pos + 1;
+            }
+            doc_pos += piece.len;
+        }
+
+        (line, (pos - line_start) as u32)
+    }
+
+    /// Appends text to the end of the original buffer, extending the document. This allows a
+    /// document to be loaded incrementally, with each section of text treated as if it were part
+    /// of the original document.
+    ///
+    /// # Arguments
+    ///
+    /// * 'text' - The text that will be appended to the original buffer
+    pub fn extend_original(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        let start = self.original.len();
+        self.original += text;
+
+        // Extend the final span if it ends at the end of the original buffer, rather than adding
+        // a new span for each section of text.
+        if let Some(span) = self.table.last_mut() {
+            if span.buffer == BufferType::Original && span.end == start {
+                let offset = span.len;
+                span.lines
+                    .extend(newline_positions(text).map(|pos| pos + offset));
+                span.end += text.len();
+                span.len += text.len();
+                return;
+            }
+        }
+
+        self.table
+            .push(self.create_span(BufferType::Original, start, text.len()));
+    }
+
     fn add_to_buffer(&mut self, text: &str) -> usize {
         let pos = self.add.len();
         self.add += text;
@@ -492,13 +556,8 @@ impl TextBuffer {
 
         // Cache new line character positions so we don't have to iterate over the text each time
         // we want to get line numbers.
-        let mut lines = vec![];
         let contents = self.get_buffer_contents(buffer, start, end);
-        for (pos, c) in contents.chars().enumerate() {
-            if is_newline_char(c) {
-                lines.push(pos);
-            }
-        }
+        let lines = newline_positions(contents).collect();
 
         Span::new(buffer, start, len, lines)
     }
@@ -528,9 +587,35 @@ impl TextBuffer {
         None
     }
 
+    /// The number of pieces the document is made up of. Edits split pieces up, so the table grows
+    /// as the document is edited until it's compacted.
+    pub fn piece_count(&self) -> usize {
+        self.table.len()
+    }
+
+    /// The number of bytes in the add buffer. Inserted text is added to the end of the buffer,
+    /// so it only grows as the document is edited until it's compacted.
+    pub fn add_len(&self) -> usize {
+        self.add.len()
+    }
+
+    /// Rebuilds the document as a single piece of original text, dropping the text which has
+    /// been deleted from both buffers.
+    pub fn compact(&mut self) {
+        let text = self.text();
+        *self = TextBuffer::new(Some(text));
+    }
+
+    /// Returns the last character in the document, or None if the document is empty.
+    pub fn last_char(&self) -> Option<char> {
+        self.table
+            .last()
+            .and_then(|span| self.get_span_contents(span).chars().last())
+    }
+
     pub fn doc_len(&self) -> usize {
         let mut current_pos = 0;
-        for (_, piece) in self.table.iter().enumerate() {
+        for piece in self.table.iter() {
             current_pos += piece.len;
         }
         current_pos
@@ -571,6 +656,13 @@ fn is_newline_char(c: char) -> bool {
     c == 0xA as char
 }
 
+/// Returns the byte position of each new line character in the text.
+fn newline_positions(text: &str) -> impl Iterator<Item = usize> + '_ {
+    text.char_indices()
+        .filter(|(_, c)| is_newline_char(*c))
+        .map(|(pos, _)| pos)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -713,6 +805,62 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn delete_across_multiple_pieces() {
+        let mut buffer = TextBuffer::new(Some(String::from("Lorem amet")));
+        buffer.insert(6, "ipsum ");
+        buffer.insert(12, "dolor ");
+        buffer.insert(18, "sit ");
+        buffer.delete(3, 20);
+
+        let expected = "Lort amet";
+        let actual = buffer.text();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn delete_whole_pieces() {
+        let mut buffer = TextBuffer::new(Some(String::from("Lorem amet")));
+        buffer.insert(6, "ipsum ");
+        buffer.delete(6, 12);
+
+        let expected = "Lorem amet";
+        let actual = buffer.text();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn text_range_across_pieces() {
+        let mut buffer = TextBuffer::new(Some(String::from("Lorem dolor")));
+        buffer.insert(6, "ipsum ");
+
+        assert_eq!("em ipsum do", buffer.text_range(3, 14));
+        assert_eq!("dolor", buffer.text_range(12, 100));
+    }
+
+    #[test]
+    fn insert_between_pieces() {
+        let mut buffer = TextBuffer::new(Some(String::from("sit amet")));
+        buffer.prepend("Lorem ");
+        buffer.insert(6, "ipsum ");
+        buffer.insert(12, "dolor ");
+
+        let expected = "Lorem ipsum dolor sit amet";
+        let actual = buffer.text();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn insert_into_add_piece() {
+        let mut buffer = TextBuffer::new(Some(String::from("Lorem")));
+        buffer.append(" dolor amet");
+        buffer.insert(12, "sit ");
+
+        let expected = "Lorem dolor sit amet";
+        let actual = buffer.text();
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn insert_single_character() {
         let mut buffer = TextBuffer::new(Some(String::from("Lorem psum dolor sit amet")));
@@ -858,6 +1006,43 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn construct_empty_text() {
+        let buffer = TextBuffer::new(Some(String::new()));
+        assert_eq!("", buffer.text());
+        assert_eq!(0, buffer.doc_len());
+    }
+
+    #[test]
+    fn last_char() {
+        let mut buffer = TextBuffer::new(Some(String::from("Lorem ipsum")));
+        assert_eq!(Some('m'), buffer.last_char());
+
+        buffer.append(" dolor\n");
+        assert_eq!(Some('\n'), buffer.last_char());
+    }
+
+    #[test]
+    fn last_char_empty() {
+        let buffer = TextBuffer::new(None);
+        assert_eq!(None, buffer.last_char());
+    }
+
+    #[test]
+    fn compact_keeps_text() {
+        let mut buffer = TextBuffer::new(Some(String::from("Lorem\nipsum")));
+        buffer.insert(5, " dolor");
+        buffer.delete(0, 2);
+        buffer.insert(0, "Lo");
+        assert_eq!(4, buffer.piece_count());
+
+        buffer.compact();
+        assert_eq!(1, buffer.piece_count());
+        assert_eq!("Lorem dolor\nipsum", buffer.text());
+        assert_eq!(Some(String::from("ipsum")), buffer.get_line_content(2));
+        assert!(buffer.add.is_empty());
+    }
+
     #[test]
     fn get_line_count_empty() {
         let buffer = TextBuffer::new(None);
@@ -880,6 +1065,83 @@ mod tests {
         assert_eq!(4, buffer.get_line_count());
     }
 
+    #[test]
+    fn get_doc_pos_first_line() {
+        let buffer = TextBuffer::new(Some(String::from("Lorem ipsum\ndolor sit amet")));
+        assert_eq!(Some(6), buffer.get_doc_pos(1, 6));
+    }
+
+    #[test]
+    fn get_doc_pos_multiple_pieces() {
+        let mut buffer = TextBuffer::new(Some(String::from("Lorem ipsum\ndolor")));
+        buffer.append(" sit\namet");
+
+        assert_eq!(Some(12), buffer.get_doc_pos(2, 0));
+        assert_eq!(Some(24), buffer.get_doc_pos(3, 2));
+        assert_eq!(None, buffer.get_doc_pos(4, 0));
+    }
+
+    #[test]
+    fn get_line_pos_multiple_pieces() {
+        let mut buffer = TextBuffer::new(Some(String::from("Lorem ipsum\ndolor")));
+        buffer.append(" sit\namet");
+
+        assert_eq!((1, 11), buffer.get_line_pos(11));
+        assert_eq!((2, 0), buffer.get_line_pos(12));
+        assert_eq!((3, 2), buffer.get_line_pos(24));
+        assert_eq!((3, 4), buffer.get_line_pos(100));
+    }
+
+    #[test]
+    fn get_line_contents_multibyte_characters() {
+        let buffer = TextBuffer::new(Some(String::from("Lörem ïpsum\ndolor sit amet")));
+
+        let expected = Some(String::from("dolor sit amet"));
+        let actual = buffer.get_line_content(2);
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn extend_original_empty() {
+        let mut buffer = TextBuffer::new(None);
+        buffer.extend_original("Lorem ipsum\ndolor");
+
+        assert_eq!("Lorem ipsum\ndolor", buffer.text());
+        assert_eq!(2, buffer.get_line_count());
+    }
+
+    #[test]
+    fn extend_original_extends_last_span() {
+        let mut buffer = TextBuffer::new(Some(String::from("Lorem ipsum\ndolor")));
+        buffer.extend_original(" sit\namet");
+
+        assert_eq!(1, buffer.table.len());
+        assert_eq!(vec![11, 21], buffer.table[0].lines);
+        assert_eq!(Some(String::from("amet")), buffer.get_line_content(3));
+    }
+
+    #[test]
+    fn extend_original_after_append() {
+        let mut buffer = TextBuffer::new(Some(String::from("Lorem ipsum ")));
+        buffer.append("dolor");
+        buffer.extend_original(" sit amet");
+
+        assert_eq!("Lorem ipsum dolor sit amet", buffer.text());
+        assert_eq!(3, buffer.table.len());
+    }
+
+    #[test]
+    fn add_buffer_grows_until_compacted() {
+        let mut buffer = TextBuffer::new(Some(String::from("Lorem")));
+        buffer.insert(5, " ipsum");
+        buffer.delete(0, 6);
+        assert_eq!(6, buffer.add_len());
+
+        buffer.compact();
+        assert_eq!(0, buffer.add_len());
+        assert_eq!("ipsum", buffer.text());
+    }
+
     #[test]
     fn cache_line_numbers_no_new_line_characters() {
         let mut buffer = TextBuffer::new(None);